@@ -0,0 +1,134 @@
+use crate::error::Error;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// One executed query, written as a line of JSON
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    /// The user running logql, from `USER` or `USERNAME`
+    pub user: Option<String>,
+    /// The SQL, or `None` when every event was selected
+    pub query: Option<String>,
+    /// Values for the query's placeholders
+    pub params: Vec<String>,
+    pub schema: String,
+    pub table: String,
+    /// Files that were read
+    pub sources: Vec<String>,
+    pub duration_ms: f64,
+    /// Rows returned, or `None` if the query failed
+    pub rows: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    pub fn new(schema: &str, table: &str, query: Option<&str>, sources: Vec<String>) -> Self {
+        AuditRecord {
+            timestamp: Utc::now(),
+            user: std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .ok(),
+            query: query.map(str::to_string),
+            params: Vec::new(),
+            schema: schema.to_string(),
+            table: table.to_string(),
+            sources,
+            duration_ms: 0.0,
+            rows: None,
+            error: None,
+        }
+    }
+
+    /// Record how the query went
+    pub fn finish<T>(
+        &mut self,
+        duration: Duration,
+        result: &Result<T, Error>,
+        rows: impl Fn(&T) -> usize,
+    ) {
+        self.duration_ms = duration.as_secs_f64() * 1000.0;
+        match result {
+            Ok(result) => self.rows = Some(rows(result)),
+            Err(e) => self.error = Some(e.to_string()),
+        }
+    }
+}
+
+/// Appends a record of every executed query to a file as newline delimited JSON, so queries in
+/// a shared environment can be accounted for. Each record is a single append, so processes can
+/// share a log.
+pub struct AuditLog {
+    pub path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn append(&self, record: &AuditRecord) -> Result<(), Error> {
+        let mut line =
+            serde_json::to_string(record).map_err(|e| Error::AuditFailed(e.to_string()))?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| Error::AuditFailed(format!("{}: {}", self.path.display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn append_records() {
+        let path = std::env::temp_dir().join(format!("logql-audit-{}.ndjson", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let audit_log = AuditLog { path: path.clone() };
+
+        let mut record = AuditRecord::new(
+            "schema.yml",
+            "logs",
+            Some("SELECT * FROM logs"),
+            vec!["a.log".to_string(), "b.log".to_string()],
+        );
+        record.finish(Duration::from_millis(1500), &Ok(vec![1, 2, 3]), Vec::len);
+        audit_log.append(&record).unwrap();
+        let mut failed = AuditRecord::new("schema.yml", "logs", Some("SELECT"), Vec::new());
+        failed.finish(Duration::ZERO, &Err::<(), _>(Error::InvalidSqlQuery), |_| 0);
+        audit_log.append(&failed).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["query"], "SELECT * FROM logs");
+        assert_eq!(lines[0]["sources"], serde_json::json!(["a.log", "b.log"]));
+        assert_eq!(lines[0]["duration_ms"], 1500.0);
+        assert_eq!(lines[0]["rows"], 3);
+        assert_eq!(lines[0]["error"], Value::Null);
+        assert_eq!(lines[1]["rows"], Value::Null);
+        assert_eq!(lines[1]["error"], "The SQL was invalid.");
+        assert!(lines[1]["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn append_to_missing_directory() {
+        let audit_log = AuditLog {
+            path: PathBuf::from("/nonexistent/logql/audit.ndjson"),
+        };
+        let record = AuditRecord::new("schema.yml", "logs", None, Vec::new());
+        match audit_log.append(&record) {
+            Err(Error::AuditFailed(_)) => (),
+            x => panic!("Error should be Error::AuditFailed. Actual {:?}", x),
+        }
+    }
+}
@@ -1,62 +1,459 @@
+mod aggregate;
+pub mod cancel;
+mod expression;
 mod filter;
-
+pub mod functions;
+mod params;
+mod pushdown;
+mod row_filter;
+mod sample;
+mod sql;
+mod streaming;
+mod tdigest;
+mod unnest;
+
+use crate::engine::aggregate::{contains_aggregate, group_by_expressions};
+use crate::engine::cancel::CancellationToken;
+use crate::engine::functions::{json_document, FunctionRegistry};
+use crate::engine::params::bind_params;
+use crate::engine::pushdown::line_filter;
+use crate::engine::row_filter::parse_row_filter;
+use crate::engine::sample::Sample;
+use crate::engine::sql::parse_sql;
+use crate::engine::unnest::Unnest;
 use crate::error::Error;
+use crate::parser::custom::CustomType;
+use crate::parser::stream::{LineFilter, ParseStats};
 use crate::parser::values::{Event, Type};
 use crate::parser::Parser;
+use crate::schema::Unit;
+use chrono::Utc;
 use comfy_table::{presets, ContentArrangement, Table};
 use serde::Serialize;
-use sqlparser::ast::{Expr, Offset, SelectItem, SetExpr, Statement, Value};
-use sqlparser::dialect::GenericDialect;
-use sqlparser::parser::Parser as SqlParser;
+use sqlparser::ast::{
+    ExcludeSelectItem, Expr, FunctionArg, FunctionArgExpr, Ident, Join, JoinOperator, ObjectName,
+    Offset, Query, SelectItem, SetExpr, SetOperator, SetQuantifier, Statement, TableFactor,
+    TableWithJoins, Value, WildcardAdditionalOptions,
+};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
 pub struct Engine {
     parser: Parser,
     columns: Vec<String>,
-    statement: Option<Statement>,
+    statements: Vec<Statement>,
+    functions: FunctionRegistry,
+    /// Seed for `TABLESAMPLE` clauses without `REPEATABLE`
+    seed: u64,
+    /// File names of the sources, for the `_file` meta column
+    source_names: Vec<String>,
 }
 
 impl Engine {
     pub fn new(parser: Parser) -> Engine {
-        let columns = parser
-            .schema
-            .columns
-            .iter()
-            .map(|c| c.name.clone())
-            .collect();
+        let columns = parser.schema.column_names();
+        let mut functions = FunctionRegistry::default();
+        if let Some(template) = &parser.schema.trace_url {
+            functions.register_trace_url(template);
+        }
+
         Engine {
             parser,
             columns,
-            statement: None,
+            statements: Vec::new(),
+            functions,
+            seed: 0,
+            source_names: Vec::new(),
         }
     }
 
+    /// Create an engine for a query, which can be several statements separated by semicolons
+    /// to run with [`Engine::execute_all`]
     pub fn with_query(parser: Parser, query: String) -> Result<Engine, Error> {
-        let dialect = GenericDialect {};
-        let mut ast: Vec<Statement> = SqlParser::parse_sql(&dialect, query.as_str())?;
-        match ast.len() {
-            0 => return Err(Error::InvalidSqlQuery),
-            1 => (),
-            _ => return Err(Error::TooManySqlQueries),
-        }
+        Engine::with_params(parser, query, Vec::new())
+    }
 
-        let statement = ast.pop().unwrap();
+    /// Create an engine for a query with placeholders like `$1` or `?`, which are replaced by
+    /// `params` in order
+    pub fn with_params(
+        parser: Parser,
+        query: String,
+        params: Vec<String>,
+    ) -> Result<Engine, Error> {
         let mut engine = Engine::new(parser);
-        engine.statement = Some(statement);
+        engine.statements = engine.parse_statements(&query, &params)?;
         Ok(engine)
     }
 
+    /// Seed the rows `TABLESAMPLE` picks when the query doesn't say `REPEATABLE (seed)`, so a
+    /// sample can be reproduced by running with the same seed. Defaults to 0.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    /// Name the sources passed to [`Engine::execute`] or [`Engine::load`], in the same order, for
+    /// the `_file` meta column. Without names it's null.
+    pub fn set_source_names(&mut self, names: Vec<String>) {
+        self.source_names = names;
+    }
+
+    /// Register a custom column type so schema columns can refer to it by name
+    pub fn register_type<T: CustomType>(&mut self, custom_type: T) {
+        self.parser.register_type(custom_type);
+    }
+
+    /// Execute the engine's query. Queries with several statements run with
+    /// [`Engine::execute_all`] instead.
     pub fn execute<T: AsRef<str>>(&self, lines: Vec<T>) -> Result<TableResult, Error> {
-        let events = self.parser.parse(lines);
-        let table_result = TableResult {
-            columns: self.columns.clone(),
+        self.execute_with_cancellation(lines, &CancellationToken::default())
+    }
+
+    /// Execute like [`Engine::execute`], stopping with [`Error::Cancelled`] once the token is
+    /// cancelled
+    pub fn execute_with_cancellation<T: AsRef<str>>(
+        &self,
+        lines: Vec<T>,
+        cancellation: &CancellationToken,
+    ) -> Result<TableResult, Error> {
+        if self.statements.len() > 1 {
+            return Err(Error::TooManySqlQueries);
+        }
+        let mut results = self.execute_all_with_cancellation(lines, cancellation)?;
+        Ok(results.remove(0))
+    }
+
+    /// Execute each statement of the engine's query against the same parsed events, returning a
+    /// result per statement
+    pub fn execute_all<T: AsRef<str>>(&self, lines: Vec<T>) -> Result<Vec<TableResult>, Error> {
+        self.execute_all_with_cancellation(lines, &CancellationToken::default())
+    }
+
+    /// Execute like [`Engine::execute_all`], stopping with [`Error::Cancelled`] once the token is
+    /// cancelled
+    pub fn execute_all_with_cancellation<T: AsRef<str>>(
+        &self,
+        lines: Vec<T>,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<TableResult>, Error> {
+        self.execute_named(lines, &self.source_names, cancellation)
+    }
+
+    /// Execute each statement against the lines of sources with the given names
+    fn execute_named<T: AsRef<str>>(
+        &self,
+        lines: Vec<T>,
+        names: &[String],
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<TableResult>, Error> {
+        if self.statements.is_empty() {
+            let dataset = self.load_named(lines, names, None, cancellation)?;
+            return Ok(vec![self.run(None, &dataset, cancellation)?]);
+        }
+        // lines that can't start an event a single statement selects don't need parsing, but
+        // several statements share the events
+        let mut filter = None;
+        if let [statement] = self.statements.as_slice() {
+            if self.streams(statement) {
+                let names = (0..lines.len()).map(|index| names.get(index).cloned());
+                let sources = lines.into_iter().map(|text| std::iter::once(Ok(text)));
+                let result = self.stream(statement, names.zip(sources), cancellation)?;
+                return Ok(vec![result]);
+            }
+            filter = line_filter(statement, &self.parser.schema);
+        }
+
+        // describing the table doesn't need the source
+        let describe_only = self
+            .statements
+            .iter()
+            .all(|statement| matches!(statement, Statement::ExplainTable { .. }));
+        let dataset = if describe_only {
+            Dataset::default()
+        } else {
+            self.load_named(lines, names, filter.as_ref(), cancellation)?
+        };
+        self.statements
+            .iter()
+            .map(|statement| self.run(Some(statement), &dataset, cancellation))
+            .collect()
+    }
+
+    /// Parse the lines once so any number of queries can run against them with [`Engine::query`].
+    /// The engine and the dataset are `Send` and `Sync`, so queries can run from multiple
+    /// threads at the same time.
+    pub fn load<T: AsRef<str>>(&self, lines: Vec<T>) -> Result<Dataset, Error> {
+        self.load_with_cancellation(lines, &CancellationToken::default())
+    }
+
+    /// Load like [`Engine::load`], stopping with [`Error::Cancelled`] once the token is cancelled
+    pub fn load_with_cancellation<T: AsRef<str>>(
+        &self,
+        lines: Vec<T>,
+        cancellation: &CancellationToken,
+    ) -> Result<Dataset, Error> {
+        self.load_named(lines, &self.source_names, None, cancellation)
+    }
+
+    fn load_named<T: AsRef<str>>(
+        &self,
+        lines: Vec<T>,
+        names: &[String],
+        filter: Option<&LineFilter>,
+        cancellation: &CancellationToken,
+    ) -> Result<Dataset, Error> {
+        self.parser.verify_custom_types()?;
+        let bytes_read = lines.iter().map(|chunk| chunk.as_ref().len()).sum();
+        let (events, warnings, parse_stats) =
+            self.parser
+                .parse_files_with_filter(lines, names, filter, cancellation)?;
+        let rows_scanned = events.len();
+        Ok(Dataset {
+            source: Arc::new(self.source_table(events, cancellation)?),
+            warnings,
+            parse_stats,
+            bytes_read,
+            rows_scanned,
+        })
+    }
+
+    /// The table queries select from, with the multiline column's extra text joined and the
+    /// events the schema's `row_filter` rejects dropped
+    fn source_table(
+        &self,
+        events: Vec<Event>,
+        cancellation: &CancellationToken,
+    ) -> Result<DerivedTable, Error> {
+        let (columns, events) = self.discover_columns(events);
+        let mut table_result = self.table_result(columns.clone(), events);
+        table_result.units = self
+            .parser
+            .schema
+            .columns
+            .iter()
+            .filter_map(|column| Some((column.name.clone(), column.unit?)))
+            .collect();
+        let mut table_result = table_result.handle_extra_text();
+
+        // filtering the source once also covers WITH queries and subqueries
+        if let Some(filter) = &self.parser.schema.row_filter {
+            let filter = parse_row_filter(filter, &columns, |name| std::env::var(name).ok())?;
+            let events = std::mem::take(&mut table_result.events);
+            for event in events {
+                cancellation.check()?;
+                if table_result.evaluate_predicate(&filter, &event)? {
+                    table_result.events.push(event);
+                }
+            }
+        }
+
+        Ok(DerivedTable {
+            columns: table_result.columns,
+            events: table_result.events,
+            units: table_result.units,
+        })
+    }
+
+    /// The table's columns with the keys of `kv` sources, or the fields of `cef` and `leef`
+    /// sources without columns, that aren't schema columns, sorted and before the meta columns.
+    /// Events without a key are null in its column.
+    fn discover_columns(&self, mut events: Vec<Event>) -> (Vec<String>, Vec<Event>) {
+        let mut columns = self.columns.clone();
+        if !self.parser.schema.discovers_columns() {
+            return (columns, events);
+        }
+
+        let discovered: BTreeSet<_> = events
+            .iter()
+            .flat_map(|event| event.values.keys())
+            .filter(|key| !columns.contains(key))
+            .cloned()
+            .collect();
+        for event in &mut events {
+            for key in &discovered {
+                event.values.entry(key.clone()).or_insert(Type::Null);
+            }
+        }
+        let position = self.parser.schema.columns.len();
+        columns.splice(position..position, discovered);
+        (columns, events)
+    }
+
+    /// Run a query against a loaded dataset instead of the engine's own query
+    pub fn query(&self, dataset: &Dataset, query: &str) -> Result<TableResult, Error> {
+        self.query_with_cancellation(dataset, query, &CancellationToken::default())
+    }
+
+    /// Query like [`Engine::query`], replacing placeholders like `$1` or `?` with `params`
+    pub fn query_with_params(
+        &self,
+        dataset: &Dataset,
+        query: &str,
+        params: &[String],
+    ) -> Result<TableResult, Error> {
+        let statements = self.parse_statements(query, params)?;
+        if statements.len() > 1 {
+            return Err(Error::TooManySqlQueries);
+        }
+        self.run(statements.first(), dataset, &CancellationToken::default())
+    }
+
+    /// Query like [`Engine::query`], stopping with [`Error::Cancelled`] once the token is
+    /// cancelled
+    pub fn query_with_cancellation(
+        &self,
+        dataset: &Dataset,
+        query: &str,
+        cancellation: &CancellationToken,
+    ) -> Result<TableResult, Error> {
+        let statements = self.parse_statements(query, &[])?;
+        if statements.len() > 1 {
+            return Err(Error::TooManySqlQueries);
+        }
+        self.run(statements.first(), dataset, cancellation)
+    }
+
+    /// Run each statement of a query against a loaded dataset, returning a result per statement
+    pub fn query_all(&self, dataset: &Dataset, query: &str) -> Result<Vec<TableResult>, Error> {
+        self.parse_statements(query, &[])?
+            .iter()
+            .map(|statement| self.run(Some(statement), dataset, &CancellationToken::default()))
+            .collect()
+    }
+
+    fn parse_statements(&self, query: &str, params: &[String]) -> Result<Vec<Statement>, Error> {
+        let mut statements = parse_sql(query, &self.columns)?;
+        if statements.is_empty() {
+            return Err(Error::InvalidSqlQuery);
+        }
+        for statement in &mut statements {
+            bind_params(statement, params, &self.parser.schema)?;
+        }
+        Ok(statements)
+    }
+
+    fn run(
+        &self,
+        statement: Option<&Statement>,
+        dataset: &Dataset,
+        cancellation: &CancellationToken,
+    ) -> Result<TableResult, Error> {
+        if let Some(statement @ Statement::ExplainTable { table_name, .. }) = statement {
+            if !table_name
+                .to_string()
+                .eq_ignore_ascii_case(&self.parser.schema.table)
+            {
+                return Err(Error::InvalidQuery(Box::new(statement.clone())));
+            }
+            return self.describe();
+        }
+
+        // queries copy the events they select from the shared source, so only a query without
+        // a statement needs them up front
+        let events = match statement {
+            Some(_) => Vec::new(),
+            None => dataset.source.events.clone(),
+        };
+        let mut table_result = self.table_result(dataset.source.columns.clone(), events);
+        table_result.statement = statement.cloned();
+        table_result.functions.register_now(Utc::now());
+        table_result.units = dataset.source.units.clone();
+        table_result.source = dataset.source.clone();
+        table_result.warnings = dataset.warnings.clone();
+        table_result.parse_stats = dataset.parse_stats.clone();
+        table_result.cancellation = cancellation.clone();
+        let mut table_result = table_result.process()?;
+        table_result.stats = QueryStats {
+            bytes_read: dataset.bytes_read,
+            rows_scanned: dataset.rows_scanned,
+            process_peak_memory_bytes: process_peak_memory(),
+        };
+        Ok(table_result)
+    }
+
+    /// The schema's columns with their types, descriptions and earlier names, which is the
+    /// result of `DESCRIBE <table>`. Fails if a column has a custom type that isn't registered,
+    /// e.g. a misspelled type.
+    pub fn describe(&self) -> Result<TableResult, Error> {
+        self.parser.verify_custom_types()?;
+        let columns = vec![
+            "column".to_string(),
+            "type".to_string(),
+            "description".to_string(),
+            "aliases".to_string(),
+        ];
+        let events = self
+            .parser
+            .schema
+            .columns
+            .iter()
+            .map(|column| {
+                let description = column.description.clone().map_or(Type::Null, Type::String);
+                let aliases = if column.aliases.is_empty() {
+                    Type::Null
+                } else {
+                    Type::String(column.aliases.join(", "))
+                };
+                let values = columns
+                    .iter()
+                    .cloned()
+                    .zip([
+                        Type::String(column.name.clone()),
+                        Type::String(column.r#type.to_string()),
+                        description,
+                        aliases,
+                    ])
+                    .collect();
+                Event {
+                    values,
+                    extra_text: None,
+                }
+            })
+            .collect();
+
+        Ok(self.table_result(columns, events))
+    }
+
+    fn table_result(&self, columns: Vec<String>, events: Vec<Event>) -> TableResult {
+        TableResult {
+            columns,
             events,
             parser: self.parser.clone(),
-            statement: self.statement.clone(),
-        };
-        table_result.process()
+            statement: None,
+            functions: self.functions.clone(),
+            ctes: HashMap::new(),
+            units: HashMap::new(),
+            source: Arc::default(),
+            subqueries: Mutex::default(),
+            warnings: Vec::new(),
+            parse_stats: Vec::new(),
+            cancellation: CancellationToken::default(),
+            stats: QueryStats::default(),
+            seed: self.seed,
+        }
+    }
+}
+
+/// Parsed events that queries can share, from [`Engine::load`]. Cloning it is cheap.
+#[derive(Clone, Default)]
+pub struct Dataset {
+    source: Arc<DerivedTable>,
+    warnings: Vec<String>,
+    parse_stats: Vec<ParseStats>,
+    bytes_read: usize,
+    rows_scanned: usize,
+}
+
+impl Dataset {
+    /// Values the parser had to clamp and extra text it truncated while loading
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// How many lines of each file were read, matched and unmatched while loading
+    pub fn parse_stats(&self) -> &[ParseStats] {
+        &self.parse_stats
     }
 }
 
@@ -68,6 +465,53 @@ pub struct TableResult {
     parser: Parser,
     #[serde(skip)]
     statement: Option<Statement>,
+    #[serde(skip)]
+    functions: FunctionRegistry,
+    /// Results of `WITH` queries, keyed by lowercase name
+    #[serde(skip)]
+    ctes: HashMap<String, DerivedTable>,
+    /// Display units of the columns, from the schema or the columns an expression used
+    #[serde(skip)]
+    units: HashMap<String, Unit>,
+    /// Every event, which queries that don't select from a `WITH` query read from
+    #[serde(skip)]
+    source: Arc<DerivedTable>,
+    /// Results of subqueries in expressions, keyed by their SQL so they only run once
+    #[serde(skip)]
+    subqueries: Mutex<HashMap<String, Arc<DerivedTable>>>,
+    /// Values the parser had to clamp, e.g. leap seconds in lenient datetime columns
+    #[serde(skip)]
+    pub warnings: Vec<String>,
+    /// How many lines of each file were read, matched and unmatched
+    #[serde(skip)]
+    pub parse_stats: Vec<ParseStats>,
+    /// Checked while scanning events so another thread can stop the query
+    #[serde(skip)]
+    cancellation: CancellationToken,
+    /// What the query cost, for callers that enforce budgets or report it
+    pub stats: QueryStats,
+    #[serde(skip)]
+    seed: u64,
+}
+
+/// Resources used by a query
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct QueryStats {
+    /// Bytes of source text the events were parsed from
+    pub bytes_read: usize,
+    /// Events parsed from the source, which the query scanned
+    pub rows_scanned: usize,
+    /// Peak resident memory of the whole process in bytes, where the platform reports it. It
+    /// isn't per query: it includes earlier queries and everything else the process did.
+    pub process_peak_memory_bytes: Option<u64>,
+}
+
+/// The result of a `WITH` query that later queries can select from
+#[derive(Clone, Default)]
+struct DerivedTable {
+    columns: Vec<String>,
+    events: Vec<Event>,
+    units: HashMap<String, Unit>,
 }
 
 impl TableResult {
@@ -78,34 +522,286 @@ impl TableResult {
     }
 
     fn process(self) -> Result<TableResult, Error> {
-        self.handle_extra_text()
-            .filter()?
-            .project()?
-            .order_by()?
-            .offset()?
-            .limit()
+        self.select()?.order_by()?.offset()?.limit()
+    }
+
+    /// Filter and project the query body. `UNION ALL` runs each `SELECT` against all events and
+    /// combines the results before the outer `ORDER BY`, `OFFSET` and `LIMIT`. `WITH` queries
+    /// run first so the body can select from them.
+    fn select(mut self) -> Result<TableResult, Error> {
+        let query = match &self.statement {
+            Some(Statement::Query(query)) => query.clone(),
+            _ => return self.filter()?.project(),
+        };
+
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                let table = self.derive(&cte.query)?;
+                let table = self.alias_columns(table, &cte.alias.columns)?;
+                self.ctes.insert(cte.alias.name.value.to_lowercase(), table);
+            }
+        }
+
+        let selected = self.select_body(&query.body)?;
+        Ok(TableResult {
+            columns: selected.columns,
+            events: selected.events,
+            units: selected.units,
+            ..self
+        })
+    }
+
+    fn select_body(&self, body: &SetExpr) -> Result<TableResult, Error> {
+        match body {
+            SetExpr::Select(_) => self.with_body(body)?.filter()?.project(),
+            SetExpr::Query(query)
+                if query.order_by.is_empty() && query.limit.is_none() && query.offset.is_none() =>
+            {
+                self.select_body(&query.body)
+            }
+            SetExpr::SetOperation {
+                op: SetOperator::Union,
+                set_quantifier: SetQuantifier::All,
+                left,
+                right,
+            } => {
+                let mut left = self.select_body(left)?;
+                let right = self.select_body(right)?;
+                if left.columns.len() != right.columns.len() {
+                    return Err(Error::UnionColumnCountMismatch(
+                        left.columns.len(),
+                        right.columns.len(),
+                    ));
+                }
+
+                // the left side names the columns, like in postgres
+                for mut event in right.events {
+                    event.values = left
+                        .columns
+                        .iter()
+                        .zip(&right.columns)
+                        .map(|(name, column)| (name.clone(), event.values[column].clone()))
+                        .collect();
+                    left.events.push(event);
+                }
+                Ok(left)
+            }
+            SetExpr::SetOperation {
+                op, set_quantifier, ..
+            } => Err(Error::UnsupportedExpression(
+                format!("{} {}", op, set_quantifier).trim().to_string(),
+            )),
+            _ => Err(Error::InvalidQuery(Box::new(
+                self.statement.as_ref().unwrap().clone(),
+            ))),
+        }
+    }
+
+    /// Run a `WITH` query against the same events
+    fn derive(&self, query: &Query) -> Result<DerivedTable, Error> {
+        let result = TableResult {
+            columns: self.columns.clone(),
+            events: self.events.clone(),
+            parser: self.parser.clone(),
+            statement: Some(Statement::Query(Box::new(query.clone()))),
+            functions: self.functions.clone(),
+            ctes: self.ctes.clone(),
+            units: self.units.clone(),
+            source: self.source.clone(),
+            subqueries: Mutex::default(),
+            warnings: Vec::new(),
+            parse_stats: Vec::new(),
+            cancellation: self.cancellation.clone(),
+            stats: QueryStats::default(),
+            seed: self.seed,
+        }
+        .select()?
+        .order_by()?
+        .offset()?
+        .limit()?;
+
+        Ok(DerivedTable {
+            columns: result.columns,
+            events: result.events,
+            units: result.units,
+        })
+    }
+
+    /// Rename the columns of a `WITH` query or a subquery in `FROM`, like `t (a, b)`
+    fn alias_columns(
+        &self,
+        mut table: DerivedTable,
+        aliases: &[Ident],
+    ) -> Result<DerivedTable, Error> {
+        if aliases.is_empty() {
+            return Ok(table);
+        }
+        if aliases.len() != table.columns.len() {
+            return Err(Error::InvalidQuery(Box::new(
+                self.statement.as_ref().unwrap().clone(),
+            )));
+        }
+
+        for event in &mut table.events {
+            event.values = aliases
+                .iter()
+                .zip(&table.columns)
+                .map(|(alias, column)| (alias.value.clone(), event.values[column].clone()))
+                .collect();
+        }
+        table.units = aliases
+            .iter()
+            .zip(&table.columns)
+            .filter_map(|(alias, column)| Some((alias.value.clone(), *table.units.get(column)?)))
+            .collect();
+        table.columns = aliases.iter().map(|alias| alias.value.clone()).collect();
+        Ok(table)
+    }
+
+    /// Run a subquery in an expression, e.g. `IN (SELECT ...)`. Subqueries can't refer to the
+    /// outer query's columns so each one only runs once.
+    fn subquery(&self, query: &Query) -> Result<Arc<DerivedTable>, Error> {
+        let key = query.to_string();
+        if let Some(table) = self.subqueries.lock().unwrap().get(&key) {
+            return Ok(table.clone());
+        }
+
+        let table = Arc::new(self.derive(query)?);
+        self.subqueries.lock().unwrap().insert(key, table.clone());
+        Ok(table)
+    }
+
+    /// Copy of the result whose query body is replaced by one side of a set operation. Selecting
+    /// from a `WITH` query or a subquery in `FROM` swaps in its events, `TABLESAMPLE` keeps a
+    /// sample of them and `UNNEST` repeats them per element of a list.
+    fn with_body(&self, body: &SetExpr) -> Result<TableResult, Error> {
+        let mut statement = self.statement.clone();
+        if let Some(Statement::Query(query)) = &mut statement {
+            *query.body = body.clone();
+        }
+
+        let (relation, unnest) = match body {
+            SetExpr::Select(select) => match select.from.as_slice() {
+                [TableWithJoins { relation, joins }] => match joins.as_slice() {
+                    [] => (Some(relation), None),
+                    [Join {
+                        relation: unnest @ TableFactor::UNNEST { .. },
+                        join_operator: JoinOperator::CrossJoin,
+                    }] => (Some(relation), Some(unnest)),
+                    _ => {
+                        return Err(Error::InvalidQuery(Box::new(
+                            self.statement.as_ref().unwrap().clone(),
+                        )))
+                    }
+                },
+                [TableWithJoins {
+                    relation,
+                    joins: table_joins,
+                }, TableWithJoins {
+                    relation: unnest @ TableFactor::UNNEST { .. },
+                    joins: unnest_joins,
+                }] if table_joins.is_empty() && unnest_joins.is_empty() => {
+                    (Some(relation), Some(unnest))
+                }
+                [] => (None, None),
+                // joins other than with UNNEST aren't supported
+                _ => {
+                    return Err(Error::InvalidQuery(Box::new(
+                        self.statement.as_ref().unwrap().clone(),
+                    )))
+                }
+            },
+            _ => (None, None),
+        };
+        // a subquery in `FROM` runs like a `WITH` query
+        let mut subquery = None;
+        let (name, hints) = match relation {
+            Some(TableFactor::Table {
+                name, with_hints, ..
+            }) => (Some(name), with_hints.as_slice()),
+            Some(TableFactor::Derived {
+                subquery: query,
+                alias,
+                ..
+            }) => {
+                let table = self.derive(query)?;
+                let aliases = alias.as_ref().map_or(&[][..], |alias| &alias.columns);
+                subquery = Some(self.alias_columns(table, aliases)?);
+                (None, &[][..])
+            }
+            Some(_) => {
+                return Err(Error::InvalidQuery(Box::new(
+                    self.statement.as_ref().unwrap().clone(),
+                )))
+            }
+            None => (None, &[][..]),
+        };
+        let cte = name.and_then(|name| self.ctes.get(&name.to_string().to_lowercase()));
+        let table = match (&subquery, cte) {
+            (Some(table), _) | (None, Some(table)) => table,
+            (None, None) => &*self.source,
+        };
+        let mut events = match Sample::from_hints(hints, self.seed)? {
+            Some(sample) => sample.apply(&table.events),
+            None => table.events.clone(),
+        };
+        let mut columns = table.columns.clone();
+        if let Some(unnest) = unnest {
+            let unnest = Unnest::new(unnest)?;
+            columns.extend(unnest.columns(&columns)?);
+            events = self.unnest(&unnest, events)?;
+        }
+
+        Ok(TableResult {
+            columns,
+            events,
+            parser: self.parser.clone(),
+            statement,
+            functions: self.functions.clone(),
+            ctes: self.ctes.clone(),
+            units: table.units.clone(),
+            source: self.source.clone(),
+            subqueries: Mutex::default(),
+            warnings: Vec::new(),
+            parse_stats: Vec::new(),
+            cancellation: self.cancellation.clone(),
+            stats: QueryStats::default(),
+            seed: self.seed,
+        })
     }
 
     fn order_by(mut self) -> Result<TableResult, Error> {
         if let Some(statement) = &self.statement {
             if let Statement::Query(query) = statement {
-                if query.order_by.len() > 0 {
+                if !query.order_by.is_empty() {
+                    let mut sort_columns = Vec::new();
+                    for order_by in &query.order_by {
+                        let column = self.resolve_order_by_column(&order_by.expr, statement)?;
+                        let asc = order_by.asc.unwrap_or(true);
+                        // like postgres, NULLs are larger than every other value by default
+                        let nulls_first = order_by.nulls_first.unwrap_or(!asc);
+                        sort_columns.push((column, asc, nulls_first));
+                    }
+
                     self.events.sort_by(|a, b| {
                         let mut result = Ordering::Equal;
-                        for order_by in &query.order_by {
-                            result = match &order_by.expr {
-                                Expr::Identifier(identifier) => {
-                                    let column = identifier.value.as_str();
-                                    let a_type = &a.values[column];
-                                    let b_type = &b.values[column];
-                                    let (left, right) = if order_by.asc.unwrap_or(true) {
-                                        (a_type, b_type)
+                        for (column, asc, nulls_first) in &sort_columns {
+                            let left = &a.values[column];
+                            let right = &b.values[column];
+                            result = match (left, right) {
+                                (Type::Null, Type::Null) => Ordering::Equal,
+                                (Type::Null, _) if *nulls_first => Ordering::Less,
+                                (Type::Null, _) => Ordering::Greater,
+                                (_, Type::Null) if *nulls_first => Ordering::Greater,
+                                (_, Type::Null) => Ordering::Less,
+                                _ => {
+                                    let ordering = compare_for_sort(left, right);
+                                    if *asc {
+                                        ordering
                                     } else {
-                                        (b_type, a_type)
-                                    };
-                                    left.partial_cmp(right).unwrap()
+                                        ordering.reverse()
+                                    }
                                 }
-                                _ => panic!("{:?}", statement),
                             };
 
                             if result != Ordering::Equal {
@@ -122,6 +818,34 @@ impl TableResult {
         Ok(self)
     }
 
+    /// Resolve an `ORDER BY` expression against the projected columns. Identifiers match a column
+    /// name or alias, numbers are 1-based positions in the projection and other expressions
+    /// match an unaliased projection of the same expression, e.g. `ORDER BY COUNT(*)`.
+    fn resolve_order_by_column(&self, expr: &Expr, statement: &Statement) -> Result<String, Error> {
+        match expr {
+            Expr::Identifier(identifier) => self
+                .columns
+                .iter()
+                .find(|c| **c == identifier.value)
+                .cloned()
+                .ok_or_else(|| self.unknown_column(&identifier.value)),
+            Expr::Value(Value::Number(position, _)) => usize::from_str(position.as_str())
+                .ok()
+                .filter(|position| (1..=self.columns.len()).contains(position))
+                .map(|position| self.columns[position - 1].clone())
+                .ok_or_else(|| Error::InvalidOrderByPosition(position.clone())),
+            _ => {
+                // aggregates and other expressions match the column named after their SQL
+                let name = expr.to_string();
+                self.columns
+                    .iter()
+                    .find(|c| **c == name)
+                    .cloned()
+                    .ok_or_else(|| Error::InvalidQuery(Box::new(statement.clone())))
+            }
+        }
+    }
+
     fn offset(mut self) -> Result<TableResult, Error> {
         if let Some(statement) = &self.statement {
             if let Statement::Query(query) = statement {
@@ -137,7 +861,7 @@ impl TableResult {
                             self.events = self.events[offset..].to_vec().clone();
                         }
                     }
-                    Some(_) => return Err(Error::InvalidQuery(statement.clone())),
+                    Some(_) => return Err(Error::InvalidQuery(Box::new(statement.clone()))),
                     None => (),
                 }
             }
@@ -154,7 +878,7 @@ impl TableResult {
                         let end = limit.min(self.events.len());
                         self.events = self.events[..end].to_vec().clone();
                     }
-                    Some(_) => return Err(Error::InvalidQuery(statement.clone())),
+                    Some(_) => return Err(Error::InvalidQuery(Box::new(statement.clone()))),
                     None => (),
                 }
             }
@@ -164,14 +888,20 @@ impl TableResult {
     }
 
     fn handle_extra_text(mut self) -> TableResult {
-        if let Some(multiline_column) = &self.parser.multiline_column {
+        let column = self.parser.schema.columns.iter().find(|c| c.multiline);
+        if let Some(column) = column {
+            let separator = column.join_separator.as_deref().unwrap_or("\n");
             for event in &mut self.events {
                 if let Some(extra_text) = event.extra_text.take() {
-                    match event.values.get_mut(multiline_column) {
+                    match event.values.get_mut(&column.name) {
                         Some(Type::String(value)) => {
-                            for line in extra_text {
-                                value.push('\n');
-                                value.push_str(line.as_str());
+                            for line in &extra_text {
+                                value.push_str(separator);
+                                if column.keep_leading_whitespace {
+                                    value.push_str(line);
+                                } else {
+                                    value.push_str(line.trim_start());
+                                }
                             }
                         }
                         _ => panic!("Multiline is only valid on string types"),
@@ -184,57 +914,92 @@ impl TableResult {
     }
 
     fn project(mut self) -> Result<TableResult, Error> {
-        if let Some(statement) = &self.statement {
+        if let Some(statement) = &self.statement.clone() {
             if let Statement::Query(query) = statement {
-                return match &query.body {
+                return match query.body.as_ref() {
                     SetExpr::Select(select) => {
-                        let mut columns = None;
-                        for event in self.events.iter_mut() {
-                            let mut projected_values = HashMap::new();
-                            let mut inner_columns = Vec::new();
-                            for projection in &select.projection {
-                                match projection {
-                                    SelectItem::UnnamedExpr(unnamed_expr) => match unnamed_expr {
-                                        Expr::Identifier(identifier) => {
-                                            let value = event
-                                                .values
-                                                .remove(identifier.value.as_str())
-                                                .unwrap();
-                                            projected_values
-                                                .insert(identifier.value.clone(), value);
-                                            if columns.is_none() {
-                                                inner_columns.push(identifier.value.clone());
-                                            }
-                                        }
-                                        _ => return Err(Error::InvalidQuery(statement.clone())),
-                                    },
-                                    SelectItem::Wildcard => return Ok(self),
-                                    SelectItem::ExprWithAlias {
-                                        expr: Expr::Identifier(identifier),
-                                        alias,
-                                    } => {
-                                        let value =
-                                            event.values.remove(identifier.value.as_str()).unwrap();
-                                        projected_values.insert(alias.value.clone(), value);
-                                        if columns.is_none() {
-                                            inner_columns.push(alias.value.clone());
-                                        }
-                                    }
-                                    _ => return Err(Error::InvalidQuery(statement.clone())),
-                                }
+                        let group_by = group_by_expressions(select)?;
+                        if let [SelectItem::Wildcard(options)] = select.projection.as_slice() {
+                            let plain = *options == WildcardAdditionalOptions::default();
+                            if plain && group_by.is_empty() && select.having.is_none() {
+                                return Ok(self);
                             }
-                            event.values = projected_values;
-                            if columns.is_none() {
-                                columns = Some(inner_columns);
+                        }
+
+                        // `*` can be mixed with other items, e.g. `SELECT 'prod' AS env, *`
+                        let all_columns: Vec<_> = self
+                            .columns
+                            .iter()
+                            .map(|column| Expr::Identifier(Ident::new(column)))
+                            .collect();
+                        let map_columns = select
+                            .projection
+                            .iter()
+                            .map(|projection| match projection {
+                                SelectItem::QualifiedWildcard(name, _) => self.map_columns(name),
+                                _ => Ok(Vec::new()),
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let mut projections = Vec::new();
+                        for (projection, map_columns) in select.projection.iter().zip(&map_columns)
+                        {
+                            let projection = match projection {
+                                SelectItem::UnnamedExpr(expr @ Expr::Identifier(identifier)) => {
+                                    (identifier.value.clone(), expr)
+                                }
+                                SelectItem::UnnamedExpr(expr) => (expr.to_string(), expr),
+                                SelectItem::ExprWithAlias { expr, alias } => {
+                                    (alias.value.clone(), expr)
+                                }
+                                SelectItem::Wildcard(options) => {
+                                    let excluded = self.excluded_columns(options)?;
+                                    projections.extend(
+                                        self.columns
+                                            .iter()
+                                            .cloned()
+                                            .zip(all_columns.iter())
+                                            .filter(|(column, _)| !excluded.contains(column)),
+                                    );
+                                    continue;
+                                }
+                                SelectItem::QualifiedWildcard(..) => {
+                                    projections.extend(
+                                        map_columns
+                                            .iter()
+                                            .map(|(column, expr)| (column.clone(), expr)),
+                                    );
+                                    continue;
+                                }
+                            };
+                            projections.push(projection);
+                        }
+
+                        let grouped = !group_by.is_empty()
+                            || select.having.is_some()
+                            || projections.iter().any(|(_, expr)| contains_aggregate(expr));
+                        if grouped {
+                            return self.group(select, projections);
+                        }
+
+                        let mut projected_events = Vec::with_capacity(self.events.len());
+                        for event in &self.events {
+                            self.cancellation.check()?;
+                            let mut projected_values = HashMap::new();
+                            for (column, expr) in &projections {
+                                projected_values
+                                    .insert(column.clone(), self.evaluate(expr, event)?);
                             }
+                            projected_events.push(projected_values);
                         }
 
-                        if let Some(columns) = columns {
-                            self.columns = columns;
+                        for (event, values) in self.events.iter_mut().zip(projected_events) {
+                            event.values = values;
                         }
+                        self.units = self.projection_units(&projections);
+                        self.columns = projections.into_iter().map(|(column, _)| column).collect();
                         Ok(self)
                     }
-                    _ => Err(Error::InvalidQuery(statement.clone())),
+                    _ => Err(Error::InvalidQuery(Box::new(statement.clone()))),
                 };
             }
         }
@@ -242,6 +1007,127 @@ impl TableResult {
         Ok(self)
     }
 
+    /// Expand `attrs.*` into `attrs['key']` for each key of the JSON objects in the column,
+    /// named like `attrs.key` and sorted by key
+    fn map_columns(&self, name: &ObjectName) -> Result<Vec<(String, Expr)>, Error> {
+        let column = match name.0.as_slice() {
+            [column] if self.columns.contains(&column.value) => column,
+            _ => return Err(self.unknown_column(&name.to_string())),
+        };
+
+        let mut keys = BTreeSet::new();
+        for event in &self.events {
+            let value = event.values.get(&column.value).and_then(json_document);
+            if let Some(serde_json::Value::Object(object)) = value {
+                keys.extend(object.keys().cloned());
+            }
+        }
+        Ok(keys
+            .into_iter()
+            .map(|key| {
+                let expr = Expr::ArrayIndex {
+                    obj: Box::new(Expr::Identifier(column.clone())),
+                    indexes: vec![Expr::Value(Value::SingleQuotedString(key.clone()))],
+                };
+                (format!("{}.{}", column.value, key), expr)
+            })
+            .collect())
+    }
+
+    /// Error for a column that doesn't exist, suggesting a column with a similar name along with
+    /// its description
+    pub(crate) fn unknown_column(&self, name: &str) -> Error {
+        let suggestion = self
+            .columns
+            .iter()
+            .map(|column| {
+                (
+                    edit_distance(&column.to_lowercase(), &name.to_lowercase()),
+                    column,
+                )
+            })
+            .filter(|(distance, _)| *distance <= (name.chars().count() / 3).max(2))
+            .min_by_key(|(distance, _)| *distance);
+
+        let hint = match suggestion {
+            None => String::new(),
+            Some((_, column)) => {
+                let description = self
+                    .parser
+                    .schema
+                    .columns
+                    .iter()
+                    .find(|c| c.name == *column)
+                    .and_then(|c| c.description.as_ref());
+                match description {
+                    Some(description) => format!(" Did you mean '{}' ({})?", column, description),
+                    None => format!(" Did you mean '{}'?", column),
+                }
+            }
+        };
+        Error::UnknownColumn(name.to_string(), hint)
+    }
+
+    /// Columns removed from `*` by `* EXCEPT (a, b)` or `* EXCLUDE (a, b)`
+    fn excluded_columns(&self, options: &WildcardAdditionalOptions) -> Result<Vec<String>, Error> {
+        if let Some(rename) = &options.opt_rename {
+            return Err(Error::UnsupportedExpression(rename.to_string()));
+        }
+        if let Some(replace) = &options.opt_replace {
+            return Err(Error::UnsupportedExpression(replace.to_string()));
+        }
+
+        let mut excluded = Vec::new();
+        match &options.opt_exclude {
+            Some(ExcludeSelectItem::Single(column)) => excluded.push(column),
+            Some(ExcludeSelectItem::Multiple(columns)) => excluded.extend(columns),
+            None => (),
+        }
+        if let Some(except) = &options.opt_except {
+            excluded.push(&except.first_element);
+            excluded.extend(&except.additional_elements);
+        }
+
+        excluded
+            .into_iter()
+            .map(|column| match self.columns.contains(&column.value) {
+                true => Ok(column.value.clone()),
+                false => Err(self.unknown_column(&column.value)),
+            })
+            .collect()
+    }
+
+    /// Units of projected columns. Columns keep their unit when selected or aliased, and so do
+    /// aggregates of them other than `COUNT`.
+    pub(crate) fn projection_units(
+        &self,
+        projections: &[(String, &Expr)],
+    ) -> HashMap<String, Unit> {
+        projections
+            .iter()
+            .filter_map(|(column, expr)| Some((column.clone(), self.expression_unit(expr)?)))
+            .collect()
+    }
+
+    fn expression_unit(&self, expr: &Expr) -> Option<Unit> {
+        match expr {
+            Expr::Identifier(identifier) => self.units.get(&identifier.value).copied(),
+            Expr::Nested(expr) => self.expression_unit(expr),
+            Expr::Function(function) => {
+                let name = function.name.to_string().to_ascii_uppercase();
+                match function.args.as_slice() {
+                    [FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))]
+                        if ["SUM", "AVG", "MIN", "MAX"].contains(&name.as_str()) =>
+                    {
+                        self.expression_unit(expr)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
     fn create_table(&self) -> Table {
         let mut table = Table::new();
         let header: Vec<_> = self.columns.iter().map(|c| c.to_owned()).collect();
@@ -257,19 +1143,81 @@ impl TableResult {
             let result: Vec<_> = self
                 .columns
                 .iter()
-                .map(|c| &event.values[c])
-                .map(|t| t.to_string())
+                .map(|c| match self.units.get(c) {
+                    Some(unit) => unit.format(&event.values[c]),
+                    None => event.values[c].to_string(),
+                })
                 .collect();
             table.add_row(result);
         }
     }
 }
 
+/// Order values for `ORDER BY`. Values of different types, like a string and a number in a
+/// column mixing types, are ordered by their type first so the sort is a total order. Like
+/// postgres orders `jsonb`, strings come before numbers and numbers before booleans, followed by
+/// the types JSON doesn't have.
+fn compare_for_sort(a: &Type, b: &Type) -> Ordering {
+    let rank = |value: &Type| match value {
+        Type::String(_) => 0,
+        Type::Int32(_)
+        | Type::Int64(_)
+        | Type::UInt32(_)
+        | Type::UInt64(_)
+        | Type::Float(_)
+        | Type::Double(_)
+        | Type::Decimal(_) => 1,
+        Type::Bool(_) => 2,
+        Type::DateTime(_) | Type::DateTimeTz(_) => 3,
+        Type::Interval(_) => 4,
+        Type::Custom(_) => 5,
+        Type::Null => 6,
+    };
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (Type::Custom(x), Type::Custom(y)) if x.type_name() != y.type_name() => {
+            x.type_name().cmp(y.type_name())
+        }
+        _ => a.compare(b).unwrap_or(Ordering::Equal),
+    })
+}
+
+/// Peak resident set size of the whole process from `/proc/self/status`, which is only
+/// available on Linux. It's the high water mark since the process started, so it includes
+/// earlier queries and everything else the process did.
+fn process_peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line
+        .trim_start_matches("VmHWM:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Number of single character insertions, deletions or substitutions to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<_> = b.chars().collect();
+    let mut previous: Vec<_> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::parser::values::Type;
-    use crate::schema::Schema;
+    use crate::schema::{ColumnType, Schema};
+    use chrono::prelude::*;
 
     fn generate_events(source: &[&[(&str, &str)]]) -> Vec<Event> {
         source
@@ -537,40 +1485,1446 @@ columns:
     }
 
     #[test]
-    fn sql_projection_alias_subset() {
+    fn sql_projection_alias_subset() {
+        let schema = "\
+regex: (?P<col1>.+)\t(?P<col2>.+)\t(?P<col3>.+)
+filename: .*
+table: logs
+columns:
+    - name: col1
+      type: string
+    - name: col2
+      type: string
+    - name: col3
+      type: string
+";
+        let source = "\
+1\tone\tfirst
+2\ttwo\tsecond
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "SELECT col1 as column1, col3 as column3 FROM table1";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(
+            table_result.columns,
+            vec!["column1".to_string(), "column3".to_string()]
+        );
+
+        let events = generate_events(
+            [
+                [("column1", "1"), ("column3", "first")].as_slice(),
+                [("column1", "2"), ("column3", "second")].as_slice(),
+            ]
+            .as_slice(),
+        );
+        assert_eq!(table_result.events, events);
+    }
+
+    #[test]
+    fn sql_cast() {
+        let schema = "\
+regex: (?P<timestamp>\\S+) (?P<bytes>\\S+) (?P<latency>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: timestamp
+      type: string
+    - name: bytes
+      type: string
+    - name: latency
+      type: string
+";
+        let source = "\
+2022-05-01T10:00:00Z 512 1.5
+2022-05-01T11:00:00Z 64 0.25
+";
+        let query = "SELECT CAST(timestamp AS TIMESTAMP) AS ts, CAST(bytes AS BIGINT) AS bytes, \
+            CAST(latency AS DOUBLE) AS latency FROM logs \
+            WHERE CAST(bytes AS BIGINT) > 100 AND CAST(timestamp AS TIMESTAMP) < '2022-05-02T00:00:00Z'";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+
+        let events = generate_typed_events(vec![vec![
+            ("ts", Utc.ymd(2022, 5, 1).and_hms(10, 0, 0).into()),
+            ("bytes", 512i64.into()),
+            ("latency", 1.5f64.into()),
+        ]]);
+        assert_eq!(table_result.events, events);
+    }
+
+    #[test]
+    fn sql_cast_invalid() {
+        let schema = "\
+regex: (?P<bytes>.+)
+filename: .*
+table: logs
+columns:
+    - name: bytes
+      type: string
+";
+        let query = "SELECT CAST(bytes AS INT) FROM logs";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec!["lots"]).err().unwrap() {
+            Error::InvalidCast(value, column_type) => {
+                assert_eq!(value, "lots".into());
+                assert_eq!(column_type, ColumnType::Int32);
+            }
+            x => panic!("Error should be Error::InvalidCast. Actual error {:?}", x),
+        }
+    }
+
+    #[test]
+    fn sql_trace_url() {
+        let schema = "\
+regex: (?P<trace>\\S+) (?P<span>\\S+)
+filename: .*
+table: logs
+trace_url: https://tracing.example.com/trace/{trace_id}
+columns:
+    - name: trace
+      type: trace_id
+    - name: span
+      type: span_id
+";
+        let source = "\
+4BF92F3577B34DA6A3CE929D0E0E4736 00f067aa0ba902b7
+0af7651916cd43dd8448eb211c80319c b7ad6b7169203331
+";
+        let query = "SELECT span, trace_url(trace) AS link FROM logs \
+            WHERE trace = '4bf92f3577b34da6a3ce929d0e0e4736'";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+
+        assert_eq!(table_result.events.len(), 1);
+        let values = &table_result.events[0].values;
+        assert_eq!(values["span"].to_string(), "00f067aa0ba902b7");
+        assert_eq!(
+            values["link"],
+            "https://tracing.example.com/trace/4bf92f3577b34da6a3ce929d0e0e4736".into()
+        );
+    }
+
+    #[test]
+    fn sql_non_finite_floats() {
+        let schema = "\
+regex: (?P<name>\\S+) (?P<value>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: name
+      type: string
+    - name: value
+      type: f64
+";
+        let source = "\
+nan NaN
+big 1.2e3
+inf inf
+small 5
+negative -inf
+";
+        let queries = [
+            (
+                "select name, value from logs order by value",
+                vec!["negative", "small", "big", "inf", "nan"],
+            ),
+            (
+                "select name, value from logs where value > 100 order by value desc",
+                vec!["nan", "inf", "big"],
+            ),
+            (
+                "select name, value from logs where value < 100 order by value",
+                vec!["negative", "small"],
+            ),
+        ];
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        for (query, expected) in queries {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            let names: Vec<_> = table_result
+                .events
+                .iter()
+                .map(|e| e.values["name"].to_string())
+                .collect();
+            assert_eq!(names, expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn sql_percent() {
+        let schema = "\
+regex: (?P<host>\\S+) (?P<cpu>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: cpu
+      type: percent
+";
+        let source = "\
+a 85%
+b 0.5
+c 91.5%
+";
+        let queries = [
+            "select host from logs where cpu > 0.8",
+            "select host from logs where cpu > '80%'",
+            "select host from logs where '80%' < cpu",
+        ];
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        for query in queries {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            let events =
+                generate_typed_events(vec![vec![("host", "a".into())], vec![("host", "c".into())]]);
+            assert_eq!(table_result.events, events, "{}", query);
+        }
+    }
+
+    #[test]
+    fn sql_union_all() {
+        let schema = "\
+regex: (?P<level>\\S+) (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+";
+        let source = "\
+error disk full
+info started
+warn slow disk
+";
+        let query = "SELECT level, message AS text FROM logs WHERE level = 'error' \
+            UNION ALL SELECT level, UPPER(message) FROM logs WHERE level = 'warn' \
+            ORDER BY text DESC LIMIT 5";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+
+        assert_eq!(
+            table_result.columns,
+            vec!["level".to_string(), "text".to_string()]
+        );
+        let events = generate_typed_events(vec![
+            vec![("level", "error".into()), ("text", "disk full".into())],
+            vec![("level", "warn".into()), ("text", "SLOW DISK".into())],
+        ]);
+        assert_eq!(table_result.events, events);
+    }
+
+    #[test]
+    fn sql_union_all_column_count_mismatch() {
+        let schema = "\
+regex: (?P<col1>.+)\t(?P<col2>.+)
+filename: .*
+table: logs
+columns:
+    - name: col1
+      type: string
+    - name: col2
+      type: string
+";
+        let query = "SELECT col1 FROM logs UNION ALL SELECT col1, col2 FROM logs";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec!["a\tb"]).err().unwrap() {
+            Error::UnionColumnCountMismatch(left, right) => {
+                assert_eq!(left, 1);
+                assert_eq!(right, 2);
+            }
+            x => panic!(
+                "Error should be Error::UnionColumnCountMismatch. Actual error {:?}",
+                x
+            ),
+        }
+
+        let query = "SELECT col1 FROM logs UNION SELECT col2 FROM logs";
+        let engine = Engine::with_query(engine.parser, query.to_string()).unwrap();
+        match engine.execute(vec!["a\tb"]).err().unwrap() {
+            Error::UnsupportedExpression(op) => assert_eq!(op, "UNION"),
+            x => panic!(
+                "Error should be Error::UnsupportedExpression. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn sql_datetime_with_offset() {
+        let schema = "\
+regex: (?P<ts>\\S+) (?P<user>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: ts
+      type: datetimetz
+    - name: user
+      type: string
+";
+        let source = "\
+2022-05-01T09:00:00-05:00 bob
+2022-05-01T12:00:00+02:00 alice
+2022-05-01T10:30:00Z carol
+";
+        let query = "select ts, user from logs where ts > '2022-05-01T10:00:00Z' order by ts";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+
+        let rows: Vec<_> = table_result
+            .events
+            .iter()
+            .map(|e| format!("{} {}", e.values["ts"], e.values["user"]))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                "2022-05-01 10:30:00 +00:00 carol",
+                "2022-05-01 09:00:00 -05:00 bob"
+            ]
+        );
+    }
+
+    #[test]
+    fn sql_multiline_join() {
+        let schema = "\
+regex: (?P<level>[A-Z]+) (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+      multiline: true
+";
+        let source = "\
+ERROR request failed
+    at handler
+\tat main
+";
+        let cases = [
+            ("", "request failed\n    at handler\n\tat main"),
+            (
+                "      join_separator: ' | '\n      keep_leading_whitespace: false\n",
+                "request failed | at handler | at main",
+            ),
+        ];
+        for (options, expected) in cases {
+            let schema = Schema::try_from(format!("{}{}", schema, options).as_str()).unwrap();
+            let parser = Parser::new(schema).unwrap();
+            let engine =
+                Engine::with_query(parser, "select message from logs".to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(table_result.events[0].values["message"], expected.into());
+        }
+    }
+
+    #[test]
+    fn sql_time_bucket() {
+        let schema = "\
+regex: (?P<ts>\\S+) (?P<level>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: ts
+      type: datetime
+    - name: level
+      type: string
+";
+        let source = "\
+2022-05-01T10:01:00Z ERROR
+2022-05-01T10:04:59Z INFO
+2022-05-01T10:05:00Z ERROR
+2022-05-01T10:17:00Z ERROR
+";
+        let query = "select time_bucket('5m', ts) as bucket, count(*) as errors from logs \
+            where level = 'ERROR' group by time_bucket('5m', ts) order by bucket";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+
+        let rows: Vec<_> = table_result
+            .events
+            .iter()
+            .map(|e| format!("{} {}", e.values["bucket"], e.values["errors"]))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                "2022-05-01 10:00:00 UTC 1",
+                "2022-05-01 10:05:00 UTC 1",
+                "2022-05-01 10:15:00 UTC 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn sql_with() {
+        let schema = "\
+regex: (?P<host>\\S+) (?P<level>\\S+) (?P<bytes>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: level
+      type: string
+    - name: bytes
+      type: string
+";
+        let source = "\
+a ERROR 10
+b INFO 20
+a ERROR 30
+c ERROR 40
+";
+        let queries = [
+            "WITH errors AS (SELECT * FROM logs WHERE level = 'ERROR') \
+                SELECT host, COUNT(*) FROM errors GROUP BY host ORDER BY host",
+            "WITH errors (h, l) AS (SELECT host, level FROM logs), \
+                only_errors AS (SELECT h AS host FROM errors WHERE l = 'ERROR') \
+                SELECT host, COUNT(*) FROM only_errors GROUP BY host ORDER BY 2 DESC, host LIMIT 2",
+        ];
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let expected = generate_typed_events(vec![
+            vec![("host", "a".into()), ("COUNT(*)", 2i64.into())],
+            vec![("host", "c".into()), ("COUNT(*)", 1i64.into())],
+        ]);
+        for query in queries {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+
+        // derived columns can have different types than the schema
+        let query = "WITH sizes AS (SELECT host, CAST(bytes AS BIGINT) AS bytes FROM logs) \
+            SELECT host FROM sizes WHERE bytes >= 30";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        let expected =
+            generate_typed_events(vec![vec![("host", "a".into())], vec![("host", "c".into())]]);
+        assert_eq!(table_result.events, expected);
+
+        // subqueries in FROM run like WITH queries
+        let cases = [
+            (
+                "SELECT * FROM (SELECT host FROM logs ORDER BY host DESC LIMIT 1) t",
+                vec![vec![("host", "c".into())]],
+            ),
+            (
+                "SELECT h FROM (SELECT host, level FROM logs WHERE bytes = '20') AS t (h, l)",
+                vec![vec![("h", "b".into())]],
+            ),
+        ];
+        for (query, expected) in cases {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            assert!(!engine.streams(&engine.statements[0]), "{}", query);
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(
+                table_result.events,
+                generate_typed_events(expected),
+                "{}",
+                query
+            );
+        }
+
+        // other relations don't fall back to the table
+        let query = "SELECT * FROM logs JOIN logs AS other ON logs.host = other.host";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::InvalidQuery(_)) => {}
+            Err(e) => panic!("Error should be Error::InvalidQuery. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::InvalidQuery"),
+        }
+    }
+
+    #[test]
+    fn sql_subqueries() {
+        let schema = "\
+regex: (?P<req_id>\\d+) (?P<status>\\d+) (?P<path>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: req_id
+      type: i32
+    - name: status
+      type: i32
+    - name: path
+      type: string
+";
+        let source = "\
+1 200 /a
+2 200 /b
+1 500 /c
+3 404 /d
+2 200 /e
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let cases = [
+            (
+                "SELECT path FROM logs WHERE req_id IN (SELECT req_id FROM logs WHERE status = 500)",
+                vec!["/a", "/c"],
+            ),
+            (
+                "SELECT path FROM logs WHERE req_id NOT IN \
+                    (SELECT req_id FROM logs WHERE status >= 400) AND status = 200",
+                vec!["/b", "/e"],
+            ),
+            ("SELECT path FROM logs WHERE status IN (404, 500)", vec!["/c", "/d"]),
+            (
+                "SELECT path FROM logs WHERE EXISTS (SELECT * FROM logs WHERE status = 404) \
+                    AND req_id = 3",
+                vec!["/d"],
+            ),
+            (
+                "SELECT path FROM logs WHERE NOT EXISTS (SELECT * FROM logs WHERE status = 503)",
+                vec!["/a", "/b", "/c", "/d", "/e"],
+            ),
+            (
+                "WITH ok AS (SELECT * FROM logs WHERE status = 200) \
+                    SELECT path FROM ok WHERE req_id IN (SELECT req_id FROM logs WHERE status = 500)",
+                vec!["/a"],
+            ),
+        ];
+        for (query, paths) in cases {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            let expected = generate_typed_events(
+                paths
+                    .into_iter()
+                    .map(|p| vec![("path", p.into())])
+                    .collect(),
+            );
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+
+        let query = "SELECT path FROM logs WHERE req_id IN (SELECT req_id, status FROM logs)";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::SubqueryColumnCount(2)) => {}
+            x => panic!(
+                "Error should be Error::SubqueryColumnCount. Actual {:?}",
+                x.map(|result| result.events)
+            ),
+        }
+    }
+
+    #[test]
+    fn sql_multiple_statements() {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let source = "a 200\nb 500\na 500\n";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "SELECT COUNT(*) AS n FROM logs; \
+            SELECT host FROM logs WHERE status = 500 ORDER BY host DESC; DESCRIBE logs;";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let results = engine.execute_all(vec![source]).unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].events,
+            generate_typed_events(vec![vec![("n", Type::Int64(3))]])
+        );
+        assert_eq!(
+            results[1].events,
+            generate_typed_events(vec![vec![("host", "b".into())], vec![("host", "a".into())]])
+        );
+        assert_eq!(results[2].columns[0], "column");
+        assert!(matches!(
+            engine.execute(vec![source]),
+            Err(Error::TooManySqlQueries)
+        ));
+
+        let dataset = engine.load(vec![source]).unwrap();
+        let results = engine
+            .query_all(&dataset, "SELECT * FROM logs LIMIT 1; SELECT * FROM logs")
+            .unwrap();
+        let counts: Vec<_> = results.iter().map(|r| r.events.len()).collect();
+        assert_eq!(counts, vec![1, 3]);
+
+        // a trailing semicolon is still one statement
+        let engine = Engine::with_query(parser, "SELECT host FROM logs;".to_string()).unwrap();
+        assert_eq!(engine.execute(vec![source]).unwrap().events.len(), 3);
+    }
+
+    #[test]
+    fn sql_qualified_columns() {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let source = "a 200\nb 500\n";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let expected = generate_typed_events(vec![vec![("host", "b".into())]]);
+        for query in [
+            "SELECT logs.host FROM logs WHERE logs.status = 500",
+            "SELECT l.host FROM logs l WHERE l.status = 500",
+            "WITH errors AS (SELECT * FROM logs WHERE status = 500) SELECT e.host FROM errors e",
+        ] {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn sql_unnest() {
+        let schema = "\
+regex: (?P<id>\\d+) (?P<tags>\\S+) (?P<to>\\S*)
+filename: .*
+table: logs
+columns:
+    - name: id
+      type: i32
+    - name: tags
+      type: string
+    - name: to
+      type: string
+";
+        let source = "1 [\"a\",\"b\"] x@y.com,z@y.com\n2 [] \n3 [\"a\",7] z@y.com\n4 oops a\n";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let rows = |query: &str| {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            engine.execute(vec![source]).map(|result| result.events)
+        };
+
+        assert_eq!(
+            rows("SELECT tag, COUNT(*) AS n FROM logs, UNNEST(tags) AS tag GROUP BY tag ORDER BY n DESC, tag")
+                .unwrap(),
+            generate_typed_events(vec![
+                vec![("tag", "a".into()), ("n", Type::Int64(2))],
+                vec![("tag", "b".into()), ("n", Type::Int64(1))],
+                vec![("tag", Type::Int64(7)), ("n", Type::Int64(1))],
+            ])
+        );
+        assert_eq!(
+            rows(
+                "SELECT l.id, r.recipient, pos FROM logs l CROSS JOIN UNNEST(l.to, ',') \
+                AS r(recipient) WITH OFFSET AS pos WHERE recipient = 'z@y.com'"
+            )
+            .unwrap(),
+            generate_typed_events(vec![
+                vec![
+                    ("id", 1.into()),
+                    ("recipient", "z@y.com".into()),
+                    ("pos", Type::Int64(1))
+                ],
+                vec![
+                    ("id", 3.into()),
+                    ("recipient", "z@y.com".into()),
+                    ("pos", Type::Int64(0))
+                ],
+            ])
+        );
+        let all = rows("SELECT * FROM logs, UNNEST(tags)").unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].values["unnest"], "a".into());
+
+        match rows("SELECT * FROM logs, UNNEST(tags) AS id") {
+            Err(Error::DuplicateColumnName(column)) => assert_eq!(column, "id"),
+            Err(e) => panic!("Error should be Error::DuplicateColumnName. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::DuplicateColumnName"),
+        }
+        match rows("SELECT * FROM logs, UNNEST(id)") {
+            Err(Error::InvalidFunctionArguments(_)) => (),
+            Err(e) => panic!(
+                "Error should be Error::InvalidFunctionArguments. Actual {:?}",
+                e
+            ),
+            Ok(_) => panic!("Error should be Error::InvalidFunctionArguments"),
+        }
+    }
+
+    #[test]
+    fn sql_json_column() {
+        let schema = "\
+regex: (?P<level>[A-Z]+) (?P<payload>.+)
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: payload
+      type: json
+";
+        let source = r#"INFO {"user": {"id": 42, "name": "alice"}, "tags": ["a", "b"]}
+WARN {"user": {"id": 7}}
+"#;
+        let parser = Parser::try_from(schema).unwrap();
+        let query = "SELECT level, payload.user.name AS name, payload.tags[1] AS tag, \
+            JSON_EXTRACT(payload, '$.user.id') AS id FROM logs WHERE payload.user.id > 10";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(
+            table_result.events,
+            generate_typed_events(vec![vec![
+                ("level", "INFO".into()),
+                ("name", "alice".into()),
+                ("tag", "b".into()),
+                ("id", Type::Int64(42)),
+            ]])
+        );
+
+        // JSON output keeps the structure
+        let engine = Engine::with_query(parser.clone(), "SELECT payload FROM logs".to_string());
+        let table_result = engine.unwrap().execute(vec![source]).unwrap();
+        let json = serde_json::to_value(&table_result.events[1].values["payload"]).unwrap();
+        assert_eq!(json, serde_json::json!({"Custom": {"user": {"id": 7}}}));
+
+        let engine = Engine::with_query(parser.clone(), "SELECT payload.* FROM logs".to_string());
+        let table_result = engine.unwrap().execute(vec![source]).unwrap();
+        assert_eq!(table_result.columns, vec!["payload.tags", "payload.user"]);
+
+        match parser.parse(vec!["INFO {not json"]) {
+            Err(Error::InvalidColumnValue(column, _, _)) => assert_eq!(column, "payload"),
+            x => panic!("Error should be Error::InvalidColumnValue. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn sql_map_access() {
+        let schema = "\
+format: json_array
+filename: .*
+table: logs
+columns:
+    - name: id
+      type: i32
+    - name: attrs
+      type: string
+";
+        let source = r#"[
+            {"id": 1, "attrs": {"user": "alice", "tags": ["a", "b"], "geo": {"city": "Oslo"}}},
+            {"id": 2, "attrs": {"user": "bob", "retries": 3}},
+            {"id": 3, "attrs": "not json"}
+        ]"#;
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "SELECT id, attrs['user'] AS user, attrs['tags'][1] AS tag, \
+            attrs['geo']['city'] AS city FROM logs WHERE id <> 2";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(
+            table_result.events,
+            generate_typed_events(vec![
+                vec![
+                    ("id", 1.into()),
+                    ("user", "alice".into()),
+                    ("tag", "b".into()),
+                    ("city", "Oslo".into()),
+                ],
+                vec![
+                    ("id", 3.into()),
+                    ("user", Type::Null),
+                    ("tag", Type::Null),
+                    ("city", Type::Null),
+                ],
+            ])
+        );
+
+        let query = "SELECT id, attrs.* FROM logs WHERE id < 3";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(
+            table_result.columns,
+            vec![
+                "id",
+                "attrs.geo",
+                "attrs.retries",
+                "attrs.tags",
+                "attrs.user"
+            ]
+        );
+        assert_eq!(
+            table_result.events[1].values["attrs.retries"],
+            Type::Int64(3)
+        );
+        assert_eq!(table_result.events[0].values["attrs.retries"], Type::Null);
+        assert_eq!(
+            table_result.events[0].values["attrs.geo"],
+            r#"{"city":"Oslo"}"#.into()
+        );
+
+        let engine = Engine::with_query(parser, "SELECT nope.* FROM logs".to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::UnknownColumn(column, _)) => assert_eq!(column, "nope"),
+            Err(e) => panic!("Error should be Error::UnknownColumn. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::UnknownColumn"),
+        }
+    }
+
+    #[test]
+    fn sql_query_stats() {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let sources = vec!["a 200\nb 500\n", "c 500\nnot a match\n"];
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "SELECT host FROM logs WHERE status = 500";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(sources).unwrap();
+        assert_eq!(table_result.events.len(), 2);
+        assert_eq!(table_result.stats.bytes_read, 30);
+        assert_eq!(table_result.stats.rows_scanned, 3);
+        if cfg!(target_os = "linux") {
+            assert!(table_result.stats.process_peak_memory_bytes.unwrap() > 0);
+        }
+
+        let json = serde_json::to_value(&table_result).unwrap();
+        assert_eq!(json["stats"]["bytes_read"], 30);
+        assert_eq!(json["stats"]["rows_scanned"], 3);
+    }
+
+    #[test]
+    fn sql_line_filter() {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let sources = vec!["a 200\nb 500\nnot a match\n", "bb 404\nc 500\n"];
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+
+        // lines without `b` aren't parsed, and `bb` is parsed but not selected
+        let query = "SELECT host FROM logs WHERE host = 'b'";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let table_result = engine.execute(sources.clone()).unwrap();
+        assert_eq!(table_result.events.len(), 1);
+        assert_eq!(
+            table_result.events[0].values["host"],
+            Type::String("b".into())
+        );
+        assert_eq!(table_result.stats.rows_scanned, 2);
+        let stats = &table_result.parse_stats[0];
+        assert_eq!((stats.lines, stats.events, stats.skipped), (5, 2, 3));
+        assert!(stats
+            .to_string()
+            .ends_with(", 3 lines skipped by the query's filter"));
+
+        let query = "SELECT COUNT(*) AS n FROM logs WHERE host IN ('a', 'c') AND status = 500";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(sources).unwrap();
+        assert_eq!(table_result.events[0].values["n"], Type::Int64(1));
+        assert_eq!(table_result.stats.rows_scanned, 2);
+    }
+
+    #[test]
+    fn sql_params() {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let sources = vec!["007 200\nb 500\nc 404\n"];
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let hosts = |result: TableResult| -> Vec<String> {
+            result
+                .events
+                .iter()
+                .map(|event| event.values["host"].to_string())
+                .collect()
+        };
+
+        // a number-like value compared to a string column stays a string
+        let query = "SELECT host FROM logs WHERE host = $1 OR status = $2";
+        let params = vec!["007".to_string(), "500".to_string()];
+        let engine = Engine::with_params(parser.clone(), query.to_string(), params).unwrap();
+        assert_eq!(
+            hosts(engine.execute(sources.clone()).unwrap()),
+            ["007", "b"]
+        );
+
+        let engine = Engine::new(parser.clone());
+        let dataset = engine.load(sources).unwrap();
+        let query = "SELECT host FROM logs WHERE status IN (?, ?) AND host <> ?";
+        let params = ["500", "404", "c"].map(String::from);
+        let result = engine.query_with_params(&dataset, query, &params).unwrap();
+        assert_eq!(hosts(result), ["b"]);
+
+        // quotes in values are never part of the SQL
+        let query = "SELECT host FROM logs WHERE host = $1";
+        let params = ["b' OR '1' = '1".to_string()];
+        let result = engine.query_with_params(&dataset, query, &params).unwrap();
+        assert!(result.events.is_empty());
+
+        let query = "SELECT host FROM logs WHERE status = $2";
+        match engine
+            .query_with_params(&dataset, query, &params)
+            .err()
+            .unwrap()
+        {
+            Error::MissingParameter(placeholder) => assert_eq!(placeholder, "$2"),
+            x => panic!(
+                "Error should be Error::MissingParameter. Actual error {:?}",
+                x
+            ),
+        }
+        let query = "SELECT host FROM logs WHERE status = $1";
+        match engine
+            .query_with_params(&dataset, query, &params)
+            .err()
+            .unwrap()
+        {
+            Error::InvalidParameter(placeholder, _) => assert_eq!(placeholder, "$1"),
+            x => panic!(
+                "Error should be Error::InvalidParameter. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn sql_concurrent_queries() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Engine>();
+        assert_send_sync::<Dataset>();
+        assert_send_sync::<TableResult>();
+
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let source = "\
+a 200
+b 500
+a 500
+c 404
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let engine = Engine::new(Parser::new(schema).unwrap());
+        let dataset = engine.load(vec![source]).unwrap();
+        let cases = [
+            ("SELECT host FROM logs WHERE status = 500", vec!["b", "a"]),
+            (
+                "SELECT host FROM logs WHERE host IN (SELECT host FROM logs WHERE status = 200)",
+                vec!["a", "a"],
+            ),
+            (
+                "SELECT host, status FROM logs ORDER BY status DESC LIMIT 1",
+                vec!["b"],
+            ),
+        ];
+
+        std::thread::scope(|scope| {
+            for (query, expected) in &cases {
+                let (engine, dataset) = (&engine, &dataset);
+                scope.spawn(move || {
+                    let table_result = engine.query(dataset, query).unwrap();
+                    let hosts: Vec<_> = table_result
+                        .events
+                        .iter()
+                        .map(|e| e.values["host"].to_string())
+                        .collect();
+                    assert_eq!(&hosts, expected, "{}", query);
+                });
+            }
+        });
+
+        assert!(matches!(
+            engine.query(&dataset, "SELECT 1; SELECT 2"),
+            Err(Error::TooManySqlQueries)
+        ));
+    }
+
+    #[test]
+    fn sql_cancelled() {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let query = "SELECT host FROM logs WHERE status >= 500";
+        let engine = Engine::with_query(Parser::new(schema).unwrap(), query.to_string()).unwrap();
+        let dataset = engine.load(vec!["a 200\nb 500"]).unwrap();
+        let cancellation = CancellationToken::new();
+        assert_eq!(
+            1,
+            engine
+                .query_with_cancellation(&dataset, query, &cancellation)
+                .unwrap()
+                .events
+                .len()
+        );
+
+        cancellation.cancel();
+        assert!(matches!(
+            engine.execute_with_cancellation(vec!["a 200\nb 500"], &cancellation),
+            Err(Error::Cancelled)
+        ));
+        assert!(matches!(
+            engine.query_with_cancellation(&dataset, query, &cancellation),
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn sql_row_filter() {
+        let schema = "\
+regex: (?P<tenant>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+row_filter: tenant = '$LOGQL_TEST_TENANT'
+columns:
+    - name: tenant
+      type: string
+    - name: status
+      type: i32
+";
+        let source = "\
+acme 200
+other 500
+acme 500
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "SELECT tenant, status FROM logs WHERE status = 500 OR tenant = 'other'";
+        let cases = [
+            (
+                query,
+                generate_typed_events(vec![vec![
+                    ("tenant", "acme".into()),
+                    ("status", 500.into()),
+                ]]),
+            ),
+            // the subquery can't see the other tenant's events either
+            (
+                "WITH all_rows AS (SELECT * FROM logs) SELECT tenant, status FROM all_rows \
+                    WHERE status IN (SELECT status FROM logs WHERE tenant <> 'acme')",
+                Vec::new(),
+            ),
+        ];
+
+        std::env::set_var("LOGQL_TEST_TENANT", "acme");
+        for (query, expected) in cases {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+
+        std::env::remove_var("LOGQL_TEST_TENANT");
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::InvalidRowFilter(..)) => (),
+            Err(e) => panic!("Error should be Error::InvalidRowFilter. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::InvalidRowFilter"),
+        }
+    }
+
+    #[test]
+    fn sql_tablesample() {
+        let schema = "\
+regex: (?P<id>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: id
+      type: i32
+";
+        let source: String = (0..1000).map(|i| format!("{}\n", i)).collect();
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let count = |query: &str| {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            engine
+                .execute(vec![source.as_str()])
+                .map(|r| r.events.len())
+        };
+
+        let sampled = count("SELECT * FROM logs TABLESAMPLE (10 PERCENT)").unwrap();
+        assert!((50..150).contains(&sampled), "{}", sampled);
+        assert_eq!(
+            count("SELECT * FROM logs TABLESAMPLE (10 PERCENT) REPEATABLE (0)").unwrap(),
+            sampled
+        );
+        assert_eq!(
+            count("SELECT * FROM logs TABLESAMPLE (25 ROWS) WHERE id < 1000").unwrap(),
+            25
+        );
+
+        let ids = |seed: u64, query: &str| {
+            let mut engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            engine.set_seed(seed);
+            let result = engine.execute(vec![source.as_str()]).unwrap();
+            result
+                .events
+                .iter()
+                .map(|e| e.values["id"].clone())
+                .collect::<Vec<_>>()
+        };
+        let query = "SELECT * FROM logs TABLESAMPLE (10 PERCENT)";
+        assert_eq!(ids(42, query), ids(42, query));
+        assert_ne!(ids(42, query), ids(0, query));
+        assert_eq!(
+            ids(
+                42,
+                "SELECT * FROM logs TABLESAMPLE (10 PERCENT) REPEATABLE (0)"
+            ),
+            ids(0, query)
+        );
+        // the sample is taken before the filter
+        assert!(count("SELECT * FROM logs TABLESAMPLE (25 ROWS) WHERE id < 500").unwrap() < 25);
+        assert_eq!(
+            count(
+                "WITH small AS (SELECT * FROM logs WHERE id < 10) \
+                    SELECT * FROM small TABLESAMPLE (100 ROWS)"
+            )
+            .unwrap(),
+            10
+        );
+
+        for query in [
+            "SELECT * FROM logs TABLESAMPLE (150 PERCENT)",
+            "SELECT * FROM logs TABLESAMPLE (1.5 ROWS)",
+            "SELECT * FROM logs TABLESAMPLE (id PERCENT)",
+            "SELECT * FROM logs TABLESAMPLE (10) REPEATABLE (-1)",
+        ] {
+            match count(query) {
+                Err(Error::InvalidTableSample(_)) => (),
+                x => panic!("Error should be Error::InvalidTableSample. Actual {:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn sql_units() {
+        let schema = "\
+regex: (?P<host>\\S+) (?P<bytes>\\d+) (?P<latency>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: bytes
+      type: i64
+      unit: bytes
+    - name: latency
+      type: i32
+      unit: ms
+";
+        let source = "\
+a 2147483648 250
+a 1288490189 1750
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "SELECT host, SUM(bytes) AS total, MAX(latency), COUNT(latency) \
+            FROM logs GROUP BY host";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        let table = table_result.table().to_string();
+        assert!(table.contains("3.2 GiB"), "{}", table);
+        assert!(table.contains("1.8 s"), "{}", table);
+        assert!(table.contains("┆ 2 "), "{}", table);
+
+        // json output keeps the raw values
+        assert_eq!(
+            table_result.events[0].values["total"],
+            Type::Int64(3435973837)
+        );
+    }
+
+    #[test]
+    fn sql_literal_columns() {
+        let schema = "\
+regex: (?P<host>\\S+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let source = "a 200\nb 500\n";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+
+        let query = "SELECT 'prod' AS env, *, 1 AS run, NULL AS note FROM logs WHERE status = 500";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(
+            table_result.columns,
+            vec!["env", "host", "status", "run", "note"]
+        );
+        let expected = generate_typed_events(vec![vec![
+            ("env", "prod".into()),
+            ("host", "b".into()),
+            ("status", 500.into()),
+            ("run", 1i64.into()),
+            ("note", Type::Null),
+        ]]);
+        assert_eq!(table_result.events, expected);
+
+        let query = "SELECT 'prod' AS env, COUNT(*) FROM logs";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        let expected = generate_typed_events(vec![vec![
+            ("env", "prod".into()),
+            ("COUNT(*)", 2i64.into()),
+        ]]);
+        assert_eq!(table_result.events, expected);
+    }
+
+    #[test]
+    fn sql_wildcard_except() {
+        let schema = "\
+regex: (?P<host>\\S+) (?P<status>\\d+) (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+    - name: message
+      type: string
+      multiline: true
+";
+        let source = "a 200 started\n  details\nb 500 failed\n";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let expected =
+            generate_typed_events(vec![vec![("host", "a".into())], vec![("host", "b".into())]]);
+        let queries = [
+            "SELECT * EXCEPT (status, message) FROM logs",
+            "SELECT * EXCLUDE (status, message) FROM logs",
+        ];
+        for query in queries {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(table_result.columns, vec!["host"], "{}", query);
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+
+        let query = "SELECT * EXCLUDE message FROM logs WHERE status = 500";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(table_result.columns, vec!["host", "status"]);
+
+        let query = "SELECT * EXCEPT (missing) FROM logs";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::UnknownColumn(column, _)) => assert_eq!(column, "missing"),
+            x => panic!(
+                "Error should be Error::UnknownColumn. Actual {:?}",
+                x.map(|result| result.events)
+            ),
+        }
+    }
+
+    #[test]
+    fn sql_describe() {
+        let schema_text = "\
+regex: (?P<host>\\S+) (?P<status>\\d+)
+filename: .*
+table: logs
+description: Web server access logs
+columns:
+    - name: host
+      type: string
+      description: Host that served the request
+    - name: status
+      type: i32
+      aliases: [code, status_code]
+";
+        let schema = Schema::try_from(schema_text).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser.clone(), "DESCRIBE logs".to_string()).unwrap();
+        let table_result = engine.execute(Vec::<String>::new()).unwrap();
+        assert_eq!(
+            table_result.columns,
+            vec!["column", "type", "description", "aliases"]
+        );
+        let expected = generate_typed_events(vec![
+            vec![
+                ("column", "host".into()),
+                ("type", "string".into()),
+                ("description", "Host that served the request".into()),
+                ("aliases", Type::Null),
+            ],
+            vec![
+                ("column", "status".into()),
+                ("type", "i32".into()),
+                ("description", Type::Null),
+                ("aliases", "code, status_code".into()),
+            ],
+        ]);
+        assert_eq!(table_result.events, expected);
+
+        let engine = Engine::with_query(parser, "SELECT hots FROM logs".to_string()).unwrap();
+        let error = engine.execute(vec!["a 200"]).err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Column 'hots' does not exist. Did you mean 'host' (Host that served the request)?"
+        );
+
+        // a misspelled type is an unregistered custom type
+        let schema = Schema::try_from(&*schema_text.replace("i32", "i33")).unwrap();
+        let engine = Engine::new(Parser::new(schema).unwrap());
+        match engine.describe() {
+            Err(Error::UnknownColumnType(column, name)) => {
+                assert_eq!((column, name), ("status".to_string(), "i33".to_string()))
+            }
+            Err(e) => panic!("Error should be Error::UnknownColumnType. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::UnknownColumnType"),
+        }
+    }
+
+    #[test]
+    fn sql_projection_functions() {
+        let schema = "\
+regex: (?P<level>.+)\t(?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+";
+        let source = "\
+info\thello world
+error\t  oops  
+";
+        let query = "SELECT UPPER(level), SUBSTR(message, 1, 5) AS short, LENGTH(TRIM(message)) \
+            FROM logs";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(
+            table_result.columns,
+            vec![
+                "UPPER(level)".to_string(),
+                "short".to_string(),
+                "LENGTH(TRIM(message))".to_string()
+            ]
+        );
+
+        let events = generate_typed_events(vec![
+            vec![
+                ("UPPER(level)", "INFO".into()),
+                ("short", "hello".into()),
+                ("LENGTH(TRIM(message))", 11i64.into()),
+            ],
+            vec![
+                ("UPPER(level)", "ERROR".into()),
+                ("short", "  oop".into()),
+                ("LENGTH(TRIM(message))", 4i64.into()),
+            ],
+        ]);
+        assert_eq!(table_result.events, events);
+    }
+
+    #[test]
+    fn sql_projection_math_functions() {
         let schema = "\
-regex: (?P<col1>.+)\t(?P<col2>.+)\t(?P<col3>.+)
+regex: (?P<host>\\S+) (?P<latency>\\d+)
 filename: .*
 table: logs
 columns:
-    - name: col1
-      type: string
-    - name: col2
-      type: string
-    - name: col3
+    - name: host
       type: string
+    - name: latency
+      type: i64
 ";
         let source = "\
-1\tone\tfirst
-2\ttwo\tsecond
+a 1234
+a 2001
+b 999
 ";
+        let query = "SELECT host, ROUND(AVG(latency) / 1000.0, 2) AS seconds, \
+            CEIL(MAX(latency) / 1000.0) AS ceil, FLOOR(MIN(latency) / 1000.0) AS floor \
+            FROM logs GROUP BY host ORDER BY host";
         let schema = Schema::try_from(schema).unwrap();
         let parser = Parser::new(schema).unwrap();
-        let query = "SELECT col1 as column1, col3 as column3 FROM table1";
         let engine = Engine::with_query(parser, query.to_string()).unwrap();
         let table_result = engine.execute(vec![source]).unwrap();
-        assert_eq!(
-            table_result.columns,
-            vec!["column1".to_string(), "column3".to_string()]
-        );
 
-        let events = generate_events(
-            [
-                [("column1", "1"), ("column3", "first")].as_slice(),
-                [("column1", "2"), ("column3", "second")].as_slice(),
-            ]
-            .as_slice(),
-        );
+        let events = generate_typed_events(vec![
+            vec![
+                ("host", "a".into()),
+                ("seconds", 1.62f64.into()),
+                ("ceil", 3f64.into()),
+                ("floor", 1f64.into()),
+            ],
+            vec![
+                ("host", "b".into()),
+                ("seconds", 1f64.into()),
+                ("ceil", 1f64.into()),
+                ("floor", 0f64.into()),
+            ],
+        ]);
         assert_eq!(table_result.events, events);
     }
 
@@ -1102,4 +3456,458 @@ columns:
         ]);
         execute_query(schema, source, query, &events);
     }
+
+    #[test]
+    fn sql_order_by_alias() {
+        let schema = "\
+regex: (?P<index>.+)\t(?P<value>.+)
+filename: .*
+table: logs
+columns:
+    - name: index
+      type: i32
+    - name: value
+      type: i32
+";
+        let source = "\
+1\t3
+2\t2
+3\t1
+";
+        let query = "select index as i, value as v from logs order by v";
+        let events = generate_typed_events(vec![
+            vec![("i", 3.into()), ("v", 1.into())],
+            vec![("i", 2.into()), ("v", 2.into())],
+            vec![("i", 1.into()), ("v", 3.into())],
+        ]);
+        execute_query(schema, source, query, &events);
+    }
+
+    #[test]
+    fn sql_order_by_ordinal() {
+        let schema = "\
+regex: (?P<index>.+)\t(?P<value1>.+)\t(?P<value2>.+)
+filename: .*
+table: logs
+columns:
+    - name: index
+      type: i32
+    - name: value1
+      type: i32
+    - name: value2
+      type: i32
+";
+        let source = "\
+1\t1\t2
+2\t2\t0
+3\t1\t1
+";
+        let queries = [
+            "select * from logs order by 2 asc, 3 desc",
+            "select value1, value2, index from logs order by 1 asc, 2 desc",
+        ];
+        for query in queries {
+            let schema = Schema::try_from(schema).unwrap();
+            let parser = Parser::new(schema).unwrap();
+            let engine = Engine::with_query(parser, query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+
+            let indexes: Vec<_> = table_result
+                .events
+                .iter()
+                .map(|e| e.values["index"].clone())
+                .collect();
+            assert_eq!(indexes, vec![1.into(), 3.into(), 2.into()]);
+        }
+    }
+
+    #[test]
+    fn sql_order_by_ordinal_out_of_range() {
+        let schema = "\
+regex: (?P<index>.+)\t(?P<value>.+)
+filename: .*
+table: logs
+columns:
+    - name: index
+      type: i32
+    - name: value
+      type: i32
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        for query in [
+            "select * from logs order by 0",
+            "select * from logs order by 3",
+            "select value from logs order by 2",
+        ] {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            match engine.execute(vec!["1\t2"]).err().unwrap() {
+                Error::InvalidOrderByPosition(_) => {}
+                x => panic!(
+                    "Error should be Error::InvalidOrderByPosition. Actual error {:?}",
+                    x
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn sql_order_by_nulls() {
+        let schema = "\
+format: json_array
+filename: .*
+table: logs
+columns:
+    - name: id
+      type: i32
+    - name: value
+      type: string
+";
+        let source = r#"[
+            {"id": 1, "value": "b"}, {"id": 2}, {"id": 3, "value": "a"}, {"id": 4}
+        ]"#;
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        for (order_by, expected) in [
+            ("value, id", [3, 1, 2, 4]),
+            ("value DESC, id", [2, 4, 1, 3]),
+            ("value NULLS FIRST, id", [2, 4, 3, 1]),
+            ("value DESC NULLS LAST, id", [1, 3, 2, 4]),
+            ("value ASC NULLS LAST, id DESC", [3, 1, 4, 2]),
+        ] {
+            let query = format!("SELECT id, value FROM logs ORDER BY {}", order_by);
+            let engine = Engine::with_query(parser.clone(), query).unwrap();
+            let ids: Vec<_> = engine
+                .execute(vec![source])
+                .unwrap()
+                .events
+                .iter()
+                .map(|e| e.values["id"].clone())
+                .collect();
+            let expected: Vec<_> = expected.into_iter().map(Type::Int32).collect();
+            assert_eq!(ids, expected, "{}", order_by);
+        }
+
+        // values of different types are ordered by type, even if they could be compared
+        let (a, b) = (Type::String("x".into()), Type::Int32(1));
+        assert_eq!(compare_for_sort(&a, &b), Ordering::Less);
+        assert_eq!(compare_for_sort(&b, &a), Ordering::Greater);
+        let datetime = Type::DateTime(Utc.ymd(2022, 1, 2).and_hms(0, 0, 0));
+        let (before, after) = (Type::from("2021"), Type::from("2022-01-01"));
+        assert_eq!(compare_for_sort(&before, &after), Ordering::Less);
+        assert_eq!(compare_for_sort(&after, &datetime), Ordering::Less);
+        assert_eq!(compare_for_sort(&before, &datetime), Ordering::Less);
+    }
+
+    #[test]
+    fn sql_null_values() {
+        let schema = "\
+format: json_array
+filename: .*
+table: logs
+columns:
+    - name: id
+      type: i32
+    - name: user
+      type: string
+";
+        let source = r#"[{"id": 1, "user": "ann"}, {"id": 2}, {"id": 3, "user": "bo"}]"#;
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let cases = [
+            (
+                "SELECT id FROM logs WHERE user IS NULL",
+                vec![vec![("id", 2.into())]],
+            ),
+            (
+                "SELECT id FROM logs WHERE user IS NOT NULL AND id > 1",
+                vec![vec![("id", 3.into())]],
+            ),
+            (
+                "SELECT UPPER(user) AS u, LENGTH(user) AS n FROM logs WHERE id <= 2",
+                vec![
+                    vec![("u", "ANN".into()), ("n", 3i64.into())],
+                    vec![("u", Type::Null), ("n", Type::Null)],
+                ],
+            ),
+        ];
+        for (query, expected) in cases {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(
+                table_result.events,
+                generate_typed_events(expected),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn sql_order_by_column_not_in_projection() {
+        let schema = "\
+regex: (?P<index>.+)\t(?P<value>.+)
+filename: .*
+table: logs
+columns:
+    - name: index
+      type: i32
+    - name: value
+      type: i32
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "select index as i from logs order by value";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec!["1\t2"]).err().unwrap() {
+            Error::UnknownColumn(column, _) => assert_eq!(column, "value"),
+            x => panic!("Error should be Error::UnknownColumn. Actual error {:?}", x),
+        }
+    }
+
+    /// Dotted version numbers compared numerically, e.g. 1.10 > 1.9
+    struct VersionType;
+
+    impl CustomType for VersionType {
+        type Value = Vec<u32>;
+
+        fn name(&self) -> &str {
+            "version"
+        }
+
+        fn parse(&self, value: &str) -> Result<Self::Value, String> {
+            value
+                .split('.')
+                .map(|part| {
+                    part.parse()
+                        .map_err(|_| format!("invalid version {}", value))
+                })
+                .collect()
+        }
+
+        fn compare(&self, a: &Self::Value, b: &Self::Value) -> Option<Ordering> {
+            a.partial_cmp(b)
+        }
+
+        fn display(&self, value: &Self::Value) -> String {
+            let parts: Vec<_> = value.iter().map(|part| part.to_string()).collect();
+            parts.join(".")
+        }
+    }
+
+    #[test]
+    fn custom_column_type() {
+        let schema = "\
+regex: (?P<name>.+)\t(?P<version>.+)
+filename: .*
+table: logs
+columns:
+    - name: name
+      type: string
+    - name: version
+      type: version
+";
+        let source = "\
+a\t1.10.0
+b\t1.9.2
+c\t1.2
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let queries = [
+            "select name from logs where version > '1.2.0'",
+            "select name from logs where '1.2.0' < version",
+            "select name from logs where version != '1.2'",
+        ];
+        for query in queries {
+            let mut engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            engine.register_type(VersionType);
+            let table_result = engine.execute(vec![source]).unwrap();
+
+            let events =
+                generate_typed_events(vec![vec![("name", "a".into())], vec![("name", "b".into())]]);
+            assert_eq!(table_result.events, events);
+        }
+
+        let query = "select * from logs order by version desc".to_string();
+        let mut engine = Engine::with_query(parser, query).unwrap();
+        engine.register_type(VersionType);
+        let table_result = engine.execute(vec![source]).unwrap();
+        let versions: Vec<_> = table_result
+            .events
+            .iter()
+            .map(|e| e.values["version"].to_string())
+            .collect();
+        assert_eq!(versions, vec!["1.10.0", "1.9.2", "1.2"]);
+    }
+
+    #[test]
+    fn custom_column_type_not_registered() {
+        let schema = "\
+regex: (?P<version>.+)
+filename: .*
+table: logs
+columns:
+    - name: version
+      type: version
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::new(parser);
+        match engine.execute(vec!["1.0"]).err().unwrap() {
+            Error::UnknownColumnType(column, name) => {
+                assert_eq!(column, "version");
+                assert_eq!(name, "version");
+            }
+            x => panic!(
+                "Error should be Error::UnknownColumnType. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn sql_meta_columns() {
+        let schema = "\
+regex: (?P<level>[A-Z]+) (?P<message>.+)
+meta_columns: true
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+      multiline: true
+";
+        let sources = vec!["INFO start\nERROR boom\n  at main", "noise\nERROR again"];
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let query = "select _file, _line, message from logs where level = 'ERROR' order by _file";
+        let mut engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        engine.set_source_names(vec!["a.log".to_string(), "b.log".to_string()]);
+        let table_result = engine.execute(sources.clone()).unwrap();
+
+        assert_eq!(table_result.columns, ["_file", "_line", "message"]);
+        let events = generate_typed_events(vec![
+            vec![
+                ("_file", "a.log".into()),
+                ("_line", 2i64.into()),
+                ("message", "boom\n  at main".into()),
+            ],
+            vec![
+                ("_file", "b.log".into()),
+                ("_line", 2i64.into()),
+                ("message", "again".into()),
+            ],
+        ]);
+        assert_eq!(table_result.events, events);
+        let counts: Vec<_> = table_result
+            .parse_stats
+            .iter()
+            .map(|stats| {
+                (
+                    stats.file.as_deref(),
+                    stats.lines,
+                    stats.events,
+                    stats.unmatched,
+                )
+            })
+            .collect();
+        assert_eq!(counts, [(Some("a.log"), 3, 2, 0), (Some("b.log"), 2, 1, 1)]);
+
+        // without names the file is unknown
+        let engine = Engine::with_query(parser.clone(), "select * from logs".to_string()).unwrap();
+        let table_result = engine.execute(sources).unwrap();
+        assert_eq!(table_result.columns, ["level", "message", "_file", "_line"]);
+        assert_eq!(table_result.events[0].values["_file"], Type::Null);
+
+        let schema = "\
+regex: (?P<_line>.+)
+meta_columns: true
+filename: .*
+table: logs
+columns:
+    - name: _line
+      type: string
+";
+        match Schema::try_from(schema) {
+            Err(Error::DuplicateColumnName(name)) => assert_eq!(name, "_line"),
+            x => panic!("Error should be Error::DuplicateColumnName. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn query_key_value_columns() {
+        let schema = "\
+format: kv
+filename: .*
+table: logs
+meta_columns: true
+columns:
+    - name: status
+      type: i32
+";
+        let source = "\
+level=info status=200 path=/home
+not a pair
+level=warn status=503 retry=3 msg=\"upstream timed out\"
+status=404";
+        let parser = Parser::new(Schema::try_from(schema).unwrap()).unwrap();
+        let engine = Engine::new(parser.clone());
+        let table_result = engine.execute(vec![source]).unwrap();
+        assert_eq!(
+            table_result.columns,
+            ["status", "level", "msg", "path", "retry", "_file", "_line"]
+        );
+
+        let query = "select status, level, msg from logs where status > 250 order by status";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        let events = generate_typed_events(vec![
+            vec![
+                ("status", 404.into()),
+                ("level", Type::Null),
+                ("msg", Type::Null),
+            ],
+            vec![
+                ("status", 503.into()),
+                ("level", "warn".into()),
+                ("msg", "upstream timed out".into()),
+            ],
+        ]);
+        assert_eq!(table_result.events, events);
+    }
+
+    #[test]
+    fn query_cef_fields() {
+        let schema = "\
+format: cef
+filename: .*
+table: logs
+columns: []
+";
+        let source = "\
+CEF:0|Vendor|Firewall|1.0|100|Blocked|7|src=10.0.0.1 cs1Label=env cs1=prod
+CEF:0|Vendor|Firewall|1.0|200|Allowed|1|src=10.0.0.2 act=allow";
+        let parser = Parser::new(Schema::try_from(schema).unwrap()).unwrap();
+        let query = "select name, src, env, act from logs order by src";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let table_result = engine.execute(vec![source]).unwrap();
+        let events = generate_typed_events(vec![
+            vec![
+                ("name", "Blocked".into()),
+                ("src", "10.0.0.1".into()),
+                ("env", "prod".into()),
+                ("act", Type::Null),
+            ],
+            vec![
+                ("name", "Allowed".into()),
+                ("src", "10.0.0.2".into()),
+                ("env", Type::Null),
+                ("act", "allow".into()),
+            ],
+        ]);
+        assert_eq!(table_result.events, events);
+    }
 }
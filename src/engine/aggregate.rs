@@ -0,0 +1,794 @@
+use crate::engine::tdigest::TDigest;
+use crate::engine::TableResult;
+use crate::error::Error;
+use crate::parser::values::{Event, Type};
+use rust_decimal::Decimal;
+use sqlparser::ast::{Expr, Function, FunctionArg, FunctionArgExpr, GroupByExpr, Select, Value};
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+const AGGREGATES: [&str; 6] = ["COUNT", "SUM", "AVG", "MIN", "MAX", "APPROX_PERCENTILE"];
+
+/// Whether a function is an aggregate, which is calculated once per group instead of per event
+pub(crate) fn is_aggregate(function: &Function) -> bool {
+    let name = function.name.to_string().to_ascii_uppercase();
+    AGGREGATES.contains(&name.as_str())
+}
+
+/// Whether an expression calls an aggregate, which makes its query grouped
+pub(crate) fn contains_aggregate(expr: &Expr) -> bool {
+    let mut aggregates = Vec::new();
+    collect_aggregates(expr, &mut aggregates);
+    !aggregates.is_empty()
+}
+
+/// Find the aggregate calls in an expression. Aggregates can't be nested so their arguments
+/// aren't searched.
+fn collect_aggregates<'a>(expr: &'a Expr, aggregates: &mut Vec<&'a Function>) {
+    match expr {
+        Expr::Function(function) if is_aggregate(function) => aggregates.push(function),
+        Expr::Function(function) => {
+            for arg in &function.args {
+                if let FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) = arg {
+                    collect_aggregates(arg, aggregates);
+                }
+            }
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            collect_aggregates(left, aggregates);
+            collect_aggregates(right, aggregates);
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::Cast { expr, .. }
+        | Expr::Ceil { expr, .. }
+        | Expr::Floor { expr, .. } => collect_aggregates(expr, aggregates),
+        _ => (),
+    }
+}
+
+/// Columns used by an expression outside of aggregate calls and the `grouped` expressions
+fn collect_columns<'a>(expr: &'a Expr, grouped: &[&Expr], columns: &mut Vec<&'a str>) {
+    if grouped.contains(&expr) {
+        return;
+    }
+
+    match expr {
+        Expr::Identifier(identifier) => columns.push(&identifier.value),
+        Expr::Function(function) if is_aggregate(function) => (),
+        Expr::Function(function) => {
+            for arg in &function.args {
+                if let FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) = arg {
+                    collect_columns(arg, grouped, columns);
+                }
+            }
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            collect_columns(left, grouped, columns);
+            collect_columns(right, grouped, columns);
+        }
+        Expr::UnaryOp { expr, .. }
+        | Expr::Nested(expr)
+        | Expr::Cast { expr, .. }
+        | Expr::Ceil { expr, .. }
+        | Expr::Floor { expr, .. } => collect_columns(expr, grouped, columns),
+        Expr::Trim {
+            expr,
+            trim_what,
+            trim_characters,
+            ..
+        } => {
+            collect_columns(expr, grouped, columns);
+            for characters in trim_what.as_deref().into_iter() {
+                collect_columns(characters, grouped, columns);
+            }
+            for characters in trim_characters.iter().flatten() {
+                collect_columns(characters, grouped, columns);
+            }
+        }
+        Expr::Substring {
+            expr,
+            substring_from,
+            substring_for,
+            ..
+        } => {
+            collect_columns(expr, grouped, columns);
+            for expr in substring_from.iter().chain(substring_for) {
+                collect_columns(expr, grouped, columns);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Expressions in `GROUP BY`. `GROUP BY ALL` isn't supported.
+pub(crate) fn group_by_expressions(select: &Select) -> Result<&[Expr], Error> {
+    match &select.group_by {
+        GroupByExpr::Expressions(exprs) => Ok(exprs),
+        GroupByExpr::All => Err(Error::UnsupportedExpression("GROUP BY ALL".to_string())),
+    }
+}
+
+impl TableResult {
+    /// Group events by the `GROUP BY` expressions and evaluate the projection once per group.
+    /// Aggregate results are stored in each group's event under the aggregate's SQL, which is
+    /// where [`TableResult::evaluate`] looks them up.
+    pub(crate) fn group(
+        mut self,
+        select: &Select,
+        projections: Vec<(String, &Expr)>,
+    ) -> Result<TableResult, Error> {
+        let grouping_sets = grouping_sets(group_by_expressions(select)?, &projections)?;
+        // every expression a grouping set groups by, which are NULL in the other sets' rows
+        let mut group_by: Vec<&Expr> = Vec::new();
+        for expr in grouping_sets.iter().flatten() {
+            if !group_by.contains(expr) {
+                group_by.push(expr);
+            }
+        }
+        let group_by_columns: HashSet<_> = group_by
+            .iter()
+            .filter_map(|expr| match expr {
+                Expr::Identifier(identifier) => Some(identifier.value.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        let mut aggregates = Vec::new();
+        let having = select.having.iter();
+        for expr in projections.iter().map(|(_, expr)| *expr).chain(having) {
+            collect_aggregates(expr, &mut aggregates);
+
+            // like postgres, everything else has to be grouped so each group has one value
+            let mut columns = Vec::new();
+            collect_columns(expr, &group_by, &mut columns);
+            if let Some(column) = columns.iter().find(|c| !group_by_columns.contains(*c)) {
+                return Err(Error::UngroupedColumn(column.to_string()));
+            }
+        }
+
+        let mut grouped_events = Vec::new();
+        for grouping_set in &grouping_sets {
+            let mut groups: Vec<Vec<&Event>> = Vec::new();
+            let mut group_indexes = HashMap::new();
+            for event in &self.events {
+                self.cancellation.check()?;
+                let key = grouping_set
+                    .iter()
+                    .map(|expr| self.evaluate(expr, event))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let index = *group_indexes.entry(GroupKey(key)).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[index].push(event);
+            }
+
+            // without GROUP BY the whole table is one group, even if it's empty
+            if grouping_set.is_empty() && groups.is_empty() {
+                groups.push(Vec::new());
+            }
+
+            let rolled_up: Vec<_> = group_by
+                .iter()
+                .filter(|expr| !grouping_set.contains(expr))
+                .collect();
+            for group in &groups {
+                let mut values = group
+                    .first()
+                    .map(|event| event.values.clone())
+                    .unwrap_or_default();
+                for expr in &rolled_up {
+                    if let Expr::Identifier(identifier) = expr {
+                        values.insert(identifier.value.clone(), Type::Null);
+                    }
+                }
+                for function in &aggregates {
+                    values.insert(function.to_string(), self.aggregate(function, group)?);
+                }
+                let event = Event {
+                    values,
+                    extra_text: None,
+                };
+
+                if let Some(having) = &select.having {
+                    if !self.evaluate_predicate(having, &event)? {
+                        continue;
+                    }
+                }
+
+                let mut projected_values = HashMap::new();
+                for (column, expr) in &projections {
+                    let value = if rolled_up.contains(&expr) {
+                        Type::Null
+                    } else {
+                        self.evaluate(expr, &event)?
+                    };
+                    projected_values.insert(column.clone(), value);
+                }
+                grouped_events.push(Event {
+                    values: projected_values,
+                    extra_text: None,
+                });
+            }
+        }
+
+        self.events = grouped_events;
+        self.units = self.projection_units(&projections);
+        self.columns = projections.into_iter().map(|(column, _)| column).collect();
+        Ok(self)
+    }
+
+    /// Calculate an aggregate over a group of events, or the events matching its `FILTER`
+    fn aggregate(&self, function: &Function, events: &[&Event]) -> Result<Type, Error> {
+        let name = function.name.to_string().to_ascii_uppercase();
+        let mut filtered = Vec::new();
+        let events = match &function.filter {
+            Some(filter) => {
+                for event in events {
+                    if self.evaluate_predicate(filter, event)? {
+                        filtered.push(*event);
+                    }
+                }
+                filtered.as_slice()
+            }
+            None => events,
+        };
+        let percentile = name == "APPROX_PERCENTILE";
+        let (expr, percentile) = match function.args.as_slice() {
+            [FunctionArg::Unnamed(FunctionArgExpr::Wildcard)] if name == "COUNT" => {
+                return Ok(Type::Int64(events.len() as i64));
+            }
+            [FunctionArg::Unnamed(FunctionArgExpr::Expr(expr))] if !percentile => (expr, None),
+            [FunctionArg::Unnamed(FunctionArgExpr::Expr(expr)), FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(Value::Number(
+                fraction,
+                _,
+            ))))]
+                if percentile =>
+            {
+                let fraction = f64::from_str(fraction)
+                    .ok()
+                    .filter(|fraction| (0.0..=1.0).contains(fraction))
+                    .ok_or_else(|| Error::InvalidFunctionArguments(name.clone()))?;
+                (expr, Some(fraction))
+            }
+            _ => return Err(Error::InvalidFunctionArguments(name)),
+        };
+
+        let mut values = Vec::with_capacity(events.len());
+        for event in events {
+            let value = self.evaluate(expr, event)?;
+            if value != Type::Null {
+                values.push(value);
+            }
+        }
+        if function.distinct {
+            let mut seen = HashSet::new();
+            values.retain(|value| seen.insert(GroupKey(vec![value.clone()])));
+        }
+
+        match name.as_str() {
+            "COUNT" => Ok(Type::Int64(values.len() as i64)),
+            "SUM" => sum(&name, &values),
+            "AVG" => match sum(&name, &values)? {
+                Type::Decimal(total) => Ok(total
+                    .checked_div(Decimal::from(values.len()))
+                    .map_or(Type::Null, Type::Decimal)),
+                total => match total.as_f64() {
+                    Some(total) => Ok(Type::Double(total / values.len() as f64)),
+                    None => Ok(Type::Null),
+                },
+            },
+            "MIN" | "MAX" => {
+                let mut values = values.into_iter();
+                let mut result = match values.next() {
+                    Some(value) => value,
+                    None => return Ok(Type::Null),
+                };
+                for value in values {
+                    let ordering = value
+                        .compare(&result)
+                        .ok_or_else(|| Error::InvalidComparison(value.clone(), result.clone()))?;
+                    if ordering == std::cmp::Ordering::Less && name == "MIN"
+                        || ordering == std::cmp::Ordering::Greater && name == "MAX"
+                    {
+                        result = value;
+                    }
+                }
+                Ok(result)
+            }
+            "APPROX_PERCENTILE" => {
+                let mut digest = TDigest::default();
+                for value in &values {
+                    let value = value
+                        .as_f64()
+                        .ok_or_else(|| Error::InvalidFunctionArguments(name.clone()))?;
+                    digest.add(value);
+                }
+                let percentile = percentile.unwrap_or_default();
+                Ok(digest.quantile(percentile).map_or(Type::Null, Type::Double))
+            }
+            _ => Err(Error::UnknownFunction(name)),
+        }
+    }
+}
+
+/// Values a group is keyed on. Values of different types are different keys, so 1 and '1' are
+/// separate groups, and like postgres NaN is grouped with itself.
+struct GroupKey(Vec<Type>);
+
+impl PartialEq for GroupKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self.0.iter().zip(&other.0).all(|(a, b)| match (a, b) {
+                (Type::Float(a), Type::Float(b)) => a == b || a.is_nan() && b.is_nan(),
+                (Type::Double(a), Type::Double(b)) => a == b || a.is_nan() && b.is_nan(),
+                _ => a == b,
+            })
+    }
+}
+
+impl Eq for GroupKey {}
+
+impl Hash for GroupKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for value in &self.0 {
+            std::mem::discriminant(value).hash(state);
+            match value {
+                Type::String(x) => x.hash(state),
+                Type::Int32(x) => x.hash(state),
+                Type::Int64(x) => x.hash(state),
+                Type::UInt32(x) => x.hash(state),
+                Type::UInt64(x) => x.hash(state),
+                Type::Bool(x) => x.hash(state),
+                Type::Float(x) => hash_float(*x as f64, state),
+                Type::Double(x) => hash_float(*x, state),
+                Type::Decimal(x) => x.hash(state),
+                Type::DateTime(x) => x.hash(state),
+                Type::DateTimeTz(x) => x.hash(state),
+                Type::Interval(x) => x.hash(state),
+                // equal custom values are expected to display the same
+                Type::Custom(x) => x.to_string().hash(state),
+                Type::Null => (),
+            }
+        }
+    }
+}
+
+/// Hash every NaN the same, and 0.0 the same as -0.0 since they're equal
+fn hash_float<H: Hasher>(value: f64, state: &mut H) {
+    match value {
+        _ if value.is_nan() => f64::NAN.to_bits().hash(state),
+        _ => (value + 0.0).to_bits().hash(state),
+    }
+}
+
+/// Integers sum to an i64, decimals to an exact decimal and other numbers to an f64. The sum of
+/// no values is NULL.
+fn sum(name: &str, values: &[Type]) -> Result<Type, Error> {
+    if values.is_empty() {
+        return Ok(Type::Null);
+    }
+
+    let mut integer_total = Some(0i64);
+    let mut decimal_total = values
+        .iter()
+        .any(|value| matches!(value, Type::Decimal(_)))
+        .then_some(Decimal::ZERO);
+    let mut total = 0.0;
+    for value in values {
+        decimal_total = decimal_total.and_then(|t| t.checked_add(value.as_decimal()?));
+        integer_total = match value {
+            Type::Int32(x) => integer_total.and_then(|t| t.checked_add(*x as i64)),
+            Type::Int64(x) => integer_total.and_then(|t| t.checked_add(*x)),
+            Type::UInt32(x) => integer_total.and_then(|t| t.checked_add(*x as i64)),
+            Type::UInt64(x) => integer_total.and_then(|t| t.checked_add(i64::try_from(*x).ok()?)),
+            _ => None,
+        };
+        total += value
+            .as_f64()
+            .ok_or_else(|| Error::InvalidFunctionArguments(name.to_string()))?;
+    }
+
+    if let Some(total) = decimal_total {
+        return Ok(Type::Decimal(total));
+    }
+    Ok(integer_total.map_or(Type::Double(total), Type::Int64))
+}
+
+/// Expand `ROLLUP`, `CUBE` and `GROUPING SETS` into the sets of expressions to group by. Plain
+/// expressions are in every set, so `GROUP BY host, ROLLUP (level)` groups by `host, level`
+/// and then by `host`. Without `GROUP BY` there's a single empty set.
+fn grouping_sets<'a>(
+    group_by: &'a [Expr],
+    projections: &[(String, &'a Expr)],
+) -> Result<Vec<Vec<&'a Expr>>, Error> {
+    let mut sets = vec![Vec::new()];
+    for element in group_by {
+        let choices: Vec<Vec<&Expr>> = match element {
+            Expr::Rollup(items) => (0..=items.len())
+                .rev()
+                .map(|length| items[..length].iter().flatten().collect())
+                .collect(),
+            Expr::Cube(items) if items.len() > 10 => {
+                return Err(Error::UnsupportedExpression(element.to_string()));
+            }
+            Expr::Cube(items) => (0..1usize << items.len())
+                .rev()
+                .map(|mask| {
+                    items
+                        .iter()
+                        .enumerate()
+                        .filter(|(index, _)| mask & (1 << (items.len() - 1 - index)) != 0)
+                        .flat_map(|(_, item)| item)
+                        .collect()
+                })
+                .collect(),
+            Expr::GroupingSets(items) => items.iter().map(|set| set.iter().collect()).collect(),
+            expr => vec![vec![expr]],
+        };
+        sets = sets
+            .iter()
+            .flat_map(|set| {
+                choices
+                    .iter()
+                    .map(move |choice| set.iter().chain(choice).copied().collect())
+            })
+            .collect();
+    }
+
+    sets.into_iter()
+        .map(|set: Vec<&Expr>| {
+            set.into_iter()
+                .map(|expr| resolve_group_by(expr, projections))
+                .collect()
+        })
+        .collect()
+}
+
+/// `GROUP BY` can refer to a projection by alias or 1-based position, like `ORDER BY`
+fn resolve_group_by<'a>(
+    expr: &'a Expr,
+    projections: &[(String, &'a Expr)],
+) -> Result<&'a Expr, Error> {
+    match expr {
+        Expr::Value(Value::Number(position, _)) => usize::from_str(position)
+            .ok()
+            .filter(|position| (1..=projections.len()).contains(position))
+            .map(|position| projections[position - 1].1)
+            .ok_or_else(|| Error::InvalidGroupByPosition(position.clone())),
+        Expr::Identifier(identifier) => Ok(projections
+            .iter()
+            .find(|(column, expr)| {
+                *column == identifier.value && !matches!(expr, Expr::Identifier(_))
+            })
+            .map_or(expr, |(_, expr)| *expr)),
+        _ => Ok(expr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GroupKey;
+    use crate::engine::tests::generate_typed_events;
+    use crate::error::Error;
+    use crate::parser::values::Type;
+    use crate::schema::Schema;
+    use crate::{Engine, Parser};
+    use rust_decimal::Decimal;
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    const SCHEMA: &str = "\
+regex: (?P<host>\\S+) (?P<level>\\S+) (?P<bytes>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: level
+      type: string
+    - name: bytes
+      type: i64
+";
+
+    const SOURCE: &str = "\
+a ERROR 10
+b INFO 20
+a INFO 30
+c ERROR 40
+a ERROR 50
+";
+
+    fn execute(query: &str) -> Result<Vec<crate::parser::values::Event>, Error> {
+        let schema = Schema::try_from(SCHEMA).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser, query.to_string())?;
+        Ok(engine.execute(vec![SOURCE])?.events)
+    }
+
+    #[test]
+    fn group_by_with_aggregates() {
+        let query = "SELECT host, COUNT(*), SUM(bytes) AS total, AVG(bytes) AS average, \
+            MIN(level) AS min_level, MAX(bytes) FROM logs GROUP BY host ORDER BY host";
+        let expected = generate_typed_events(vec![
+            vec![
+                ("host", "a".into()),
+                ("COUNT(*)", 3i64.into()),
+                ("total", 90i64.into()),
+                ("average", 30f64.into()),
+                ("min_level", "ERROR".into()),
+                ("MAX(bytes)", 50i64.into()),
+            ],
+            vec![
+                ("host", "b".into()),
+                ("COUNT(*)", 1i64.into()),
+                ("total", 20i64.into()),
+                ("average", 20f64.into()),
+                ("min_level", "INFO".into()),
+                ("MAX(bytes)", 20i64.into()),
+            ],
+            vec![
+                ("host", "c".into()),
+                ("COUNT(*)", 1i64.into()),
+                ("total", 40i64.into()),
+                ("average", 40f64.into()),
+                ("min_level", "ERROR".into()),
+                ("MAX(bytes)", 40i64.into()),
+            ],
+        ]);
+        assert_eq!(execute(query).unwrap(), expected);
+    }
+
+    #[test]
+    fn group_by_having_and_order_by_aggregate() {
+        let query = "SELECT level, COUNT(DISTINCT host) FROM logs WHERE bytes > 10 \
+            GROUP BY 1 HAVING SUM(bytes) > 45 ORDER BY COUNT(DISTINCT host) DESC";
+        let expected = generate_typed_events(vec![
+            vec![
+                ("level", "INFO".into()),
+                ("COUNT(DISTINCT host)", 2i64.into()),
+            ],
+            vec![
+                ("level", "ERROR".into()),
+                ("COUNT(DISTINCT host)", 2i64.into()),
+            ],
+        ]);
+        assert_eq!(execute(query).unwrap(), expected);
+    }
+
+    #[test]
+    fn group_by_expression_alias() {
+        let query = "SELECT LOWER(level) AS lower_level, COUNT(*) AS count FROM logs \
+            GROUP BY lower_level ORDER BY lower_level";
+        let expected = generate_typed_events(vec![
+            vec![("lower_level", "error".into()), ("count", 3i64.into())],
+            vec![("lower_level", "info".into()), ("count", 2i64.into())],
+        ]);
+        assert_eq!(execute(query).unwrap(), expected);
+    }
+
+    #[test]
+    fn aggregate_filters() {
+        let query =
+            "SELECT host, COUNT(*) AS total, COUNT(*) FILTER (WHERE level = 'ERROR') AS errors, \
+            SUM(bytes) FILTER (WHERE level = 'ERROR' AND bytes > 10) FROM logs GROUP BY host \
+            HAVING COUNT(*) FILTER (WHERE level = 'ERROR') > 0 ORDER BY host";
+        let expected = generate_typed_events(vec![
+            vec![
+                ("host", "a".into()),
+                ("total", 3i64.into()),
+                ("errors", 2i64.into()),
+                (
+                    "SUM(bytes) FILTER (WHERE level = 'ERROR' AND bytes > 10)",
+                    50i64.into(),
+                ),
+            ],
+            vec![
+                ("host", "c".into()),
+                ("total", 1i64.into()),
+                ("errors", 1i64.into()),
+                (
+                    "SUM(bytes) FILTER (WHERE level = 'ERROR' AND bytes > 10)",
+                    40i64.into(),
+                ),
+            ],
+        ]);
+        assert_eq!(execute(query).unwrap(), expected);
+
+        match execute("SELECT LOWER(host) FILTER (WHERE bytes > 1) FROM logs") {
+            Err(Error::UnsupportedExpression(_)) => (),
+            x => panic!(
+                "Error should be Error::UnsupportedExpression. Actual {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn approx_percentiles() {
+        let query = "SELECT level, APPROX_PERCENTILE(bytes, 0.5) AS p50, \
+            APPROX_PERCENTILE(bytes, 1) AS p100 FROM logs GROUP BY level ORDER BY level";
+        let expected = generate_typed_events(vec![
+            vec![
+                ("level", "ERROR".into()),
+                ("p50", 40f64.into()),
+                ("p100", 50f64.into()),
+            ],
+            vec![
+                ("level", "INFO".into()),
+                ("p50", 25f64.into()),
+                ("p100", 30f64.into()),
+            ],
+        ]);
+        assert_eq!(execute(query).unwrap(), expected);
+
+        for query in [
+            "SELECT APPROX_PERCENTILE(bytes) FROM logs",
+            "SELECT APPROX_PERCENTILE(bytes, 1.5) FROM logs",
+            "SELECT APPROX_PERCENTILE(bytes, bytes) FROM logs",
+            "SELECT APPROX_PERCENTILE(host, 0.5) FROM logs",
+        ] {
+            match execute(query) {
+                Err(Error::InvalidFunctionArguments(name)) => {
+                    assert_eq!(name, "APPROX_PERCENTILE")
+                }
+                x => panic!(
+                    "Error should be Error::InvalidFunctionArguments. Actual {:?}",
+                    x
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn group_by_rollup_and_grouping_sets() {
+        let row = |host: Type, level: Type, count: i64| {
+            vec![
+                ("host", host),
+                ("level", level),
+                ("count", Type::Int64(count)),
+            ]
+        };
+        let cases = [
+            (
+                "SELECT host, level, COUNT(*) AS count FROM logs \
+                    GROUP BY ROLLUP (host, level) ORDER BY host, level",
+                vec![
+                    row("a".into(), "ERROR".into(), 2),
+                    row("a".into(), "INFO".into(), 1),
+                    row("a".into(), Type::Null, 3),
+                    row("b".into(), "INFO".into(), 1),
+                    row("b".into(), Type::Null, 1),
+                    row("c".into(), "ERROR".into(), 1),
+                    row("c".into(), Type::Null, 1),
+                    row(Type::Null, Type::Null, 5),
+                ],
+            ),
+            (
+                "SELECT host, level, COUNT(*) AS count FROM logs \
+                    GROUP BY GROUPING SETS ((host), (level), ()) HAVING COUNT(*) > 1 \
+                    ORDER BY host, level",
+                vec![
+                    row("a".into(), Type::Null, 3),
+                    row(Type::Null, "ERROR".into(), 3),
+                    row(Type::Null, "INFO".into(), 2),
+                    row(Type::Null, Type::Null, 5),
+                ],
+            ),
+            (
+                "SELECT host, level, COUNT(*) AS count FROM logs WHERE host <> 'a' \
+                    GROUP BY CUBE (host, level) ORDER BY host, level",
+                vec![
+                    row("b".into(), "INFO".into(), 1),
+                    row("b".into(), Type::Null, 1),
+                    row("c".into(), "ERROR".into(), 1),
+                    row("c".into(), Type::Null, 1),
+                    row(Type::Null, "ERROR".into(), 1),
+                    row(Type::Null, "INFO".into(), 1),
+                    row(Type::Null, Type::Null, 2),
+                ],
+            ),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(
+                execute(query).unwrap(),
+                generate_typed_events(expected),
+                "{}",
+                query
+            );
+        }
+
+        // plain expressions are in every grouping set
+        let query = "SELECT host, UPPER(level) AS level, COUNT(*) AS count FROM logs \
+            WHERE host = 'a' GROUP BY host, ROLLUP (2) ORDER BY level";
+        let expected = generate_typed_events(vec![
+            row("a".into(), "ERROR".into(), 2),
+            row("a".into(), "INFO".into(), 1),
+            row("a".into(), Type::Null, 3),
+        ]);
+        assert_eq!(execute(query).unwrap(), expected);
+    }
+
+    #[test]
+    fn aggregate_without_group_by() {
+        let query = "SELECT COUNT(*), SUM(bytes), MAX(host) FROM logs WHERE host = 'z'";
+        let expected = generate_typed_events(vec![vec![
+            ("COUNT(*)", 0i64.into()),
+            ("SUM(bytes)", Type::Null),
+            ("MAX(host)", Type::Null),
+        ]]);
+        assert_eq!(execute(query).unwrap(), expected);
+    }
+
+    #[test]
+    fn group_keys() {
+        let keys: HashSet<_> = [
+            vec![Type::Int32(1)],
+            vec!["1".into()],
+            vec![Type::Double(f64::NAN)],
+            vec![Type::Double(-f64::NAN)],
+            vec![Type::Double(0.0)],
+            vec![Type::Double(-0.0)],
+            vec![Type::Int32(1), Type::Null],
+            vec![Type::Int32(1), Type::Null],
+        ]
+        .into_iter()
+        .map(GroupKey)
+        .collect();
+        assert_eq!(keys.len(), 5);
+    }
+
+    #[test]
+    fn invalid_grouping() {
+        match execute("SELECT host, level, COUNT(*) FROM logs GROUP BY host") {
+            Err(Error::UngroupedColumn(column)) => assert_eq!(column, "level"),
+            x => panic!("Error should be Error::UngroupedColumn. Actual {:?}", x),
+        }
+        match execute("SELECT host, COUNT(*) FROM logs GROUP BY host HAVING level = 'ERROR'") {
+            Err(Error::UngroupedColumn(column)) => assert_eq!(column, "level"),
+            x => panic!("Error should be Error::UngroupedColumn. Actual {:?}", x),
+        }
+        match execute("SELECT host FROM logs WHERE COUNT(*) > 1") {
+            Err(Error::MisplacedAggregate(function)) => assert_eq!(function, "COUNT(*)"),
+            x => panic!("Error should be Error::MisplacedAggregate. Actual {:?}", x),
+        }
+        match execute("SELECT host FROM logs GROUP BY 2") {
+            Err(Error::InvalidGroupByPosition(position)) => assert_eq!(position, "2"),
+            x => panic!(
+                "Error should be Error::InvalidGroupByPosition. Actual {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn decimal_aggregates() {
+        let schema = "\
+regex: (?P<amount>.+)
+filename: .*
+table: logs
+columns:
+    - name: amount
+      type: decimal
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let query = "SELECT SUM(amount) AS total, AVG(amount) AS average, MAX(amount) AS most, \
+            ROUND(SUM(amount) * 1.075, 2) AS taxed FROM logs";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        let events = engine
+            .execute(vec!["0.1\n0.2\n0.3\n19.99\n"])
+            .unwrap()
+            .events;
+
+        let decimal = |value: &str| Type::Decimal(Decimal::from_str(value).unwrap());
+        let expected = generate_typed_events(vec![vec![
+            ("total", decimal("20.59")),
+            ("average", decimal("5.1475")),
+            ("most", decimal("19.99")),
+            ("taxed", decimal("22.13")),
+        ]]);
+        assert_eq!(events, expected);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Stops a running query from another thread, e.g. when the user presses Ctrl-C. Clones share
+/// the same flag, so keep one and pass another to [`Engine::execute_with_cancellation`] or
+/// [`Engine::query_with_cancellation`], which return [`Error::Cancelled`] soon after
+/// [`CancellationToken::cancel`] is called.
+///
+/// [`Engine::execute_with_cancellation`]: crate::Engine::execute_with_cancellation
+/// [`Engine::query_with_cancellation`]: crate::Engine::query_with_cancellation
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Checked once per line or event while scanning
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_cancellation() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(clone.check().is_ok());
+
+        token.cancel();
+        assert!(clone.is_cancelled());
+        match clone.check() {
+            Err(Error::Cancelled) => (),
+            x => panic!("Error should be Error::Cancelled. Actual {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,525 @@
+use crate::engine::aggregate::is_aggregate;
+use crate::engine::functions::{json_document, json_value};
+use crate::engine::TableResult;
+use crate::error::Error;
+use crate::parser::values::{interval_unit, parse_interval, Event, Type};
+use crate::schema::ColumnType;
+use sqlparser::ast::{
+    BinaryOperator, DataType, DateTimeField, Expr, FunctionArg, FunctionArgExpr, Interval,
+    TrimWhereField, UnaryOperator, Value,
+};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+impl TableResult {
+    /// Evaluate an expression against a single event
+    pub(crate) fn evaluate(&self, expr: &Expr, event: &Event) -> Result<Type, Error> {
+        match expr {
+            Expr::Identifier(identifier) => event
+                .values
+                .get(identifier.value.as_str())
+                .cloned()
+                .ok_or_else(|| self.unknown_column(&identifier.value)),
+            // `payload.user.id` looks up keys in a JSON column
+            Expr::CompoundIdentifier(idents) => {
+                let (column, keys) = idents.split_first().unwrap();
+                let value = event
+                    .values
+                    .get(column.value.as_str())
+                    .cloned()
+                    .ok_or_else(|| self.unknown_column(&column.value))?;
+                let keys: Vec<_> = keys
+                    .iter()
+                    .map(|key| Type::String(key.value.clone()))
+                    .collect();
+                evaluate_index(value, &keys)
+                    .ok_or_else(|| Error::UnsupportedExpression(expr.to_string()))
+            }
+            Expr::Value(value) => evaluate_literal(value),
+            Expr::Interval(interval) => evaluate_interval(interval),
+            Expr::Nested(nested) => self.evaluate(nested, event),
+            Expr::Function(function) if is_aggregate(function) => event
+                .values
+                .get(&function.to_string())
+                .cloned()
+                .ok_or_else(|| Error::MisplacedAggregate(function.to_string())),
+            Expr::Function(function) if function.filter.is_some() => {
+                // FILTER only makes sense for aggregates
+                Err(Error::UnsupportedExpression(function.to_string()))
+            }
+            Expr::Function(function) => {
+                let args = function
+                    .args
+                    .iter()
+                    .map(|arg| match arg {
+                        FunctionArg::Unnamed(FunctionArgExpr::Expr(arg)) => {
+                            self.evaluate(arg, event)
+                        }
+                        _ => Err(Error::UnsupportedExpression(arg.to_string())),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.functions.call(&function.name.to_string(), &args)
+            }
+            Expr::Trim {
+                expr,
+                trim_where,
+                trim_what,
+                trim_characters,
+            } => {
+                let mut args = vec![self.evaluate(expr, event)?];
+                let characters = trim_what.as_deref().into_iter();
+                for characters in characters.chain(trim_characters.iter().flatten()) {
+                    args.push(self.evaluate(characters, event)?);
+                }
+                let name = match trim_where {
+                    None | Some(TrimWhereField::Both) => "TRIM",
+                    Some(TrimWhereField::Leading) => "LTRIM",
+                    Some(TrimWhereField::Trailing) => "RTRIM",
+                };
+                self.functions.call(name, &args)
+            }
+            Expr::Substring {
+                expr,
+                substring_from,
+                substring_for,
+                ..
+            } => {
+                let mut args = vec![self.evaluate(expr, event)?];
+                args.push(match substring_from {
+                    Some(from) => self.evaluate(from, event)?,
+                    None => Type::Int64(1),
+                });
+                if let Some(length) = substring_for {
+                    args.push(self.evaluate(length, event)?);
+                }
+                self.functions.call("SUBSTR", &args)
+            }
+            Expr::Ceil {
+                expr,
+                field: DateTimeField::NoDateTime,
+            } => {
+                let value = self.evaluate(expr, event)?;
+                self.functions.call("CEIL", &[value])
+            }
+            Expr::Floor {
+                expr,
+                field: DateTimeField::NoDateTime,
+            } => {
+                let value = self.evaluate(expr, event)?;
+                self.functions.call("FLOOR", &[value])
+            }
+            Expr::Cast {
+                expr, data_type, ..
+            } => {
+                let value = self.evaluate(expr, event)?;
+                self.cast(value, data_type)
+            }
+            Expr::UnaryOp {
+                op: UnaryOperator::Not,
+                expr,
+            } => match self.evaluate(expr, event)? {
+                Type::Bool(value) => Ok(Type::Bool(!value)),
+                Type::Null => Ok(Type::Null),
+                value => Err(Error::InvalidFilter(value)),
+            },
+            Expr::UnaryOp {
+                op: UnaryOperator::Minus,
+                expr,
+            } => match self.evaluate(expr, event)? {
+                Type::Interval(value) => Ok(Type::Interval(-value)),
+                Type::Int32(value) => value
+                    .checked_neg()
+                    .map(Type::Int32)
+                    .ok_or_else(|| Error::ArithmeticOverflow(format!("-({})", value))),
+                Type::Int64(value) => value
+                    .checked_neg()
+                    .map(Type::Int64)
+                    .ok_or_else(|| Error::ArithmeticOverflow(format!("-({})", value))),
+                Type::UInt32(value) => Ok(Type::Int64(-(value as i64))),
+                Type::UInt64(value) => Ok(i64::try_from(value)
+                    .map_or(Type::Double(-(value as f64)), |value| Type::Int64(-value))),
+                Type::Float(value) => Ok(Type::Float(-value)),
+                Type::Double(value) => Ok(Type::Double(-value)),
+                Type::Decimal(value) => Ok(Type::Decimal(-value)),
+                Type::Null => Ok(Type::Null),
+                _ => Err(Error::UnsupportedExpression(expr.to_string())),
+            },
+            Expr::BinaryOp { left, op, right } => self.evaluate_binary_op(left, op, right, event),
+            Expr::InList {
+                expr,
+                list,
+                negated,
+            } => {
+                let value = self.evaluate(expr, event)?;
+                let list = list
+                    .iter()
+                    .map(|item| self.evaluate(item, event))
+                    .collect::<Result<Vec<_>, _>>()?;
+                evaluate_in(value, list.iter(), *negated)
+            }
+            Expr::InSubquery {
+                expr,
+                subquery,
+                negated,
+            } => {
+                let value = self.evaluate(expr, event)?;
+                let table = self.subquery(subquery)?;
+                let column = match table.columns.as_slice() {
+                    [column] => column,
+                    columns => return Err(Error::SubqueryColumnCount(columns.len())),
+                };
+                let values = table.events.iter().map(|event| &event.values[column]);
+                evaluate_in(value, values, *negated)
+            }
+            Expr::Exists { subquery, negated } => {
+                let exists = !self.subquery(subquery)?.events.is_empty();
+                Ok(Type::Bool(exists != *negated))
+            }
+            Expr::IsNull(expr) => Ok(Type::Bool(self.evaluate(expr, event)? == Type::Null)),
+            Expr::IsNotNull(expr) => Ok(Type::Bool(self.evaluate(expr, event)? != Type::Null)),
+            Expr::ArrayIndex { obj, indexes } => {
+                let value = self.evaluate(obj, event)?;
+                let keys = indexes
+                    .iter()
+                    .map(|index| self.evaluate(index, event))
+                    .collect::<Result<Vec<_>, _>>()?;
+                evaluate_index(value, &keys)
+                    .ok_or_else(|| Error::UnsupportedExpression(expr.to_string()))
+            }
+            _ => Err(Error::UnsupportedExpression(expr.to_string())),
+        }
+    }
+
+    /// Evaluate an expression that is expected to be a boolean, such as a `WHERE` clause. NULL
+    /// doesn't match.
+    pub(crate) fn evaluate_predicate(&self, expr: &Expr, event: &Event) -> Result<bool, Error> {
+        match self.evaluate(expr, event)? {
+            Type::Bool(value) => Ok(value),
+            Type::Null => Ok(false),
+            value => Err(Error::InvalidFilter(value)),
+        }
+    }
+
+    /// Convert a value for `CAST(expr AS type)`. Custom types registered with the parser can be
+    /// used by name, e.g. `CAST(col AS uuid)`.
+    fn cast(&self, value: Type, data_type: &DataType) -> Result<Type, Error> {
+        let column_type = match data_type {
+            DataType::Char(_) | DataType::Varchar(_) | DataType::Text | DataType::String(_) => {
+                ColumnType::String
+            }
+            DataType::TinyInt(_) | DataType::SmallInt(_) | DataType::Int(_) => ColumnType::Int32,
+            DataType::BigInt(_) => ColumnType::Int64,
+            DataType::UnsignedTinyInt(_)
+            | DataType::UnsignedSmallInt(_)
+            | DataType::UnsignedInt(_) => ColumnType::UInt32,
+            DataType::UnsignedBigInt(_) => ColumnType::UInt64,
+            DataType::Boolean => ColumnType::Bool,
+            DataType::Real => ColumnType::Float,
+            DataType::Float(_) | DataType::Double => ColumnType::Double,
+            DataType::Decimal(_) | DataType::Dec(_) | DataType::Numeric(_) => ColumnType::Decimal,
+            DataType::Timestamp(_, _) => ColumnType::DateTime,
+            DataType::Uuid => ColumnType::Custom("uuid".to_string()),
+            DataType::JSON => ColumnType::Custom("json".to_string()),
+            DataType::Custom(name, _) if name.to_string().eq_ignore_ascii_case("percent") => {
+                ColumnType::Percent
+            }
+            DataType::Custom(name, _) => ColumnType::Custom(name.to_string()),
+            _ => return Err(Error::UnsupportedExpression(data_type.to_string())),
+        };
+
+        let cast = match &column_type {
+            ColumnType::Custom(name) => match &value {
+                Type::Custom(custom) if custom.type_name() == name => Some(value.clone()),
+                _ => match self.parser.custom_types.parse(name, &value.to_string()) {
+                    Some(parsed) => parsed.ok().map(Type::Custom),
+                    None => return Err(Error::UnsupportedExpression(data_type.to_string())),
+                },
+            },
+            _ => value.cast(&column_type),
+        };
+        cast.ok_or(Error::InvalidCast(value, column_type))
+    }
+
+    fn evaluate_binary_op(
+        &self,
+        left: &Expr,
+        op: &BinaryOperator,
+        right: &Expr,
+        event: &Event,
+    ) -> Result<Type, Error> {
+        match op {
+            BinaryOperator::And => Ok(Type::Bool(
+                self.evaluate_predicate(left, event)? && self.evaluate_predicate(right, event)?,
+            )),
+            BinaryOperator::Or => Ok(Type::Bool(
+                self.evaluate_predicate(left, event)? || self.evaluate_predicate(right, event)?,
+            )),
+            BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Gt
+            | BinaryOperator::Lt
+            | BinaryOperator::GtEq
+            | BinaryOperator::LtEq => {
+                let left_value = self.evaluate(left, event)?;
+                let right_value = self.evaluate(right, event)?;
+                if left_value == Type::Null || right_value == Type::Null {
+                    return Ok(Type::Null);
+                }
+
+                let left = coerce_quoted_literal(left, left_value, &right_value);
+                let right = coerce_quoted_literal(right, right_value, &left);
+                let ordering = left
+                    .compare(&right)
+                    .ok_or_else(|| Error::InvalidComparison(left.clone(), right.clone()))?;
+                let result = match op {
+                    BinaryOperator::Eq => ordering == Ordering::Equal,
+                    BinaryOperator::NotEq => ordering != Ordering::Equal,
+                    BinaryOperator::Gt => ordering == Ordering::Greater,
+                    BinaryOperator::Lt => ordering == Ordering::Less,
+                    BinaryOperator::GtEq => ordering != Ordering::Less,
+                    _ => ordering != Ordering::Greater,
+                };
+                Ok(Type::Bool(result))
+            }
+            BinaryOperator::Plus
+            | BinaryOperator::Minus
+            | BinaryOperator::Multiply
+            | BinaryOperator::Divide
+            | BinaryOperator::Modulo => {
+                let left = self.evaluate(left, event)?;
+                let right = self.evaluate(right, event)?;
+                if left == Type::Null || right == Type::Null {
+                    return Ok(Type::Null);
+                }
+
+                match evaluate_datetime_arithmetic(&left, op, &right) {
+                    Some(result) => Ok(result),
+                    None => evaluate_numeric_arithmetic(&left, op, &right)?
+                        .ok_or_else(|| Error::InvalidArithmetic(left, op.to_string(), right)),
+                }
+            }
+            _ => Err(Error::UnsupportedExpression(op.to_string())),
+        }
+    }
+}
+
+/// `value IN (...)` is true if any item equals the value. Like SQL, it is NULL instead of false
+/// when the value or any item is NULL.
+fn evaluate_in<'a>(
+    value: Type,
+    items: impl Iterator<Item = &'a Type>,
+    negated: bool,
+) -> Result<Type, Error> {
+    if value == Type::Null {
+        return Ok(Type::Null);
+    }
+
+    let mut contains_null = false;
+    for item in items {
+        if *item == Type::Null {
+            contains_null = true;
+            continue;
+        }
+
+        let ordering = value
+            .compare(item)
+            .ok_or_else(|| Error::InvalidComparison(value.clone(), item.clone()))?;
+        if ordering == Ordering::Equal {
+            return Ok(Type::Bool(!negated));
+        }
+    }
+
+    if contains_null {
+        Ok(Type::Null)
+    } else {
+        Ok(Type::Bool(negated))
+    }
+}
+
+/// Integers become i64 and every other number becomes f64
+/// Look up `attrs['user']` in a JSON object, or `tags[0]` in a JSON array counting from 0 like
+/// `JSON_EXTRACT`. It's NULL if the key doesn't exist or the value isn't JSON. Returns `None` if
+/// the value isn't text or a `json` column, or a key isn't a string or an integer.
+fn evaluate_index(value: Type, keys: &[Type]) -> Option<Type> {
+    let value = match value {
+        Type::Null => return Some(Type::Null),
+        Type::String(_) => match json_document(&value) {
+            Some(value) => value,
+            None => return Some(Type::Null),
+        },
+        value => json_document(&value)?,
+    };
+
+    let mut current = Some(&value);
+    for key in keys {
+        current = match key {
+            Type::String(key) => current.and_then(|value| value.get(key)),
+            Type::Int32(_) | Type::Int64(_) | Type::UInt32(_) | Type::UInt64(_) => {
+                let index = usize::try_from(key.as_f64()? as i64).ok();
+                current.and_then(|value| value.get(index?))
+            }
+            Type::Null => None,
+            _ => return None,
+        };
+    }
+    Some(current.map_or(Type::Null, json_value))
+}
+
+fn evaluate_literal(value: &Value) -> Result<Type, Error> {
+    match value {
+        Value::Number(number, _) => match (i64::from_str(number), u64::from_str(number)) {
+            (Ok(number), _) => Ok(Type::Int64(number)),
+            (_, Ok(number)) => Ok(Type::UInt64(number)),
+            _ => f64::from_str(number)
+                .map(Type::Double)
+                .map_err(|_| Error::UnsupportedExpression(value.to_string())),
+        },
+        Value::SingleQuotedString(value) => Ok(Type::String(value.clone())),
+        Value::Boolean(value) => Ok(Type::Bool(*value)),
+        Value::Null => Ok(Type::Null),
+        _ => Err(Error::UnsupportedExpression(value.to_string())),
+    }
+}
+
+/// Like postgres, a quoted literal compared to a number or bool is read as one, e.g.
+/// `status > '5'`. Numbers are read as exact decimals when they can be.
+fn coerce_quoted_literal(expr: &Expr, value: Type, other: &Type) -> Type {
+    let cast = match (expr, other) {
+        (Expr::Value(Value::SingleQuotedString(_)), Type::Bool(_)) => value.cast(&ColumnType::Bool),
+        (Expr::Value(Value::SingleQuotedString(_)), _) if other.as_f64().is_some() => value
+            .cast(&ColumnType::Decimal)
+            .or_else(|| value.cast(&ColumnType::Double)),
+        _ => None,
+    };
+    cast.unwrap_or(value)
+}
+
+/// `INTERVAL '90 minutes'` or `INTERVAL '90' MINUTE`
+fn evaluate_interval(interval: &Interval) -> Result<Type, Error> {
+    let value = match interval.value.as_ref() {
+        Expr::Value(Value::SingleQuotedString(value)) => value.clone(),
+        Expr::Value(Value::Number(value, _)) => value.clone(),
+        _ => return Err(Error::InvalidInterval(interval.to_string())),
+    };
+
+    let parsed = match (&interval.leading_field, &interval.last_field) {
+        (None, _) => parse_interval(&value),
+        (Some(field), None) => {
+            let unit = match field {
+                DateTimeField::Week => "week",
+                DateTimeField::Day => "day",
+                DateTimeField::Hour => "hour",
+                DateTimeField::Minute => "minute",
+                DateTimeField::Second => "second",
+                DateTimeField::Millisecond | DateTimeField::Milliseconds => "millisecond",
+                DateTimeField::Microsecond | DateTimeField::Microseconds => "microsecond",
+                _ => return Err(Error::InvalidInterval(interval.to_string())),
+            };
+            value
+                .trim()
+                .parse()
+                .ok()
+                .and_then(|amount| interval_unit(unit, amount))
+        }
+        _ => None,
+    };
+    parsed
+        .map(Type::Interval)
+        .ok_or_else(|| Error::InvalidInterval(interval.to_string()))
+}
+
+/// Integers stay integers, so like postgres `7 / 2` is 3, decimals stay exact decimals, and
+/// anything else with a float becomes a double. Returns `None` if the values aren't numbers, and
+/// fails if integer or decimal arithmetic overflows.
+fn evaluate_numeric_arithmetic(
+    left: &Type,
+    op: &BinaryOperator,
+    right: &Type,
+) -> Result<Option<Type>, Error> {
+    let integer = |value: &Type| match value {
+        Type::Int32(value) => Some(*value as i64),
+        Type::Int64(value) => Some(*value),
+        Type::UInt32(value) => Some(*value as i64),
+        // larger values fall back to double arithmetic
+        Type::UInt64(value) => i64::try_from(*value).ok(),
+        _ => None,
+    };
+    if let (Some(a), Some(b)) = (integer(left), integer(right)) {
+        let result = match op {
+            BinaryOperator::Plus => a.checked_add(b),
+            BinaryOperator::Minus => a.checked_sub(b),
+            BinaryOperator::Multiply => a.checked_mul(b),
+            _ if b == 0 => return Err(Error::DivisionByZero),
+            BinaryOperator::Divide => a.checked_div(b),
+            _ => a.checked_rem(b),
+        };
+        return result
+            .map(|result| Some(Type::Int64(result)))
+            .ok_or_else(|| Error::ArithmeticOverflow(format!("{} {} {}", a, op, b)));
+    }
+
+    if matches!(left, Type::Decimal(_)) || matches!(right, Type::Decimal(_)) {
+        if let (Some(a), Some(b)) = (left.as_decimal(), right.as_decimal()) {
+            let result = match op {
+                BinaryOperator::Plus => a.checked_add(b),
+                BinaryOperator::Minus => a.checked_sub(b),
+                BinaryOperator::Multiply => a.checked_mul(b),
+                _ if b.is_zero() => return Err(Error::DivisionByZero),
+                BinaryOperator::Divide => a.checked_div(b),
+                _ => a.checked_rem(b),
+            };
+            return result
+                .map(|result| Some(Type::Decimal(result)))
+                .ok_or_else(|| Error::ArithmeticOverflow(format!("{} {} {}", a, op, b)));
+        }
+    }
+
+    let (a, b) = match (left.as_f64(), right.as_f64()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Ok(None),
+    };
+    let result = match op {
+        BinaryOperator::Plus => a + b,
+        BinaryOperator::Minus => a - b,
+        BinaryOperator::Multiply => a * b,
+        _ if b == 0.0 => return Err(Error::DivisionByZero),
+        BinaryOperator::Divide => a / b,
+        _ => a % b,
+    };
+    Ok(Some(Type::Double(result)))
+}
+
+/// Datetimes can be shifted by intervals, intervals added together and datetimes subtracted
+/// to get the interval between them
+fn evaluate_datetime_arithmetic(left: &Type, op: &BinaryOperator, right: &Type) -> Option<Type> {
+    match (left, op, right) {
+        (Type::DateTime(a), BinaryOperator::Plus, Type::Interval(b))
+        | (Type::Interval(b), BinaryOperator::Plus, Type::DateTime(a)) => {
+            a.checked_add_signed(*b).map(Type::DateTime)
+        }
+        (Type::DateTime(a), BinaryOperator::Minus, Type::Interval(b)) => {
+            a.checked_sub_signed(*b).map(Type::DateTime)
+        }
+        (Type::DateTimeTz(a), BinaryOperator::Plus, Type::Interval(b))
+        | (Type::Interval(b), BinaryOperator::Plus, Type::DateTimeTz(a)) => {
+            a.checked_add_signed(*b).map(Type::DateTimeTz)
+        }
+        (Type::DateTimeTz(a), BinaryOperator::Minus, Type::Interval(b)) => {
+            a.checked_sub_signed(*b).map(Type::DateTimeTz)
+        }
+        (
+            Type::DateTime(_) | Type::DateTimeTz(_),
+            BinaryOperator::Minus,
+            Type::DateTime(_) | Type::DateTimeTz(_),
+        ) => Some(Type::Interval(
+            left.as_utc()?.signed_duration_since(right.as_utc()?),
+        )),
+        (Type::Interval(a), BinaryOperator::Plus, Type::Interval(b)) => {
+            a.checked_add(b).map(Type::Interval)
+        }
+        (Type::Interval(a), BinaryOperator::Minus, Type::Interval(b)) => {
+            a.checked_sub(b).map(Type::Interval)
+        }
+        _ => None,
+    }
+}
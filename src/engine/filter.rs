@@ -1,411 +1,45 @@
 use crate::engine::TableResult;
 use crate::error::Error;
-use crate::parser::values::Type;
-use crate::schema::ColumnType;
-use chrono::{DateTime, Utc};
-use sqlparser::ast::{BinaryOperator, Expr, SetExpr, Statement, Value};
-use std::collections::HashSet;
-use std::str::FromStr;
+use sqlparser::ast::{SetExpr, Statement};
 
 impl TableResult {
     pub fn filter(mut self) -> Result<TableResult, Error> {
         if let Some(statement) = self.statement.clone() {
-            let indexes: Option<HashSet<_>> = match &statement {
-                Statement::Query(query) => match &query.body {
-                    SetExpr::Select(select) => match &select.selection {
-                        None => Ok(None),
-                        Some(expr) => Ok(Some(self.process_filter(&expr, &statement)?)),
-                    },
-                    _ => Err(Error::InvalidQuery(statement.clone())),
+            let selection = match &statement {
+                Statement::Query(query) => match query.body.as_ref() {
+                    SetExpr::Select(select) => Ok(select.selection.as_ref()),
+                    _ => Err(Error::InvalidQuery(Box::new(statement.clone()))),
                 },
-                _ => Err(Error::InvalidQuery(statement)),
+                _ => Err(Error::InvalidQuery(Box::new(statement.clone()))),
             }?;
 
-            if let Some(indexes) = indexes {
-                let events = std::mem::replace(&mut self.events, Vec::new());
-                self.events = events
-                    .into_iter()
-                    .enumerate()
-                    .filter(|(index, _)| indexes.contains(index))
-                    .map(|(_, event)| event)
-                    .collect();
-            }
-        }
-
-        Ok(self)
-    }
-
-    fn process_filter(
-        &mut self,
-        expr: &Expr,
-        statement: &Statement,
-    ) -> Result<HashSet<usize>, Error> {
-        match expr {
-            Expr::BinaryOp { left, op, right } => self.filter_binary_op(left, op, right, statement),
-            Expr::Nested(nested) => self.process_filter(nested, statement),
-            _ => Err(Error::InvalidQuery(statement.clone())),
-        }
-    }
-
-    fn filter_binary_op(
-        &mut self,
-        left: &Box<Expr>,
-        op: &BinaryOperator,
-        right: &Box<Expr>,
-        statement: &Statement,
-    ) -> Result<HashSet<usize>, Error> {
-        match (&**left, &**right) {
-            (Expr::Identifier(column), Expr::Value(literal)) => {
-                self.route_filter_column_with_literal(column.value.as_str(), literal, op)
-            }
-            (Expr::Value(literal), Expr::Identifier(column)) => {
-                self.route_filter_literal_with_column(literal, column.value.as_str(), op)
-            }
-            _ => {
-                let result1 = self.process_filter(left, statement)?;
-                let result2 = self.process_filter(right, statement)?;
-                match op {
-                    BinaryOperator::And => Ok(result1.intersection(&result2).map(|i| *i).collect()),
-                    BinaryOperator::Or => Ok(result1.union(&result2).map(|i| *i).collect()),
-                    _ => Err(Error::InvalidQuery(statement.clone())),
+            if let Some(expr) = selection {
+                let events = std::mem::take(&mut self.events);
+                let mut filtered_events = Vec::new();
+                for event in events {
+                    self.cancellation.check()?;
+                    if self.evaluate_predicate(expr, &event)? {
+                        filtered_events.push(event);
+                    }
                 }
+                self.events = filtered_events;
             }
         }
-    }
 
-    fn route_filter_column_with_literal(
-        &mut self,
-        column: &str,
-        literal: &Value,
-        op: &BinaryOperator,
-    ) -> Result<HashSet<usize>, Error> {
-        match op {
-            BinaryOperator::Eq => self.filter_column_equals_literal(column, literal),
-            BinaryOperator::NotEq => self.filter_column_does_not_equal_literal(column, literal),
-            BinaryOperator::Gt => self.filter_column_greater_than_literal(column, literal),
-            BinaryOperator::Lt => self.filter_column_less_than_literal(column, literal),
-            BinaryOperator::GtEq => {
-                self.filter_column_greater_than_or_equal_to_literal(column, literal)
-            }
-            BinaryOperator::LtEq => {
-                self.filter_column_less_than_or_equal_to_literal(column, literal)
-            }
-            _ => Err(Error::InvalidQuery(
-                self.statement.as_ref().unwrap().clone(),
-            )),
-        }
-    }
-
-    fn route_filter_literal_with_column(
-        &mut self,
-        literal: &Value,
-        column: &str,
-        op: &BinaryOperator,
-    ) -> Result<HashSet<usize>, Error> {
-        match op {
-            BinaryOperator::Eq => self.filter_column_equals_literal(column, literal),
-            BinaryOperator::NotEq => self.filter_column_does_not_equal_literal(column, literal),
-            BinaryOperator::Gt => self.filter_column_less_than_literal(column, literal),
-            BinaryOperator::Lt => self.filter_column_greater_than_literal(column, literal),
-            BinaryOperator::GtEq => {
-                self.filter_column_less_than_or_equal_to_literal(column, literal)
-            }
-            BinaryOperator::LtEq => {
-                self.filter_column_greater_than_or_equal_to_literal(column, literal)
-            }
-            _ => Err(Error::InvalidQuery(
-                self.statement.as_ref().unwrap().clone(),
-            )),
-        }
-    }
-
-    fn get_schema_type_for_column(&self, column: &str) -> ColumnType {
-        // TODO: this can easily be simplified so we don't have to do a linear search every time
-        self.parser
-            .schema
-            .columns
-            .iter()
-            .find(|c| c.name.eq_ignore_ascii_case(column))
-            .unwrap()
-            .r#type
-    }
-
-    fn filter_column_with_literal<T: Fn(ColumnType, &Type, &Value) -> Result<bool, Error>>(
-        &self,
-        column: &str,
-        literal: &Value,
-        filter: T,
-    ) -> Result<HashSet<usize>, Error> {
-        let schema_type = self.get_schema_type_for_column(column);
-        let mut filtered_events = HashSet::new();
-
-        for (index, event) in self.events.iter().enumerate() {
-            let event_type = event.values.get(column).unwrap();
-            let should_keep = filter(schema_type, event_type, literal)?;
-            if should_keep {
-                filtered_events.insert(index);
-            }
-        }
-
-        Ok(filtered_events)
-    }
-
-    fn filter_column_equals_literal(
-        &mut self,
-        column: &str,
-        literal: &Value,
-    ) -> Result<HashSet<usize>, Error> {
-        self.filter_column_with_literal(column, literal, |schema_type, event_type, literal| match (
-            schema_type,
-            event_type,
-            literal,
-        ) {
-            (ColumnType::String, Type::String(value), Value::SingleQuotedString(literal)) => {
-                Ok(value == literal)
-            }
-            (ColumnType::Int32, Type::Int32(value), Value::Number(literal, false)) => {
-                let literal = i32::from_str(literal.as_str()).unwrap();
-                Ok(*value == literal)
-            }
-            (ColumnType::Int64, Type::Int64(value), Value::Number(literal, false)) => {
-                let literal = i64::from_str(literal.as_str()).unwrap();
-                Ok(*value == literal)
-            }
-            (ColumnType::Float, Type::Float(value), Value::Number(literal, false)) => {
-                let literal = f32::from_str(literal.as_str()).unwrap();
-                Ok(*value == literal)
-            }
-            (ColumnType::Double, Type::Double(value), Value::Number(literal, false)) => {
-                let literal = f64::from_str(literal.as_str()).unwrap();
-                Ok(*value == literal)
-            }
-            (ColumnType::Bool, Type::Bool(value), Value::Boolean(literal)) => Ok(value == literal),
-            (ColumnType::DateTime, Type::DateTime(value), Value::SingleQuotedString(literal)) => {
-                let literal: DateTime<Utc> = literal.parse().unwrap();
-                Ok(*value == literal)
-            }
-            _ => Err(Error::TypeMismatch(
-                schema_type,
-                event_type.clone(),
-                literal.clone(),
-            )),
-        })
-    }
-
-    fn filter_column_does_not_equal_literal(
-        &mut self,
-        column: &str,
-        literal: &Value,
-    ) -> Result<HashSet<usize>, Error> {
-        self.filter_column_with_literal(column, literal, |schema_type, event_type, literal| match (
-            schema_type,
-            event_type,
-            literal,
-        ) {
-            (ColumnType::String, Type::String(value), Value::SingleQuotedString(literal)) => {
-                Ok(value != literal)
-            }
-            (ColumnType::Int32, Type::Int32(value), Value::Number(literal, false)) => {
-                let literal = i32::from_str(literal.as_str()).unwrap();
-                Ok(*value != literal)
-            }
-            (ColumnType::Int64, Type::Int64(value), Value::Number(literal, false)) => {
-                let literal = i64::from_str(literal.as_str()).unwrap();
-                Ok(*value != literal)
-            }
-            (ColumnType::Float, Type::Float(value), Value::Number(literal, false)) => {
-                let literal = f32::from_str(literal.as_str()).unwrap();
-                Ok(*value != literal)
-            }
-            (ColumnType::Double, Type::Double(value), Value::Number(literal, false)) => {
-                let literal = f64::from_str(literal.as_str()).unwrap();
-                Ok(*value != literal)
-            }
-            (ColumnType::Bool, Type::Bool(value), Value::Boolean(literal)) => Ok(value != literal),
-            (ColumnType::DateTime, Type::DateTime(value), Value::SingleQuotedString(literal)) => {
-                let literal: DateTime<Utc> = literal.parse().unwrap();
-                Ok(*value != literal)
-            }
-            _ => Err(Error::TypeMismatch(
-                schema_type,
-                event_type.clone(),
-                literal.clone(),
-            )),
-        })
-    }
-
-    fn filter_column_less_than_literal(
-        &mut self,
-        column: &str,
-        literal: &Value,
-    ) -> Result<HashSet<usize>, Error> {
-        self.filter_column_with_literal(column, literal, |schema_type, event_type, literal| match (
-            schema_type,
-            event_type,
-            literal,
-        ) {
-            (ColumnType::Int32, Type::Int32(value), Value::Number(literal, false)) => {
-                let literal = i32::from_str(literal.as_str()).unwrap();
-                Ok(*value < literal)
-            }
-            (ColumnType::Int64, Type::Int64(value), Value::Number(literal, false)) => {
-                let literal = i64::from_str(literal.as_str()).unwrap();
-                Ok(*value < literal)
-            }
-            (ColumnType::Float, Type::Float(value), Value::Number(literal, false)) => {
-                let literal = f32::from_str(literal.as_str()).unwrap();
-                Ok(*value < literal)
-            }
-            (ColumnType::Double, Type::Double(value), Value::Number(literal, false)) => {
-                let literal = f64::from_str(literal.as_str()).unwrap();
-                Ok(*value < literal)
-            }
-            (ColumnType::DateTime, Type::DateTime(value), Value::SingleQuotedString(literal)) => {
-                let literal: DateTime<Utc> = literal.parse().unwrap();
-                Ok(*value < literal)
-            }
-            (ColumnType::String, Type::String(value), Value::SingleQuotedString(literal)) => {
-                Ok(value < literal)
-            }
-            _ => Err(Error::TypeMismatch(
-                schema_type,
-                event_type.clone(),
-                literal.clone(),
-            )),
-        })
-    }
-
-    fn filter_column_greater_than_literal(
-        &mut self,
-        column: &str,
-        literal: &Value,
-    ) -> Result<HashSet<usize>, Error> {
-        self.filter_column_with_literal(column, literal, |schema_type, event_type, literal| match (
-            schema_type,
-            event_type,
-            literal,
-        ) {
-            (ColumnType::Int32, Type::Int32(value), Value::Number(literal, false)) => {
-                let literal = i32::from_str(literal.as_str()).unwrap();
-                Ok(*value > literal)
-            }
-            (ColumnType::Int64, Type::Int64(value), Value::Number(literal, false)) => {
-                let literal = i64::from_str(literal.as_str()).unwrap();
-                Ok(*value > literal)
-            }
-            (ColumnType::Float, Type::Float(value), Value::Number(literal, false)) => {
-                let literal = f32::from_str(literal.as_str()).unwrap();
-                Ok(*value > literal)
-            }
-            (ColumnType::Double, Type::Double(value), Value::Number(literal, false)) => {
-                let literal = f64::from_str(literal.as_str()).unwrap();
-                Ok(*value > literal)
-            }
-            (ColumnType::DateTime, Type::DateTime(value), Value::SingleQuotedString(literal)) => {
-                let literal: DateTime<Utc> = literal.parse().unwrap();
-                Ok(*value > literal)
-            }
-            (ColumnType::String, Type::String(value), Value::SingleQuotedString(literal)) => {
-                Ok(value > literal)
-            }
-            _ => Err(Error::TypeMismatch(
-                schema_type,
-                event_type.clone(),
-                literal.clone(),
-            )),
-        })
-    }
-
-    fn filter_column_less_than_or_equal_to_literal(
-        &mut self,
-        column: &str,
-        literal: &Value,
-    ) -> Result<HashSet<usize>, Error> {
-        self.filter_column_with_literal(column, literal, |schema_type, event_type, literal| match (
-            schema_type,
-            event_type,
-            literal,
-        ) {
-            (ColumnType::Int32, Type::Int32(value), Value::Number(literal, false)) => {
-                let literal = i32::from_str(literal.as_str()).unwrap();
-                Ok(*value <= literal)
-            }
-            (ColumnType::Int64, Type::Int64(value), Value::Number(literal, false)) => {
-                let literal = i64::from_str(literal.as_str()).unwrap();
-                Ok(*value <= literal)
-            }
-            (ColumnType::Float, Type::Float(value), Value::Number(literal, false)) => {
-                let literal = f32::from_str(literal.as_str()).unwrap();
-                Ok(*value <= literal)
-            }
-            (ColumnType::Double, Type::Double(value), Value::Number(literal, false)) => {
-                let literal = f64::from_str(literal.as_str()).unwrap();
-                Ok(*value <= literal)
-            }
-            (ColumnType::DateTime, Type::DateTime(value), Value::SingleQuotedString(literal)) => {
-                let literal: DateTime<Utc> = literal.parse().unwrap();
-                Ok(*value <= literal)
-            }
-            (ColumnType::String, Type::String(value), Value::SingleQuotedString(literal)) => {
-                Ok(value <= literal)
-            }
-            _ => Err(Error::TypeMismatch(
-                schema_type,
-                event_type.clone(),
-                literal.clone(),
-            )),
-        })
-    }
-
-    fn filter_column_greater_than_or_equal_to_literal(
-        &mut self,
-        column: &str,
-        literal: &Value,
-    ) -> Result<HashSet<usize>, Error> {
-        self.filter_column_with_literal(column, literal, |schema_type, event_type, literal| match (
-            schema_type,
-            event_type,
-            literal,
-        ) {
-            (ColumnType::Int32, Type::Int32(value), Value::Number(literal, false)) => {
-                let literal = i32::from_str(literal.as_str()).unwrap();
-                Ok(*value >= literal)
-            }
-            (ColumnType::Int64, Type::Int64(value), Value::Number(literal, false)) => {
-                let literal = i64::from_str(literal.as_str()).unwrap();
-                Ok(*value >= literal)
-            }
-            (ColumnType::Float, Type::Float(value), Value::Number(literal, false)) => {
-                let literal = f32::from_str(literal.as_str()).unwrap();
-                Ok(*value >= literal)
-            }
-            (ColumnType::Double, Type::Double(value), Value::Number(literal, false)) => {
-                let literal = f64::from_str(literal.as_str()).unwrap();
-                Ok(*value >= literal)
-            }
-            (ColumnType::DateTime, Type::DateTime(value), Value::SingleQuotedString(literal)) => {
-                let literal: DateTime<Utc> = literal.parse().unwrap();
-                Ok(*value >= literal)
-            }
-            (ColumnType::String, Type::String(value), Value::SingleQuotedString(literal)) => {
-                Ok(value >= literal)
-            }
-            _ => Err(Error::TypeMismatch(
-                schema_type,
-                event_type.clone(),
-                literal.clone(),
-            )),
-        })
+        Ok(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::engine::tests::generate_typed_events;
+    use crate::error::Error;
     use crate::parser::values::Type;
     use crate::schema::Schema;
     use crate::{Engine, Parser};
-    use chrono::{TimeZone, Utc};
+    use chrono::{Duration, TimeZone, Utc};
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
 
     #[test]
     fn sql_where_column_equals_literal() {
@@ -767,13 +401,65 @@ columns:
             "select * from logs where '2022-01-02T00:00:00Z' < datetime",
             "select * from logs where string > 'b'",
             "select * from logs where 'b' < string",
+            // literals that aren't the column's type are compared by value
+            "select * from logs where i32 > 2.5",
+            "select * from logs where i32 > '2'",
+            "select * from logs where '2000.5' < i64",
+            "select * from logs where i32 > 2 and i32 < 99999999999",
         ];
 
         for query in queries {
             let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
             let table_result = engine.execute(vec![source]).unwrap();
 
-            assert_eq!(table_result.events, events);
+            assert_eq!(table_result.events, events, "{}", query);
+        }
+    }
+
+    #[test]
+    fn sql_where_literal_outside_column_type() {
+        let schema = "\
+regex: (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: status
+      type: i32
+";
+        let source = "200\n404\n";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let cases = [
+            ("select * from logs where status = 1.5", vec![]),
+            ("select * from logs where status = 99999999999", vec![]),
+            (
+                "select * from logs where status <> 18446744073709551615",
+                vec![200, 404],
+            ),
+            ("select * from logs where status < 200.5", vec![200]),
+            ("select * from logs where status = '404'", vec![404]),
+        ];
+        for (query, expected) in cases {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            let expected = generate_typed_events(
+                expected
+                    .into_iter()
+                    .map(|status| vec![("status", Type::Int32(status))])
+                    .collect(),
+            );
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+
+        let engine =
+            Engine::with_query(parser, "select * from logs where status = 'ok'".to_string())
+                .unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::InvalidComparison(value, literal)) => {
+                assert_eq!((value, literal), (Type::Int32(200), "ok".into()))
+            }
+            Err(e) => panic!("Error should be Error::InvalidComparison. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::InvalidComparison"),
         }
     }
 
@@ -1038,4 +724,320 @@ multi-line
             assert_eq!(table_result.events, events);
         }
     }
+
+    #[test]
+    fn sql_where_interval_arithmetic() {
+        let schema = "\
+regex: (?P<ts>\\S+) (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: ts
+      type: datetime
+    - name: message
+      type: string
+";
+        let source = "\
+2022-01-01T01:00:00Z early
+2022-01-01T02:00:00Z late
+2022-01-02T00:00:00Z next day
+";
+        let queries = [
+            "select message from logs where ts > datetime('2022-01-01') + INTERVAL '90 minutes' \
+                and ts < datetime('2022-01-02')",
+            "select message from logs where ts - INTERVAL '1 hour 30m' > '2022-01-01T00:00:00Z' \
+                and ts < datetime('2022-01-02') - INTERVAL '1' HOUR",
+            "select message from logs where ts - datetime('2022-01-01') > INTERVAL '1 hour' \
+                and ts - datetime('2022-01-01') <= INTERVAL '3 hours'",
+            "select message from logs where datetime('2022-01-02') + -INTERVAL '23 hours' < ts \
+                and ts <> '2022-01-02T00:00:00Z'",
+        ];
+        let expected = generate_typed_events(vec![vec![("message", "late".into())]]);
+        for query in queries {
+            let schema = Schema::try_from(schema).unwrap();
+            let parser = Parser::new(schema).unwrap();
+            let engine = Engine::with_query(parser, query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn sql_where_relative_to_now() {
+        let schema = "\
+regex: (?P<ts>\\S+) (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: ts
+      type: datetime
+    - name: message
+      type: string
+";
+        let now = Utc::now();
+        let source = format!(
+            "{} old\n{} recent\n",
+            (now - Duration::hours(1)).to_rfc3339(),
+            (now - Duration::minutes(1)).to_rfc3339()
+        );
+        let queries = [
+            "select message from logs where ts > NOW() - INTERVAL '15 minutes'",
+            "select message from logs where ts >= current_timestamp - INTERVAL '15' MINUTE",
+            "select message from logs where now() - ts < INTERVAL '15 minutes'",
+        ];
+        let expected = generate_typed_events(vec![vec![("message", "recent".into())]]);
+        for query in queries {
+            let schema = Schema::try_from(schema).unwrap();
+            let parser = Parser::new(schema).unwrap();
+            let engine = Engine::with_query(parser, query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source.as_str()]).unwrap();
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+    }
+
+    #[test]
+    fn sql_where_arithmetic() {
+        let schema = "\
+regex: (?P<request>\\S+) (?P<latency_ms>\\d+) (?P<ratio>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: request
+      type: string
+    - name: latency_ms
+      type: i64
+    - name: ratio
+      type: f64
+";
+        let source = "\
+a 1500 0.5
+b 2500 0.25
+c 3000 1.0
+";
+        let cases = [
+            (
+                "select request from logs where latency_ms / 1000 > 2",
+                vec!["c"],
+            ),
+            (
+                "select request from logs where latency_ms / 1000.0 > 2.2",
+                vec!["b", "c"],
+            ),
+            (
+                "select request from logs where latency_ms * ratio >= 625 \
+                    and latency_ms % 1000 = 500",
+                vec!["a", "b"],
+            ),
+            (
+                "select request from logs where (latency_ms - 500) / 2 = 1000",
+                vec!["b"],
+            ),
+            (
+                "select request from logs where 2 < latency_ms / 1000 + ratio - 0.5",
+                vec!["c"],
+            ),
+        ];
+        for (query, expected) in cases {
+            let schema = Schema::try_from(schema).unwrap();
+            let parser = Parser::new(schema).unwrap();
+            let engine = Engine::with_query(parser, query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+            let expected = generate_typed_events(
+                expected
+                    .into_iter()
+                    .map(|request| vec![("request", request.into())])
+                    .collect(),
+            );
+            assert_eq!(table_result.events, expected, "{}", query);
+        }
+
+        let query = "select request from logs where latency_ms / 0 > 1";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::DivisionByZero) => (),
+            x => panic!(
+                "Error should be Error::DivisionByZero. Actual {:?}",
+                x.map(|_| ())
+            ),
+        }
+
+        // negating the smallest integer overflows
+        let query = "select request from logs \
+            where -(latency_ms - 1500 - 9223372036854775807 - 1) > 0";
+        let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::ArithmeticOverflow(expression)) => {
+                assert_eq!(expression, "-(-9223372036854775808)")
+            }
+            Err(e) => panic!("Error should be Error::ArithmeticOverflow. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::ArithmeticOverflow"),
+        }
+
+        let query = "select request from logs where latency_ms * 9223372036854775807 > 0";
+        let engine = Engine::with_query(parser, query.to_string()).unwrap();
+        match engine.execute(vec![source]) {
+            Err(Error::ArithmeticOverflow(expression)) => {
+                assert_eq!(expression, "1500 * 9223372036854775807")
+            }
+            Err(e) => panic!("Error should be Error::ArithmeticOverflow. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::ArithmeticOverflow"),
+        }
+    }
+
+    #[test]
+    fn sql_where_function() {
+        let schema = "\
+regex: (?P<level>.+)\t(?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+";
+        let source = "\
+info\thello
+Error\tsomething broke
+ERROR\tstill broken
+";
+
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let queries = vec![
+            "select * from logs where upper(level) = 'ERROR'",
+            "select * from logs where 'error' = LOWER(level)",
+            "select * from logs where length(message) > 5",
+            "select * from logs where substr(level, 1, 1) = 'E' and level != 'info'",
+        ];
+        let events = generate_typed_events(vec![
+            vec![
+                ("level", Type::String("Error".to_string())),
+                ("message", Type::String("something broke".to_string())),
+            ],
+            vec![
+                ("level", Type::String("ERROR".to_string())),
+                ("message", Type::String("still broken".to_string())),
+            ],
+        ]);
+
+        for query in queries {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+
+            assert_eq!(table_result.events, events);
+        }
+    }
+
+    #[test]
+    fn sql_where_unsigned() {
+        let schema = "\
+regex: (?P<pid>.+)\t(?P<bytes>.+)
+filename: .*
+table: logs
+columns:
+    - name: pid
+      type: u32
+    - name: bytes
+      type: u64
+";
+        let source = "\
+4000000000\t18446744073709551615
+12\t0
+300\t9223372036854775808
+";
+
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let cases = [
+            ("select * from logs where pid > 2147483647", vec![0]),
+            (
+                "select * from logs where bytes >= 9223372036854775808",
+                vec![0, 2],
+            ),
+            ("select * from logs where 12 = pid", vec![1]),
+            ("select * from logs where bytes < 0.5", vec![1]),
+            ("select * from logs where pid != 12", vec![0, 2]),
+            ("select * from logs where pid > -1 and bytes <= 0", vec![1]),
+            ("select * from logs order by bytes desc", vec![0, 2, 1]),
+        ];
+        let rows = [
+            (4_000_000_000, u64::MAX),
+            (12, 0),
+            (300, 9_223_372_036_854_775_808),
+        ];
+
+        for (query, expected) in cases {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+
+            let events = generate_typed_events(
+                expected
+                    .iter()
+                    .map(|i| {
+                        vec![
+                            ("pid", Type::UInt32(rows[*i].0)),
+                            ("bytes", Type::UInt64(rows[*i].1)),
+                        ]
+                    })
+                    .collect(),
+            );
+            assert_eq!(table_result.events, events, "{}", query);
+        }
+    }
+
+    #[test]
+    fn sql_where_decimal() {
+        let schema = "\
+regex: (?P<amount>.+)
+filename: .*
+table: logs
+columns:
+    - name: amount
+      type: decimal
+";
+        let source = "\
+0.1
+19.99
+1e2
+-0.20
+";
+
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let cases = [
+            ("select * from logs where amount = 0.1", vec![0]),
+            // exact, where doubles would make it 0.30000000000000004
+            ("select * from logs where amount + 0.2 = 0.3", vec![0]),
+            ("select * from logs where amount * 3 = 59.97", vec![1]),
+            (
+                "select * from logs where amount > 19.98 and amount < 19.995",
+                vec![1],
+            ),
+            ("select * from logs where amount >= 100", vec![2]),
+            ("select * from logs where amount = -0.2", vec![3]),
+            ("select * from logs order by amount desc", vec![2, 1, 0, 3]),
+        ];
+        let rows = ["0.1", "19.99", "100", "-0.20"];
+
+        for (query, expected) in cases {
+            let engine = Engine::with_query(parser.clone(), query.to_string()).unwrap();
+            let table_result = engine.execute(vec![source]).unwrap();
+
+            let events = generate_typed_events(
+                expected
+                    .iter()
+                    .map(|i| {
+                        vec![(
+                            "amount",
+                            Type::Decimal(Decimal::from_str(rows[*i]).unwrap()),
+                        )]
+                    })
+                    .collect(),
+            );
+            assert_eq!(table_result.events, events, "{}", query);
+        }
+    }
 }
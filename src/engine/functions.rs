@@ -0,0 +1,928 @@
+use crate::error::Error;
+use crate::parser::values::{parse_interval, Type};
+use chrono::format::{Item, StrftimeItems};
+use chrono::prelude::*;
+use chrono::Duration;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// A scalar function called once per event with its evaluated arguments
+pub type ScalarFunction = Arc<dyn Fn(&[Type]) -> Result<Type, Error> + Send + Sync>;
+
+/// Scalar functions that can be called from projections and `WHERE` clauses. Names are case
+/// insensitive.
+#[derive(Clone)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, ScalarFunction>,
+}
+
+impl FunctionRegistry {
+    /// Create a registry without any functions
+    pub fn empty() -> FunctionRegistry {
+        FunctionRegistry {
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Register a function, replacing any existing function with the same name
+    pub fn register<F>(&mut self, name: &str, function: F)
+    where
+        F: Fn(&[Type]) -> Result<Type, Error> + Send + Sync + 'static,
+    {
+        self.functions
+            .insert(name.to_ascii_uppercase(), Arc::new(function));
+    }
+
+    /// Register `TRACE_URL(trace_id)`, which fills in the `{trace_id}` placeholder in `template`
+    pub fn register_trace_url(&mut self, template: &str) {
+        let template = template.to_string();
+        self.register("TRACE_URL", move |args| match args {
+            [trace_id @ (Type::Custom(_) | Type::String(_))] => Ok(Type::String(
+                template.replace("{trace_id}", &trace_id.to_string()),
+            )),
+            _ => Err(Error::InvalidFunctionArguments("TRACE_URL".to_string())),
+        });
+    }
+
+    /// Register `NOW()` and `CURRENT_TIMESTAMP`, so every event in a query sees the same time
+    pub fn register_now(&mut self, now: DateTime<Utc>) {
+        for name in ["NOW", "CURRENT_TIMESTAMP"] {
+            self.register(name, move |args| match args {
+                [] => Ok(Type::DateTime(now)),
+                _ => Err(Error::InvalidFunctionArguments(name.to_string())),
+            });
+        }
+    }
+
+    /// Call a function by name
+    pub fn call(&self, name: &str, args: &[Type]) -> Result<Type, Error> {
+        match self.functions.get(&name.to_ascii_uppercase()) {
+            Some(function) => function(args),
+            None => Err(Error::UnknownFunction(name.to_string())),
+        }
+    }
+}
+
+impl Default for FunctionRegistry {
+    /// Create a registry with all builtin functions
+    fn default() -> Self {
+        let mut registry = FunctionRegistry::empty();
+        registry.register(
+            "UPPER",
+            strict(|args| {
+                let value = string_arg("UPPER", args, 0, 1)?;
+                Ok(Type::String(value.to_uppercase()))
+            }),
+        );
+        registry.register(
+            "LOWER",
+            strict(|args| {
+                let value = string_arg("LOWER", args, 0, 1)?;
+                Ok(Type::String(value.to_lowercase()))
+            }),
+        );
+        registry.register(
+            "LENGTH",
+            strict(|args| {
+                let value = string_arg("LENGTH", args, 0, 1)?;
+                Ok(Type::Int64(value.chars().count() as i64))
+            }),
+        );
+        registry.register(
+            "TRIM",
+            strict(|args| trim("TRIM", args, |v, c| v.trim_matches(c))),
+        );
+        registry.register(
+            "LTRIM",
+            strict(|args| trim("LTRIM", args, |v, c| v.trim_start_matches(c))),
+        );
+        registry.register(
+            "RTRIM",
+            strict(|args| trim("RTRIM", args, |v, c| v.trim_end_matches(c))),
+        );
+        registry.register("SUBSTR", strict(substr));
+        registry.register("SPLIT_PART", strict(split_part));
+        registry.register("JSON_EXTRACT", json_extract);
+        registry.register("ABS", |args| {
+            math("ABS", args, 1, |value| match value {
+                Type::Int32(value) => value.checked_abs().map(Type::Int32),
+                Type::Int64(value) => value.checked_abs().map(Type::Int64),
+                Type::UInt32(_) | Type::UInt64(_) => Some(value.clone()),
+                Type::Decimal(value) => Some(Type::Decimal(value.abs())),
+                value => value.as_f64().map(|value| Type::Double(value.abs())),
+            })
+        });
+        registry.register("CEIL", |args| {
+            math("CEIL", args, 1, |value| {
+                rounded(value, f64::ceil, Decimal::ceil)
+            })
+        });
+        registry.register("CEILING", |args| {
+            math("CEILING", args, 1, |value| {
+                rounded(value, f64::ceil, Decimal::ceil)
+            })
+        });
+        registry.register("FLOOR", |args| {
+            math("FLOOR", args, 1, |value| {
+                rounded(value, f64::floor, Decimal::floor)
+            })
+        });
+        registry.register("ROUND", round);
+        registry.register("POW", |args| pow("POW", args));
+        registry.register("POWER", |args| pow("POWER", args));
+        registry.register("GREATEST", |args| {
+            extreme("GREATEST", args, Ordering::Greater)
+        });
+        registry.register("LEAST", |args| extreme("LEAST", args, Ordering::Less));
+        registry.register("DATETIME", strict(datetime));
+        registry.register("STRFTIME", |args| strftime("STRFTIME", args));
+        registry.register("DATE_FORMAT", |args| strftime("DATE_FORMAT", args));
+        registry.register_now(Utc::now());
+        registry.register("TIME_BUCKET", |args| {
+            time_bucket("TIME_BUCKET", args, 2..=3)
+        });
+        registry.register("DATE_BIN", |args| time_bucket("DATE_BIN", args, 3..=3));
+        registry
+    }
+}
+
+/// Get the string argument at `index`, checking the function was called with `count` arguments
+/// Make a function NULL when any of its arguments is NULL, like SQL's string functions
+fn strict<F>(function: F) -> impl Fn(&[Type]) -> Result<Type, Error> + Send + Sync + 'static
+where
+    F: Fn(&[Type]) -> Result<Type, Error> + Send + Sync + 'static,
+{
+    move |args| match args.contains(&Type::Null) {
+        true => Ok(Type::Null),
+        false => function(args),
+    }
+}
+
+fn string_arg<'a>(
+    name: &str,
+    args: &'a [Type],
+    index: usize,
+    count: usize,
+) -> Result<&'a str, Error> {
+    match args.get(index) {
+        Some(Type::String(value)) if args.len() == count => Ok(value),
+        _ => Err(Error::InvalidFunctionArguments(name.to_string())),
+    }
+}
+
+fn integer_arg(name: &str, args: &[Type], index: usize) -> Result<i64, Error> {
+    match args.get(index) {
+        Some(Type::Int32(value)) => Ok(*value as i64),
+        Some(Type::Int64(value)) => Ok(*value),
+        Some(Type::UInt32(value)) => Ok(*value as i64),
+        Some(Type::UInt64(value)) => {
+            i64::try_from(*value).map_err(|_| Error::InvalidFunctionArguments(name.to_string()))
+        }
+        _ => Err(Error::InvalidFunctionArguments(name.to_string())),
+    }
+}
+
+/// Trim whitespace, or the characters in the optional second argument
+fn trim(
+    name: &str,
+    args: &[Type],
+    trim: impl for<'v> Fn(&'v str, &[char]) -> &'v str,
+) -> Result<Type, Error> {
+    let value = string_arg(name, args, 0, args.len().clamp(1, 2))?;
+    let characters: Vec<char> = match args.len() {
+        1 => vec![' ', '\t', '\r', '\n'],
+        _ => string_arg(name, args, 1, 2)?.chars().collect(),
+    };
+    Ok(Type::String(trim(value, &characters).to_string()))
+}
+
+/// `SUBSTR(value, start[, length])` with 1-based character positions. Like postgres, a start
+/// before the first character shortens the length instead of shifting the window.
+fn substr(args: &[Type]) -> Result<Type, Error> {
+    let value = string_arg("SUBSTR", args, 0, args.len().clamp(2, 3))?;
+    let start = integer_arg("SUBSTR", args, 1)?;
+    let end = match args.get(2) {
+        Some(_) => {
+            let length = integer_arg("SUBSTR", args, 2)?;
+            if length < 0 {
+                return Err(Error::InvalidFunctionArguments("SUBSTR".to_string()));
+            }
+            Some(start.saturating_add(length))
+        }
+        None => None,
+    };
+
+    let skip = (start - 1).max(0) as usize;
+    let substring = match end {
+        Some(end) => {
+            let take = (end - 1).max(0) as usize;
+            value.chars().take(take).skip(skip).collect()
+        }
+        None => value.chars().skip(skip).collect(),
+    };
+    Ok(Type::String(substring))
+}
+
+/// Call a math function with `count` numeric arguments, which is NULL if the first argument is
+fn math(
+    name: &str,
+    args: &[Type],
+    count: usize,
+    function: impl Fn(&Type) -> Option<Type>,
+) -> Result<Type, Error> {
+    let numeric = args
+        .iter()
+        .all(|arg| arg.as_f64().is_some() || *arg == Type::Null);
+    if args.len() != count || !numeric {
+        return Err(Error::InvalidFunctionArguments(name.to_string()));
+    }
+
+    match &args[0] {
+        Type::Null => Ok(Type::Null),
+        value => function(value).ok_or_else(|| Error::InvalidFunctionArguments(name.to_string())),
+    }
+}
+
+/// Integers are already whole so they're left alone, decimals stay decimals and floats become
+/// doubles
+fn rounded(value: &Type, round: fn(f64) -> f64, decimal: fn(&Decimal) -> Decimal) -> Option<Type> {
+    match value {
+        Type::Int32(_) | Type::Int64(_) | Type::UInt32(_) | Type::UInt64(_) => Some(value.clone()),
+        Type::Decimal(value) => Some(Type::Decimal(decimal(value))),
+        value => value.as_f64().map(|value| Type::Double(round(value))),
+    }
+}
+
+/// Round a decimal half away from zero to `digits` after the point, or to tens, hundreds and so
+/// on when negative
+fn round_decimal(value: &Decimal, digits: i64) -> Option<Decimal> {
+    let strategy = RoundingStrategy::MidpointAwayFromZero;
+    if digits >= 0 {
+        return Some(value.round_dp_with_strategy(digits.min(28) as u32, strategy));
+    }
+    let scale = Decimal::from_i128_with_scale(10i128.pow(digits.unsigned_abs().min(28) as u32), 0);
+    value
+        .checked_div(scale)?
+        .round_dp_with_strategy(0, strategy)
+        .checked_mul(scale)
+}
+
+/// `ROUND(value[, digits])` rounds half away from zero. Negative digits round to tens, hundreds
+/// and so on.
+fn round(args: &[Type]) -> Result<Type, Error> {
+    let digits = match args.get(1) {
+        Some(Type::Null) => return Ok(Type::Null),
+        Some(_) => integer_arg("ROUND", args, 1)?,
+        None => 0,
+    };
+    math("ROUND", args, args.len().clamp(1, 2), |value| {
+        if let Type::Decimal(value) = value {
+            return round_decimal(value, digits).map(Type::Decimal);
+        }
+        if digits == 0 {
+            return rounded(value, f64::round, Decimal::round);
+        }
+
+        let scale = 10f64.powi(digits.clamp(-308, 308) as i32);
+        let rounded = (value.as_f64()? * scale).round() / scale;
+        match value {
+            Type::Int32(_) | Type::Int64(_) | Type::UInt32(_) | Type::UInt64(_) if digits > 0 => {
+                Some(value.clone())
+            }
+            Type::Int32(_) => Some(Type::Int32(rounded as i32)),
+            Type::Int64(_) => Some(Type::Int64(rounded as i64)),
+            Type::UInt32(_) => Some(Type::UInt32(rounded as u32)),
+            Type::UInt64(_) => Some(Type::UInt64(rounded as u64)),
+            _ => Some(Type::Double(rounded)),
+        }
+    })
+}
+
+/// `POW(base, exponent)` is always a double
+fn pow(name: &str, args: &[Type]) -> Result<Type, Error> {
+    math(name, args, 2, |base| match &args[1] {
+        Type::Null => Some(Type::Null),
+        exponent => Some(Type::Double(base.as_f64()?.powf(exponent.as_f64()?))),
+    })
+}
+
+/// `GREATEST(value, ...)` and `LEAST(value, ...)` pick the largest or smallest of their numeric
+/// or datetime arguments, ignoring NULLs like postgres. Mixing integers and floats gives a
+/// double, and mixing `i32` with `i64` gives an `i64`.
+fn extreme(name: &str, args: &[Type], keep: Ordering) -> Result<Type, Error> {
+    let invalid = || Error::InvalidFunctionArguments(name.to_string());
+    if args.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut result = &Type::Null;
+    for arg in args {
+        let comparable = matches!(
+            arg,
+            Type::Int32(_)
+                | Type::Int64(_)
+                | Type::UInt32(_)
+                | Type::UInt64(_)
+                | Type::Float(_)
+                | Type::Double(_)
+                | Type::Decimal(_)
+                | Type::DateTime(_)
+                | Type::DateTimeTz(_)
+                | Type::Null
+        );
+        if !comparable {
+            return Err(invalid());
+        }
+        if *arg == Type::Null {
+            continue;
+        }
+        if *result == Type::Null || arg.compare(result).ok_or_else(invalid)? == keep {
+            result = arg;
+        }
+    }
+
+    let floats = args
+        .iter()
+        .any(|arg| matches!(arg, Type::Float(_) | Type::Double(_)));
+    let integers = args.iter().any(|arg| {
+        matches!(
+            arg,
+            Type::Int32(_) | Type::Int64(_) | Type::UInt32(_) | Type::UInt64(_)
+        )
+    });
+    Ok(match result {
+        Type::Int32(_) | Type::Int64(_) | Type::UInt32(_) | Type::UInt64(_) | Type::Float(_)
+            if floats && integers =>
+        {
+            Type::Double(result.as_f64().unwrap())
+        }
+        Type::Int32(value) if args.iter().any(|arg| matches!(arg, Type::Int64(_))) => {
+            Type::Int64(*value as i64)
+        }
+        result => result.clone(),
+    })
+}
+
+/// `SPLIT_PART(value, delimiter, index)` with a 1-based index. Like postgres, negative indexes
+/// count from the end and an index past the last part is an empty string.
+fn split_part(args: &[Type]) -> Result<Type, Error> {
+    let value = string_arg("SPLIT_PART", args, 0, 3)?;
+    let delimiter = string_arg("SPLIT_PART", args, 1, 3)?;
+    let index = integer_arg("SPLIT_PART", args, 2)?;
+    if index == 0 {
+        return Err(Error::InvalidFunctionArguments("SPLIT_PART".to_string()));
+    }
+
+    let parts: Vec<_> = if delimiter.is_empty() {
+        vec![value]
+    } else {
+        value.split(delimiter).collect()
+    };
+    let position = if index > 0 {
+        usize::try_from(index - 1).ok()
+    } else {
+        usize::try_from(index.unsigned_abs())
+            .ok()
+            .and_then(|index| parts.len().checked_sub(index))
+    };
+    let part = position.and_then(|position| parts.get(position));
+    Ok(Type::String(part.copied().unwrap_or_default().to_string()))
+}
+
+/// `JSON_EXTRACT(value, path)` with a path like `$.user.id`, `$.tags[0]` or `$["user-agent"]`.
+/// Strings, numbers and booleans keep their type and objects and arrays are JSON text. It's
+/// NULL if the path doesn't exist or the value isn't JSON, since payloads in logs are often cut
+/// short.
+fn json_extract(args: &[Type]) -> Result<Type, Error> {
+    let path = string_arg("JSON_EXTRACT", args, 1, 2)?;
+    let path = parse_json_path(path)
+        .ok_or_else(|| Error::InvalidFunctionArguments("JSON_EXTRACT".to_string()))?;
+    let value = match &args[0] {
+        Type::Null => return Ok(Type::Null),
+        Type::String(_) => match json_document(&args[0]) {
+            Some(value) => value,
+            None => return Ok(Type::Null),
+        },
+        value => json_document(value)
+            .ok_or_else(|| Error::InvalidFunctionArguments("JSON_EXTRACT".to_string()))?,
+    };
+    let value = path.iter().try_fold(&value, |value, step| match step {
+        JsonPathStep::Key(key) => value.get(key),
+        JsonPathStep::Index(index) => value.get(index),
+    });
+    Ok(value.map_or(Type::Null, json_value))
+}
+
+/// The value of a `json` column, or text parsed as JSON. `None` if it isn't JSON.
+pub(crate) fn json_document(value: &Type) -> Option<serde_json::Value> {
+    match value {
+        Type::Custom(value) if value.type_name() == "json" => Some(value.to_json()),
+        Type::String(text) => serde_json::from_str(text).ok(),
+        _ => None,
+    }
+}
+
+/// Strings, numbers and booleans keep their type, and objects and arrays are JSON text
+pub(crate) fn json_value(value: &serde_json::Value) -> Type {
+    match value {
+        serde_json::Value::Null => Type::Null,
+        serde_json::Value::Bool(value) => Type::Bool(*value),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(number) => Type::Int64(number),
+            None => number.as_f64().map_or(Type::Null, Type::Double),
+        },
+        serde_json::Value::String(value) => Type::String(value.clone()),
+        value => Type::String(value.to_string()),
+    }
+}
+
+enum JsonPathStep {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a path starting at the root `$`, followed by `.key`, `["key"]` or `[index]` steps
+fn parse_json_path(path: &str) -> Option<Vec<JsonPathStep>> {
+    let mut rest = path.trim().strip_prefix('$')?;
+    let mut steps = Vec::new();
+    while !rest.is_empty() {
+        if let Some(key) = rest.strip_prefix('.') {
+            let end = key.find(['.', '[']).unwrap_or(key.len());
+            if end == 0 {
+                return None;
+            }
+            steps.push(JsonPathStep::Key(key[..end].to_string()));
+            rest = &key[end..];
+        } else if let Some(quoted) = rest.strip_prefix("[\"") {
+            let (key, remaining) = quoted.split_once("\"]")?;
+            steps.push(JsonPathStep::Key(key.to_string()));
+            rest = remaining;
+        } else {
+            let (index, remaining) = rest.strip_prefix('[')?.split_once(']')?;
+            steps.push(JsonPathStep::Index(index.trim().parse().ok()?));
+            rest = remaining;
+        }
+    }
+    Some(steps)
+}
+
+/// `DATETIME(value)` parses RFC 3339, `2022-01-01 10:00:00` or a date at midnight, all in UTC
+fn datetime(args: &[Type]) -> Result<Type, Error> {
+    if let [Type::DateTime(value)] = args {
+        return Ok(Type::DateTime(*value));
+    }
+
+    let value = string_arg("DATETIME", args, 0, 1)?.trim();
+    let parsed = DateTime::parse_from_rfc3339(value)
+        .map(|value| value.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f")
+                .ok()
+                .map(|value| Utc.from_utc_datetime(&value))
+        })
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(|value| Utc.from_utc_datetime(&value.and_hms(0, 0, 0)))
+        });
+    parsed
+        .map(Type::DateTime)
+        .ok_or_else(|| Error::InvalidFunctionArguments("DATETIME".to_string()))
+}
+
+/// `STRFTIME(ts, format)` and `DATE_FORMAT(ts, format)` render a datetime with a chrono format
+/// like `'%H:%M'`. Datetimes with an offset are rendered in their own offset, so `%z` keeps it.
+fn strftime(name: &str, args: &[Type]) -> Result<Type, Error> {
+    let invalid = || Error::InvalidFunctionArguments(name.to_string());
+    let format = string_arg(name, args, 1, 2)?;
+    let items: Vec<_> = StrftimeItems::new(format).collect();
+    // chrono panics when displaying an invalid format, so check it up front
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(invalid());
+    }
+
+    let formatted = match &args[0] {
+        Type::Null => return Ok(Type::Null),
+        Type::DateTimeTz(value) => value.format_with_items(items.into_iter()),
+        value => match datetime(std::slice::from_ref(value)) {
+            Ok(Type::DateTime(value)) => value.format_with_items(items.into_iter()),
+            _ => return Err(invalid()),
+        },
+    };
+    Ok(Type::String(formatted.to_string()))
+}
+
+/// `TIME_BUCKET(width, ts[, origin])` and `DATE_BIN(width, ts, origin)` round a datetime down
+/// to the start of its `width` wide window, e.g. `'5m'` or `INTERVAL '1 hour'`. Windows are
+/// aligned to `origin`, which defaults to the unix epoch.
+fn time_bucket(name: &str, args: &[Type], count: RangeInclusive<usize>) -> Result<Type, Error> {
+    let invalid = || Error::InvalidFunctionArguments(name.to_string());
+    if !count.contains(&args.len()) {
+        return Err(invalid());
+    }
+
+    let width = match &args[0] {
+        Type::Interval(width) => Some(*width),
+        Type::String(width) => parse_interval(width),
+        _ => None,
+    };
+    let width = width
+        .and_then(|width| width.num_nanoseconds())
+        .filter(|width| *width > 0)
+        .ok_or_else(invalid)?;
+    let origin = match args.get(2) {
+        Some(origin) => match datetime(std::slice::from_ref(origin)) {
+            Ok(Type::DateTime(origin)) => origin,
+            _ => return Err(invalid()),
+        },
+        None => Utc.timestamp(0, 0),
+    };
+
+    let bucket = |value: DateTime<Utc>| {
+        let offset = (value - origin).num_nanoseconds()?;
+        origin.checked_add_signed(Duration::nanoseconds(offset - offset.rem_euclid(width)))
+    };
+    let bucketed = match &args[1] {
+        Type::Null => Some(Type::Null),
+        Type::DateTimeTz(value) => bucket(value.with_timezone(&Utc))
+            .map(|b| Type::DateTimeTz(b.with_timezone(value.offset()))),
+        value => match datetime(std::slice::from_ref(value)) {
+            Ok(Type::DateTime(value)) => bucket(value).map(Type::DateTime),
+            _ => None,
+        },
+    };
+    bucketed.ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: Vec<Type>) -> Result<Type, Error> {
+        FunctionRegistry::default().call(name, &args)
+    }
+
+    #[test]
+    fn string_functions() {
+        assert_eq!(call("upper", vec!["abc".into()]).unwrap(), "ABC".into());
+        assert_eq!(call("LOWER", vec!["ABC".into()]).unwrap(), "abc".into());
+        assert_eq!(call("length", vec!["héllo".into()]).unwrap(), 5i64.into());
+        assert_eq!(call("trim", vec!["  a b  ".into()]).unwrap(), "a b".into());
+        assert_eq!(
+            call("trim", vec!["xxaxx".into(), "x".into()]).unwrap(),
+            "a".into()
+        );
+        assert_eq!(call("ltrim", vec!["  a  ".into()]).unwrap(), "a  ".into());
+        assert_eq!(call("rtrim", vec!["  a  ".into()]).unwrap(), "  a".into());
+
+        // a NULL argument makes the result NULL
+        for (name, args) in [
+            ("UPPER", vec![Type::Null]),
+            ("LENGTH", vec![Type::Null]),
+            ("TRIM", vec!["a".into(), Type::Null]),
+            ("SUBSTR", vec![Type::Null, 2.into()]),
+            ("SPLIT_PART", vec!["a/b".into(), "/".into(), Type::Null]),
+            ("DATETIME", vec![Type::Null]),
+        ] {
+            assert_eq!(call(name, args).unwrap(), Type::Null, "{}", name);
+        }
+    }
+
+    #[test]
+    fn split_parts() {
+        let path = "/api/v1/users";
+        let cases = [
+            (path, "/", 1, ""),
+            (path, "/", 2, "api"),
+            (path, "/", 4, "users"),
+            (path, "/", 5, ""),
+            (path, "/", -1, "users"),
+            (path, "/", -4, ""),
+            (path, "/", -5, ""),
+            ("a::b::c", "::", 2, "b"),
+            ("a:b", "", 1, "a:b"),
+            ("a:b", "", 2, ""),
+        ];
+        for (value, delimiter, index, expected) in cases {
+            assert_eq!(
+                call(
+                    "SPLIT_PART",
+                    vec![value.into(), delimiter.into(), index.into()]
+                )
+                .unwrap(),
+                expected.into(),
+                "{} {} {}",
+                value,
+                delimiter,
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn json_extract() {
+        let payload: Type =
+            r#"{"user": {"id": 42, "name": "alice", "admin": false}, "tags": ["a", "b"], "score": 1.5, "user-agent": "curl"}"#
+                .into();
+        let cases = [
+            ("$.user.id", Type::Int64(42)),
+            ("$.user.name", "alice".into()),
+            ("$.user.admin", false.into()),
+            ("$.score", Type::Double(1.5)),
+            ("$.tags[1]", "b".into()),
+            ("$.tags", r#"["a","b"]"#.into()),
+            (r#"$["user-agent"]"#, "curl".into()),
+            ("$.user.missing", Type::Null),
+            ("$.tags[5]", Type::Null),
+            ("$.user[0]", Type::Null),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                call("JSON_EXTRACT", vec![payload.clone(), path.into()]).unwrap(),
+                expected,
+                "{}",
+                path
+            );
+        }
+
+        let user = call("JSON_EXTRACT", vec![payload, "$.user".into()]).unwrap();
+        let user: serde_json::Value = serde_json::from_str(&user.to_string()).unwrap();
+        assert_eq!(user["id"], 42);
+
+        for value in [Type::Null, r#"{"user": "#.into()] {
+            assert_eq!(
+                call("JSON_EXTRACT", vec![value, "$.user".into()]).unwrap(),
+                Type::Null
+            );
+        }
+    }
+
+    #[test]
+    fn greatest_and_least() {
+        let early: Type = Utc.ymd(2022, 5, 1).and_hms(10, 0, 0).into();
+        let late = Type::DateTimeTz(FixedOffset::east(3600).ymd(2022, 5, 1).and_hms(12, 0, 0));
+        let cases = [
+            (vec![3.into(), 7.into(), (-1).into()], 7.into(), (-1).into()),
+            (
+                vec![3.into(), Type::Int64(2)],
+                Type::Int64(3),
+                Type::Int64(2),
+            ),
+            (
+                vec![3.into(), Type::Double(2.5)],
+                Type::Double(3.0),
+                Type::Double(2.5),
+            ),
+            (vec![Type::Null, 4.into(), Type::Null], 4.into(), 4.into()),
+            (vec![Type::Null, Type::Null], Type::Null, Type::Null),
+            (vec![early.clone(), late.clone()], late, early),
+        ];
+        for (args, greatest, least) in cases {
+            assert_eq!(
+                call("GREATEST", args.clone()).unwrap(),
+                greatest,
+                "{:?}",
+                args
+            );
+            assert_eq!(call("LEAST", args.clone()).unwrap(), least, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn math_functions() {
+        let cases = [
+            ("ABS", vec![(-5).into()], Type::Int32(5)),
+            ("abs", vec![Type::Double(-1.5)], Type::Double(1.5)),
+            ("CEIL", vec![Type::Double(1.2)], Type::Double(2.0)),
+            ("CEILING", vec![Type::Float(-1.2)], Type::Double(-1.0)),
+            ("FLOOR", vec![Type::Double(-1.2)], Type::Double(-2.0)),
+            ("FLOOR", vec![7i64.into()], Type::Int64(7)),
+            ("ROUND", vec![Type::Double(2.5)], Type::Double(3.0)),
+            ("ROUND", vec![Type::Double(-2.5)], Type::Double(-3.0)),
+            (
+                "ROUND",
+                vec![Type::Double(1.23456), 2.into()],
+                Type::Double(1.23),
+            ),
+            ("ROUND", vec![1234.into(), (-2).into()], Type::Int32(1200)),
+            ("ROUND", vec![1234i64.into(), 2.into()], Type::Int64(1234)),
+            ("POW", vec![2.into(), 10.into()], Type::Double(1024.0)),
+            (
+                "POWER",
+                vec![Type::Double(4.0), Type::Double(0.5)],
+                Type::Double(2.0),
+            ),
+            ("ABS", vec![Type::Null], Type::Null),
+            ("ROUND", vec![Type::Double(1.5), Type::Null], Type::Null),
+            ("POW", vec![2.into(), Type::Null], Type::Null),
+        ];
+        for (name, args, expected) in cases {
+            assert_eq!(call(name, args).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn substr_positions() {
+        let cases = [
+            (vec!["hello".into(), 2.into()], "ello"),
+            (vec!["hello".into(), 2.into(), 3.into()], "ell"),
+            (vec!["hello".into(), 0.into(), 2.into()], "h"),
+            (vec!["hello".into(), 4.into(), 10.into()], "lo"),
+            (vec!["hello".into(), 10.into()], ""),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(call("SUBSTR", args).unwrap(), expected.into());
+        }
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let cases = [
+            ("UPPER", vec![]),
+            ("UPPER", vec![1.into()]),
+            ("LENGTH", vec!["a".into(), "b".into()]),
+            ("SUBSTR", vec!["a".into()]),
+            ("SUBSTR", vec!["a".into(), "b".into()]),
+            ("SUBSTR", vec!["a".into(), 1.into(), (-1).into()]),
+            ("ABS", vec!["a".into()]),
+            ("ABS", vec![i64::MIN.into()]),
+            ("ROUND", vec![Type::Double(1.5), Type::Double(1.0)]),
+            ("POW", vec![2.into()]),
+            ("SPLIT_PART", vec!["a:b".into(), ":".into(), 0.into()]),
+            ("SPLIT_PART", vec!["a:b".into(), ":".into()]),
+            ("JSON_EXTRACT", vec!["{}".into(), "user.id".into()]),
+            ("JSON_EXTRACT", vec!["{}".into(), "$.tags[x]".into()]),
+            ("JSON_EXTRACT", vec!["{}".into(), "$..id".into()]),
+            ("JSON_EXTRACT", vec![1.into(), "$.id".into()]),
+            ("STRFTIME", vec!["2022-05-01".into(), "%Q".into()]),
+            ("GREATEST", vec![]),
+            ("GREATEST", vec![1.into(), "a".into()]),
+            (
+                "LEAST",
+                vec![1.into(), Utc.ymd(2022, 5, 1).and_hms(10, 0, 0).into()],
+            ),
+            ("STRFTIME", vec!["not a datetime".into(), "%H".into()]),
+            ("DATE_FORMAT", vec![1.into(), "%H".into()]),
+        ];
+        for (name, args) in cases {
+            match call(name, args) {
+                Err(Error::InvalidFunctionArguments(function)) => assert_eq!(function, name),
+                x => panic!(
+                    "Error should be Error::InvalidFunctionArguments. Actual {:?}",
+                    x
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn trace_url() {
+        let mut registry = FunctionRegistry::default();
+        registry.register_trace_url("https://tracing.example.com/trace/{trace_id}?tab=spans");
+        assert_eq!(
+            registry.call("trace_url", &["abc".into()]).unwrap(),
+            "https://tracing.example.com/trace/abc?tab=spans".into()
+        );
+        assert!(registry.call("trace_url", &[1.into()]).is_err());
+    }
+
+    #[test]
+    fn datetime_formats() {
+        let midnight = Utc.ymd(2022, 1, 1).and_hms(0, 0, 0);
+        let cases = [
+            ("2022-01-01", midnight),
+            (
+                "2022-01-01 10:30:00",
+                Utc.ymd(2022, 1, 1).and_hms(10, 30, 0),
+            ),
+            ("2022-01-01T02:00:00+02:00", midnight),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                call("DATETIME", vec![value.into()]).unwrap(),
+                expected.into()
+            );
+        }
+        assert!(call("DATETIME", vec!["yesterday".into()]).is_err());
+    }
+
+    #[test]
+    fn time_buckets() {
+        let ts = Utc.ymd(2022, 1, 1).and_hms(10, 7, 30);
+        let cases = [
+            (
+                vec!["5m".into(), ts.into()],
+                Utc.ymd(2022, 1, 1).and_hms(10, 5, 0),
+            ),
+            (
+                vec![Type::Interval(Duration::hours(1)), ts.into()],
+                Utc.ymd(2022, 1, 1).and_hms(10, 0, 0),
+            ),
+            (
+                vec!["15 minutes".into(), ts.into(), "2022-01-01 00:10:00".into()],
+                Utc.ymd(2022, 1, 1).and_hms(9, 55, 0),
+            ),
+            (
+                vec![
+                    "1 day".into(),
+                    Utc.ymd(1969, 12, 31).and_hms(12, 0, 0).into(),
+                ],
+                Utc.ymd(1969, 12, 31).and_hms(0, 0, 0),
+            ),
+        ];
+        for (args, expected) in cases {
+            assert_eq!(call("TIME_BUCKET", args).unwrap(), expected.into());
+        }
+
+        let tz = FixedOffset::east(2 * 3600)
+            .ymd(2022, 1, 1)
+            .and_hms(12, 7, 30);
+        assert_eq!(
+            call("time_bucket", vec!["10m".into(), Type::DateTimeTz(tz)]).unwrap(),
+            Type::DateTimeTz(
+                FixedOffset::east(2 * 3600)
+                    .ymd(2022, 1, 1)
+                    .and_hms(12, 0, 0)
+            )
+        );
+        assert_eq!(
+            call("TIME_BUCKET", vec!["5m".into(), Type::Null]).unwrap(),
+            Type::Null
+        );
+        assert_eq!(
+            call(
+                "DATE_BIN",
+                vec!["1h".into(), ts.into(), "2022-01-01 00:30:00".into()]
+            )
+            .unwrap(),
+            Utc.ymd(2022, 1, 1).and_hms(9, 30, 0).into()
+        );
+
+        let invalid = [
+            ("DATE_BIN", vec!["1h".into(), ts.into()]),
+            ("TIME_BUCKET", vec!["0s".into(), ts.into()]),
+            ("TIME_BUCKET", vec!["-5m".into(), ts.into()]),
+            ("TIME_BUCKET", vec!["soon".into(), ts.into()]),
+            ("TIME_BUCKET", vec!["5m".into(), 1.into()]),
+        ];
+        for (name, args) in invalid {
+            assert!(call(name, args).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn strftime() {
+        let ts: Type = Utc.ymd(2022, 5, 1).and_hms_milli(9, 5, 3, 250).into();
+        let offset = FixedOffset::east(2 * 3600);
+        let local = Type::DateTimeTz(offset.ymd(2022, 5, 1).and_hms(11, 5, 3));
+        let cases = [
+            (ts.clone(), "%H:%M", "09:05"),
+            (
+                ts.clone(),
+                "%Y-%m-%d %H:%M:%S%.3f",
+                "2022-05-01 09:05:03.250",
+            ),
+            (ts, "%a %b %e, day %j", "Sun May  1, day 121"),
+            (local, "%H:%M %z", "11:05 +0200"),
+            ("2022-05-01 09:05:03".into(), "%H:%M", "09:05"),
+        ];
+        for (value, format, expected) in cases {
+            for name in ["STRFTIME", "DATE_FORMAT"] {
+                assert_eq!(
+                    call(name, vec![value.clone(), format.into()]).unwrap(),
+                    expected.into(),
+                    "{} {}",
+                    name,
+                    format
+                );
+            }
+        }
+
+        assert_eq!(
+            call("STRFTIME", vec![Type::Null, "%H".into()]).unwrap(),
+            Type::Null
+        );
+    }
+
+    #[test]
+    fn now() {
+        let now = Utc.ymd(2022, 1, 1).and_hms(10, 0, 0);
+        let mut registry = FunctionRegistry::default();
+        registry.register_now(now);
+        assert_eq!(registry.call("now", &[]).unwrap(), now.into());
+        assert_eq!(registry.call("CURRENT_TIMESTAMP", &[]).unwrap(), now.into());
+        assert!(registry.call("NOW", &[1.into()]).is_err());
+    }
+
+    #[test]
+    fn unknown_function() {
+        match call("NOPE", vec![]) {
+            Err(Error::UnknownFunction(function)) => assert_eq!(function, "NOPE"),
+            x => panic!("Error should be Error::UnknownFunction. Actual {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use crate::error::Error;
+use crate::parser::values::parse_decimal;
+use crate::schema::{ColumnType, Schema};
+use sqlparser::ast::{visit_expressions_mut, Expr, Statement, Value, VisitMut, VisitorMut};
+use std::ops::ControlFlow;
+use std::str::FromStr;
+
+/// Replace the placeholders `$1`, `$2`, ... or `?` with the query's parameters, so values are
+/// never formatted into the SQL. A value compared to a schema column, e.g. `host = $1` or
+/// `status IN ($1, $2)`, gets the column's type. Other values are numbers if they look like
+/// one and strings otherwise.
+pub(crate) fn bind_params(
+    statement: &mut Statement,
+    params: &[String],
+    schema: &Schema,
+) -> Result<(), Error> {
+    // `?` is numbered in the order it appears
+    let mut count = 0;
+    let _ = visit_expressions_mut(statement, |expr| {
+        if let Expr::Value(Value::Placeholder(placeholder)) = expr {
+            if placeholder == "?" {
+                count += 1;
+                *placeholder = format!("${}", count);
+            }
+        }
+        ControlFlow::<()>::Continue(())
+    });
+
+    match statement.visit(&mut Binder { params, schema }) {
+        ControlFlow::Break(e) => Err(e),
+        ControlFlow::Continue(()) => Ok(()),
+    }
+}
+
+struct Binder<'a> {
+    params: &'a [String],
+    schema: &'a Schema,
+}
+
+impl Binder<'_> {
+    fn column_type(&self, expr: &Expr) -> Option<ColumnType> {
+        match expr {
+            Expr::Identifier(identifier) => self
+                .schema
+                .columns
+                .iter()
+                .find(|column| column.name == identifier.value)
+                .map(|column| column.r#type.clone()),
+            _ => None,
+        }
+    }
+
+    fn bind(&self, expr: &mut Expr, column_type: Option<&ColumnType>) -> Result<(), Error> {
+        if let Expr::Value(Value::Placeholder(placeholder)) = expr {
+            let value = self.param(placeholder)?;
+            *expr = Expr::Value(literal(placeholder, value, column_type)?);
+        }
+        Ok(())
+    }
+
+    fn param(&self, placeholder: &str) -> Result<&str, Error> {
+        placeholder
+            .strip_prefix('$')
+            .and_then(|position| usize::from_str(position).ok())
+            .and_then(|position| self.params.get(position.checked_sub(1)?))
+            .map(String::as_str)
+            .ok_or_else(|| Error::MissingParameter(placeholder.to_string()))
+    }
+}
+
+impl VisitorMut for Binder<'_> {
+    type Break = Error;
+
+    // comparisons are bound before their operands are visited so they can see the column
+    fn pre_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Error> {
+        let bound = match expr {
+            Expr::BinaryOp { left, right, .. } => {
+                let (left_type, right_type) = (self.column_type(left), self.column_type(right));
+                self.bind(left, right_type.as_ref())
+                    .and_then(|_| self.bind(right, left_type.as_ref()))
+            }
+            Expr::InList { expr, list, .. } => {
+                let list_type = self.column_type(expr);
+                list.iter_mut()
+                    .try_for_each(|item| self.bind(item, list_type.as_ref()))
+            }
+            expr => self.bind(expr, None),
+        };
+        match bound {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(e) => ControlFlow::Break(e),
+        }
+    }
+}
+
+fn literal(
+    placeholder: &str,
+    value: &str,
+    column_type: Option<&ColumnType>,
+) -> Result<Value, Error> {
+    let invalid = || Error::InvalidParameter(placeholder.to_string(), value.to_string());
+    let number = |valid: bool| match valid {
+        true => Ok(Value::Number(value.to_string(), false)),
+        false => Err(invalid()),
+    };
+    match column_type {
+        Some(ColumnType::Int32) => number(i32::from_str(value).is_ok()),
+        Some(ColumnType::Int64) => number(i64::from_str(value).is_ok()),
+        Some(ColumnType::UInt32) => number(u32::from_str(value).is_ok()),
+        Some(ColumnType::UInt64) => number(u64::from_str(value).is_ok()),
+        Some(ColumnType::Float | ColumnType::Double) => number(f64::from_str(value).is_ok()),
+        Some(ColumnType::Decimal) => number(parse_decimal(value).is_some()),
+        Some(ColumnType::Bool) => bool::from_str(value)
+            .map(Value::Boolean)
+            .map_err(|_| invalid()),
+        Some(_) => Ok(Value::SingleQuotedString(value.to_string())),
+        None if f64::from_str(value).is_ok_and(f64::is_finite) => {
+            Ok(Value::Number(value.to_string(), false))
+        }
+        None => Ok(Value::SingleQuotedString(value.to_string())),
+    }
+}
@@ -0,0 +1,187 @@
+use crate::parser::stream::LineFilter;
+use crate::schema::{ColumnType, InputFormat, Schema};
+use sqlparser::ast::{
+    visit_expressions, BinaryOperator, Expr, SetExpr, Statement, TableFactor, Value,
+};
+use std::ops::ControlFlow;
+
+/// A filter of the lines that can start an event the statement selects, from the comparisons of
+/// string columns with literals in its `WHERE` clause. `host = 'web01'` is only true for lines
+/// containing `web01`, and `host IN ('web01', 'web02')` for lines containing either. Returns
+/// `None` unless the statement selects from the table directly, without `WITH`, subqueries or
+/// `TABLESAMPLE`, which see the events the `WHERE` clause doesn't select.
+///
+/// Only `regex` schemas read columns as they're written in the line, and only string columns
+/// without transforms or a default are compared as written.
+pub(crate) fn line_filter(statement: &Statement, schema: &Schema) -> Option<LineFilter> {
+    if schema.format != InputFormat::Regex {
+        return None;
+    }
+    let query = match statement {
+        Statement::Query(query) if query.with.is_none() => query,
+        _ => return None,
+    };
+    let select = match query.body.as_ref() {
+        SetExpr::Select(select) => select,
+        _ => return None,
+    };
+    match select.from.as_slice() {
+        [table] if table.joins.is_empty() => match &table.relation {
+            TableFactor::Table { with_hints, .. } if with_hints.is_empty() => (),
+            _ => return None,
+        },
+        _ => return None,
+    }
+    let subquery = visit_expressions(statement, |expr| match expr {
+        Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists { .. } => ControlFlow::Break(()),
+        _ => ControlFlow::Continue(()),
+    });
+    if subquery.is_break() {
+        return None;
+    }
+
+    LineFilter::new(&required_texts(select.selection.as_ref()?, schema))
+}
+
+/// Groups of texts of which a line contains at least one of each group when the expression is
+/// true. Expressions that aren't understood don't require anything.
+fn required_texts(expr: &Expr, schema: &Schema) -> Vec<Vec<String>> {
+    match expr {
+        Expr::Nested(expr) => required_texts(expr, schema),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            let mut texts = required_texts(left, schema);
+            texts.extend(required_texts(right, schema));
+            texts
+        }
+        // a line needs one of the texts of a group of either side, and one group is enough
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Or,
+            right,
+        } => {
+            match (
+                required_texts(left, schema).pop(),
+                required_texts(right, schema).pop(),
+            ) {
+                (Some(mut left), Some(right)) => {
+                    left.extend(right);
+                    vec![left]
+                }
+                _ => Vec::new(),
+            }
+        }
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Identifier(column), Expr::Value(Value::SingleQuotedString(text)))
+            | (Expr::Value(Value::SingleQuotedString(text)), Expr::Identifier(column))
+                if written_as_is(&column.value, schema) =>
+            {
+                vec![vec![text.clone()]]
+            }
+            _ => Vec::new(),
+        },
+        Expr::InList {
+            expr,
+            list,
+            negated: false,
+        } => match expr.as_ref() {
+            Expr::Identifier(column) if written_as_is(&column.value, schema) => {
+                let texts: Option<Vec<_>> = list
+                    .iter()
+                    .map(|item| match item {
+                        Expr::Value(Value::SingleQuotedString(text)) => Some(text.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                texts.into_iter().collect()
+            }
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the column's values are text of the line as it's written
+fn written_as_is(name: &str, schema: &Schema) -> bool {
+    schema
+        .columns
+        .iter()
+        .find(|column| column.name.eq_ignore_ascii_case(name))
+        .is_some_and(|column| {
+            matches!(column.r#type, ColumnType::String)
+                && column.transform.is_empty()
+                && column.default.is_none()
+                && !column.multiline
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::sql::parse_sql;
+
+    #[test]
+    fn derive_line_filters() {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+) (?P<path>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+    - name: path
+      type: string
+      transform: [lowercase]
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let filter = |query: &str| {
+            let columns: Vec<_> = schema.columns.iter().map(|c| c.name.clone()).collect();
+            let statements = parse_sql(query, &columns).unwrap();
+            line_filter(&statements[0], &schema)
+        };
+        let lines = ["web01 200 /a", "web02 500 /b", "db01 500 /c"];
+        let matches = |query: &str| -> Vec<&str> {
+            let filter = filter(query).unwrap();
+            lines
+                .into_iter()
+                .filter(|line| filter.is_match(line))
+                .collect()
+        };
+
+        assert_eq!(
+            matches("SELECT * FROM logs WHERE host = 'web01'"),
+            ["web01 200 /a"]
+        );
+        assert_eq!(
+            matches("SELECT * FROM logs WHERE (status = 500 AND 'db01' = host) OR host = 'web01'"),
+            ["web01 200 /a", "db01 500 /c"]
+        );
+        assert_eq!(
+            matches("SELECT COUNT(*) FROM logs WHERE host IN ('web02', 'db01') AND status > 0"),
+            ["web02 500 /b", "db01 500 /c"]
+        );
+        for query in [
+            "SELECT * FROM logs",
+            "SELECT * FROM logs WHERE status = 500",
+            "SELECT * FROM logs WHERE path = '/a'",
+            "SELECT * FROM logs WHERE host != 'web01'",
+            "SELECT * FROM logs WHERE host NOT IN ('web01')",
+            "SELECT * FROM logs WHERE host = 'web01' OR status = 500",
+            "SELECT * FROM logs WHERE host = ''",
+            "SELECT * FROM logs WHERE host = 'web01' AND status IN (SELECT status FROM logs)",
+            "WITH t AS (SELECT * FROM logs) SELECT * FROM logs WHERE host = 'web01'",
+            "SELECT * FROM logs TABLESAMPLE (10 PERCENT) WHERE host = 'web01'",
+        ] {
+            assert!(filter(query).is_none(), "{}", query);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::engine::sql::parse_sql;
+use crate::error::Error;
+use regex::{Captures, Regex};
+use sqlparser::ast::{Expr, SetExpr, Statement};
+
+/// Parse a schema's `row_filter`, replacing `$NAME` and `${NAME}` with the environment variable
+/// `NAME`. Values have their single quotes doubled so they can't end a string literal early,
+/// e.g. `tenant = '$TENANT'` stays a single comparison whatever `TENANT` is.
+pub(crate) fn parse_row_filter(
+    filter: &str,
+    columns: &[String],
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Expr, Error> {
+    let invalid = |reason: String| Error::InvalidRowFilter(filter.to_string(), reason);
+    let variable = Regex::new(r"\$(?:\{([A-Za-z_]\w*)\}|([A-Za-z_]\w*))").unwrap();
+
+    let mut missing = None;
+    let bound = variable.replace_all(filter, |captures: &Captures| {
+        let name = captures
+            .get(1)
+            .or_else(|| captures.get(2))
+            .unwrap()
+            .as_str();
+        match env(name) {
+            Some(value) => value.replace('\'', "''"),
+            None => {
+                missing.get_or_insert_with(|| name.to_string());
+                String::new()
+            }
+        }
+    });
+    if let Some(name) = missing {
+        return Err(invalid(format!(
+            "environment variable '{}' is not set",
+            name
+        )));
+    }
+
+    let query = format!("SELECT * FROM logs WHERE {}", bound);
+    let mut statements = parse_sql(&query, columns).map_err(|e| invalid(e.to_string()))?;
+    match statements.pop() {
+        Some(Statement::Query(query)) if statements.is_empty() => match *query.body {
+            SetExpr::Select(select) if query.order_by.is_empty() && query.limit.is_none() => select
+                .selection
+                .ok_or_else(|| invalid("it is empty".to_string())),
+            _ => Err(invalid("it must be a single expression".to_string())),
+        },
+        _ => Err(invalid("it must be a single expression".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(name: &str) -> Option<String> {
+        match name {
+            "TENANT" => Some("acme".to_string()),
+            "QUOTED" => Some("x' OR '1' = '1".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn bind_environment_variables() {
+        let cases = [
+            ("tenant = '$TENANT'", "tenant = 'acme'"),
+            (
+                "tenant = '${TENANT}' AND level <> 'DEBUG'",
+                "tenant = 'acme' AND level <> 'DEBUG'",
+            ),
+            ("tenant = '$QUOTED'", "tenant = 'x'' OR ''1'' = ''1'"),
+        ];
+        for (filter, expected) in cases {
+            let expr = parse_row_filter(filter, &[], env).unwrap();
+            assert_eq!(expected, expr.to_string());
+        }
+    }
+
+    #[test]
+    fn invalid_row_filters() {
+        let cases = [
+            "tenant = '$MISSING'",
+            "",
+            "tenant = 'a' ORDER BY tenant",
+            "tenant = 'a'; SELECT 1",
+            "tenant = 'a' UNION ALL SELECT * FROM logs",
+            "tenant =",
+        ];
+        for filter in cases {
+            match parse_row_filter(filter, &[], env) {
+                Err(Error::InvalidRowFilter(actual, _)) => assert_eq!(filter, actual),
+                x => panic!("Error should be Error::InvalidRowFilter. Actual {:?}", x),
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::error::Error;
+use crate::parser::values::Event;
+use sqlparser::ast::{Expr, FunctionArg, FunctionArgExpr, Value};
+
+/// Function that `TABLESAMPLE` clauses are moved into, as a table hint, so the generic dialect
+/// can parse them
+pub(crate) const SAMPLE_MARKER: &str = "__logql_sample";
+
+/// The rows of a table kept by `TABLESAMPLE (10 PERCENT)` or `TABLESAMPLE (1000 ROWS)`. Rows are
+/// picked by hashing their position with the seed from `REPEATABLE (seed)`, which defaults to the
+/// engine's seed, so the same query samples the same rows every run.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Sample {
+    size: SampleSize,
+    seed: u64,
+}
+
+#[derive(Debug, PartialEq)]
+enum SampleSize {
+    /// Each row is kept with this probability, from 0 to 1
+    Fraction(f64),
+    /// This many rows, or every row of smaller tables
+    Rows(usize),
+}
+
+impl Sample {
+    /// Read the sample from a table's hints, if it has one, seeded with `seed` unless it's
+    /// `REPEATABLE`
+    pub(crate) fn from_hints(hints: &[Expr], seed: u64) -> Result<Option<Sample>, Error> {
+        let args = hints.iter().find_map(|hint| match hint {
+            Expr::Function(function) if function.name.to_string() == SAMPLE_MARKER => {
+                Some(&function.args)
+            }
+            _ => None,
+        });
+        let args: Vec<_> = match args {
+            Some(args) => args
+                .iter()
+                .map(|arg| match arg {
+                    FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Value(value))) => Some(value),
+                    _ => None,
+                })
+                .collect(),
+            None => return Ok(None),
+        };
+
+        let (amount, unit, repeatable) = match args.as_slice() {
+            [Some(Value::Number(amount, _)), Some(Value::SingleQuotedString(unit))] => {
+                (amount, unit, None)
+            }
+            [Some(Value::Number(amount, _)), Some(Value::SingleQuotedString(unit)), Some(Value::Number(seed, _))] => {
+                (amount, unit, Some(seed))
+            }
+            _ => {
+                return Err(Error::InvalidTableSample(
+                    "the size and seed must be numbers".to_string(),
+                ))
+            }
+        };
+        let seed = match repeatable {
+            Some(repeatable) => repeatable.parse().map_err(|_| {
+                Error::InvalidTableSample(format!("seed {} must be a positive integer", repeatable))
+            })?,
+            None => seed,
+        };
+
+        let size = match unit.as_str() {
+            "ROWS" => SampleSize::Rows(amount.parse().map_err(|_| {
+                Error::InvalidTableSample(format!("{} is not a number of rows", amount))
+            })?),
+            _ => match amount.parse::<f64>() {
+                Ok(percent) if (0.0..=100.0).contains(&percent) => {
+                    SampleSize::Fraction(percent / 100.0)
+                }
+                _ => {
+                    return Err(Error::InvalidTableSample(format!(
+                        "{} percent is not between 0 and 100",
+                        amount
+                    )))
+                }
+            },
+        };
+        Ok(Some(Sample { size, seed }))
+    }
+
+    /// Copy the sampled events, keeping their order
+    pub(crate) fn apply(&self, events: &[Event]) -> Vec<Event> {
+        match self.size {
+            SampleSize::Fraction(fraction) => events
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| self.position(*index) < fraction)
+                .map(|(_, event)| event.clone())
+                .collect(),
+            SampleSize::Rows(rows) if rows >= events.len() => events.to_vec(),
+            SampleSize::Rows(rows) => {
+                // the rows with the lowest positions are a uniform sample of the table
+                let mut indexes: Vec<_> = (0..events.len()).collect();
+                indexes.sort_by(|a, b| self.position(*a).total_cmp(&self.position(*b)));
+                indexes.truncate(rows);
+                indexes.sort_unstable();
+                indexes.into_iter().map(|i| events[i].clone()).collect()
+            }
+        }
+    }
+
+    /// Where the row lands between 0 and 1, from a splitmix64 hash of its index
+    fn position(&self, index: usize) -> f64 {
+        let mut hash = self
+            .seed
+            .wrapping_add((index as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        hash ^= hash >> 31;
+        (hash >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::values::Type;
+
+    fn events(count: i32) -> Vec<Event> {
+        (0..count)
+            .map(|i| Event {
+                values: [("i".to_string(), Type::Int32(i))].into_iter().collect(),
+                extra_text: None,
+            })
+            .collect()
+    }
+
+    fn sample(size: SampleSize, seed: u64) -> Vec<i32> {
+        Sample { size, seed }
+            .apply(&events(10_000))
+            .iter()
+            .map(|e| match e.values["i"] {
+                Type::Int32(i) => i,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sample_events() {
+        let percent = sample(SampleSize::Fraction(0.1), 0);
+        assert!((900..1100).contains(&percent.len()), "{}", percent.len());
+        assert!(percent.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(percent, sample(SampleSize::Fraction(0.1), 0));
+        assert_ne!(percent, sample(SampleSize::Fraction(0.1), 1));
+        assert!(sample(SampleSize::Fraction(0.0), 0).is_empty());
+        assert_eq!(10_000, sample(SampleSize::Fraction(1.0), 0).len());
+
+        let rows = sample(SampleSize::Rows(100), 7);
+        assert_eq!(100, rows.len());
+        assert!(rows.windows(2).all(|w| w[0] < w[1]));
+        // spread over the table rather than the first rows
+        assert!(rows.iter().any(|i| *i > 5_000));
+        assert_eq!(10_000, sample(SampleSize::Rows(20_000), 0).len());
+    }
+}
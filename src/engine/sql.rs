@@ -0,0 +1,431 @@
+use crate::engine::sample::SAMPLE_MARKER;
+use crate::error::Error;
+use sqlparser::ast::{
+    visit_expressions_mut, Expr, FunctionArg, FunctionArgExpr, Ident, Query, SelectItem, SetExpr,
+    Statement, TableFactor, VisitMut, VisitorMut,
+};
+use sqlparser::dialect::GenericDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::{Parser as SqlParser, ParserError};
+use sqlparser::tokenizer::{Token, Tokenizer};
+use std::ops::ControlFlow;
+
+/// Keywords that can follow an interval's value, e.g. `INTERVAL '1' HOUR TO MINUTE`
+const INTERVAL_FIELDS: [Keyword; 14] = [
+    Keyword::YEAR,
+    Keyword::MONTH,
+    Keyword::WEEK,
+    Keyword::DAY,
+    Keyword::HOUR,
+    Keyword::MINUTE,
+    Keyword::SECOND,
+    Keyword::MILLISECOND,
+    Keyword::MILLISECONDS,
+    Keyword::MICROSECOND,
+    Keyword::MICROSECONDS,
+    Keyword::NANOSECOND,
+    Keyword::NANOSECONDS,
+    Keyword::TO,
+];
+
+/// Function that aggregate filters are moved into so the generic dialect can parse them
+const FILTER_MARKER: &str = "__logql_filter";
+
+/// Parse SQL into statements. Columns are used to tell a column like `user` from the SQL
+/// function of the same name.
+pub(crate) fn parse_sql(query: &str, columns: &[String]) -> Result<Vec<Statement>, Error> {
+    let dialect = GenericDialect {};
+    let tokens = Tokenizer::new(&dialect, query)
+        .tokenize()
+        .map_err(ParserError::from)?;
+    let mut statements = SqlParser::new(&dialect)
+        .with_tokens(move_table_samples(move_aggregate_filters(group_intervals(
+            tokens,
+        ))))
+        .parse_statements()?;
+
+    for statement in &mut statements {
+        let _ = statement.visit(&mut Unqualify);
+        let _ = visit_expressions_mut(statement, |expr| {
+            match expr {
+                Expr::Nested(nested) if matches!(nested.as_ref(), Expr::Interval(_)) => {
+                    *expr = nested.as_ref().clone();
+                }
+                Expr::Function(function) if function.name.to_string() == FILTER_MARKER => {
+                    if let [FunctionArg::Unnamed(FunctionArgExpr::Expr(Expr::Function(aggregate))), FunctionArg::Unnamed(FunctionArgExpr::Expr(filter))] =
+                        function.args.as_slice()
+                    {
+                        let mut aggregate = aggregate.clone();
+                        aggregate.filter = Some(Box::new(filter.clone()));
+                        *expr = Expr::Function(aggregate);
+                    }
+                }
+                Expr::Function(function) if function.special && function.args.is_empty() => {
+                    let name = function.name.to_string();
+                    if columns.contains(&name) {
+                        *expr = Expr::Identifier(Ident::new(name));
+                    }
+                }
+                _ => (),
+            }
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    Ok(statements)
+}
+
+/// Remove table qualifiers from columns, so `SELECT l.host, l.* FROM logs l` is
+/// `SELECT host, * FROM logs l`, like queries copied from other tools. A qualifier can be the
+/// table's name or its alias. Other qualifiers are left for the engine to reject.
+struct Unqualify;
+
+impl VisitorMut for Unqualify {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &mut Query) -> ControlFlow<()> {
+        let relations: Vec<_> = match query.body.as_ref() {
+            SetExpr::Select(select) => select
+                .from
+                .iter()
+                .flat_map(|table| {
+                    std::iter::once(&table.relation).chain(table.joins.iter().map(|j| &j.relation))
+                })
+                .collect(),
+            _ => return ControlFlow::Continue(()),
+        };
+        // the table and the lists it's unnested with
+        let mut qualifiers = Vec::new();
+        for relation in relations {
+            match relation {
+                TableFactor::Table { name, alias, .. } if qualifiers.is_empty() => {
+                    qualifiers.extend(name.0.last().cloned());
+                    qualifiers.extend(alias.as_ref().map(|alias| alias.name.clone()));
+                }
+                TableFactor::UNNEST { alias, .. } if !qualifiers.is_empty() => {
+                    qualifiers.extend(alias.as_ref().map(|alias| alias.name.clone()));
+                }
+                _ => return ControlFlow::Continue(()),
+            }
+        }
+        let qualifiers: Vec<_> = qualifiers
+            .iter()
+            .map(|ident| ident.value.to_lowercase())
+            .collect();
+        let qualified = |idents: &[Ident]| match idents {
+            [qualifier] => qualifiers.contains(&qualifier.value.to_lowercase()),
+            _ => false,
+        };
+
+        if let SetExpr::Select(select) = query.body.as_mut() {
+            for item in &mut select.projection {
+                if let SelectItem::QualifiedWildcard(name, options) = item {
+                    if qualified(&name.0) {
+                        *item = SelectItem::Wildcard(options.clone());
+                    }
+                }
+            }
+        }
+        let _ = visit_expressions_mut(query, |expr| {
+            if let Expr::CompoundIdentifier(idents) = expr {
+                if let Some((column, qualifier)) = idents.split_last() {
+                    if qualified(qualifier) {
+                        *expr = Expr::Identifier(column.clone());
+                    }
+                }
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        ControlFlow::Continue(())
+    }
+}
+
+/// Wrap `INTERVAL '...'` in parentheses. Otherwise the interval's value swallows the rest of the
+/// expression, so `ts - INTERVAL '1 hour' > x` would be parsed as `ts - INTERVAL ('1 hour' > x)`.
+fn group_intervals(tokens: Vec<Token>) -> Vec<Token> {
+    let is_keyword = |token: Option<&Token>, keywords: &[Keyword]| match token {
+        Some(Token::Word(word)) => keywords.contains(&word.keyword),
+        _ => false,
+    };
+    let next_token = |index: usize| {
+        (index..tokens.len())
+            .find(|i| !matches!(tokens[*i], Token::Whitespace(_)))
+            .unwrap_or(tokens.len())
+    };
+
+    let mut grouped = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        let value = next_token(index + 1);
+        let literal = matches!(
+            tokens.get(value),
+            Some(Token::SingleQuotedString(_) | Token::Number(_, _))
+        );
+        if !is_keyword(tokens.get(index), &[Keyword::INTERVAL]) || !literal {
+            grouped.push(tokens[index].clone());
+            index += 1;
+            continue;
+        }
+
+        let mut last = value;
+        while is_keyword(tokens.get(next_token(last + 1)), &INTERVAL_FIELDS) {
+            last = next_token(last + 1);
+        }
+        grouped.push(Token::LParen);
+        grouped.extend_from_slice(&tokens[index..=last]);
+        grouped.push(Token::RParen);
+        index = last + 1;
+    }
+
+    grouped
+}
+
+/// The generic dialect can't parse `COUNT(*) FILTER (WHERE x)`, so rewrite it to
+/// `__logql_filter(COUNT(*), x)`, which [`parse_sql`] turns back into the aggregate's filter
+fn move_aggregate_filters(tokens: Vec<Token>) -> Vec<Token> {
+    let is_keyword = |token: Option<&Token>, keyword: Keyword| matches!(token, Some(Token::Word(word)) if word.keyword == keyword);
+    let next_token = |index: usize| {
+        (index..tokens.len())
+            .find(|i| !matches!(tokens[*i], Token::Whitespace(_)))
+            .unwrap_or(tokens.len())
+    };
+
+    let mut moved: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        let open = next_token(index + 1);
+        let condition = next_token(open + 1);
+        let filter = is_keyword(tokens.get(index), Keyword::FILTER)
+            && tokens.get(open) == Some(&Token::LParen)
+            && is_keyword(tokens.get(condition), Keyword::WHERE)
+            && function_start(&moved).is_some();
+        if !filter {
+            moved.push(tokens[index].clone());
+            index += 1;
+            continue;
+        }
+
+        let start = function_start(&moved).unwrap();
+        moved.splice(
+            start..start,
+            [Token::make_word(FILTER_MARKER, None), Token::LParen],
+        );
+        moved.push(Token::Comma);
+        // the filter's closing parenthesis closes the marker call
+        index = condition + 1;
+    }
+
+    moved
+}
+
+/// The generic dialect can't parse `logs TABLESAMPLE BERNOULLI (10 PERCENT) REPEATABLE (42)`,
+/// so rewrite it to the table hint `logs WITH (__logql_sample(10, 'PERCENT', 42))`, which
+/// [`Sample::from_hints`](crate::engine::sample::Sample::from_hints) reads. The method can be
+/// `BERNOULLI` or `SYSTEM`, which both sample rows, and the size defaults to `PERCENT`.
+fn move_table_samples(tokens: Vec<Token>) -> Vec<Token> {
+    let is_word = |index: usize, words: &[&str]| match tokens.get(index) {
+        Some(Token::Word(word)) => words.iter().any(|w| word.value.eq_ignore_ascii_case(w)),
+        _ => false,
+    };
+    let next_token = |index: usize| {
+        (index..tokens.len())
+            .find(|i| !matches!(tokens[*i], Token::Whitespace(_)))
+            .unwrap_or(tokens.len())
+    };
+    // index of the parenthesis closing the one at `open`
+    let closing = |open: usize| {
+        if tokens.get(open) != Some(&Token::LParen) {
+            return None;
+        }
+        let mut depth = 0;
+        for (index, token) in tokens.iter().enumerate().skip(open) {
+            match token {
+                Token::LParen => depth += 1,
+                Token::RParen if depth == 1 => return Some(index),
+                Token::RParen => depth -= 1,
+                _ => (),
+            }
+        }
+        None
+    };
+
+    let mut moved = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        let mut open = next_token(index + 1);
+        if is_word(open, &["BERNOULLI", "SYSTEM"]) {
+            open = next_token(open + 1);
+        }
+        let close = match closing(open) {
+            Some(close) if is_word(index, &["TABLESAMPLE"]) => close,
+            _ => {
+                moved.push(tokens[index].clone());
+                index += 1;
+                continue;
+            }
+        };
+
+        let mut size = &tokens[open + 1..close];
+        let mut unit = "PERCENT";
+        if let Some(last) = (open + 1..close)
+            .rev()
+            .find(|i| !matches!(tokens[*i], Token::Whitespace(_)))
+        {
+            if is_word(last, &["PERCENT", "ROWS"]) {
+                size = &tokens[open + 1..last];
+                unit = if is_word(last, &["ROWS"]) {
+                    "ROWS"
+                } else {
+                    "PERCENT"
+                };
+            }
+        }
+
+        let mut end = close;
+        let mut seed = None;
+        let repeatable = next_token(close + 1);
+        if is_word(repeatable, &["REPEATABLE"]) {
+            let open = next_token(repeatable + 1);
+            if let Some(close) = closing(open) {
+                seed = Some(&tokens[open + 1..close]);
+                end = close;
+            }
+        }
+
+        moved.extend([
+            Token::make_keyword("WITH"),
+            Token::LParen,
+            Token::make_word(SAMPLE_MARKER, None),
+            Token::LParen,
+        ]);
+        moved.extend_from_slice(size);
+        moved.extend([Token::Comma, Token::SingleQuotedString(unit.to_string())]);
+        if let Some(seed) = seed {
+            moved.push(Token::Comma);
+            moved.extend_from_slice(seed);
+        }
+        moved.extend([Token::RParen, Token::RParen]);
+        index = end + 1;
+    }
+
+    moved
+}
+
+/// Index of the name of the function call that the tokens end with, e.g. `COUNT` in `COUNT(*)`
+fn function_start(tokens: &[Token]) -> Option<usize> {
+    let non_whitespace = |end: usize| {
+        (0..end)
+            .rev()
+            .find(|i| !matches!(tokens[*i], Token::Whitespace(_)))
+    };
+
+    let close = non_whitespace(tokens.len())?;
+    if tokens[close] != Token::RParen {
+        return None;
+    }
+    let mut depth = 0;
+    for open in (0..=close).rev() {
+        match tokens[open] {
+            Token::RParen => depth += 1,
+            Token::LParen => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            let name = non_whitespace(open)?;
+            return matches!(tokens[name], Token::Word(_)).then_some(name);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_intervals() {
+        let query = "SELECT a FROM logs WHERE ts - INTERVAL '1 hour' > x \
+            AND ts < y + INTERVAL '2' HOUR TO MINUTE";
+        let statements = parse_sql(query, &[]).unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "SELECT a FROM logs WHERE ts - INTERVAL '1 hour' > x \
+                AND ts < y + INTERVAL '2' HOUR TO MINUTE"
+        );
+    }
+
+    #[test]
+    fn parse_aggregate_filters() {
+        let query = "SELECT host, COUNT(*) FILTER (WHERE status >= 500), \
+            SUM(bytes) FILTER (WHERE ok AND (a OR b)) AS okay FROM logs GROUP BY host";
+        let statements = parse_sql(query, &[]).unwrap();
+        assert_eq!(
+            statements[0].to_string(),
+            "SELECT host, COUNT(*) FILTER (WHERE status >= 500), \
+                SUM(bytes) FILTER (WHERE ok AND (a OR b)) AS okay FROM logs GROUP BY host"
+        );
+    }
+
+    #[test]
+    fn parse_table_samples() {
+        for (query, expected) in [
+            (
+                "SELECT * FROM logs TABLESAMPLE (1 PERCENT) WHERE a = 1",
+                "SELECT * FROM logs WITH (__logql_sample(1, 'PERCENT')) WHERE a = 1",
+            ),
+            (
+                "SELECT * FROM logs AS l tablesample bernoulli (2.5) repeatable (42)",
+                "SELECT * FROM logs AS l WITH (__logql_sample(2.5, 'PERCENT', 42))",
+            ),
+            (
+                "WITH e AS (SELECT * FROM logs) SELECT * FROM e TABLESAMPLE SYSTEM (100 ROWS)",
+                "WITH e AS (SELECT * FROM logs) SELECT * FROM e WITH (__logql_sample(100, 'ROWS'))",
+            ),
+        ] {
+            let statements = parse_sql(query, &[]).unwrap();
+            assert_eq!(statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn parse_qualified_columns() {
+        for (query, expected) in [
+            (
+                "SELECT logs.host, LOGS.status FROM logs WHERE logs.status > 1 ORDER BY logs.host",
+                "SELECT host, status FROM logs WHERE status > 1 ORDER BY host",
+            ),
+            (
+                "SELECT l.*, COUNT(l.id) FROM logs AS l GROUP BY l.host",
+                "SELECT *, COUNT(id) FROM logs AS l GROUP BY host",
+            ),
+            (
+                "SELECT e.host FROM (SELECT 1) AS e WHERE x.host IN (SELECT l.host FROM logs l)",
+                "SELECT e.host FROM (SELECT 1) AS e WHERE x.host IN (SELECT host FROM logs AS l)",
+            ),
+        ] {
+            let statements = parse_sql(query, &[]).unwrap();
+            assert_eq!(statements[0].to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn parse_columns_named_like_functions() {
+        let query = "SELECT user, current_user FROM logs";
+        let statements = parse_sql(query, &["user".to_string()]).unwrap();
+        let projection = match &statements[0] {
+            Statement::Query(query) => match query.body.as_ref() {
+                sqlparser::ast::SetExpr::Select(select) => select.projection.clone(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert!(matches!(
+            &projection[0],
+            sqlparser::ast::SelectItem::UnnamedExpr(Expr::Identifier(_))
+        ));
+        assert!(matches!(
+            &projection[1],
+            sqlparser::ast::SelectItem::UnnamedExpr(Expr::Function(_))
+        ));
+    }
+}
@@ -0,0 +1,366 @@
+use crate::engine::aggregate::{contains_aggregate, group_by_expressions};
+use crate::engine::cancel::CancellationToken;
+use crate::engine::pushdown::line_filter;
+use crate::engine::{process_peak_memory, Engine, QueryStats, TableResult};
+use crate::error::Error;
+use crate::parser::encoding::{decode_lines, Encoding};
+use crate::parser::stream::{split_lines, EventStream};
+use crate::parser::values::Event;
+use crate::schema::InputFormat;
+use chrono::{DateTime, Utc};
+use sqlparser::ast::{
+    visit_expressions, Expr, Offset, SelectItem, SetExpr, Statement, TableFactor, Value,
+};
+use std::io::BufRead;
+use std::ops::ControlFlow;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Events parsed before the query runs on them
+const BATCH_SIZE: usize = 1024;
+
+impl Engine {
+    /// Whether the statement can run on batches of events as they're parsed, keeping only the
+    /// rows it selects. That's a `SELECT` with a `WHERE`, `LIMIT` and `OFFSET` from the table,
+    /// but not one that needs every event first, like `ORDER BY`, `GROUP BY`, aggregates,
+    /// `DISTINCT`, `WITH`, subqueries or `TABLESAMPLE`. Sources whose columns
+    /// are discovered from their events, and JSON array sources, are read at once too.
+    pub(crate) fn streams(&self, statement: &Statement) -> bool {
+        let schema = &self.parser.schema;
+        if schema.discovers_columns()
+            || matches!(
+                schema.format,
+                InputFormat::JsonArray | InputFormat::Evtx | InputFormat::Pcap
+            )
+        {
+            return false;
+        }
+        let query = match statement {
+            Statement::Query(query) if query.with.is_none() && query.order_by.is_empty() => query,
+            _ => return false,
+        };
+        let select = match query.body.as_ref() {
+            SetExpr::Select(select) => select,
+            _ => return false,
+        };
+        let grouped = !group_by_expressions(select).is_ok_and(<[Expr]>::is_empty)
+            || select.having.is_some()
+            || select.projection.iter().any(|item| match item {
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => {
+                    contains_aggregate(expr)
+                }
+                // `attrs.*` has a column per key of all the events
+                SelectItem::QualifiedWildcard(..) => true,
+                SelectItem::Wildcard(_) => false,
+            });
+        // subqueries in `FROM` and `TABLESAMPLE` need the whole table
+        let mut relations = select.from.iter().flat_map(|table| {
+            std::iter::once(&table.relation).chain(table.joins.iter().map(|join| &join.relation))
+        });
+        let whole_table = relations.any(|relation| match relation {
+            TableFactor::Table { with_hints, .. } => !with_hints.is_empty(),
+            TableFactor::UNNEST { .. } => false,
+            _ => true,
+        });
+        let subquery = visit_expressions(statement, |expr| match expr {
+            Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists { .. } => {
+                ControlFlow::Break(())
+            }
+            _ => ControlFlow::Continue(()),
+        });
+        !grouped && !whole_table && select.distinct.is_none() && subquery.is_continue()
+    }
+
+    /// Execute the engine's query against sources as they're read, e.g. files opened one at a
+    /// time, with the file name of each one for the `_file` meta column. The sources are decoded
+    /// line by line in the schema's encoding, or the one detected from their start. Queries that
+    /// [stream](Engine::streams) only hold the line being read and the rows they select, and
+    /// stop reading once they have enough rows for their `LIMIT`. Other queries read every
+    /// source first and run like [`Engine::execute`].
+    pub fn execute_sources<I, R>(&self, sources: I) -> Result<TableResult, Error>
+    where
+        I: IntoIterator<Item = (Option<String>, R)>,
+        R: BufRead,
+    {
+        self.execute_sources_with_cancellation(sources, &CancellationToken::default())
+    }
+
+    /// Execute like [`Engine::execute_sources`], stopping with [`Error::Cancelled`] once the
+    /// token is cancelled
+    pub fn execute_sources_with_cancellation<I, R>(
+        &self,
+        sources: I,
+        cancellation: &CancellationToken,
+    ) -> Result<TableResult, Error>
+    where
+        I: IntoIterator<Item = (Option<String>, R)>,
+        R: BufRead,
+    {
+        if self.statements.len() > 1 {
+            return Err(Error::TooManySqlQueries);
+        }
+        let mut results = self.execute_all_sources_with_cancellation(sources, cancellation)?;
+        Ok(results.remove(0))
+    }
+
+    /// Execute each statement of the engine's query against sources as they're read, returning
+    /// a result per statement. Only a query with a single statement that
+    /// [streams](Engine::streams) reads them lazily, since the statements of other queries run
+    /// against the same events.
+    pub fn execute_all_sources<I, R>(&self, sources: I) -> Result<Vec<TableResult>, Error>
+    where
+        I: IntoIterator<Item = (Option<String>, R)>,
+        R: BufRead,
+    {
+        self.execute_all_sources_with_cancellation(sources, &CancellationToken::default())
+    }
+
+    /// Execute like [`Engine::execute_all_sources`], stopping with [`Error::Cancelled`] once the
+    /// token is cancelled
+    pub fn execute_all_sources_with_cancellation<I, R>(
+        &self,
+        sources: I,
+        cancellation: &CancellationToken,
+    ) -> Result<Vec<TableResult>, Error>
+    where
+        I: IntoIterator<Item = (Option<String>, R)>,
+        R: BufRead,
+    {
+        let encoding = match self.parser.schema.format {
+            // evtx files and packet captures are JSON once they're converted
+            InputFormat::Evtx | InputFormat::Pcap => Some(Encoding::Utf8),
+            _ => self.parser.schema.encoding,
+        };
+        let sources = sources.into_iter().map(|(name, reader)| {
+            let source = name.clone().unwrap_or_else(|| "source".to_string());
+            let lines = decode_lines(reader, encoding).map(move |line| {
+                line.map_err(|e| Error::ReadFailed(source.clone(), e.to_string()))
+            });
+            (name, lines)
+        });
+        if let [statement] = self.statements.as_slice() {
+            if self.streams(statement) {
+                return Ok(vec![self.stream(statement, sources, cancellation)?]);
+            }
+        }
+        let sources: Vec<_> = sources
+            .map(|(name, lines)| Ok((name, lines.collect::<Result<String, Error>>()?)))
+            .collect::<Result<_, Error>>()?;
+        let (names, sources): (Vec<_>, Vec<_>) = sources.into_iter().unzip();
+        // names are matched to sources by position, so they stop at the first unnamed one
+        let names: Vec<_> = names.into_iter().map_while(|name| name).collect();
+        self.execute_named(sources, &names, cancellation)
+    }
+
+    /// Parse the sources in batches, running the statement's `SELECT` on each batch before the
+    /// next one is parsed, then `OFFSET` and `LIMIT` on the rows of all of them. Each source is
+    /// read in chunks of text, like its lines.
+    pub(crate) fn stream<I, S, T>(
+        &self,
+        statement: &Statement,
+        sources: I,
+        cancellation: &CancellationToken,
+    ) -> Result<TableResult, Error>
+    where
+        I: Iterator<Item = (Option<String>, S)>,
+        S: IntoIterator<Item = Result<T, Error>>,
+        T: AsRef<str>,
+    {
+        self.parser.verify_custom_types()?;
+        let number = |expr: &Expr| match expr {
+            Expr::Value(Value::Number(number, _)) => usize::from_str(number).ok(),
+            _ => None,
+        };
+        // rows the query can return, after which the rest of the sources isn't read
+        let wanted = match statement {
+            Statement::Query(query) => query.limit.as_ref().and_then(number).map(|limit| {
+                let offset = query.offset.as_ref().map_or(Some(0), |offset| {
+                    let Offset { value, .. } = offset;
+                    number(value)
+                });
+                limit + offset.unwrap_or(0)
+            }),
+            _ => None,
+        };
+
+        let now = Utc::now();
+        let started = Instant::now();
+        let mut stream = EventStream::new(&self.parser);
+        if let Some(filter) = line_filter(statement, &self.parser.schema) {
+            stream.set_filter(filter);
+        }
+        let mut result: Option<TableResult> = None;
+        let mut batch = Vec::new();
+        let mut bytes_read = 0;
+        let mut rows_scanned = 0;
+        // selects the batch's rows, returning whether the query has all the rows it needs
+        let mut select = |batch: &mut Vec<Event>| -> Result<bool, Error> {
+            rows_scanned += batch.len();
+            let events = std::mem::take(batch);
+            let rows = self.select_batch(statement, &mut result, events, now, cancellation)?;
+            Ok(wanted.is_some_and(|wanted| rows >= wanted))
+        };
+        let mut done = false;
+        'sources: for (name, chunks) in sources {
+            if let Some(name) = name {
+                batch.extend(stream.start_file(&name));
+            }
+            for chunk in chunks {
+                let chunk = chunk?;
+                let chunk = chunk.as_ref();
+                bytes_read += chunk.len();
+                for line in split_lines(chunk) {
+                    cancellation.check()?;
+                    batch.extend(stream.push_line(line, started)?);
+                    if batch.len() >= BATCH_SIZE && select(&mut batch)? {
+                        done = true;
+                        break 'sources;
+                    }
+                }
+            }
+            // a record or a continued line can't continue in the next source
+            batch.extend(stream.end_record(started)?);
+            // with a `LIMIT`, each source's rows are selected before the next one is read
+            if wanted.is_some() && !batch.is_empty() && select(&mut batch)? {
+                done = true;
+                break;
+            }
+        }
+        if !done {
+            batch.extend(stream.finish());
+        }
+        select(&mut batch)?;
+
+        let mut result = result.unwrap();
+        // the batches ran without the `OFFSET` and `LIMIT`
+        result.statement = Some(statement.clone());
+        result.source = Arc::default();
+        let mut result = result.offset()?.limit()?;
+        result.warnings = stream.take_warnings();
+        result.parse_stats = stream.take_stats();
+        result.stats = QueryStats {
+            bytes_read,
+            rows_scanned,
+            process_peak_memory_bytes: process_peak_memory(),
+        };
+        Ok(result)
+    }
+
+    /// Run the `SELECT` of the statement on a batch of events, adding its rows to the result.
+    /// Returns the rows of all the batches so far.
+    fn select_batch(
+        &self,
+        statement: &Statement,
+        result: &mut Option<TableResult>,
+        events: Vec<Event>,
+        now: DateTime<Utc>,
+        cancellation: &CancellationToken,
+    ) -> Result<usize, Error> {
+        let source = Arc::new(self.source_table(events, cancellation)?);
+        let mut batch = self.table_result(source.columns.clone(), Vec::new());
+        let mut statement = statement.clone();
+        if let Statement::Query(query) = &mut statement {
+            query.offset = None;
+            query.limit = None;
+        }
+        batch.statement = Some(statement);
+        batch.functions.register_now(now);
+        batch.units = source.units.clone();
+        batch.source = source;
+        batch.cancellation = cancellation.clone();
+        let batch = batch.select()?;
+
+        match result {
+            Some(result) => result.events.extend(batch.events),
+            None => *result = Some(batch),
+        }
+        Ok(result.as_ref().map_or(0, |result| result.events.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::values::Type;
+    use crate::parser::Parser;
+    use crate::schema::Schema;
+
+    fn logs_engine(query: &str) -> Engine {
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        Engine::with_query(parser, query.to_string()).unwrap()
+    }
+
+    #[test]
+    fn stream_batches_of_events() {
+        let lines: String = (0..3 * BATCH_SIZE)
+            .map(|i| format!("h{} {}\n", i, if i % 2 == 0 { 200 } else { 500 }))
+            .collect();
+        let sources = vec![
+            (Some("a.log".to_string()), lines.as_bytes()),
+            (Some("b.log".to_string()), "z 500\n".as_bytes()),
+        ];
+
+        let query = "SELECT host FROM logs WHERE status = 500 LIMIT 3 OFFSET 2";
+        let engine = logs_engine(query);
+        assert!(engine.streams(&engine.statements[0]));
+        let table_result = engine.execute_sources(sources.clone()).unwrap();
+        let hosts: Vec<_> = table_result
+            .events
+            .iter()
+            .map(|event| event.values["host"].to_string())
+            .collect();
+        assert_eq!(hosts, ["h5", "h7", "h9"]);
+        // the first batch has enough rows
+        assert_eq!(table_result.stats.rows_scanned, BATCH_SIZE);
+        assert_eq!(table_result.statement, Some(engine.statements[0].clone()));
+
+        // sources after the ones with enough rows aren't read
+        let mut read = Vec::new();
+        let engine = logs_engine("SELECT host FROM logs WHERE status = 500 LIMIT 1");
+        let lazy_sources = ["a 200\nb 500\n", "c 500\n", "d 500\n"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, source)| (Some(format!("{}.log", index)), source.as_bytes()))
+            .inspect(|(name, _)| read.push(name.clone().unwrap()));
+        let table_result = engine.execute_sources(lazy_sources).unwrap();
+        assert_eq!(
+            table_result.events[0].values["host"],
+            Type::String("b".into())
+        );
+        assert_eq!(read, ["0.log"]);
+
+        let table_result = logs_engine(query.split(" LIMIT").next().unwrap())
+            .execute_sources(sources.clone())
+            .unwrap();
+        assert_eq!(table_result.events.len(), 3 * BATCH_SIZE / 2 + 1);
+        assert_eq!(table_result.stats.rows_scanned, 3 * BATCH_SIZE + 1);
+        assert_eq!(table_result.parse_stats.len(), 2);
+        assert_eq!(table_result.parse_stats[1].events, 1);
+
+        // queries that need every event read them all first
+        for query in [
+            "SELECT host FROM logs ORDER BY host LIMIT 1",
+            "SELECT COUNT(*) FROM logs",
+            "SELECT DISTINCT status FROM logs",
+            "SELECT host FROM logs WHERE status IN (SELECT MAX(status) FROM logs)",
+            "WITH t AS (SELECT host FROM logs) SELECT * FROM t LIMIT 1",
+        ] {
+            let engine = logs_engine(query);
+            assert!(!engine.streams(&engine.statements[0]), "{}", query);
+            let table_result = engine.execute_sources(sources.clone()).unwrap();
+            assert_eq!(table_result.stats.rows_scanned, 3 * BATCH_SIZE + 1);
+        }
+    }
+}
@@ -0,0 +1,176 @@
+/// Streaming quantile estimates for `APPROX_PERCENTILE`. A t-digest clusters values into
+/// centroids that stay small near the tails and grow towards the median, so extreme
+/// percentiles like p99 stay accurate while memory is bounded by the compression.
+#[derive(Debug, Clone)]
+pub(crate) struct TDigest {
+    compression: f64,
+    centroids: Vec<Centroid>,
+    /// Values added since the last merge
+    buffer: Vec<f64>,
+    min: f64,
+    max: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        TDigest::new(100.0)
+    }
+}
+
+impl TDigest {
+    pub(crate) fn new(compression: f64) -> TDigest {
+        TDigest {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Add a value. NaN has no place in an ordering of numbers so it's ignored.
+    pub(crate) fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.buffer.push(value);
+        if self.buffer.len() as f64 >= self.compression * 5.0 {
+            self.merge();
+        }
+    }
+
+    /// Estimate the value at `quantile`, from 0 to 1. Returns `None` without values.
+    pub(crate) fn quantile(&mut self, quantile: f64) -> Option<f64> {
+        self.merge();
+        let total: f64 = self.centroids.iter().map(|c| c.weight).sum();
+        let (first, last) = (self.centroids.first()?, self.centroids.last()?);
+        let target = quantile.clamp(0.0, 1.0) * total;
+
+        // each centroid's mean sits at the middle of its weight
+        if target <= first.weight / 2.0 {
+            return Some(interpolate(
+                0.0,
+                self.min,
+                first.weight / 2.0,
+                first.mean,
+                target,
+            ));
+        }
+        if target >= total - last.weight / 2.0 {
+            let start = total - last.weight / 2.0;
+            return Some(interpolate(start, last.mean, total, self.max, target));
+        }
+
+        let mut cumulative = 0.0;
+        for pair in self.centroids.windows(2) {
+            let left = cumulative + pair[0].weight / 2.0;
+            let right = cumulative + pair[0].weight + pair[1].weight / 2.0;
+            if target <= right {
+                return Some(interpolate(left, pair[0].mean, right, pair[1].mean, target));
+            }
+            cumulative += pair[0].weight;
+        }
+        Some(last.mean)
+    }
+
+    /// Merge the buffered values into the centroids. A centroid can grow while its weight is
+    /// under `4 * total * q * (1 - q) / compression`, where `q` is its quantile.
+    fn merge(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let mut centroids = std::mem::take(&mut self.centroids);
+        centroids.extend(
+            self.buffer
+                .drain(..)
+                .map(|mean| Centroid { mean, weight: 1.0 }),
+        );
+        centroids.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+        let total: f64 = centroids.iter().map(|c| c.weight).sum();
+
+        let mut merged: Vec<Centroid> = Vec::with_capacity(centroids.len());
+        let mut cumulative = 0.0;
+        for centroid in centroids {
+            if let Some(current) = merged.last_mut() {
+                let weight = current.weight + centroid.weight;
+                let q = (cumulative + weight / 2.0) / total;
+                let limit = 4.0 * total * q * (1.0 - q) / self.compression;
+                if weight <= limit.max(1.0) {
+                    current.mean += (centroid.mean - current.mean) * centroid.weight / weight;
+                    current.weight = weight;
+                    continue;
+                }
+                cumulative += current.weight;
+            }
+            merged.push(centroid);
+        }
+        self.centroids = merged;
+    }
+}
+
+/// The value at `x` on the line through `(x0, y0)` and `(x1, y1)`
+fn interpolate(x0: f64, y0: f64, x1: f64, y1: f64, x: f64) -> f64 {
+    if x1 <= x0 {
+        return y0;
+    }
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_inputs_interpolate() {
+        let mut digest = TDigest::default();
+        assert_eq!(None, digest.quantile(0.5));
+
+        for value in [4.0, 1.0, f64::NAN, 3.0, 2.0] {
+            digest.add(value);
+        }
+        assert_eq!(Some(1.0), digest.quantile(0.0));
+        assert_eq!(Some(2.5), digest.quantile(0.5));
+        assert_eq!(Some(4.0), digest.quantile(1.0));
+
+        let mut digest = TDigest::default();
+        digest.add(7.0);
+        assert_eq!(Some(7.0), digest.quantile(0.99));
+    }
+
+    #[test]
+    fn large_inputs_are_accurate() {
+        let mut digest = TDigest::default();
+        // shuffled so merges see values out of order
+        for i in 0..100_000u64 {
+            digest.add(((i * 7919) % 100_000) as f64);
+        }
+        assert!(digest.centroids.len() < 1000, "{}", digest.centroids.len());
+
+        for (quantile, expected, tolerance) in [
+            (0.5, 50_000.0, 500.0),
+            (0.95, 95_000.0, 200.0),
+            (0.99, 99_000.0, 50.0),
+            (0.999, 99_900.0, 10.0),
+        ] {
+            let estimate = digest.quantile(quantile).unwrap();
+            assert!(
+                (estimate - expected).abs() <= tolerance,
+                "p{} was {}",
+                quantile * 100.0,
+                estimate
+            );
+        }
+        assert_eq!(Some(0.0), digest.quantile(0.0));
+        assert_eq!(Some(99_999.0), digest.quantile(1.0));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::engine::functions::json_value;
+use crate::engine::TableResult;
+use crate::error::Error;
+use crate::parser::values::{Event, Type};
+use sqlparser::ast::{Expr, TableFactor, Value};
+
+/// `UNNEST(tags)` joined to the table, as in `FROM logs, UNNEST(tags) AS tag` or
+/// `FROM logs CROSS JOIN UNNEST(recipients, ',') AS t(recipient)`, which repeats each event once
+/// per element of its list. Lists are JSON arrays, or text split on the delimiter argument.
+pub(crate) struct Unnest<'a> {
+    list: &'a Expr,
+    delimiter: Option<String>,
+    /// Column of the elements, `unnest` without an alias
+    column: String,
+    /// Column of each element's position from 0, with `WITH OFFSET`
+    offset: Option<String>,
+}
+
+impl<'a> Unnest<'a> {
+    pub(crate) fn new(factor: &'a TableFactor) -> Result<Unnest<'a>, Error> {
+        let (alias, array_exprs, with_offset, with_offset_alias) = match factor {
+            TableFactor::UNNEST {
+                alias,
+                array_exprs,
+                with_offset,
+                with_offset_alias,
+            } => (alias, array_exprs, with_offset, with_offset_alias),
+            _ => return Err(Error::UnsupportedExpression(factor.to_string())),
+        };
+        let (list, delimiter) = match array_exprs.as_slice() {
+            [list] => (list, None),
+            [list, Expr::Value(Value::SingleQuotedString(delimiter))] if !delimiter.is_empty() => {
+                (list, Some(delimiter.clone()))
+            }
+            _ => return Err(Error::InvalidFunctionArguments("UNNEST".to_string())),
+        };
+
+        let column = match alias {
+            Some(alias) => match alias.columns.as_slice() {
+                [] => alias.name.value.clone(),
+                [column] => column.value.clone(),
+                _ => return Err(Error::UnsupportedExpression(factor.to_string())),
+            },
+            None => "unnest".to_string(),
+        };
+        let offset = with_offset.then(|| {
+            with_offset_alias
+                .as_ref()
+                .map_or_else(|| "offset".to_string(), |alias| alias.value.clone())
+        });
+        Ok(Unnest {
+            list,
+            delimiter,
+            column,
+            offset,
+        })
+    }
+
+    /// The new columns, which can't already be in the table
+    pub(crate) fn columns(&self, columns: &[String]) -> Result<Vec<String>, Error> {
+        let mut added = vec![self.column.clone()];
+        added.extend(self.offset.clone());
+        for column in &added {
+            if columns.contains(column) || added.iter().filter(|c| *c == column).count() > 1 {
+                return Err(Error::DuplicateColumnName(column.clone()));
+            }
+        }
+        Ok(added)
+    }
+}
+
+impl TableResult {
+    /// Repeat each event per element of its list. Events whose list is NULL, empty or isn't a
+    /// JSON array have no rows, like in postgres.
+    pub(crate) fn unnest(&self, unnest: &Unnest, events: Vec<Event>) -> Result<Vec<Event>, Error> {
+        let mut unnested = Vec::new();
+        for event in events {
+            self.cancellation.check()?;
+            let elements = match self.evaluate(unnest.list, &event)? {
+                Type::Null => Vec::new(),
+                Type::String(list) => match &unnest.delimiter {
+                    Some(delimiter) => list
+                        .split(delimiter.as_str())
+                        .map(str::trim)
+                        .filter(|element| !element.is_empty())
+                        .map(|element| Type::String(element.to_string()))
+                        .collect(),
+                    None => match serde_json::from_str(&list) {
+                        Ok(serde_json::Value::Array(elements)) => {
+                            elements.iter().map(json_value).collect()
+                        }
+                        _ => Vec::new(),
+                    },
+                },
+                _ => return Err(Error::InvalidFunctionArguments("UNNEST".to_string())),
+            };
+
+            for (index, element) in elements.into_iter().enumerate() {
+                let mut event = event.clone();
+                event.values.insert(unnest.column.clone(), element);
+                if let Some(offset) = &unnest.offset {
+                    event
+                        .values
+                        .insert(offset.clone(), Type::Int64(index as i64));
+                }
+                unnested.push(event);
+            }
+        }
+        Ok(unnested)
+    }
+}
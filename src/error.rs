@@ -1,30 +1,121 @@
 use crate::parser::values::Type;
 use crate::schema::ColumnType;
-use sqlparser::ast::{Statement, Value};
+use sqlparser::ast::Statement;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
+    #[error("Numeric overflow in '{0}'")]
+    ArithmeticOverflow(String),
+    #[error("Failed to write the audit log: {0}")]
+    AuditFailed(String),
+    #[error("The query was cancelled")]
+    Cancelled,
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Column name or alias '{0}' is used by more than one column.")]
+    DuplicateColumnName(String),
+    #[error("Failed to export results: {0}")]
+    ExportFailed(String),
+    #[error("Values {0:?} and {2:?} cannot be combined with '{1}'")]
+    InvalidArithmetic(Type, String, Type),
+    #[error("Column '{0}' cannot have true and false values: it {1}")]
+    InvalidBoolValues(String, String),
+    #[error("Value {0:?} cannot be cast to {1}")]
+    InvalidCast(Type, ColumnType),
+    #[error("Column '{0}' has an invalid condition: {1}")]
+    InvalidColumnCondition(String, String),
+    #[error("Value '{2}' in column '{0}' is not a valid '{1}'")]
+    InvalidColumnValue(String, ColumnType, String),
+    #[error("Values {0:?} and {1:?} cannot be compared")]
+    InvalidComparison(Type, Type),
+    #[error("Invalid config file {0}")]
+    InvalidConfig(String),
+    #[error("Regex definition '{0}' {1}")]
+    InvalidDefinition(String, String),
+    #[error("Invalid EVTX source: {0}")]
+    InvalidEvtx(String),
+    #[error("Invalid example '{0}': {1}")]
+    InvalidExample(String, String),
+    #[error("WHERE clauses must be boolean expressions. Found {0:?}")]
+    InvalidFilter(Type),
+    #[error("Invalid arguments for function '{0}'")]
+    InvalidFunctionArguments(String),
+    #[error("GROUP BY position {0} is not in the select list.")]
+    InvalidGroupByPosition(String),
+    #[error("Schema file '{0}' {1}")]
+    InvalidSchemaFile(String, String),
+    #[error(
+        "Invalid interval {0}. Use units from microseconds to weeks, e.g. INTERVAL '90 minutes'."
+    )]
+    InvalidInterval(String),
+    #[error("Invalid JSON source: {0}")]
+    InvalidJson(String),
+    #[error("JSON schema failed to parse: {0}")]
+    InvalidJsonSchema(serde_json::Error),
+    #[error("Column '{0}' is a '{1}' so it cannot be lenient. Only datetimes can be lenient.")]
+    InvalidLenientType(String, ColumnType),
+    #[error("Column '{0}' is not multiline so it cannot have multiline options like max_lines.")]
+    InvalidMultilineOption(String),
     #[error("Column '{0}' is a '{1}' so it cannot be multiline. Only strings can be multiline.")]
     InvalidMultilineType(String, ColumnType),
+    #[error("ORDER BY position {0} is not in the select list.")]
+    InvalidOrderByPosition(String),
+    #[error("Column '{0}' is a '{1}' so it cannot have a thousands separator or units. Only numbers can.")]
+    InvalidNumberFormat(String, ColumnType),
     #[error("The SQL query was invalid. Query: {0:#?}")]
-    InvalidQuery(Statement),
+    InvalidQuery(Box<Statement>),
+    #[error("Invalid value '{1}' for query parameter {0}")]
+    InvalidParameter(String, String),
+    #[error("Invalid pcap source: {0}")]
+    InvalidPcap(String),
+    #[error("Invalid row filter '{0}': {1}")]
+    InvalidRowFilter(String, String),
     #[error("Invalid regex statement")]
     InvalidRegex(#[from] regex::Error),
     #[error("Schema failed to parse")]
     InvalidSchema(#[from] serde_yaml::Error),
     #[error("The SQL was invalid.")]
     InvalidSqlQuery,
+    #[error("TOML schema failed to parse: {0}")]
+    InvalidTomlSchema(String),
+    #[error("Invalid TABLESAMPLE: {0}")]
+    InvalidTableSample(String),
+    #[error("Aggregate {0} is only allowed in the select list and HAVING.")]
+    MisplacedAggregate(String),
     #[error(
     "All columns must correspond to named capture groups. Columns missing in capture groups: {0:?}"
     )]
     MissingColumns(Vec<String>),
+    #[error("No value for query parameter {0}")]
+    MissingParameter(String),
+    #[error("Failed to read {0}: {1}")]
+    ReadFailed(String, String),
     #[error("Failed to parse SQL statement")]
     SqlParserError(#[from] sqlparser::parser::ParserError),
+    #[error("Subqueries used with IN must return exactly one column. Found {0}.")]
+    SubqueryColumnCount(usize),
     #[error("There can only be one multiline column. Multiline columns: {0:?}")]
     TooManyMultilineColumns(Vec<String>),
     #[error("There are too many SQL statements. The max allowed is one statement.")]
     TooManySqlQueries,
-    #[error("There was a type mismatch. Schema type = {0}. Data Type = {1:?}. Query Type = {2:?}")]
-    TypeMismatch(ColumnType, Type, Value),
+    #[error("Column '{0}' must be in GROUP BY or used in an aggregate function.")]
+    UngroupedColumn(String),
+    #[error(
+        "Both sides of UNION ALL must have the same number of columns. Left = {0}. Right = {1}."
+    )]
+    UnionColumnCountMismatch(usize, usize),
+    #[error("Line {1} of {0} did not match the schema: {2}")]
+    UnmatchedLine(String, usize, String),
+    #[error("Column '{0}' does not exist.{1}")]
+    UnknownColumn(String, String),
+    #[error("Column '{0}' has type '{1}' which has not been registered.")]
+    UnknownColumnType(String, String),
+    #[error("Function '{0}' does not exist.")]
+    UnknownFunction(String),
+    #[error("Profile '{0}' does not exist. Profiles: {1}")]
+    UnknownProfile(String, String),
+    #[error("Unsupported expression: {0}")]
+    UnsupportedExpression(String),
 }
@@ -0,0 +1,92 @@
+pub mod loki;
+pub mod otlp;
+pub mod sql;
+
+use crate::engine::TableResult;
+use crate::error::Error;
+use crate::export::sql::SqlExporter;
+use crate::parser::values::{Event, Type};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Find the timestamp of an event, either from the given column or from the first datetime column
+fn event_timestamp<'a>(
+    columns: &'a [String],
+    event: &Event,
+    column: Option<&'a str>,
+) -> Option<(&'a str, DateTime<Utc>)> {
+    let column = match column {
+        Some(column) => column,
+        None => columns
+            .iter()
+            .find(|c| event.values.get(*c).and_then(Type::as_utc).is_some())?
+            .as_str(),
+    };
+
+    let timestamp = event.values.get(column)?.as_utc()?;
+    Some((column, timestamp))
+}
+
+/// Nanoseconds since the Unix epoch, which only fit in an `i64` from 1677 to 2262. Later
+/// timestamps like a `9999-12-31` sentinel fail instead of overflowing.
+fn unix_nanos(timestamp: &DateTime<Utc>) -> Result<i64, Error> {
+    timestamp
+        .timestamp()
+        .checked_mul(1_000_000_000)
+        .and_then(|nanos| nanos.checked_add(i64::from(timestamp.timestamp_subsec_nanos())))
+        .ok_or_else(|| {
+            Error::ExportFailed(format!(
+                "Timestamp {} is out of range for nanoseconds",
+                timestamp.to_rfc3339()
+            ))
+        })
+}
+
+/// How query results are printed
+#[derive(clap::ArgEnum, serde::Deserialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    Table,
+    Json,
+    JsonHeaders,
+    /// `CREATE TABLE` and `INSERT` statements
+    Sql,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [
+        Format::Table,
+        Format::Json,
+        Format::JsonHeaders,
+        Format::Sql,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Table => "table",
+            Format::Json => "json",
+            Format::JsonHeaders => "json-headers",
+            Format::Sql => "sql",
+        }
+    }
+}
+
+/// Render a result the way it's printed. JSON is converted to a value first so its keys are
+/// sorted instead of in hash order, and the output is the same on every run.
+pub fn render(
+    table_result: &TableResult,
+    format: Format,
+    sql_exporter: &SqlExporter,
+) -> Result<String, Error> {
+    let to_json = |value: Result<Value, serde_json::Error>| {
+        value
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .map_err(|e| Error::InvalidJson(e.to_string()))
+    };
+    match format {
+        Format::Json => to_json(serde_json::to_value(&table_result.events)),
+        Format::JsonHeaders => to_json(serde_json::to_value(table_result)),
+        Format::Sql => Ok(sql_exporter.statements(table_result).join("\n")),
+        Format::Table => Ok(table_result.table().to_string()),
+    }
+}
@@ -0,0 +1,214 @@
+use crate::engine::TableResult;
+use crate::error::Error;
+use crate::export::{event_timestamp, unix_nanos};
+use crate::parser::values::Event;
+use chrono::Utc;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Max number of log lines sent to Loki per request
+const BATCH_SIZE: usize = 1000;
+
+/// Label names and values identifying a stream
+type Labels = BTreeMap<String, String>;
+
+/// Pushes query results to the Grafana Loki push API
+#[derive(Debug, Clone)]
+pub struct LokiExporter {
+    /// Push endpoint, e.g. `http://localhost:3100/loki/api/v1/push`
+    pub endpoint: String,
+    /// Value of the `job` label attached to every stream
+    pub job: String,
+    /// Columns whose values become stream labels
+    pub labels: Vec<String>,
+    /// Column used as the log line. Defaults to the remaining columns formatted as logfmt.
+    pub body_column: Option<String>,
+    /// Column used as the entry timestamp. Defaults to the first datetime value in the event.
+    pub timestamp_column: Option<String>,
+}
+
+impl LokiExporter {
+    /// Send every event in the result to Loki. Returns the number of pushed entries.
+    pub fn export(&self, table_result: &TableResult) -> Result<usize, Error> {
+        for events in table_result.events.chunks(BATCH_SIZE) {
+            let body = self.request(&table_result.columns, events)?.to_string();
+            ureq::post(&self.endpoint)
+                .set("Content-Type", "application/json")
+                .send_string(&body)
+                .map_err(|e| Error::ExportFailed(e.to_string()))?;
+        }
+
+        Ok(table_result.events.len())
+    }
+
+    /// Build a push request, grouping the events into one stream per distinct label set
+    fn request(&self, columns: &[String], events: &[Event]) -> Result<Value, Error> {
+        let now = unix_nanos(&Utc::now())?;
+        let mut streams: BTreeMap<Labels, Vec<(i64, String)>> = BTreeMap::new();
+
+        for event in events {
+            let mut labels = Labels::new();
+            labels.insert("job".to_string(), self.job.clone());
+            for label in &self.labels {
+                if let Some(value) = event.values.get(label) {
+                    labels.insert(label_name(label), value.to_string());
+                }
+            }
+
+            let timestamp = event_timestamp(columns, event, self.timestamp_column.as_deref());
+            let timestamp_column = timestamp.map(|(column, _)| column);
+            let line = match self.body_column.as_ref().and_then(|c| event.values.get(c)) {
+                Some(body) => body.to_string(),
+                None => columns
+                    .iter()
+                    .filter(|c| !self.labels.contains(c) && Some(c.as_str()) != timestamp_column)
+                    .filter_map(|c| event.values.get(c).map(|value| logfmt_pair(c, value)))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+            let timestamp = match timestamp {
+                Some((_, timestamp)) => unix_nanos(&timestamp)?,
+                None => now,
+            };
+
+            streams.entry(labels).or_default().push((timestamp, line));
+        }
+
+        let streams: Vec<_> = streams
+            .into_iter()
+            .map(|(labels, mut values)| {
+                // loki rejects out of order entries within a stream
+                values.sort_by_key(|(timestamp, _)| *timestamp);
+                let values: Vec<_> = values
+                    .into_iter()
+                    .map(|(timestamp, line)| json!([timestamp.to_string(), line]))
+                    .collect();
+                json!({ "stream": labels, "values": values })
+            })
+            .collect();
+
+        Ok(json!({ "streams": streams }))
+    }
+}
+
+/// Loki label names must match `[a-zA-Z_][a-zA-Z0-9_]*`
+fn label_name(column: &str) -> String {
+    let mut name: String = column
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn logfmt_pair(key: &str, value: &impl ToString) -> String {
+    let value = value.to_string();
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') {
+        format!("{}={:?}", key, value)
+    } else {
+        format!("{}={}", key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tests::generate_typed_events;
+    use chrono::TimeZone;
+
+    #[test]
+    fn streams_grouped_by_labels() {
+        let exporter = LokiExporter {
+            endpoint: "http://localhost:3100/loki/api/v1/push".to_string(),
+            job: "logs".to_string(),
+            labels: vec!["host.name".to_string()],
+            body_column: None,
+            timestamp_column: None,
+        };
+        let columns: Vec<_> = ["timestamp", "host.name", "message", "latency"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let events = generate_typed_events(vec![
+            vec![
+                ("timestamp", Utc.ymd(2022, 1, 1).and_hms(0, 0, 2).into()),
+                ("host.name", "a".into()),
+                ("message", "second".into()),
+                ("latency", 2.into()),
+            ],
+            vec![
+                ("timestamp", Utc.ymd(2022, 1, 1).and_hms(0, 0, 1).into()),
+                ("host.name", "a".into()),
+                ("message", "first = 1".into()),
+                ("latency", 1.into()),
+            ],
+            vec![
+                ("timestamp", Utc.ymd(2022, 1, 1).and_hms(0, 0, 3).into()),
+                ("host.name", "b".into()),
+                ("message", "third".into()),
+                ("latency", 3.into()),
+            ],
+        ]);
+
+        let request = exporter.request(&columns, &events).unwrap();
+        assert_eq!(
+            request,
+            json!({
+                "streams": [
+                    {
+                        "stream": { "host_name": "a", "job": "logs" },
+                        "values": [
+                            ["1640995201000000000", "message=\"first = 1\" latency=1"],
+                            ["1640995202000000000", "message=second latency=2"],
+                        ]
+                    },
+                    {
+                        "stream": { "host_name": "b", "job": "logs" },
+                        "values": [["1640995203000000000", "message=third latency=3"]]
+                    },
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn line_from_body_column() {
+        let exporter = LokiExporter {
+            endpoint: "http://localhost:3100/loki/api/v1/push".to_string(),
+            job: "logs".to_string(),
+            labels: vec![],
+            body_column: Some("message".to_string()),
+            timestamp_column: None,
+        };
+        let columns: Vec<_> = ["timestamp", "message"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let events = generate_typed_events(vec![vec![
+            ("timestamp", Utc.ymd(2022, 1, 1).and_hms(0, 0, 1).into()),
+            ("message", "hello\nworld".into()),
+        ]]);
+
+        let request = exporter.request(&columns, &events).unwrap();
+        assert_eq!(
+            request["streams"][0]["values"],
+            json!([["1640995201000000000", "hello\nworld"]])
+        );
+
+        // nanoseconds overflow after 2262
+        let events = generate_typed_events(vec![vec![
+            ("timestamp", Utc.ymd(9999, 12, 31).and_hms(0, 0, 0).into()),
+            ("message", "sentinel".into()),
+        ]]);
+        match exporter.request(&columns, &events) {
+            Err(Error::ExportFailed(message)) => assert_eq!(
+                message,
+                "Timestamp 9999-12-31T00:00:00+00:00 is out of range for nanoseconds"
+            ),
+            Err(e) => panic!("Error should be Error::ExportFailed. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::ExportFailed"),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::engine::TableResult;
+use crate::error::Error;
+use crate::export::{event_timestamp, unix_nanos};
+use crate::parser::values::{Event, Type};
+use chrono::Utc;
+use serde_json::{json, Value};
+
+/// Max number of log records sent to the collector per request
+const BATCH_SIZE: usize = 1000;
+
+/// Ships query results to an OpenTelemetry collector using OTLP/HTTP with JSON encoding
+#[derive(Debug, Clone)]
+pub struct OtlpExporter {
+    /// Collector logs endpoint, e.g. `http://localhost:4318/v1/logs`
+    pub endpoint: String,
+    /// Reported as the `service.name` resource attribute
+    pub service_name: String,
+    /// Column used as the log record body. Every other column becomes an attribute.
+    pub body_column: Option<String>,
+    /// Column used as the log record timestamp. Defaults to the first datetime value in the event.
+    pub timestamp_column: Option<String>,
+}
+
+impl OtlpExporter {
+    /// Send every event in the result to the collector. Returns the number of exported records.
+    pub fn export(&self, table_result: &TableResult) -> Result<usize, Error> {
+        for events in table_result.events.chunks(BATCH_SIZE) {
+            let body = self.request(&table_result.columns, events)?.to_string();
+            ureq::post(&self.endpoint)
+                .set("Content-Type", "application/json")
+                .send_string(&body)
+                .map_err(|e| Error::ExportFailed(e.to_string()))?;
+        }
+
+        Ok(table_result.events.len())
+    }
+
+    /// Build an `ExportLogsServiceRequest` for a batch of events
+    fn request(&self, columns: &[String], events: &[Event]) -> Result<Value, Error> {
+        let observed_time = unix_nanos(&Utc::now())?.to_string();
+        let log_records = events
+            .iter()
+            .map(|event| self.log_record(columns, event, &observed_time))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(json!({
+            "resourceLogs": [{
+                "resource": {
+                    "attributes": [key_value("service.name", &Type::String(self.service_name.clone()))]
+                },
+                "scopeLogs": [{
+                    "scope": { "name": "logql", "version": env!("CARGO_PKG_VERSION") },
+                    "logRecords": log_records
+                }]
+            }]
+        }))
+    }
+
+    fn log_record(
+        &self,
+        columns: &[String],
+        event: &Event,
+        observed_time: &str,
+    ) -> Result<Value, Error> {
+        let mut record = json!({ "observedTimeUnixNano": observed_time });
+        let timestamp = event_timestamp(columns, event, self.timestamp_column.as_deref());
+        if let Some((_, timestamp)) = timestamp {
+            record["timeUnixNano"] = json!(unix_nanos(&timestamp)?.to_string());
+        }
+        let timestamp_column = timestamp.map(|(column, _)| column);
+
+        let body_column = self.body_column.as_deref();
+        if let Some(body) = body_column.and_then(|c| event.values.get(c)) {
+            record["body"] = any_value(body);
+        }
+
+        let attributes: Vec<_> = columns
+            .iter()
+            .filter(|c| Some(c.as_str()) != body_column && Some(c.as_str()) != timestamp_column)
+            .filter_map(|c| event.values.get(c).map(|value| key_value(c, value)))
+            .collect();
+        record["attributes"] = json!(attributes);
+
+        Ok(record)
+    }
+}
+
+fn key_value(key: &str, value: &Type) -> Value {
+    json!({ "key": key, "value": any_value(value) })
+}
+
+/// Convert a value into an OTLP `AnyValue`. 64 bit integers are encoded as strings per the
+/// protobuf JSON mapping.
+fn any_value(value: &Type) -> Value {
+    match value {
+        Type::String(x) => json!({ "stringValue": x }),
+        Type::Int32(x) => json!({ "intValue": x.to_string() }),
+        Type::Int64(x) => json!({ "intValue": x.to_string() }),
+        Type::UInt32(x) => json!({ "intValue": x.to_string() }),
+        Type::UInt64(x) if i64::try_from(*x).is_ok() => json!({ "intValue": x.to_string() }),
+        Type::UInt64(x) => json!({ "doubleValue": *x as f64 }),
+        Type::Bool(x) => json!({ "boolValue": x }),
+        Type::Float(x) => json!({ "doubleValue": x }),
+        Type::Double(x) => json!({ "doubleValue": x }),
+        // as text so amounts don't lose precision
+        Type::Decimal(x) => json!({ "stringValue": x.to_string() }),
+        Type::DateTime(x) => json!({ "stringValue": x.to_rfc3339() }),
+        Type::DateTimeTz(x) => json!({ "stringValue": x.to_rfc3339() }),
+        Type::Interval(_) => json!({ "stringValue": value.to_string() }),
+        Type::Custom(x) => json!({ "stringValue": x.to_string() }),
+        Type::Null => json!({}),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::tests::generate_typed_events;
+    use chrono::TimeZone;
+
+    #[test]
+    fn log_records_from_events() {
+        let exporter = OtlpExporter {
+            endpoint: "http://localhost:4318/v1/logs".to_string(),
+            service_name: "logs".to_string(),
+            body_column: Some("message".to_string()),
+            timestamp_column: None,
+        };
+        let columns: Vec<_> = ["timestamp", "level", "message", "latency"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let events = generate_typed_events(vec![vec![
+            ("timestamp", Utc.ymd(2022, 1, 1).and_hms(0, 0, 1).into()),
+            ("level", "INFO".into()),
+            ("message", "hello world".into()),
+            ("latency", 12.into()),
+        ]]);
+
+        let request = exporter.request(&columns, &events).unwrap();
+        let scope_logs = &request["resourceLogs"][0]["scopeLogs"][0];
+        let record = &scope_logs["logRecords"][0];
+
+        assert_eq!(
+            request["resourceLogs"][0]["resource"]["attributes"][0],
+            json!({ "key": "service.name", "value": { "stringValue": "logs" } })
+        );
+        assert_eq!(record["timeUnixNano"], json!("1640995201000000000"));
+        assert_eq!(record["body"], json!({ "stringValue": "hello world" }));
+        assert_eq!(
+            record["attributes"],
+            json!([
+                { "key": "level", "value": { "stringValue": "INFO" } },
+                { "key": "latency", "value": { "intValue": "12" } },
+            ])
+        );
+
+        // nanoseconds overflow after 2262
+        let events = generate_typed_events(vec![vec![(
+            "timestamp",
+            Utc.ymd(9999, 12, 31).and_hms(0, 0, 0).into(),
+        )]]);
+        match exporter.request(&columns, &events) {
+            Err(Error::ExportFailed(message)) => assert_eq!(
+                message,
+                "Timestamp 9999-12-31T00:00:00+00:00 is out of range for nanoseconds"
+            ),
+            Err(e) => panic!("Error should be Error::ExportFailed. Actual {:?}", e),
+            Ok(_) => panic!("Error should be Error::ExportFailed"),
+        }
+    }
+
+    #[test]
+    fn log_records_with_explicit_timestamp_column() {
+        let exporter = OtlpExporter {
+            endpoint: "http://localhost:4318/v1/logs".to_string(),
+            service_name: "logs".to_string(),
+            body_column: None,
+            timestamp_column: Some("received".to_string()),
+        };
+        let columns: Vec<_> = ["sent", "received"].into_iter().map(String::from).collect();
+        let events = generate_typed_events(vec![vec![
+            ("sent", Utc.ymd(2022, 1, 1).and_hms(0, 0, 0).into()),
+            ("received", Utc.ymd(2022, 1, 1).and_hms(0, 0, 1).into()),
+        ]]);
+
+        let request = exporter.request(&columns, &events).unwrap();
+        let record = &request["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+
+        assert_eq!(record["timeUnixNano"], json!("1640995201000000000"));
+        assert_eq!(record.get("body"), None);
+        assert_eq!(
+            record["attributes"],
+            json!([{ "key": "sent", "value": { "stringValue": "2022-01-01T00:00:00+00:00" } }])
+        );
+    }
+}
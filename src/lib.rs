@@ -0,0 +1,12 @@
+pub mod audit;
+pub mod engine;
+pub mod error;
+pub mod export;
+pub mod parser;
+pub mod profile;
+pub mod schema;
+pub mod testing;
+pub mod validate;
+
+pub use crate::engine::Engine;
+pub use crate::parser::Parser;
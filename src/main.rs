@@ -1,79 +1,369 @@
-use crate::engine::Engine;
-use crate::parser::Parser;
 use clap::Parser as ClapParser;
+use logql::audit::{AuditLog, AuditRecord};
+use logql::export::loki::LokiExporter;
+use logql::export::otlp::OtlpExporter;
+use logql::export::sql::{Dialect, SqlExporter};
+use logql::export::{render, Format};
+use logql::parser::encoding::{decode, decode_as, Encoding};
+use logql::parser::evtx::evtx_to_json;
+use logql::parser::pcap::pcap_to_json;
+use logql::parser::stream::ParseStats;
+use logql::profile::{ConfigFile, Profile};
+use logql::schema::{InputFormat, Schema};
+use logql::validate::validate;
+use logql::{Engine, Parser};
 use regex::Regex;
-use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use walkdir::WalkDir;
 
-mod engine;
-mod error;
-mod parser;
-mod schema;
-
 #[derive(ClapParser, Debug)]
 struct Config {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// Log file or directory to read. Defaults to the profile's source.
+    #[clap(long, global = true)]
+    source: Option<String>,
+    /// Schema file. Defaults to the profile's schema.
+    #[clap(long, global = true)]
+    schema: Option<String>,
+    /// Profile of defaults from the config file, e.g. prod or staging
+    #[clap(long, global = true)]
+    profile: Option<String>,
+    /// Config file with profiles. Defaults to ~/.config/logql/config.yml.
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    /// Query to run, or several statements separated by semicolons that run against the same events
+    #[clap(long)]
+    sql: Option<String>,
+    /// Value for a query placeholder, in order for `$1`, `$2`, ... or `?`. Can be repeated.
+    #[clap(long = "param")]
+    params: Vec<String>,
+    /// Seed for `TABLESAMPLE` without `REPEATABLE`, to share a reproducible sample. Defaults to 0.
     #[clap(long)]
-    source: String,
+    seed: Option<u64>,
+    /// Encoding of the source files, instead of the schema's or the one detected from each file
+    #[clap(long, arg_enum)]
+    encoding: Option<Encoding>,
+    /// Fail on lines that don't match the schema, like the schema's `strict: true`
     #[clap(long)]
-    schema: String,
+    strict: bool,
+    /// Print how many lines of each file were read, matched and unmatched, and how many values
+    /// failed to convert
     #[clap(long)]
-    sql: Option<String>,
+    stats: bool,
     #[clap(long)]
     no_print: bool,
+    /// Check the schema and query without reading the source, and print the table's columns
+    #[clap(long)]
+    dry_run: bool,
     #[clap(long)]
     json: bool,
     #[clap(long)]
     json_headers: bool,
+    /// Output format. Defaults to the profile's format, or table.
+    #[clap(long, arg_enum)]
+    format: Option<Format>,
+    /// Create a table from the results and insert them into a database, e.g. postgres://user@localhost/logs
+    /// or clickhouse://user@localhost:8123/logs
+    #[clap(long)]
+    export: Option<String>,
+    /// Database the `sql` format's statements are written for
+    #[clap(long, arg_enum, default_value = "postgres")]
+    sql_dialect: Dialect,
+    /// Export the results as log records to an OTLP/HTTP collector, e.g. http://localhost:4318/v1/logs
+    #[clap(long)]
+    otlp_endpoint: Option<String>,
+    /// Column exported as the OTLP log body. Defaults to the multiline column.
+    #[clap(long)]
+    otlp_body_column: Option<String>,
+    /// Column exported as the OTLP log timestamp. Defaults to the first datetime column.
+    #[clap(long)]
+    otlp_timestamp_column: Option<String>,
+    /// Push the results to a Loki push API, e.g. http://localhost:3100/loki/api/v1/push
+    #[clap(long)]
+    loki_endpoint: Option<String>,
+    /// Columns used as Loki stream labels
+    #[clap(long, use_value_delimiter = true)]
+    loki_labels: Vec<String>,
+    /// Column pushed as the Loki log line. Defaults to the other columns formatted as logfmt.
+    #[clap(long)]
+    loki_body_column: Option<String>,
+    /// Column used as the Loki entry timestamp. Defaults to the first datetime column.
+    #[clap(long)]
+    loki_timestamp_column: Option<String>,
+    /// Append each executed query with its sources, duration and row count to this file as NDJSON
+    #[clap(long)]
+    audit_log: Option<PathBuf>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Parse the start of the source with the schema and report the lines that didn't match and
+    /// the values that couldn't be converted. A directory is checked with its first log file.
+    Validate {
+        /// Lines of the source to check
+        #[clap(long, default_value_t = 1000)]
+        lines: usize,
+    },
+}
+
+impl Config {
+    /// Fill in the flags that weren't given from a profile
+    fn apply(&mut self, profile: &Profile) {
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|p| p.display().to_string());
+        self.schema = self.schema.take().or_else(|| path(&profile.schema));
+        self.source = self.source.take().or_else(|| path(&profile.source));
+        self.format = self.format.or(profile.format);
+        self.export = self.export.take().or_else(|| profile.export.clone());
+        self.otlp_endpoint = self
+            .otlp_endpoint
+            .take()
+            .or_else(|| profile.otlp_endpoint.clone());
+        self.loki_endpoint = self
+            .loki_endpoint
+            .take()
+            .or_else(|| profile.loki_endpoint.clone());
+        if self.loki_labels.is_empty() {
+            self.loki_labels = profile.loki_labels.clone().unwrap_or_default();
+        }
+        self.audit_log = self.audit_log.take().or_else(|| profile.audit_log.clone());
+    }
+}
+
+/// Read a log file as text in the schema's encoding, or its detected one, converting binary
+/// formats to JSON
+fn read_source(schema: &Schema, path: &Path) -> color_eyre::eyre::Result<String> {
+    match (schema.format, schema.encoding) {
+        (InputFormat::Evtx, _) => Ok(evtx_to_json(std::fs::read(path)?)?),
+        (InputFormat::Pcap, _) => Ok(pcap_to_json(&std::fs::read(path)?)?),
+        (_, Some(encoding)) => Ok(decode_as(std::fs::read(path)?, encoding)),
+        (_, None) => Ok(decode(std::fs::read(path)?)),
+    }
+}
+
+/// Open a log file to be read line by line, converting binary formats to JSON, which needs the
+/// whole file. The engine decodes the lines in the schema's encoding.
+fn open_source(schema: &Schema, path: &Path) -> color_eyre::eyre::Result<Box<dyn BufRead>> {
+    match schema.format {
+        InputFormat::Evtx | InputFormat::Pcap => {
+            Ok(Box::new(Cursor::new(read_source(schema, path)?)))
+        }
+        _ => Ok(Box::new(BufReader::new(File::open(path)?))),
+    }
 }
 
 fn main() -> color_eyre::eyre::Result<()> {
     color_eyre::install()?;
 
-    let config: Config = Config::parse();
-    let schema = std::fs::read_to_string(&config.schema)?;
-
-    let parser = Parser::try_from(schema.as_str())?;
+    let mut config: Config = Config::parse();
+    let config_path = config.config.clone().or_else(ConfigFile::default_path);
+    let config_file = match config_path {
+        Some(path) if config.config.is_some() || path.exists() => ConfigFile::load(path)?,
+        _ => ConfigFile::default(),
+    };
+    if let Some(profile) = config_file.profile(config.profile.as_deref())? {
+        config.apply(profile);
+    }
+    let source = config.source.clone().ok_or_else(|| {
+        color_eyre::eyre::eyre!("--source is required, or a profile with a source")
+    })?;
+    let schema_path = config.schema.clone().ok_or_else(|| {
+        color_eyre::eyre::eyre!("--schema is required, or a profile with a schema")
+    })?;
+    let mut schema = Schema::load(&schema_path)?;
+    schema.strict |= config.strict;
+    schema.encoding = config.encoding.or(schema.encoding);
+    let parser = Parser::new(schema)?;
+    // only the built in custom types are registered, so a misspelled type fails before the
+    // source is read
+    parser.verify_custom_types()?;
     let filename_regex = Regex::new(&parser.schema.filename)?;
-    let engine = match &config.sql {
-        Some(s) => Engine::with_query(parser, s.clone()),
+    let schema = parser.schema.clone();
+
+    if let Some(Command::Validate { lines }) = config.command {
+        let path = if std::fs::metadata(&source)?.is_file() {
+            PathBuf::from(&source)
+        } else {
+            WalkDir::new(&source)
+                .sort_by_file_name()
+                .into_iter()
+                .flatten()
+                .filter(|entry| entry.file_type().is_file())
+                .map(|entry| entry.into_path())
+                .find(|path| {
+                    path.file_name()
+                        .and_then(|filename| filename.to_str())
+                        .is_some_and(|filename| filename_regex.is_match(filename))
+                })
+                .ok_or_else(|| {
+                    color_eyre::eyre::eyre!("No files in {} match the schema's filename", source)
+                })?
+        };
+        let report = validate(&parser, &read_source(&schema, &path)?, lines)?;
+        println!("{}", path.display());
+        print!("{}", report);
+        if !report.is_clean() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let otlp_exporter = config.otlp_endpoint.as_ref().map(|endpoint| OtlpExporter {
+        endpoint: endpoint.clone(),
+        service_name: parser.schema.table.clone(),
+        body_column: config
+            .otlp_body_column
+            .clone()
+            .or_else(|| parser.multiline_column.clone()),
+        timestamp_column: config.otlp_timestamp_column.clone(),
+    });
+    let loki_exporter = config.loki_endpoint.as_ref().map(|endpoint| LokiExporter {
+        endpoint: endpoint.clone(),
+        job: parser.schema.table.clone(),
+        labels: config.loki_labels.clone(),
+        body_column: config.loki_body_column.clone(),
+        timestamp_column: config.loki_timestamp_column.clone(),
+    });
+    let sql_exporter = SqlExporter {
+        table: parser.schema.table.clone(),
+        dialect: config.sql_dialect,
+    };
+    let table = parser.schema.table.clone();
+    let description = parser.schema.description.clone();
+    let version = parser.schema.version;
+    let mut engine = match &config.sql {
+        Some(s) => Engine::with_params(parser, s.clone(), config.params.clone()),
         None => Ok(Engine::new(parser)),
     }?;
+    if let Some(seed) = config.seed {
+        engine.set_seed(seed);
+    }
+
+    if config.dry_run {
+        let table = match version {
+            Some(version) => format!("{} (version {})", table, version),
+            None => table,
+        };
+        match description {
+            Some(description) => println!("{}: {}", table, description),
+            None => println!("{}", table),
+        }
+        println!("{}", engine.describe()?.table());
+        return Ok(());
+    }
 
-    let metadata = std::fs::metadata(&config.source)?;
+    let metadata = std::fs::metadata(&source)?;
 
-    let files = if metadata.is_file() {
-        let raw = std::fs::read_to_string(&config.source)?;
-        vec![raw]
-    } else {
-        let mut files = Vec::new();
-        for entry in WalkDir::new(&config.source) {
-            if let Ok(entry) = entry {
-                let metadata = entry.metadata()?;
-                if metadata.is_file() {
-                    let path = entry.into_path();
-                    if let Some(filename) = path.file_name() {
-                        if filename_regex.is_match(filename.to_str().unwrap()) {
-                            files.push(std::fs::read_to_string(path)?);
-                        }
-                    }
-                }
+    // a single source file that can't be read is an error, before the query runs
+    let file = match metadata.is_file() {
+        true => Some((
+            Some(source.clone()),
+            open_source(&schema, Path::new(&source))?,
+        )),
+        false => None,
+    };
+
+    let mut audit_record =
+        AuditRecord::new(&schema_path, &table, config.sql.as_deref(), Vec::new());
+    audit_record.params = config.params.clone();
+    let started = Instant::now();
+    // the files are read line by line as the engine gets to them, so a query that streams only
+    // holds the line it's reading and a `LIMIT` can stop before the rest are read
+    let mut sources = Vec::new();
+    let walk = WalkDir::new(&source)
+        .into_iter()
+        .flatten()
+        .filter(|_| !metadata.is_file())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|filename| filename_regex.is_match(&filename.to_string_lossy()))
+        })
+        .filter_map(|path| match open_source(&schema, &path) {
+            Ok(file) => Some((Some(path.display().to_string()), file)),
+            // one unreadable file shouldn't stop the rest of the scan
+            Err(e) => {
+                eprintln!("warning: skipped {}: {}", path.display(), e);
+                None
+            }
+        });
+    let files = file.into_iter().chain(walk).inspect(|(name, _)| {
+        sources.extend(name.clone());
+    });
+    let table_results = engine.execute_all_sources(files);
+    audit_record.sources = sources;
+    if let Some(path) = &config.audit_log {
+        audit_record.finish(started.elapsed(), &table_results, |results| {
+            results.iter().map(|result| result.events.len()).sum()
+        });
+        AuditLog { path: path.clone() }.append(&audit_record)?;
+    }
+    let table_results = table_results?;
+    // every statement ran against the same events so they share warnings
+    for warning in &table_results[0].warnings {
+        eprintln!("warning: {}", warning);
+    }
+    if config.stats {
+        let parse_stats = &table_results[0].parse_stats;
+        for stats in parse_stats {
+            eprintln!("{}", stats);
+        }
+        if parse_stats.len() > 1 {
+            let total = parse_stats.iter().fold(
+                ParseStats {
+                    file: Some("total".to_string()),
+                    ..ParseStats::default()
+                },
+                |total, stats| ParseStats {
+                    lines: total.lines + stats.lines,
+                    events: total.events + stats.events,
+                    unmatched: total.unmatched + stats.unmatched,
+                    failures: total.failures + stats.failures,
+                    skipped: total.skipped + stats.skipped,
+                    ..total
+                },
+            );
+            eprintln!("{}", total);
+        }
+    }
+    for (index, table_result) in table_results.iter().enumerate() {
+        if let Some(otlp_exporter) = &otlp_exporter {
+            otlp_exporter.export(table_result)?;
+        }
+        if let Some(loki_exporter) = &loki_exporter {
+            loki_exporter.export(table_result)?;
+        }
+        if let Some(url) = &config.export {
+            if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+                sql_exporter.export_postgres(url, table_result)?;
+            } else if url.starts_with("clickhouse://") {
+                sql_exporter.export_clickhouse(url, table_result)?;
+            } else {
+                return Err(color_eyre::eyre::eyre!(
+                    "Unsupported export target: {}",
+                    url
+                ));
             }
         }
-        files
-    };
 
-    let table_result = engine.execute(files)?;
-    if !config.no_print {
-        let output: Box<dyn Display> = match &config {
-            Config { json: true, .. } => {
-                Box::new(serde_json::to_string_pretty(&table_result.events)?)
+        if !config.no_print {
+            let format = match &config {
+                Config { json: true, .. } => Format::Json,
+                Config {
+                    json_headers: true, ..
+                } => Format::JsonHeaders,
+                _ => config.format.unwrap_or(Format::Table),
+            };
+            let output = render(table_result, format, &sql_exporter)?;
+            if index > 0 {
+                println!();
             }
-            Config {
-                json_headers: true, ..
-            } => Box::new(serde_json::to_string_pretty(&table_result)?),
-            _ => Box::new(table_result.table()),
-        };
-        println!("{}", output);
+            println!("{}", output);
+        }
     }
     Ok(())
 }
@@ -1,104 +1,588 @@
+pub mod cef;
+pub mod combined;
+pub mod custom;
+pub mod datetime;
+pub mod encoding;
+pub mod evtx;
+mod json;
+pub mod kv;
+pub mod lenient;
+pub mod locale;
+pub mod pcap;
+mod permissive;
+pub mod stream;
+pub mod syslog;
+pub mod trace;
 pub mod values;
 
+use crate::engine::cancel::CancellationToken;
 use crate::error::Error;
-use crate::parser::values::{Event, Type};
-use crate::schema::{ColumnType, Schema};
+use crate::parser::cef::{parse_cef, parse_leef};
+use crate::parser::combined::{parse_combined, COMBINED_COLUMNS};
+use crate::parser::custom::{CustomType, CustomTypes};
+use crate::parser::datetime::DetectedFormats;
+use crate::parser::json::JsonType;
+use crate::parser::lenient::parse_lenient_datetime;
+use crate::parser::locale::{normalize_european_number, parse_day_first_datetime};
+use crate::parser::permissive::prefixes;
+use crate::parser::stream::{split_lines, EventStream, Events, LineFilter, ParseStats};
+use crate::parser::syslog::{parse_gelf, parse_syslog, SYSLOG_COLUMNS};
+use crate::parser::trace::{SpanId, TraceId};
+use crate::parser::values::{parse_decimal, parse_percent, Event, Type};
+use crate::schema::{
+    Column, ColumnType, InputFormat, Locale, OnError, Schema, Timezone, FILE_COLUMN, LINE_COLUMN,
+};
 use chrono::prelude::*;
-use regex::Regex;
-use std::collections::HashSet;
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Parsed events with the warnings and the stats of each file
+pub type ParsedFiles = (Vec<Event>, Vec<String>, Vec<ParseStats>);
 
 #[derive(Debug, Clone)]
 pub struct Parser {
     pub schema: Schema,
-    pub regex: Regex,
+    /// The schema's `regex` and then its `patterns`
+    pub patterns: Vec<Regex>,
+    /// Finds which patterns match a line in one pass
+    pattern_set: RegexSet,
+    /// With `permissive`, each pattern cut short after each of its capture groups, longest first
+    pub(crate) prefixes: Vec<Regex>,
+    /// The schema's `ignore` patterns
+    pub(crate) ignore: RegexSet,
     pub multiline_column: Option<String>,
+    /// Matches continuation lines of the multiline column, if it has a `multiline_regex`
+    pub(crate) multiline_regex: Option<Regex>,
+    /// Matches the lines between records, if the schema has a `record_separator`
+    pub(crate) record_separator: Option<Regex>,
+    /// Indexes of the columns with an `extract` regex, and the regex
+    pub(crate) extractors: Vec<(usize, Regex)>,
+    pub custom_types: CustomTypes,
+    /// Which format each datetime column's values were last read with, shared by clones
+    pub(crate) datetime_formats: Arc<DetectedFormats>,
 }
 
 impl Parser {
-    /// Create a parser from a schema
-    pub fn new(schema: Schema) -> Result<Parser, Error> {
-        let regex = Regex::new(&schema.regex)?;
+    /// Create a parser from a schema. Syslog and combined log schemas without columns get the
+    /// standard ones.
+    pub fn new(mut schema: Schema) -> Result<Parser, Error> {
+        let standard_columns = match schema.format {
+            InputFormat::Syslog => Some(SYSLOG_COLUMNS),
+            InputFormat::Combined => Some(COMBINED_COLUMNS),
+            _ => None,
+        };
+        if let Some(columns) = standard_columns.filter(|_| schema.columns.is_empty()) {
+            schema.columns = serde_yaml::from_str(columns)?;
+        }
+        let sources: Vec<_> = match schema.patterns.is_empty() {
+            true => vec![&schema.regex],
+            false if schema.regex.is_empty() => schema.patterns.iter().collect(),
+            false => std::iter::once(&schema.regex)
+                .chain(&schema.patterns)
+                .collect(),
+        };
+        let expanded = sources
+            .into_iter()
+            .map(|pattern| schema.expand_definitions(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        // a group so alternations like `a|b` are anchored as a whole
+        let sources: Vec<_> = expanded
+            .iter()
+            .map(|pattern| match schema.anchored {
+                true => format!("^(?:{})$", pattern),
+                false => pattern.clone(),
+            })
+            .collect();
+        let flags = schema.regex_flags;
+        let build = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(flags.case_insensitive)
+                .dot_matches_new_line(flags.dot_matches_new_line)
+                .ignore_whitespace(flags.ignore_whitespace)
+                .swap_greed(flags.swap_greed)
+                .build()
+        };
+        let patterns = sources
+            .iter()
+            .map(|pattern| build(pattern))
+            .collect::<Result<_, _>>()?;
+        let prefixes = match schema.permissive {
+            true => expanded
+                .iter()
+                .flat_map(|pattern| prefixes(pattern))
+                .map(|prefix| match schema.anchored {
+                    true => format!("^(?:{})", prefix),
+                    false => prefix,
+                })
+                .map(|prefix| build(&prefix))
+                .collect::<Result<_, _>>()?,
+            false => Vec::new(),
+        };
+        let pattern_set = RegexSetBuilder::new(sources)
+            .case_insensitive(flags.case_insensitive)
+            .dot_matches_new_line(flags.dot_matches_new_line)
+            .ignore_whitespace(flags.ignore_whitespace)
+            .swap_greed(flags.swap_greed)
+            .build()?;
+        let ignore = schema
+            .ignore
+            .iter()
+            .map(|pattern| schema.expand_definitions(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ignore = RegexSet::new(ignore)?;
         let multiline_column = schema
             .columns
             .iter()
             .filter(|c| c.multiline)
             .map(|c| c.name.clone())
             .next();
+        let multiline_regex = schema
+            .columns
+            .iter()
+            .find_map(|c| c.multiline_regex.as_ref())
+            .map(|pattern| Regex::new(&schema.expand_definitions(pattern)?).map_err(Error::from))
+            .transpose()?;
+        let record_separator = schema
+            .record_separator
+            .as_ref()
+            .map(|pattern| Regex::new(&schema.expand_definitions(pattern)?).map_err(Error::from))
+            .transpose()?;
+        let extractors = schema
+            .columns
+            .iter()
+            .enumerate()
+            .filter_map(|(index, c)| c.extract.as_ref().map(|pattern| (index, pattern)))
+            .map(|(index, pattern)| Ok((index, build(&schema.expand_definitions(pattern)?)?)))
+            .collect::<Result<_, Error>>()?;
 
+        let mut custom_types = CustomTypes::default();
+        custom_types.register(TraceId);
+        custom_types.register(SpanId);
+        custom_types.register(JsonType);
+
+        let datetime_formats = Arc::new(DetectedFormats::new(&schema.columns));
         let parser = Parser {
             schema,
-            regex,
+            patterns,
+            pattern_set,
+            prefixes,
+            ignore,
             multiline_column,
+            multiline_regex,
+            record_separator,
+            extractors,
+            custom_types,
+            datetime_formats,
         };
 
-        parser.verify_columns_exist()?;
+        if parser.schema.format == InputFormat::Regex {
+            parser.verify_columns_exist()?;
+        }
+        parser.verify_defaults()?;
         Ok(parser)
     }
 
+    /// Register a custom column type so schema columns can refer to it by name
+    pub fn register_type<T: CustomType>(&mut self, custom_type: T) {
+        self.custom_types.register(custom_type);
+    }
+
+    /// Verify every custom column type in the schema has been registered
+    pub fn verify_custom_types(&self) -> Result<(), Error> {
+        for column in &self.schema.columns {
+            if let ColumnType::Custom(name) = &column.r#type {
+                if !self.custom_types.contains(name) {
+                    return Err(Error::UnknownColumnType(column.name.clone(), name.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Parse all lines
-    pub fn parse<T: AsRef<str>>(&self, chunks: Vec<T>) -> Vec<Event> {
+    pub fn parse<T: AsRef<str>>(&self, chunks: Vec<T>) -> Result<Vec<Event>, Error> {
+        self.parse_with_warnings(chunks).map(|(events, _)| events)
+    }
+
+    /// Parse lines lazily, e.g. from a [`std::io::BufRead`], yielding each event once the lines
+    /// after it can no longer be its continuation lines, so large logs are read in constant
+    /// memory. JSON array sources are read entirely before their events are yielded.
+    pub fn parse_iter<I, S>(&self, lines: I) -> Events<'_, I::IntoIter>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Events::new(self, lines.into_iter())
+    }
+
+    /// Parse all lines, also returning warnings about values that were clamped by lenient columns
+    /// and events whose extra text was truncated by the multiline column's limits
+    pub fn parse_with_warnings<T: AsRef<str>>(
+        &self,
+        chunks: Vec<T>,
+    ) -> Result<(Vec<Event>, Vec<String>), Error> {
+        self.parse_with_cancellation(chunks, &CancellationToken::default())
+    }
+
+    /// Parse all lines like [`Parser::parse_with_warnings`], stopping with [`Error::Cancelled`]
+    /// once the token is cancelled
+    pub fn parse_with_cancellation<T: AsRef<str>>(
+        &self,
+        chunks: Vec<T>,
+        cancellation: &CancellationToken,
+    ) -> Result<(Vec<Event>, Vec<String>), Error> {
+        self.parse_files_with_cancellation(chunks, &[], cancellation)
+    }
+
+    /// Parse like [`Parser::parse_with_cancellation`], with the file name of each chunk for the
+    /// `_file` meta column. Continuation lines at the start of a named file don't belong to the
+    /// last event of the file before it.
+    pub fn parse_files_with_cancellation<T: AsRef<str>>(
+        &self,
+        chunks: Vec<T>,
+        names: &[String],
+        cancellation: &CancellationToken,
+    ) -> Result<(Vec<Event>, Vec<String>), Error> {
+        self.parse_files_with_stats(chunks, names, cancellation)
+            .map(|(events, warnings, _)| (events, warnings))
+    }
+
+    /// Parse like [`Parser::parse_files_with_cancellation`], also returning the stats of each
+    /// chunk, or a single one for chunks without names
+    pub fn parse_files_with_stats<T: AsRef<str>>(
+        &self,
+        chunks: Vec<T>,
+        names: &[String],
+        cancellation: &CancellationToken,
+    ) -> Result<ParsedFiles, Error> {
+        self.parse_files_with_filter(chunks, names, None, cancellation)
+    }
+
+    /// Parse like [`Parser::parse_files_with_stats`], skipping lines that don't pass the filter
+    /// without parsing them
+    pub fn parse_files_with_filter<T: AsRef<str>>(
+        &self,
+        chunks: Vec<T>,
+        names: &[String],
+        filter: Option<&LineFilter>,
+        cancellation: &CancellationToken,
+    ) -> Result<ParsedFiles, Error> {
+        // evtx files and packet captures are converted to JSON arrays when they're read
+        if matches!(
+            self.schema.format,
+            InputFormat::JsonArray | InputFormat::Evtx | InputFormat::Pcap
+        ) {
+            cancellation.check()?;
+            return self.parse_json_arrays(chunks, names);
+        }
+
+        let mut stream = EventStream::new(self);
+        if let Some(filter) = filter {
+            stream.set_filter(filter.clone());
+        }
+        let now = Instant::now();
         let mut parsed = Vec::new();
-        for chunk in chunks {
-            for line in chunk.as_ref().lines() {
-                if let Some(matched_result) = self.parse_line(line) {
-                    parsed.push(matched_result);
-                } else if self.multiline_column.is_some() {
-                    // attempt to get extra lines only if multiline is enabled
-                    if let Some(last) = parsed.last_mut() {
-                        match last.extra_text.as_mut() {
-                            None => last.extra_text = Some(vec![line.to_string()]),
-                            Some(extra_text) => extra_text.push(line.to_string()),
-                        }
+        for (index, chunk) in chunks.iter().enumerate() {
+            if let Some(name) = names.get(index) {
+                parsed.extend(stream.start_file(name));
+            }
+            for line in split_lines(chunk.as_ref()) {
+                cancellation.check()?;
+                parsed.extend(stream.push_line(line, now)?);
+            }
+            // a record or a continued line can't continue in the next file
+            parsed.extend(stream.end_record(now)?);
+        }
+        parsed.extend(stream.finish());
+        let warnings = stream.take_warnings();
+
+        Ok((parsed, warnings, stream.take_stats()))
+    }
+
+    /// Parse the capture groups into columns. Returns `None` if the line doesn't match. A line
+    /// that `on_error: skip` would drop is an error.
+    pub fn parse_line(&self, line: &str) -> Result<Option<Event>, Error> {
+        self.parse_line_with_warnings(line, &mut Warnings::default())
+    }
+
+    fn parse_line_with_warnings(
+        &self,
+        line: &str,
+        warnings: &mut Warnings,
+    ) -> Result<Option<Event>, Error> {
+        match self.schema.format {
+            InputFormat::Json => return self.parse_json_line(line, warnings),
+            InputFormat::KeyValue => return self.parse_key_value_line(line, warnings),
+            _ => (),
+        }
+        let fields = match self.schema.format {
+            InputFormat::Cef => Some(parse_cef(line)),
+            InputFormat::Leef => Some(parse_leef(line)),
+            InputFormat::Syslog => Some(parse_syslog(line)),
+            InputFormat::Gelf => Some(parse_gelf(line)),
+            InputFormat::Combined => Some(parse_combined(line)),
+            _ => None,
+        };
+        if let Some(fields) = fields {
+            return match fields {
+                Some(fields) if self.schema.discovers_columns() => {
+                    self.parse_pairs(fields, warnings).map(Some)
+                }
+                Some(fields) => self
+                    .parse_fields(
+                        |name| fields.get(name).map(|v| Cow::Borrowed(v.as_str())),
+                        warnings,
+                    )
+                    .map(Some),
+                None => Ok(None),
+            };
+        }
+
+        let pattern = match self.patterns.as_slice() {
+            [pattern] => Some(pattern),
+            patterns => self
+                .pattern_set
+                .matches(line)
+                .iter()
+                .next()
+                .map(|index| &patterns[index]),
+        };
+        let captures = pattern
+            .and_then(|pattern| pattern.captures(line))
+            .or_else(|| {
+                self.prefixes
+                    .iter()
+                    .find_map(|prefix| prefix.captures(line))
+            });
+        let captures = match captures {
+            Some(captures) => captures,
+            None => return Ok(None),
+        };
+        self.parse_fields(
+            |name| {
+                captures
+                    .name(name)
+                    .map(|value| Cow::Borrowed(value.as_str()))
+            },
+            warnings,
+        )
+        .map(Some)
+    }
+
+    /// Parse named fields, such as JSON keys or capture groups, into columns. Columns without a
+    /// field are null, under their name or group, or an alias for lines from before the column
+    /// was renamed.
+    /// So are conditional columns whose discriminator doesn't have one of their values,
+    /// nullable columns with an empty field and fields that are one of the column's null values.
+    /// Those fields take the column's default if it has one. Fields are transformed before
+    /// they're checked or parsed, and groups of `extract` regexes are fields too. Values that
+    /// fail to convert are null with `on_error: null`, and an error otherwise.
+    fn parse_fields<'a>(
+        &self,
+        field: impl Fn(&str) -> Option<Cow<'a, str>>,
+        warnings: &mut Warnings,
+    ) -> Result<Event, Error> {
+        let mut extracted = HashMap::new();
+        for (index, regex) in &self.extractors {
+            let text = match self.schema.columns[*index].fields().find_map(&field) {
+                Some(text) => text,
+                None => continue,
+            };
+            if let Some(captures) = regex.captures(&text) {
+                for name in regex.capture_names().flatten() {
+                    if let Some(value) = captures.name(name) {
+                        extracted
+                            .entry(name)
+                            .or_insert_with(|| value.as_str().to_string());
                     }
                 }
             }
         }
+        let field = |name: &str| {
+            field(name).or_else(|| extracted.get(name).map(|value| Cow::Owned(value.clone())))
+        };
 
-        parsed
+        let values = self
+            .schema
+            .columns
+            .iter()
+            .map(|column| {
+                if let Some(condition) = &column.when {
+                    let discriminator = self
+                        .schema
+                        .columns
+                        .iter()
+                        .find(|c| c.name == condition.column)
+                        .and_then(|c| c.fields().find_map(&field));
+                    let applies = discriminator
+                        .is_some_and(|value| condition.values.iter().any(|v| *v == value));
+                    if !applies {
+                        return Ok((column.name.clone(), Type::Null));
+                    }
+                }
+
+                let value = column
+                    .fields()
+                    .find_map(&field)
+                    .map(|value| {
+                        column.transform.iter().fold(value, |value, transform| {
+                            Cow::Owned(transform.apply(&value))
+                        })
+                    })
+                    .filter(|value| {
+                        let empty = column.nullable && value.is_empty();
+                        !empty && !column.null_values.iter().any(|v| v == value)
+                    });
+                let value = match (value, &column.default) {
+                    (Some(value), _) => value,
+                    (_, Some(default)) => Cow::Borrowed(default.as_str()),
+                    _ => return Ok((column.name.clone(), Type::Null)),
+                };
+                let parsed = match self.parse_value(column, &value, &mut warnings.messages) {
+                    Some(parsed) => parsed,
+                    None if self.on_error(column) == OnError::Null => {
+                        *warnings.nulled.entry(column.name.clone()).or_default() += 1;
+                        warnings.failures += 1;
+                        Type::Null
+                    }
+                    None => {
+                        return Err(Error::InvalidColumnValue(
+                            column.name.clone(),
+                            column.r#type.clone(),
+                            value.into_owned(),
+                        ))
+                    }
+                };
+                Ok((column.name.clone(), parsed))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Event {
+            values,
+            extra_text: None,
+        })
+    }
+
+    /// The column's `on_error` policy, or the schema's
+    fn on_error(&self, column: &Column) -> OnError {
+        column.on_error.unwrap_or(self.schema.on_error)
     }
 
-    /// Parse the capture groups into columns
-    pub fn parse_line<'a>(&'a self, line: &'a str) -> Option<Event> {
-        self.regex.captures(line).map(|captures| {
-            let values = self
+    /// Whether the error is a value that fails to convert in a column with `on_error: skip`, so
+    /// its event is dropped
+    pub(crate) fn skips(&self, error: &Error) -> bool {
+        match error {
+            Error::InvalidColumnValue(name, _, _) => self
                 .schema
                 .columns
                 .iter()
-                .map(|column| {
-                    let column_name = column.name.as_str();
-                    let value = captures.name(column_name).unwrap().as_str();
-                    let value = match column.r#type {
-                        ColumnType::String => Type::String(value.to_string()),
-                        ColumnType::Int32 => Type::Int32(i32::from_str(value).unwrap()),
-                        ColumnType::Int64 => Type::Int64(i64::from_str(value).unwrap()),
-                        ColumnType::Bool => Type::Bool(bool::from_str(value).unwrap()),
-                        ColumnType::Float => Type::Float(f32::from_str(value).unwrap()),
-                        ColumnType::Double => Type::Double(f64::from_str(value).unwrap()),
-                        ColumnType::DateTime => Type::DateTime(DateTime::from_str(value).unwrap()),
-                    };
-
-                    (column_name.to_string(), value)
-                })
-                .collect();
+                .find(|c| c.name == *name)
+                .is_some_and(|c| self.on_error(c) == OnError::Skip),
+            _ => false,
+        }
+    }
 
-            Event {
-                values,
-                extra_text: None,
+    /// Set the `_file` and `_line` meta columns, if the schema has them
+    fn add_meta_columns(&self, event: &mut Event, file: Option<&str>, line: usize) {
+        if !self.schema.meta_columns {
+            return;
+        }
+        let file = file.map_or(Type::Null, |file| Type::String(file.to_string()));
+        event.values.insert(FILE_COLUMN.to_string(), file);
+        event
+            .values
+            .insert(LINE_COLUMN.to_string(), Type::Int64(line as i64));
+    }
+
+    /// Datetimes in a detected format, such as RFC 3339, RFC 2822 or Unix timestamps, or
+    /// day-first ones for European logs. Timestamps without an offset are in the column's or
+    /// the schema's timezone, if there is one.
+    fn parse_datetime(&self, column: &Column, value: &str) -> Option<DateTime<FixedOffset>> {
+        let timezone = column.timezone.or(self.schema.timezone);
+        if let Some(datetime) = self.datetime_formats.parse(&column.name, value, timezone) {
+            return Some(datetime);
+        }
+        match self.schema.locale {
+            Some(Locale::European) => {
+                parse_day_first_datetime(value, timezone.unwrap_or(Timezone::Utc))
             }
-        })
+            None => None,
+        }
     }
 
-    /// Verify all columns exist as capture groups
+    fn parse_value(
+        &self,
+        column: &Column,
+        value: &str,
+        warnings: &mut Vec<String>,
+    ) -> Option<Type> {
+        let value = if column.r#type.is_numeric() {
+            Cow::Owned(normalize_number(column, value, self.schema.locale))
+        } else {
+            Cow::Borrowed(value)
+        };
+
+        let value = match &column.r#type {
+            ColumnType::String => Type::String(value.into_owned()),
+            ColumnType::Int32 => Type::Int32(i32::from_str(&value).ok()?),
+            ColumnType::Int64 => Type::Int64(i64::from_str(&value).ok()?),
+            ColumnType::UInt32 => Type::UInt32(u32::from_str(&value).ok()?),
+            ColumnType::UInt64 => Type::UInt64(u64::from_str(&value).ok()?),
+            ColumnType::Bool => Type::Bool(parse_bool(column, &value)?),
+            ColumnType::Float => Type::Float(f32::from_str(&value).ok()?),
+            ColumnType::Double => Type::Double(f64::from_str(&value).ok()?),
+            ColumnType::Decimal => Type::Decimal(parse_decimal(&value)?),
+            ColumnType::DateTime | ColumnType::DateTimeTz if column.lenient => {
+                let timezone = column.timezone.or(self.schema.timezone);
+                let (datetime, clamped) =
+                    parse_lenient_datetime(&value, timezone.unwrap_or(Timezone::Utc))?;
+                if clamped {
+                    warnings.push(format!(
+                        "Clamped '{}' in column '{}' to '{}'",
+                        value,
+                        column.name,
+                        datetime.to_rfc3339()
+                    ));
+                }
+                match column.r#type {
+                    ColumnType::DateTime => Type::DateTime(datetime.with_timezone(&Utc)),
+                    _ => Type::DateTimeTz(datetime),
+                }
+            }
+            ColumnType::DateTime => {
+                Type::DateTime(self.parse_datetime(column, &value)?.with_timezone(&Utc))
+            }
+            ColumnType::DateTimeTz => Type::DateTimeTz(self.parse_datetime(column, &value)?),
+            ColumnType::Percent => Type::Double(parse_percent(&value)?),
+            ColumnType::Custom(name) => Type::Custom(self.custom_types.parse(name, &value)?.ok()?),
+        };
+        match value {
+            Type::Float(x) if !x.is_finite() && !column.allow_non_finite => None,
+            Type::Double(x) if !x.is_finite() && !column.allow_non_finite => None,
+            value => Some(value),
+        }
+    }
+
+    /// Verify all columns exist as capture groups in at least one pattern or `extract` regex,
+    /// under their name or group, or an alias
     fn verify_columns_exist(&self) -> Result<(), Error> {
-        let capture_names: HashSet<_> = self.regex.capture_names().flatten().collect();
+        let capture_names: HashSet<_> = self
+            .patterns
+            .iter()
+            .chain(self.extractors.iter().map(|(_, regex)| regex))
+            .flat_map(|pattern| pattern.capture_names().flatten())
+            .collect();
         let non_existent_columns: Vec<_> = self
             .schema
             .columns
             .iter()
-            .map(|column| column.name.as_str())
-            .filter(|x| !capture_names.contains(x))
-            .map(str::to_string)
+            .filter(|column| !column.fields().any(|name| capture_names.contains(name)))
+            .map(|column| column.name.clone())
             .collect();
 
         if non_existent_columns.is_empty() {
@@ -107,6 +591,25 @@ impl Parser {
             Err(Error::MissingColumns(non_existent_columns))
         }
     }
+
+    /// Verify column defaults parse with their column's type. Custom types can be registered
+    /// after the parser is created, so their defaults are checked when they're used.
+    fn verify_defaults(&self) -> Result<(), Error> {
+        for column in &self.schema.columns {
+            let default = match &column.default {
+                Some(default) if !matches!(column.r#type, ColumnType::Custom(_)) => default,
+                _ => continue,
+            };
+            if self.parse_value(column, default, &mut Vec::new()).is_none() {
+                return Err(Error::InvalidColumnValue(
+                    column.name.clone(),
+                    column.r#type.clone(),
+                    default.clone(),
+                ));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl TryFrom<&str> for Parser {
@@ -119,16 +622,101 @@ impl TryFrom<&str> for Parser {
     }
 }
 
+/// Warnings found while parsing, with counts of the values handled by an `on_error` policy that
+/// are reported once parsing is done
+#[derive(Debug, Default)]
+pub(crate) struct Warnings {
+    pub messages: Vec<String>,
+    /// Values of each column that failed to convert and were read as null
+    pub nulled: BTreeMap<String, usize>,
+    /// Events dropped for values that failed to convert
+    pub skipped: usize,
+    /// Values that failed to convert so far, which taking the warnings doesn't reset
+    pub failures: usize,
+}
+
+impl Warnings {
+    /// The messages followed by the counts, starting over afterwards
+    pub fn take(&mut self) -> Vec<String> {
+        let mut messages = std::mem::take(&mut self.messages);
+        for (column, count) in std::mem::take(&mut self.nulled) {
+            messages.push(format!(
+                "Read {} values in column '{}' that failed to convert as NULL",
+                count, column
+            ));
+        }
+        if self.skipped > 0 {
+            messages.push(format!(
+                "Skipped {} events with values that failed to convert",
+                self.skipped
+            ));
+            self.skipped = 0;
+        }
+        messages
+    }
+}
+
+/// `true` and `false`, or one of the column's `true_values` and `false_values`
+fn parse_bool(column: &Column, value: &str) -> Option<bool> {
+    if column.true_values.iter().any(|v| v == value) {
+        Some(true)
+    } else if column.false_values.iter().any(|v| v == value) {
+        Some(false)
+    } else {
+        bool::from_str(value).ok()
+    }
+}
+
+/// Remove the column's thousands separators and trailing unit, e.g. `1,234 ms` becomes `1234`.
+/// Values that are already numbers, such as `1.2e3` or `inf`, are left alone.
+fn normalize_number(column: &Column, value: &str, locale: Option<Locale>) -> String {
+    let mut value = value.trim();
+    // `1.234` is a thousand in European numbers
+    if locale.is_none() && f64::from_str(value).is_ok() {
+        return value.to_string();
+    }
+
+    if column.strip_units {
+        value = value
+            .trim_end_matches(|c: char| {
+                // percent columns need the sign to tell 85% from a fraction
+                let percent = c == '%' && column.r#type != ColumnType::Percent;
+                c.is_alphabetic() || percent || c == '/'
+            })
+            .trim_end();
+    }
+
+    let value = match column.thousands_separator {
+        Some(separator) => value.replace(separator, ""),
+        None => value.to_string(),
+    };
+    match locale {
+        Some(Locale::European) => normalize_european_number(&value),
+        None => value,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::schema::{Column, ColumnType};
+    use crate::schema::{OnError, RegexFlags};
     use std::collections::HashMap;
 
     #[test]
     fn create_parser() {
         let schema = Schema {
             regex: r"(?P<index>\d+)\t(?P<string_value>.+)\t(?P<double_value>\d+\.\d+)".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
             filename: ".*".to_string(),
             table: "log".to_string(),
             columns: vec![
@@ -136,6 +724,15 @@ mod tests {
                 Column::new("string_value", ColumnType::String),
                 Column::new("double_value", ColumnType::String),
             ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
         };
 
         let _parser = Parser::new(schema).unwrap();
@@ -145,6 +742,17 @@ mod tests {
     fn verify_columns_exist() {
         let schema = Schema {
             regex: r"(?P<index>\d+)\t(?P<string_value>.+)\t(?P<double_value>\d+\.\d+)".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
             filename: ".*".to_string(),
             table: "log".to_string(),
             columns: vec![
@@ -153,6 +761,15 @@ mod tests {
                 Column::new("double_value", ColumnType::String),
                 Column::new("unknown", ColumnType::String),
             ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
         };
 
         assert!(Parser::new(schema).is_err());
@@ -169,6 +786,17 @@ mod tests {
             (?P<float_value>\\d+\\.\\d+)\\t\
             (?P<timestamp>.+)"
                 .to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
             filename: ".*".to_string(),
             table: "log".to_string(),
             columns: vec![
@@ -180,11 +808,20 @@ mod tests {
                 Column::new("float_value", ColumnType::Float),
                 Column::new("timestamp", ColumnType::DateTime),
             ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
         };
 
         let int_value = 1234;
         let string_value = "this is some string";
-        let double_value = 3.14;
+        let double_value = 2.5;
         let long_value = i64::MAX;
         let bool_value = true;
         let float_value = 1.23;
@@ -195,7 +832,7 @@ mod tests {
             int_value, string_value, double_value, long_value, bool_value, float_value, timestamp
         );
         let parser = Parser::new(schema).unwrap();
-        let parsed_value = parser.parse_line(&line).unwrap();
+        let parsed_value = parser.parse_line(&line).unwrap().unwrap();
 
         let mut expected_values = HashMap::new();
         expected_values.insert("int_value".to_string(), Type::Int32(int_value));
@@ -221,6 +858,17 @@ mod tests {
     fn parse_into_columns_no_match() {
         let schema = Schema {
             regex: r"(?P<index>\d+)\t(?P<string_value>.+)\t(?P<double_value>\d+\.\d+)".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
             filename: ".*".to_string(),
             table: "log".to_string(),
             columns: vec![
@@ -228,18 +876,551 @@ mod tests {
                 Column::new("string_value", ColumnType::String),
                 Column::new("double_value", ColumnType::String),
             ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
         };
 
         let line = "1234\t3.14159";
         let parser = Parser::new(schema).unwrap();
-        let map = parser.parse_line(line);
+        let map = parser.parse_line(line).unwrap();
         assert_eq!(None, map);
     }
 
+    #[test]
+    fn parse_into_columns_with_aliases() {
+        let schema = "\
+regex: '(?P<host>\\S+) (?:duration_ms=(?P<duration_ms>\\d+)|latency=(?P<latency>\\d+)|-)'
+filename: .*
+table: logs
+version: 2
+columns:
+    - name: host
+      type: string
+    - name: duration_ms
+      type: i64
+      aliases: [latency]
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let durations: Vec<_> = ["new duration_ms=12", "old latency=34", "none -"]
+            .into_iter()
+            .map(|line| parser.parse_line(line).unwrap().unwrap().values["duration_ms"].clone())
+            .collect();
+        assert_eq!(
+            durations,
+            vec![Type::Int64(12), Type::Int64(34), Type::Null]
+        );
+
+        // the alias alone is enough for the column to exist
+        let schema = schema.replace("duration_ms=(?P<duration_ms>\\d+)|", "");
+        assert!(Parser::try_from(schema.as_str()).is_ok());
+        let schema = schema.replace("latency=(?P<latency>\\d+)|", "");
+        match Parser::try_from(schema.as_str()) {
+            Err(Error::MissingColumns(columns)) => assert_eq!(columns, vec!["duration_ms"]),
+            x => panic!("Error should be Error::MissingColumns. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_conditional_columns() {
+        let schema = "\
+regex: 'event=(?P<event>\\w+) (?P<payload>\\S+)'
+filename: .*
+table: logs
+columns:
+    - name: event
+      type: string
+    - name: user
+      type: string
+      aliases: [payload]
+      when: {column: event, values: [login, logout]}
+    - name: amount
+      type: f64
+      aliases: [payload]
+      when: {column: event, values: [purchase]}
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let rows: Vec<_> = [
+            "event=login alice",
+            "event=purchase 9.99",
+            "event=logout bob",
+            "event=refund abc",
+        ]
+        .into_iter()
+        .map(|line| {
+            let values = parser.parse_line(line).unwrap().unwrap().values;
+            (values["user"].clone(), values["amount"].clone())
+        })
+        .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("alice".into(), Type::Null),
+                (Type::Null, Type::Double(9.99)),
+                ("bob".into(), Type::Null),
+                (Type::Null, Type::Null),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_nullable_columns() {
+        let schema = "\
+regex: (?P<level>\\w+) (?P<user>\\S*) ?(?P<latency>\\d+)?
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: user
+      type: string
+      nullable: true
+    - name: latency
+      type: i32
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let rows: Vec<_> = ["INFO alice 12", "WARN  30", "ERROR bob"]
+            .into_iter()
+            .map(|line| {
+                let values = parser.parse_line(line).unwrap().unwrap().values;
+                (values["user"].clone(), values["latency"].clone())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("alice".into(), Type::Int32(12)),
+                (Type::Null, Type::Int32(30)),
+                ("bob".into(), Type::Null),
+            ]
+        );
+
+        // without nullable an empty capture is an empty string
+        let parser =
+            Parser::try_from(schema.replace("      nullable: true\n", "").as_str()).unwrap();
+        let values = parser.parse_line("WARN  30").unwrap().unwrap().values;
+        assert_eq!(values["user"], "".into());
+    }
+
+    #[test]
+    fn parse_null_values() {
+        let schema = "\
+regex: (?P<status>\\S+) (?P<bytes>\\S+) (?P<referer>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: status
+      type: i32
+      null_values: ['-']
+    - name: bytes
+      type: u64
+      null_values: ['-', N/A]
+      default: 0
+    - name: referer
+      type: string
+      null_values: ['-']
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let rows: Vec<_> = ["200 512 /home", "- - -", "404 N/A /"]
+            .into_iter()
+            .map(|line| {
+                let values = parser.parse_line(line).unwrap().unwrap().values;
+                (
+                    values["status"].clone(),
+                    values["bytes"].clone(),
+                    values["referer"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Type::Int32(200), Type::UInt64(512), "/home".into()),
+                (Type::Null, Type::UInt64(0), Type::Null),
+                (Type::Int32(404), Type::UInt64(0), "/".into()),
+            ]
+        );
+
+        // other columns don't share the sentinels
+        match parser.parse_line("N/A 1 /") {
+            Err(Error::InvalidColumnValue(column, ColumnType::Int32, value)) => {
+                assert_eq!(column, "status");
+                assert_eq!(value, "N/A");
+            }
+            x => panic!("Error should be Error::InvalidColumnValue. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_with_on_error_policies() {
+        let schema = "\
+regex: (?P<status>\\S+) (?P<bytes>\\S+)
+filename: .*
+table: logs
+on_error: skip
+columns:
+    - name: status
+      type: i32
+    - name: bytes
+      type: u64
+      on_error: null
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let lines = "200 512\nok 1\n404 -\n500 x";
+        let (events, warnings) = parser.parse_with_warnings(vec![lines]).unwrap();
+        let rows: Vec<_> = events
+            .iter()
+            .map(|e| (e.values["status"].clone(), e.values["bytes"].clone()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Type::Int32(200), Type::UInt64(512)),
+                (Type::Int32(404), Type::Null),
+                (Type::Int32(500), Type::Null),
+            ]
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "Read 2 values in column 'bytes' that failed to convert as NULL",
+                "Skipped 1 events with values that failed to convert",
+            ]
+        );
+
+        let parser = Parser::try_from(schema.replace("on_error: skip\n", "").as_str()).unwrap();
+        match parser.parse(vec![lines]) {
+            Err(Error::InvalidColumnValue(column, _, value)) => {
+                assert_eq!(column, "status");
+                assert_eq!(value, "ok");
+            }
+            x => panic!("Error should be Error::InvalidColumnValue. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_column_defaults() {
+        let schema = "\
+regex: (?P<level>\\w+)( (?P<user>[a-z]+))?( (?P<latency>\\d+))?
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: user
+      type: string
+      default: unknown
+    - name: latency
+      type: i32
+      default: 0
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let rows: Vec<_> = ["INFO alice 12", "WARN 30", "ERROR"]
+            .into_iter()
+            .map(|line| {
+                let values = parser.parse_line(line).unwrap().unwrap().values;
+                (values["user"].clone(), values["latency"].clone())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("alice".into(), Type::Int32(12)),
+                ("unknown".into(), Type::Int32(30)),
+                ("unknown".into(), Type::Int32(0)),
+            ]
+        );
+
+        match Parser::try_from(schema.replace("default: 0", "default: none").as_str()) {
+            Err(Error::InvalidColumnValue(column, ColumnType::Int32, value)) => {
+                assert_eq!(column, "latency");
+                assert_eq!(value, "none");
+            }
+            x => panic!("Error should be Error::InvalidColumnValue. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_bool_values() {
+        let schema = "\
+regex: (?P<cached>\\S+) (?P<ok>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: cached
+      type: bool
+      true_values: [1, yes, Y]
+      false_values: [0, no, N]
+    - name: ok
+      type: bool
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let rows: Vec<_> = ["1 true", "N false", "yes true", "false true"]
+            .into_iter()
+            .map(|line| parser.parse_line(line).unwrap().unwrap().values["cached"].clone())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![true.into(), false.into(), true.into(), false.into()]
+        );
+
+        // values are matched exactly, and only by columns that list them
+        for line in ["YES true", "1 1"] {
+            match parser.parse_line(line) {
+                Err(Error::InvalidColumnValue(_, ColumnType::Bool, _)) => (),
+                x => panic!("Error should be Error::InvalidColumnValue. Actual {:?}", x),
+            }
+        }
+
+        let schema = schema.replace("false_values: [0, no, N]", "false_values: [0, yes]");
+        match Parser::try_from(schema.as_str()) {
+            Err(Error::InvalidBoolValues(column, _)) => assert_eq!(column, "cached"),
+            x => panic!("Error should be Error::InvalidBoolValues. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_european_locale() {
+        let schema = "\
+regex: (?P<ts>[^|]+)\\|(?P<count>[^|]+)\\|(?P<price>[^|]+)
+filename: .*
+table: logs
+locale: european
+columns:
+    - name: ts
+      type: datetime
+    - name: count
+      type: i32
+    - name: price
+      type: f64
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let values = parser
+            .parse_line("31.12.2022 23:59:59|1.234|1.234,56")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(
+            values["ts"],
+            Type::DateTime(Utc.ymd(2022, 12, 31).and_hms(23, 59, 59))
+        );
+        assert_eq!(values["count"], Type::Int32(1234));
+        assert_eq!(values["price"], Type::Double(1234.56));
+
+        // standard formats still parse
+        let values = parser
+            .parse_line("2022-12-31T23:59:59Z|42|0.5")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["count"], Type::Int32(42));
+        assert_eq!(values["price"], Type::Double(0.5));
+
+        let parser = Parser::try_from(schema.replace("locale: european\n", "").as_str()).unwrap();
+        assert!(parser.parse_line("31.12.2022 23:59:59|1|1,5").is_err());
+    }
+
+    #[test]
+    fn parse_naive_timestamps_in_timezone() {
+        let schema = "\
+regex: (?P<ts>[^|]+)\\|(?P<utc>[^|]+)\\|(?P<lenient>[^|]+)
+filename: .*
+table: logs
+timezone: +02:00
+columns:
+    - name: ts
+      type: datetimetz
+    - name: utc
+      type: datetime
+      timezone: UTC
+    - name: lenient
+      type: datetime
+      lenient: true
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let values = parser
+            .parse_line("2022-05-01 12:00:00|2022-05-01T12:00:00|2022-05-01 12:00:60|")
+            .unwrap()
+            .unwrap()
+            .values;
+        let offset = FixedOffset::east(2 * 3600);
+        assert_eq!(
+            values["ts"],
+            Type::DateTimeTz(offset.ymd(2022, 5, 1).and_hms(12, 0, 0))
+        );
+        assert_eq!(
+            values["utc"],
+            Type::DateTime(Utc.ymd(2022, 5, 1).and_hms(12, 0, 0))
+        );
+        assert_eq!(
+            values["lenient"],
+            Type::DateTime(Utc.ymd(2022, 5, 1).and_hms_nano(10, 0, 59, 999_999_999))
+        );
+        // timestamps with an offset keep it
+        let values = parser
+            .parse_line("2022-05-01T12:00:00Z|2022-05-01T12:00:00Z|2022-05-01T12:00:00Z")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(
+            values["ts"],
+            Type::DateTimeTz(FixedOffset::east(0).ymd(2022, 5, 1).and_hms(12, 0, 0))
+        );
+
+        let parser = Parser::try_from(schema.replace("timezone: +02:00\n", "").as_str()).unwrap();
+        assert!(parser.parse_line("2022-05-01 12:00:00|x|x").is_err());
+        assert!(Schema::try_from(schema.replace("+02:00", "Mars/Olympus").as_str()).is_err());
+    }
+
+    #[test]
+    fn parse_lines_with_patterns() {
+        let schema = "\
+patterns:
+    - (?P<ts>\\S+) \\[(?P<level>\\w+)\\] (?P<message>.+)
+    - (?P<ts>\\S+) level=(?P<level>\\w+) status=(?P<status>\\d+)
+    - (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: ts
+      type: string
+    - name: level
+      type: string
+    - name: status
+      type: i32
+    - name: message
+      type: string
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let rows: Vec<_> = [
+            "10:00 [INFO] started",
+            "10:01 level=WARN status=503",
+            "panic",
+        ]
+        .into_iter()
+        .map(|line| {
+            let values = parser.parse_line(line).unwrap().unwrap().values;
+            ["ts", "level", "status", "message"].map(|column| values[column].clone())
+        })
+        .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ["10:00".into(), "INFO".into(), Type::Null, "started".into()],
+                ["10:01".into(), "WARN".into(), Type::Int32(503), Type::Null],
+                [Type::Null, Type::Null, Type::Null, "panic".into()],
+            ]
+        );
+
+        // a column needs a capture group in at least one pattern
+        let schema = schema.replace("status=(?P<status>\\d+)", "status=\\d+");
+        match Parser::try_from(schema.as_str()) {
+            Err(Error::MissingColumns(columns)) => assert_eq!(columns, ["status"]),
+            x => panic!("Error should be Error::MissingColumns. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_lines_with_definitions() {
+        let schema = "\
+definitions:
+    date: \\d{4}-\\d{2}-\\d{2}
+    timestamp: '{{date}}T\\S+'
+    level: (?P<level>[A-Z]+)
+regex: (?P<ts>{{timestamp}}) {{level}} (?P<message>.+)
+patterns:
+    - (?P<ts>{{timestamp}}) {{level}}
+filename: .*
+table: logs
+columns:
+    - name: ts
+      type: string
+    - name: level
+      type: string
+    - name: message
+      type: string
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let values = parser
+            .parse_line("2022-05-01T10:00:00Z WARN disk full")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["ts"], "2022-05-01T10:00:00Z".into());
+        assert_eq!(values["level"], "WARN".into());
+        assert_eq!(values["message"], "disk full".into());
+        let values = parser.parse_line("2022-05-01T10:00:00Z INFO").unwrap();
+        assert_eq!(values.unwrap().values["message"], Type::Null);
+
+        let cases = [
+            (schema.replace("{{level}} (", "{{severity}} ("), "severity"),
+            (schema.replace("{{date}}T", "{{timestamp}}T"), "timestamp"),
+        ];
+        for (schema, expected) in cases {
+            match Parser::try_from(schema.as_str()) {
+                Err(Error::InvalidDefinition(name, _)) => assert_eq!(name, expected),
+                x => panic!("Error should be Error::InvalidDefinition. Actual {:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_transformed_columns() {
+        let schema = "\
+regex: (?P<method>\\w+) (?P<path>\\S+) (?P<user>\\S+) (?P<status>\\S+)
+filename: .*
+table: logs
+columns:
+    - name: method
+      type: string
+      transform: [uppercase]
+    - name: path
+      type: string
+      transform: [url_decode, lowercase]
+    - name: user
+      type: string
+      transform: [strip_quotes, trim]
+      nullable: true
+    - name: status
+      type: i32
+      transform: [strip_quotes]
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let values = parser
+            .parse_line("get /Search%3Fq=Caf%C3%A9 \"\" '200'")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["method"], "GET".into());
+        assert_eq!(values["path"], "/search?q=café".into());
+        assert_eq!(values["user"], Type::Null);
+        assert_eq!(values["status"], Type::Int32(200));
+
+        let schema = schema.replace("[uppercase]", "[reverse]");
+        assert!(Schema::try_from(schema.as_str()).is_err());
+    }
+
     #[test]
     fn parse_lines_with_multiline_enabled() {
         let schema = Schema {
             regex: r"(?P<index>\d+)\t(?P<string_value>.+)\t(?P<double_value>\d+\.\d+)".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
             filename: ".*".to_string(),
             table: "log".to_string(),
             columns: vec![
@@ -247,11 +1428,20 @@ mod tests {
                 Column::multiline_string("string_value"),
                 Column::new("double_value", ColumnType::String),
             ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
         };
 
         let line = "1234\tthis is some string\t3.14159\nthis is extra text";
         let parser = Parser::new(schema).unwrap();
-        let parsed_result = parser.parse(vec![line]);
+        let parsed_result = parser.parse(vec![line]).unwrap();
 
         let mut expected_values = HashMap::new();
         expected_values.insert("index".to_string(), Type::Int32(1234));
@@ -272,10 +1462,581 @@ mod tests {
         assert_eq!(expected, parsed_result);
     }
 
+    #[test]
+    fn parse_lines_with_multiline_limits() {
+        let mut column = Column::multiline_string("string_value");
+        column.max_lines = Some(2);
+        column.max_bytes = Some(12);
+        let schema = Schema {
+            regex: r"(?P<index>\d+)\t(?P<string_value>.+)".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
+            filename: ".*".to_string(),
+            table: "log".to_string(),
+            columns: vec![Column::new("index", ColumnType::Int32), column],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
+        };
+
+        let lines = "1\tfirst\na\nb\nc\n2\tsecond\nlong line\nlonger line\n3\tthird\nd";
+        let parser = Parser::new(schema).unwrap();
+        let (events, warnings) = parser.parse_with_warnings(vec![lines]).unwrap();
+
+        let extra_text: Vec<_> = events.into_iter().map(|e| e.extra_text).collect();
+        assert_eq!(
+            vec![
+                Some(vec!["a".to_string(), "b".to_string()]),
+                Some(vec!["long line".to_string()]),
+                Some(vec!["d".to_string()]),
+            ],
+            extra_text
+        );
+        assert_eq!(
+            vec![
+                "Truncated extra text in multiline column 'string_value' (kept lines: 2, bytes: 4)",
+                "Truncated extra text in multiline column 'string_value' (kept lines: 1, bytes: 10)",
+            ],
+            warnings
+        );
+    }
+
+    #[test]
+    fn parse_lines_with_multiline_regex() {
+        let schema = "\
+regex: (?P<level>[A-Z]+) (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+      multiline: true
+      multiline_regex: ^\\s+at
+";
+        let lines = "ERROR boom\n    at main.rs:1\ngarbage\n    at lib.rs:2\nINFO ok\n\tat x";
+        let parser = Parser::try_from(schema).unwrap();
+        let (events, warnings) = parser.parse_with_warnings(vec![lines]).unwrap();
+
+        let extra_text: Vec<_> = events.into_iter().map(|e| e.extra_text).collect();
+        assert_eq!(
+            vec![
+                Some(vec![
+                    "    at main.rs:1".to_string(),
+                    "    at lib.rs:2".to_string()
+                ]),
+                Some(vec!["\tat x".to_string()]),
+            ],
+            extra_text
+        );
+        assert_eq!(
+            vec!["Line did not match the schema or the multiline regex: garbage"],
+            warnings
+        );
+    }
+
+    #[test]
+    fn parse_lines_with_ignore_patterns() {
+        let schema = "\
+regex: (?P<level>[A-Z]+) (?P<message>.+)
+ignore:
+    - ^=+$
+    - ^--- {{banner}}
+definitions:
+    banner: restarted
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+      multiline: true
+";
+        let lines = "ERROR boom\n  detail\n=====\n--- restarted at 10:00\nINFO ok\nINFO skip me";
+        let mut schema = Schema::try_from(schema).unwrap();
+        schema.ignore.push("skip me$".to_string());
+        let events = Parser::new(schema).unwrap().parse(vec![lines]).unwrap();
+
+        let rows: Vec<_> = events
+            .into_iter()
+            .map(|e| (e.values["level"].clone(), e.extra_text))
+            .collect();
+        assert_eq!(
+            vec![
+                ("ERROR".into(), Some(vec!["  detail".to_string()])),
+                ("INFO".into(), None),
+            ],
+            rows
+        );
+    }
+
+    #[test]
+    fn parse_lines_with_regex_flags() {
+        let schema = "\
+regex: |
+    (?P<level>error|warn)  # the level in any case
+    \\s+ (?P<message>.+?) \\.?$
+regex_flags: ix
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let values = parser
+            .parse_line("ERROR disk full.")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["level"], "ERROR".into());
+        assert_eq!(values["message"], "disk full".into());
+
+        let schema = schema.replace("regex_flags: ix", "regex_flags: iq");
+        match Schema::try_from(schema.as_str()) {
+            Err(Error::InvalidSchema(e)) => assert!(e.to_string().contains("'q'"), "{}", e),
+            x => panic!("Error should be Error::InvalidSchema. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_anchored_patterns() {
+        let schema = "\
+regex: (?P<level>INFO|WARN) (?P<message>\\w+)
+patterns:
+    - (?P<level>ERROR)
+filename: .*
+table: logs
+anchored: true
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let lines = [
+            "INFO started",
+            "ERROR",
+            "proxy: INFO started",
+            "INFO started twice",
+            "ERROR at 10:00",
+        ];
+        let matched: Vec<_> = lines
+            .iter()
+            .map(|line| parser.parse_line(line).unwrap().is_some())
+            .collect();
+        assert_eq!(matched, [true, true, false, false, false]);
+
+        let parser = Parser::try_from(schema.replace("anchored: true\n", "").as_str()).unwrap();
+        let values = parser
+            .parse_line("proxy: INFO started")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["level"], "INFO".into());
+    }
+
+    #[test]
+    fn parse_permissive_lines() {
+        let schema = "\
+regex: (?P<ts>\\S+) (?P<level>[A-Z]+) (?P<latency>\\d+)ms (?P<user>\\w+)
+filename: .*
+table: logs
+permissive: true
+columns:
+    - name: ts
+      type: string
+    - name: level
+      type: string
+    - name: latency
+      type: i32
+    - name: user
+      type: string
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let rows: Vec<_> = [
+            "10:00 INFO 12ms alice",
+            "10:01 WARN 30ms",
+            "10:02 ERROR",
+            "10:03 debug",
+        ]
+        .into_iter()
+        .map(|line| {
+            let values = parser.parse_line(line).unwrap().unwrap().values;
+            (
+                values["level"].clone(),
+                values["latency"].clone(),
+                values["user"].clone(),
+            )
+        })
+        .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("INFO".into(), Type::Int32(12), "alice".into()),
+                ("WARN".into(), Type::Int32(30), Type::Null),
+                ("ERROR".into(), Type::Null, Type::Null),
+                (Type::Null, Type::Null, Type::Null),
+            ]
+        );
+
+        let parser = Parser::try_from(schema.replace("permissive: true\n", "").as_str()).unwrap();
+        assert_eq!(parser.parse_line("10:01 WARN 30ms").unwrap(), None);
+    }
+
+    #[test]
+    fn parse_columns_from_groups() {
+        let schema = "\
+regex: (?P<ts>\\S+) (?P<ts2>\\S+) (?P<msg>.+)
+filename: .*
+table: logs
+columns:
+    - name: received
+      type: string
+      group: ts
+    - name: timestamp
+      type: string
+      group: ts2
+    - name: message
+      type: string
+      group: msg
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let values = parser
+            .parse_line("10:00 09:59 hello")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["received"], "10:00".into());
+        assert_eq!(values["timestamp"], "09:59".into());
+        assert_eq!(values["message"], "hello".into());
+        assert!(!values.contains_key("ts"));
+
+        // the group replaces the column's name
+        let schema = schema.replace("(?P<msg>", "(?P<message>");
+        match Parser::try_from(schema.as_str()) {
+            Err(Error::MissingColumns(columns)) => assert_eq!(columns, ["message"]),
+            x => panic!("Error should be Error::MissingColumns. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_columns_extracted_from_columns() {
+        let schema = "\
+regex: (?P<level>[A-Z]+) (?P<message>.+)
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+      extract: 'request_id=(?P<request_id>\\w+)(?: took (?P<duration>\\d+)ms)?'
+    - name: request_id
+      type: string
+    - name: duration
+      type: i32
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let values = parser
+            .parse_line("INFO done request_id=abc123 took 42ms")
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["message"], "done request_id=abc123 took 42ms".into());
+        assert_eq!(values["request_id"], "abc123".into());
+        assert_eq!(values["duration"], 42.into());
+
+        let values = parser.parse_line("WARN no id").unwrap().unwrap().values;
+        assert_eq!(values["request_id"], Type::Null);
+        assert_eq!(values["duration"], Type::Null);
+
+        // fields of other formats can be extracted from too
+        let gelf = schema.replace("regex: (?P<level>[A-Z]+) (?P<message>.+)", "format: gelf");
+        let parser = Parser::try_from(gelf.as_str()).unwrap();
+        let values = parser
+            .parse_line(
+                r#"{"_level": "INFO", "_message": "request_id=x1 took 3ms", "_duration": 7}"#,
+            )
+            .unwrap()
+            .unwrap()
+            .values;
+        assert_eq!(values["request_id"], "x1".into());
+        // the line's own field wins
+        assert_eq!(values["duration"], 7.into());
+
+        let schema = schema.replace("(?P<duration>", "(?P<elapsed>");
+        match Parser::try_from(schema.as_str()) {
+            Err(Error::MissingColumns(columns)) => assert_eq!(columns, ["duration"]),
+            x => panic!("Error should be Error::MissingColumns. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_cef_and_leef_lines() {
+        let cases = [
+            (
+                "cef",
+                "CEF:0|Vendor|Firewall|1.0|100|Blocked|7|src=10.0.0.1 spt=443 cs1=prod",
+            ),
+            (
+                "leef",
+                "LEEF:1.0|Vendor|Firewall|1.0|100|src=10.0.0.1\tspt=443\tcs1=prod",
+            ),
+        ];
+        for (format, line) in cases {
+            let schema = format!(
+                "
+format: {}
+filename: .*
+table: logs
+columns:
+    - name: device_product
+      type: string
+    - name: src
+      type: string
+    - name: spt
+      type: i32
+    - name: act
+      type: string
+",
+                format
+            );
+            let parser = Parser::try_from(schema.as_str()).unwrap();
+            let events = parser
+                .parse(vec![format!("{}\nnot an event", line)])
+                .unwrap();
+
+            let mut expected = HashMap::new();
+            expected.insert("device_product".to_string(), "Firewall".into());
+            expected.insert("src".to_string(), "10.0.0.1".into());
+            expected.insert("spt".to_string(), Type::Int32(443));
+            expected.insert("act".to_string(), Type::Null);
+            let expected = vec![Event {
+                values: expected,
+                extra_text: None,
+            }];
+            assert_eq!(expected, events, "{}", format);
+        }
+    }
+
+    #[test]
+    fn parse_syslog_and_gelf_lines() {
+        let cases = [
+            (
+                "syslog",
+                r#"<165>1 2022-05-01T10:00:00Z web01 nginx - - [origin@32473 ip="10.0.0.1"] GET /"#,
+                ["hostname", "origin.ip"],
+            ),
+            (
+                "gelf",
+                r#"{"version": "1.1", "host": "web01", "timestamp": 1651399200, "_ip": "10.0.0.1"}"#,
+                ["host", "ip"],
+            ),
+        ];
+        for (format, line, names) in cases {
+            let schema = format!(
+                "
+format: {}
+filename: .*
+table: logs
+columns:
+    - name: timestamp
+      type: datetime
+    - name: {}
+      type: string
+    - name: {}
+      type: string
+",
+                format, names[0], names[1]
+            );
+            let parser = Parser::try_from(schema.as_str()).unwrap();
+            let events = parser
+                .parse(vec![format!("{}\nnot an event", line)])
+                .unwrap();
+
+            let mut expected = HashMap::new();
+            expected.insert(
+                "timestamp".to_string(),
+                Utc.ymd(2022, 5, 1).and_hms(10, 0, 0).into(),
+            );
+            expected.insert(names[0].to_string(), "web01".into());
+            expected.insert(names[1].to_string(), "10.0.0.1".into());
+            let expected = vec![Event {
+                values: expected,
+                extra_text: None,
+            }];
+            assert_eq!(expected, events, "{}", format);
+        }
+    }
+
+    #[test]
+    fn parse_syslog_with_standard_columns() {
+        let schema = "\
+format: syslog
+filename: .*
+table: logs
+columns: []
+timezone: +02:00
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let lines = "\
+<165>1 2022-05-01T10:00:00Z web01 nginx 42 ID47 - GET /
+<34>Apr 30 22:14:15 mymachine su: 'su root' failed";
+        let events = parser.parse(vec![lines]).unwrap();
+        let values = &events[0].values;
+        assert_eq!(values["pri"], Type::Int32(165));
+        assert_eq!(values["facility"], Type::Int32(20));
+        assert_eq!(values["severity"], Type::Int32(5));
+        assert_eq!(values["host"], "web01".into());
+        assert_eq!(values["app"], "nginx".into());
+        assert_eq!(values["procid"], "42".into());
+        assert_eq!(values["msgid"], "ID47".into());
+        assert_eq!(values["message"], "GET /".into());
+
+        let values = &events[1].values;
+        assert_eq!(values["host"], "mymachine".into());
+        assert_eq!(values["app"], "su".into());
+        assert_eq!(values["procid"], Type::Null);
+        let timestamp = values["timestamp"].to_string();
+        assert!(
+            timestamp.ends_with("-04-30 22:14:15 +02:00"),
+            "{}",
+            timestamp
+        );
+    }
+
+    #[test]
+    fn parse_combined_log_lines() {
+        let schema = "\
+format: combined
+filename: .*
+table: access
+columns: []
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let lines = r#"10.0.0.1 - - [01/May/2022:10:00:00 +0200] "POST /login HTTP/1.1" 302 0 "-" "curl/7.79"
+not an access log
+10.0.0.2 - bob [01/May/2022:10:00:01 +0200] "GET /big.iso HTTP/2.0" 200 5000000000 "https://example.com/" "-""#;
+        let events = parser.parse(vec![lines]).unwrap();
+        assert_eq!(events.len(), 2);
+
+        let values = &events[0].values;
+        let offset = FixedOffset::east(2 * 3600);
+        assert_eq!(
+            values["timestamp"],
+            Type::DateTimeTz(offset.ymd(2022, 5, 1).and_hms(10, 0, 0))
+        );
+        assert_eq!(values["method"], "POST".into());
+        assert_eq!(values["status"], Type::Int32(302));
+        assert_eq!(values["bytes"], Type::Int64(0));
+        assert_eq!(values["referer"], Type::Null);
+        assert_eq!(values["user_agent"], "curl/7.79".into());
+
+        let values = &events[1].values;
+        assert_eq!(values["remote_user"], "bob".into());
+        assert_eq!(values["bytes"], Type::Int64(5_000_000_000));
+        assert_eq!(values["referer"], "https://example.com/".into());
+        assert_eq!(values["user_agent"], Type::Null);
+    }
+
+    #[test]
+    fn parse_records_separated_by_blank_lines() {
+        let schema = r#"
+regex: '(?s)\[(?P<uptime>[\d.]+)s\] (?P<kind>GC|Full GC)\n(?P<details>.*)'
+record_separator: '^\s*$'
+meta_columns: true
+filename: .*
+table: gc
+columns:
+    - name: uptime
+      type: f64
+    - name: kind
+      type: string
+    - name: details
+      type: string
+"#;
+        let parser = Parser::try_from(schema).unwrap();
+        let lines = "\
+[0.512s] GC
+  young: 10M->2M
+  took: 3ms
+
+not a record
+
+
+[1.024s] Full GC
+  old: 90M->40M
+";
+        let events = parser.parse(vec![lines]).unwrap();
+        let rows: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.values["uptime"].clone(),
+                    event.values["details"].clone(),
+                    event.values[LINE_COLUMN].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (
+                    Type::Double(0.512),
+                    "  young: 10M->2M\n  took: 3ms".into(),
+                    Type::Int64(1)
+                ),
+                (
+                    Type::Double(1.024),
+                    "  old: 90M->40M".into(),
+                    Type::Int64(8)
+                ),
+            ]
+        );
+
+        // the last record doesn't need a separator after it
+        let lines = lines.trim_end().lines();
+        let events: Vec<_> = parser.parse_iter(lines).map(Result::unwrap).collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].values["kind"], "Full GC".into());
+    }
+
     #[test]
     fn parse_lines_with_multiline_disabled() {
         let schema = Schema {
             regex: r"(?P<index>\d+)\t(?P<string_value>.+)\t(?P<double_value>\d+\.\d+)".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
             filename: ".*".to_string(),
             table: "log".to_string(),
             columns: vec![
@@ -283,11 +2044,20 @@ mod tests {
                 Column::new("string_value", ColumnType::String),
                 Column::new("double_value", ColumnType::String),
             ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
         };
 
         let line = "1234\tthis is some string\t3.14159\nthis is extra text";
         let parser = Parser::new(schema).unwrap();
-        let parsed_result = parser.parse(vec![line]);
+        let parsed_result = parser.parse(vec![line]).unwrap();
 
         let mut expected_values = HashMap::new();
         expected_values.insert("index".to_string(), Type::Int32(1234));
@@ -307,4 +2077,173 @@ mod tests {
 
         assert_eq!(expected, parsed_result);
     }
+
+    #[test]
+    fn parse_numbers_with_separators_and_units() {
+        let schema = "\
+regex: (?P<bytes>.+)\t(?P<latency>.+)
+filename: .*
+table: log
+columns:
+    - name: bytes
+      type: i64
+      thousands_separator: ','
+    - name: latency
+      type: f64
+      thousands_separator: ','
+      strip_units: true
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let event = parser.parse_line("1,234,567\t1,234.5 ms").unwrap().unwrap();
+        assert_eq!(event.values["bytes"], Type::Int64(1234567));
+        assert_eq!(event.values["latency"], Type::Double(1234.5));
+
+        let event = parser.parse_line("12\t7ms").unwrap().unwrap();
+        assert_eq!(event.values["latency"], Type::Double(7.0));
+    }
+
+    #[test]
+    fn parse_invalid_value() {
+        let schema = "\
+regex: (?P<bytes>.+)
+filename: .*
+table: log
+columns:
+    - name: bytes
+      type: i64
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        match parser.parse(vec!["1,234 ms"]).err().unwrap() {
+            Error::InvalidColumnValue(column, column_type, value) => {
+                assert_eq!(column, "bytes");
+                assert_eq!(column_type, ColumnType::Int64);
+                assert_eq!(value, "1,234 ms");
+            }
+            x => panic!(
+                "Error should be Error::InvalidColumnValue. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn parse_non_finite_floats() {
+        let schema = "\
+regex: (?P<value>.+)
+filename: .*
+table: log
+columns:
+    - name: value
+      type: f64
+      strip_units: true
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let values: Vec<_> = parser
+            .parse(vec!["1.2e3\ninf\n-Infinity\n2.5e-1 ms"])
+            .unwrap()
+            .into_iter()
+            .map(|event| event.values["value"].clone())
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                Type::Double(1200.0),
+                Type::Double(f64::INFINITY),
+                Type::Double(f64::NEG_INFINITY),
+                Type::Double(0.25)
+            ]
+        );
+        let event = parser.parse_line("NaN").unwrap().unwrap();
+        assert!(matches!(event.values["value"], Type::Double(x) if x.is_nan()));
+
+        let schema = "\
+regex: (?P<value>.+)
+filename: .*
+table: log
+columns:
+    - name: value
+      type: f32
+      allow_non_finite: false
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        assert!(parser.parse_line("1e3").unwrap().is_some());
+        assert!(parser.parse_line("NaN").is_err());
+        assert!(parser.parse_line("-inf").is_err());
+    }
+
+    #[test]
+    fn parse_percent_values() {
+        let schema = "\
+regex: (?P<value>.+)
+filename: .*
+table: log
+columns:
+    - name: value
+      type: percent
+      strip_units: true
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+        let cases = [
+            ("85%", 0.85),
+            ("0.85", 0.85),
+            ("12.5 %", 0.125),
+            ("100% cpu", 1.0),
+        ];
+        for (line, expected) in cases {
+            let event = parser.parse_line(line).unwrap().unwrap();
+            assert_eq!(event.values["value"], Type::Double(expected), "{}", line);
+        }
+        assert!(parser.parse_line("high").is_err());
+    }
+
+    #[test]
+    fn parse_lenient_datetimes() {
+        let schema = "\
+regex: (?P<strict>[^|]+)\\|(?P<lenient>.+)
+filename: .*
+table: log
+columns:
+    - name: strict
+      type: datetime
+    - name: lenient
+      type: datetimetz
+      lenient: true
+";
+        let schema = Schema::try_from(schema).unwrap();
+        let parser = Parser::new(schema).unwrap();
+
+        let lines = vec![
+            "2022-06-30T10:00:00Z|2022-06-30 12:00:00,5 +0200\n\
+2022-06-30T10:00:00Z|2016-12-31T23:59:60Z",
+        ];
+        let (events, warnings) = parser.parse_with_warnings(lines).unwrap();
+        let offset = FixedOffset::east(2 * 3600);
+        assert_eq!(
+            events[0].values["lenient"],
+            Type::DateTimeTz(offset.ymd(2022, 6, 30).and_hms_milli(12, 0, 0, 500))
+        );
+        assert_eq!(
+            events[1].values["lenient"],
+            Type::DateTimeTz(FixedOffset::east(0).ymd(2016, 12, 31).and_hms_nano(
+                23,
+                59,
+                59,
+                999_999_999
+            ))
+        );
+        assert_eq!(
+            warnings,
+            vec!["Clamped '2016-12-31T23:59:60Z' in column 'lenient' to '2016-12-31T23:59:59.999999999+00:00'"]
+        );
+
+        // columns without lenient still fail the row
+        assert!(parser
+            .parse_line("2022-06-30 10:00:00,5|2022-06-30T10:00:00Z")
+            .is_err());
+    }
 }
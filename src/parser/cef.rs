@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+const CEF_HEADER: [&str; 7] = [
+    "version",
+    "device_vendor",
+    "device_product",
+    "device_version",
+    "signature_id",
+    "name",
+    "severity",
+];
+const LEEF_HEADER: [&str; 5] = [
+    "version",
+    "device_vendor",
+    "device_product",
+    "device_version",
+    "event_id",
+];
+
+/// Parse a CEF line like `CEF:0|Vendor|Product|1.0|100|Blocked|5|src=10.0.0.1 act=blocked` into
+/// its header fields and extensions. Anything before `CEF:`, like a syslog prefix, is skipped.
+/// Custom extensions are also under their label, e.g. `cs1Label=env cs1=prod` has `env` too,
+/// unless it's already a key. Returns `None` if the line isn't CEF.
+pub fn parse_cef(line: &str) -> Option<HashMap<String, String>> {
+    let start = line.find("CEF:")?;
+    let (header, extension) = split_header(&line[start + 4..], CEF_HEADER.len())?;
+    let mut fields: HashMap<_, _> = CEF_HEADER
+        .iter()
+        .map(|name| name.to_string())
+        .zip(header)
+        .collect();
+    fields.extend(parse_cef_extension(extension));
+
+    let labelled: Vec<_> = fields
+        .iter()
+        .filter_map(|(key, label)| {
+            let value = fields.get(key.strip_suffix("Label")?)?;
+            Some((label.clone(), value.clone()))
+        })
+        .collect();
+    for (label, value) in labelled {
+        if !label.is_empty() {
+            fields.entry(label).or_insert(value);
+        }
+    }
+    Some(fields)
+}
+
+/// Parse a LEEF 1.0 line, whose attributes are separated by tabs, or a LEEF 2.0 line, which has
+/// the attribute delimiter as an extra header field, e.g. `LEEF:2.0|Vendor|Product|1.0|4625|^|`.
+/// Returns `None` if the line isn't LEEF.
+pub fn parse_leef(line: &str) -> Option<HashMap<String, String>> {
+    let start = line.find("LEEF:")?;
+    let line = &line[start + 5..];
+    let version_two = line.starts_with('2');
+    let count = LEEF_HEADER.len() + usize::from(version_two);
+    let (mut header, attributes) = split_header(line, count)?;
+
+    let delimiter = if version_two {
+        parse_delimiter(&header.pop()?)?
+    } else {
+        '\t'
+    };
+    let mut fields: HashMap<_, _> = LEEF_HEADER
+        .iter()
+        .map(|name| name.to_string())
+        .zip(header)
+        .collect();
+    for attribute in attributes.split(delimiter) {
+        if let Some((key, value)) = attribute.split_once('=') {
+            fields.insert(key.trim().to_string(), value.to_string());
+        }
+    }
+    Some(fields)
+}
+
+/// Split `count` pipe separated header fields from the rest of the line. Pipes and backslashes
+/// in the header can be escaped with a backslash.
+fn split_header(line: &str, count: usize) -> Option<(Vec<String>, &str)> {
+    let mut fields = Vec::with_capacity(count);
+    let mut field = String::new();
+    let mut chars = line.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('|' | '\\'))) => field.push(escaped),
+                Some((_, other)) => {
+                    field.push('\\');
+                    field.push(other);
+                }
+                None => field.push('\\'),
+            },
+            '|' => {
+                fields.push(std::mem::take(&mut field));
+                if fields.len() == count {
+                    return Some((fields, &line[index + 1..]));
+                }
+            }
+            c => field.push(c),
+        }
+    }
+
+    None
+}
+
+/// Extensions are `key=value` pairs separated by spaces, but values can contain spaces too, so a
+/// value runs until the next key. Equals signs in values are escaped as `\=`.
+fn parse_cef_extension(extension: &str) -> Vec<(String, String)> {
+    let bytes = extension.as_bytes();
+    let is_key = |b: u8| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-' | b'[' | b']');
+
+    // start of each key and its equals sign
+    let mut keys = Vec::new();
+    for (index, _) in extension.match_indices('=') {
+        if index > 0 && bytes[index - 1] == b'\\' {
+            continue;
+        }
+        let start = (0..index)
+            .rev()
+            .take_while(|i| is_key(bytes[*i]))
+            .last()
+            .unwrap_or(index);
+        if start < index && (start == 0 || bytes[start - 1].is_ascii_whitespace()) {
+            keys.push((start, index));
+        }
+    }
+
+    keys.iter()
+        .enumerate()
+        .map(|(i, (start, equals))| {
+            let end = keys.get(i + 1).map_or(extension.len(), |(next, _)| *next);
+            let value = extension[equals + 1..end].trim_end();
+            (extension[*start..*equals].to_string(), unescape(value))
+        })
+        .collect()
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(escaped) => unescaped.push(escaped),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// A LEEF 2.0 delimiter is a character or its hex code, e.g. `^`, `x5E` or `0x5E`. Defaults to
+/// a tab when empty.
+fn parse_delimiter(delimiter: &str) -> Option<char> {
+    let hex = delimiter
+        .strip_prefix("0x")
+        .or_else(|| delimiter.strip_prefix('x'))
+        .filter(|hex| !hex.is_empty());
+    match hex {
+        Some(hex) => char::from_u32(u32::from_str_radix(hex, 16).ok()?),
+        None => Some(delimiter.chars().next().unwrap_or('\t')),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_cef_lines() {
+        let line = r"Sep 19 08:26:10 host CEF:0|Security|threat\|manager|1.0|100|worm stopped|10|src=10.0.0.1 dst=2.1.2.2 msg=Detected a threat. No action needed\=true filePath=C:\\Temp\\x.exe";
+        assert_eq!(
+            parse_cef(line),
+            Some(fields(&[
+                ("version", "0"),
+                ("device_vendor", "Security"),
+                ("device_product", "threat|manager"),
+                ("device_version", "1.0"),
+                ("signature_id", "100"),
+                ("name", "worm stopped"),
+                ("severity", "10"),
+                ("src", "10.0.0.1"),
+                ("dst", "2.1.2.2"),
+                ("msg", "Detected a threat. No action needed=true"),
+                ("filePath", r"C:\Temp\x.exe"),
+            ]))
+        );
+
+        let fields =
+            parse_cef("CEF:0|V|P|1|sig|name|3|cs1Label=env cs1=prod cn1Label=src cn1=7").unwrap();
+        assert_eq!(fields["cs1"], "prod");
+        assert_eq!(fields["env"], "prod");
+        assert_eq!(fields["src"], "7");
+        assert_eq!(fields.len(), 13);
+
+        let fields = parse_cef("CEF:1|V|P|2|sig|name|Low|").unwrap();
+        assert_eq!(fields["severity"], "Low");
+        assert_eq!(fields.len(), 7);
+
+        assert_eq!(parse_cef("just a line"), None);
+        assert_eq!(parse_cef("CEF:0|missing|fields"), None);
+    }
+
+    #[test]
+    fn parse_leef_lines() {
+        let line = "LEEF:1.0|Microsoft|MSExchange|4.0 SP1|15345|src=10.50.1.1\tdst=2.10.20.20\tusrName=joe smith";
+        assert_eq!(
+            parse_leef(line),
+            Some(fields(&[
+                ("version", "1.0"),
+                ("device_vendor", "Microsoft"),
+                ("device_product", "MSExchange"),
+                ("device_version", "4.0 SP1"),
+                ("event_id", "15345"),
+                ("src", "10.50.1.1"),
+                ("dst", "2.10.20.20"),
+                ("usrName", "joe smith"),
+            ]))
+        );
+
+        for delimiter in ["^", "x5E", "0x5e"] {
+            let line = format!(
+                "LEEF:2.0|Lancope|StealthWatch|1.0|41|{}|src=10.0.1.8^dst=10.0.0.5^sev=5",
+                delimiter
+            );
+            let fields = parse_leef(&line).unwrap();
+            assert_eq!(fields["event_id"], "41", "{}", delimiter);
+            assert_eq!(fields["dst"], "10.0.0.5", "{}", delimiter);
+            assert_eq!(fields["sev"], "5", "{}", delimiter);
+        }
+
+        assert_eq!(parse_leef("CEF:0|a|b|c|d|e|f|"), None);
+    }
+}
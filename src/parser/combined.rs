@@ -0,0 +1,149 @@
+use chrono::prelude::*;
+use std::collections::HashMap;
+
+/// Columns of `combined` schemas that don't list any
+pub const COMBINED_COLUMNS: &str = "
+- {name: remote_addr, type: string}
+- {name: remote_user, type: string}
+- {name: timestamp, type: datetimetz}
+- {name: method, type: string}
+- {name: path, type: string}
+- {name: protocol, type: string}
+- {name: status, type: i32}
+- {name: bytes, type: i64, group: body_bytes_sent}
+- {name: referer, type: string, group: http_referer}
+- {name: user_agent, type: string, group: http_user_agent}
+";
+
+/// Parse an Apache or nginx access log line in the combined log format, like
+/// `127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif HTTP/1.0" 200 2326 "-" "curl"`,
+/// or the common log format, which doesn't have the referer and user agent. The fields are
+/// named like nginx's variables, `remote_addr`, `remote_user`, `time_local`, `request`,
+/// `status`, `body_bytes_sent`, `http_referer` and `http_user_agent`, with the request split into
+/// `method`, `path` and `protocol`, and the time as RFC 3339 in `timestamp`. Dashes are missing
+/// values. Returns `None` if the line isn't in either format.
+pub fn parse_combined(line: &str) -> Option<HashMap<String, String>> {
+    let mut fields = HashMap::new();
+    let mut insert = |name: &str, value: &str| {
+        if value != "-" && !value.is_empty() {
+            fields.insert(name.to_string(), value.to_string());
+        }
+    };
+
+    let (remote_addr, rest) = line.split_once(' ')?;
+    let (ident, rest) = rest.split_once(' ')?;
+    let (remote_user, rest) = rest.split_once(" [")?;
+    let (time_local, rest) = rest.split_once("] ")?;
+    let timestamp = DateTime::parse_from_str(time_local, "%d/%b/%Y:%H:%M:%S %z").ok()?;
+    insert("remote_addr", remote_addr);
+    insert("ident", ident);
+    insert("remote_user", remote_user);
+    insert("time_local", time_local);
+    insert("timestamp", &timestamp.to_rfc3339());
+
+    let (request, rest) = quoted(rest)?;
+    let mut parts = request.split(' ');
+    if let (Some(method), Some(path), protocol, None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    {
+        insert("method", method);
+        insert("path", path);
+        insert("protocol", protocol.unwrap_or_default());
+    }
+    insert("request", &request);
+
+    let rest = rest.strip_prefix(' ')?;
+    let (status, rest) = rest.split_once(' ')?;
+    if status.len() != 3 || !status.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    insert("status", status);
+    let (bytes, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    if bytes != "-" && !bytes.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    insert("body_bytes_sent", bytes);
+
+    // the common log format ends here, and custom formats can log more after the user agent
+    if let Some((referer, rest)) = quoted(rest) {
+        insert("http_referer", &referer);
+        if let Some((user_agent, _)) = rest.strip_prefix(' ').and_then(quoted) {
+            insert("http_user_agent", &user_agent);
+        }
+    }
+    Some(fields)
+}
+
+/// Read a double quoted value at the start of `line`, returning it and the rest of the line.
+/// Apache escapes quotes as `\"` and nginx as `\x22`.
+fn quoted(line: &str) -> Option<(String, &str)> {
+    let line = line.strip_prefix('"')?;
+    let mut value = String::new();
+    let mut chars = line.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((value, &line[index + 1..])),
+            '\\' if line[index + 1..].starts_with("x22") => {
+                value.push('"');
+                chars.nth(2);
+            }
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_combined_lines() {
+        let line = r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /a.gif?q=\"x\" HTTP/1.0" 200 2326 "http://example.com/" "Mozilla/5.0 (X11)""#;
+        let fields = parse_combined(line).unwrap();
+        let expected: HashMap<_, _> = [
+            ("remote_addr", "127.0.0.1"),
+            ("remote_user", "frank"),
+            ("time_local", "10/Oct/2000:13:55:36 -0700"),
+            ("timestamp", "2000-10-10T13:55:36-07:00"),
+            ("request", r#"GET /a.gif?q="x" HTTP/1.0"#),
+            ("method", "GET"),
+            ("path", r#"/a.gif?q="x""#),
+            ("protocol", "HTTP/1.0"),
+            ("status", "200"),
+            ("body_bytes_sent", "2326"),
+            ("http_referer", "http://example.com/"),
+            ("http_user_agent", "Mozilla/5.0 (X11)"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(expected, fields);
+
+        // common log format, with a malformed request and no body
+        let line = r#"::1 - - [01/May/2022:10:00:00 +0000] "\x16\x03\x01" 400 -"#;
+        let fields = parse_combined(line).unwrap();
+        assert_eq!(fields["request"], r"\x16\x03\x01");
+        assert_eq!(fields["status"], "400");
+        assert!(!fields.contains_key("method"));
+        assert!(!fields.contains_key("remote_user"));
+        assert!(!fields.contains_key("body_bytes_sent"));
+
+        for line in [
+            "not an access log",
+            r#"::1 - - [yesterday] "GET / HTTP/1.1" 200 5"#,
+            r#"::1 - - [01/May/2022:10:00:00 +0000] "GET / HTTP/1.1" ok 5"#,
+            r#"::1 - - [01/May/2022:10:00:00 +0000] "GET / HTTP/1.1 200 5"#,
+        ] {
+            assert_eq!(None, parse_combined(line), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Serialize, Serializer};
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+/// A column type provided by an embedder, e.g. UUIDs or validated trace ids. Columns use it by
+/// setting their `type` to [`CustomType::name`].
+pub trait CustomType: Send + Sync + 'static {
+    /// The parsed representation of a value
+    type Value: Send + Sync + 'static;
+
+    /// Name used as the column `type` in schemas
+    fn name(&self) -> &str;
+
+    /// Parse a captured value. Returns a message describing why the value is invalid on failure.
+    fn parse(&self, value: &str) -> Result<Self::Value, String>;
+
+    /// Order two values, used by `ORDER BY` and comparisons in `WHERE`
+    fn compare(&self, a: &Self::Value, b: &Self::Value) -> Option<Ordering>;
+
+    /// Render a value for table output
+    fn display(&self, value: &Self::Value) -> String;
+
+    /// Render a value for JSON output. Defaults to the displayed string.
+    fn serialize(&self, value: &Self::Value) -> serde_json::Value {
+        serde_json::Value::String(self.display(value))
+    }
+}
+
+/// Object safe version of [`CustomType`] so types with different values can share a registry
+trait DynCustomType: Send + Sync {
+    fn name(&self) -> &str;
+    fn parse(&self, value: &str) -> Result<Arc<dyn Any + Send + Sync>, String>;
+    fn compare(&self, a: &dyn Any, b: &dyn Any) -> Option<Ordering>;
+    fn display(&self, value: &dyn Any) -> String;
+    fn serialize(&self, value: &dyn Any) -> serde_json::Value;
+}
+
+impl<T: CustomType> DynCustomType for T {
+    fn name(&self) -> &str {
+        CustomType::name(self)
+    }
+
+    fn parse(&self, value: &str) -> Result<Arc<dyn Any + Send + Sync>, String> {
+        let value = CustomType::parse(self, value)?;
+        Ok(Arc::new(value))
+    }
+
+    fn compare(&self, a: &dyn Any, b: &dyn Any) -> Option<Ordering> {
+        let a = a.downcast_ref::<T::Value>()?;
+        let b = b.downcast_ref::<T::Value>()?;
+        CustomType::compare(self, a, b)
+    }
+
+    fn display(&self, value: &dyn Any) -> String {
+        match value.downcast_ref::<T::Value>() {
+            Some(value) => CustomType::display(self, value),
+            None => String::new(),
+        }
+    }
+
+    fn serialize(&self, value: &dyn Any) -> serde_json::Value {
+        match value.downcast_ref::<T::Value>() {
+            Some(value) => CustomType::serialize(self, value),
+            None => serde_json::Value::Null,
+        }
+    }
+}
+
+/// Custom types available to a parser, keyed by name
+#[derive(Clone, Default)]
+pub struct CustomTypes {
+    types: HashMap<String, Arc<dyn DynCustomType>>,
+}
+
+impl CustomTypes {
+    /// Register a type, replacing any existing type with the same name
+    pub fn register<T: CustomType>(&mut self, custom_type: T) {
+        let name = CustomType::name(&custom_type).to_string();
+        self.types.insert(name, Arc::new(custom_type));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.types.contains_key(name)
+    }
+
+    /// Parse a value with the named type. Returns `None` if the type isn't registered.
+    pub fn parse(&self, name: &str, value: &str) -> Option<Result<CustomValue, String>> {
+        let custom_type = self.types.get(name)?;
+        Some(custom_type.parse(value).map(|value| CustomValue {
+            r#type: custom_type.clone(),
+            value,
+        }))
+    }
+}
+
+impl Debug for CustomTypes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.types.keys()).finish()
+    }
+}
+
+/// A value parsed by a [`CustomType`]
+#[derive(Clone)]
+pub struct CustomValue {
+    r#type: Arc<dyn DynCustomType>,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl CustomValue {
+    /// Name of the type that parsed this value
+    pub fn type_name(&self) -> &str {
+        self.r#type.name()
+    }
+
+    /// Parse another value with the same type, e.g. a literal it is being compared to
+    pub fn parse_same_type(&self, value: &str) -> Result<CustomValue, String> {
+        self.r#type.parse(value).map(|value| CustomValue {
+            r#type: self.r#type.clone(),
+            value,
+        })
+    }
+
+    /// The value as it's rendered in JSON output
+    pub fn to_json(&self) -> serde_json::Value {
+        self.r#type.serialize(&*self.value)
+    }
+
+    /// Compare values of the same type. Values of different types are incomparable.
+    pub fn compare(&self, other: &CustomValue) -> Option<Ordering> {
+        if self.type_name() != other.type_name() {
+            return None;
+        }
+        self.r#type.compare(&*self.value, &*other.value)
+    }
+}
+
+impl PartialEq for CustomValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.compare(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for CustomValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.compare(other)
+    }
+}
+
+impl Display for CustomValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.r#type.display(&*self.value))
+    }
+}
+
+impl Debug for CustomValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.type_name(), self)
+    }
+}
+
+impl Serialize for CustomValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.r#type.serialize(&*self.value).serialize(serializer)
+    }
+}
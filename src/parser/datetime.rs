@@ -0,0 +1,150 @@
+use crate::schema::{Column, ColumnType, Timezone};
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Parses a datetime in one format, with timestamps without an offset in the timezone
+type Format = fn(&str, Option<Timezone>) -> Option<DateTime<FixedOffset>>;
+
+/// Formats datetimes are tried with, in order. A value can only be read by one of them, so the
+/// order only decides how fast a value is read.
+const FORMATS: [Format; 5] = [rfc3339, rfc2822, common_log, naive, epoch];
+
+/// The format each datetime column last read a value with, which its next value is tried with
+/// first since a column's values are usually written the same way
+#[derive(Debug, Default)]
+pub struct DetectedFormats(HashMap<String, AtomicUsize>);
+
+impl DetectedFormats {
+    pub fn new(columns: &[Column]) -> DetectedFormats {
+        let datetimes = columns.iter().filter(|column| {
+            matches!(column.r#type, ColumnType::DateTime | ColumnType::DateTimeTz)
+        });
+        DetectedFormats(
+            datetimes
+                .map(|column| (column.name.clone(), AtomicUsize::new(0)))
+                .collect(),
+        )
+    }
+
+    /// Parse an RFC 3339 datetime like `2022-05-01T10:00:00+02:00`, an RFC 2822 one like
+    /// `Sun, 1 May 2022 10:00:00 +0200`, a common log format one like
+    /// `01/May/2022:10:00:00 +0200`, one without an offset like `2022-05-01 10:00:00` in the
+    /// timezone, or a Unix timestamp in seconds, milliseconds, microseconds or nanoseconds
+    pub fn parse(
+        &self,
+        column: &str,
+        value: &str,
+        timezone: Option<Timezone>,
+    ) -> Option<DateTime<FixedOffset>> {
+        let detected = self.0.get(column);
+        let first = detected.map_or(0, |index| index.load(Ordering::Relaxed));
+        if let Some(datetime) = FORMATS[first](value, timezone) {
+            return Some(datetime);
+        }
+
+        let (index, datetime) = FORMATS
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != first)
+            .find_map(|(index, format)| Some((index, format(value, timezone)?)))?;
+        if let Some(detected) = detected {
+            detected.store(index, Ordering::Relaxed);
+        }
+        Some(datetime)
+    }
+}
+
+fn rfc3339(value: &str, _: Option<Timezone>) -> Option<DateTime<FixedOffset>> {
+    DateTime::from_str(value).ok()
+}
+
+fn rfc2822(value: &str, _: Option<Timezone>) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc2822(value).ok()
+}
+
+fn common_log(value: &str, _: Option<Timezone>) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_str(value, "%d/%b/%Y:%H:%M:%S %z").ok()
+}
+
+fn naive(value: &str, timezone: Option<Timezone>) -> Option<DateTime<FixedOffset>> {
+    let naive = NaiveDateTime::from_str(value)
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()?;
+    timezone?.localize(&naive)
+}
+
+/// Seconds since the epoch, with a fraction or as milliseconds, microseconds or nanoseconds told
+/// apart by their digits. At least 9 digits are needed so numbers like `20220501` aren't read as
+/// timestamps from 1970.
+fn epoch(value: &str, _: Option<Timezone>) -> Option<DateTime<FixedOffset>> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if !(9..=19).contains(&whole.len()) || !digits(whole) || !digits(fraction) {
+        return None;
+    }
+
+    let scale = match whole.len() {
+        9 | 10 => 1,
+        11..=13 => 1_000,
+        14..=16 => 1_000_000,
+        _ => 1_000_000_000,
+    };
+    if scale > 1 && !fraction.is_empty() {
+        return None;
+    }
+    let whole: i64 = whole.parse().ok()?;
+    let nanos = match scale {
+        1 => format!("{:0<9}", fraction).get(..9)?.parse().ok()?,
+        scale => (whole % scale) * (1_000_000_000 / scale),
+    };
+    let datetime = Utc.timestamp_opt(whole / scale, nanos as u32).single()?;
+    Some(datetime.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_datetime_formats() {
+        let utc = FixedOffset::east(0).ymd(2022, 5, 1).and_hms(10, 0, 0);
+        let offset = FixedOffset::east(2 * 3600)
+            .ymd(2022, 5, 1)
+            .and_hms(12, 0, 0);
+        let cases = [
+            ("2022-05-01T12:00:00+02:00", offset),
+            ("2022-05-01 12:00:00+02:00", offset),
+            ("Sun, 1 May 2022 12:00:00 +0200", offset),
+            ("01/May/2022:12:00:00 +0200", offset),
+            ("2022-05-01T10:00:00", utc),
+            ("2022-05-01 10:00:00", utc),
+            ("1651399200", utc),
+            ("1651399200000", utc),
+            ("1651399200000000", utc),
+            ("1651399200000000000", utc),
+        ];
+        let columns = [Column::new("ts", ColumnType::DateTimeTz)];
+        let formats = DetectedFormats::new(&columns);
+        for (value, expected) in cases {
+            let datetime = formats.parse("ts", value, Some(Timezone::Utc));
+            assert_eq!(Some(expected), datetime, "{}", value);
+            assert_eq!(expected.offset(), datetime.unwrap().offset(), "{}", value);
+        }
+
+        let datetime = formats.parse("ts", "1651399200.25", None).unwrap();
+        assert_eq!(datetime.timestamp_millis(), 1_651_399_200_250);
+        // the last format that matched is tried first
+        assert_eq!(formats.0["ts"].load(Ordering::Relaxed), 4);
+
+        for value in [
+            "20220501",
+            "2022-05-01 10:00:00",
+            "1651399200000.5",
+            "May 1",
+        ] {
+            assert_eq!(None, formats.parse("ts", value, None), "{}", value);
+        }
+    }
+}
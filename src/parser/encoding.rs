@@ -0,0 +1,413 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+
+/// Text encodings of log files
+#[derive(clap::ArgEnum, serde::Deserialize, Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    #[clap(name = "utf-8", alias = "utf8")]
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    #[clap(name = "utf-16le")]
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[clap(name = "utf-16be")]
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    /// ISO 8859-1, where each byte is the character with its code
+    #[clap(name = "latin-1", alias = "iso-8859-1")]
+    #[serde(rename = "latin-1", alias = "iso-8859-1")]
+    Latin1,
+    /// Detected bytes that aren't valid UTF-8 and aren't UTF-16 are read line by line, with the
+    /// lines that aren't valid UTF-8 read as Windows code page 1252
+    #[clap(name = "windows-1252", alias = "cp1252")]
+    #[serde(rename = "windows-1252", alias = "cp1252")]
+    Windows1252,
+}
+
+/// Guess the encoding from the byte order mark. Without one, text with a NUL byte in most odd
+/// or even positions is UTF-16 since logs are mostly ASCII.
+pub fn detect_encoding(bytes: &[u8]) -> Encoding {
+    match bytes {
+        [0xef, 0xbb, 0xbf, ..] => return Encoding::Utf8,
+        [0xff, 0xfe, ..] => return Encoding::Utf16Le,
+        [0xfe, 0xff, ..] => return Encoding::Utf16Be,
+        _ => (),
+    }
+
+    let pairs = bytes.len() / 2;
+    if pairs > 0 {
+        let nuls = |offset: usize| {
+            bytes
+                .iter()
+                .skip(offset)
+                .step_by(2)
+                .filter(|b| **b == 0)
+                .count()
+        };
+        let (even, odd) = (nuls(0), nuls(1));
+        if odd * 4 > pairs * 3 && even * 4 < pairs {
+            return Encoding::Utf16Le;
+        }
+        if even * 4 > pairs * 3 && odd * 4 < pairs {
+            return Encoding::Utf16Be;
+        }
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(_) => Encoding::Utf8,
+        Err(_) => Encoding::Windows1252,
+    }
+}
+
+/// Decode a file in its detected encoding, without the byte order mark. Invalid UTF-16 is
+/// replaced with `U+FFFD`, and a corrupted line of a UTF-8 file doesn't change how the rest of
+/// the file is read.
+pub fn decode(bytes: Vec<u8>) -> String {
+    match detect_encoding(&bytes) {
+        Encoding::Windows1252 => bytes
+            .split_inclusive(|b| *b == b'\n')
+            .map(|line| match std::str::from_utf8(line) {
+                Ok(line) => Cow::Borrowed(line),
+                Err(_) => Cow::Owned(line.iter().map(|b| windows_1252(*b)).collect()),
+            })
+            .collect(),
+        encoding => decode_as(bytes, encoding),
+    }
+}
+
+/// Decode a file in an encoding that's known, e.g. from the schema, before its lines are split.
+/// A leading byte order mark is dropped, and invalid UTF-8 or UTF-16 is replaced with `U+FFFD`.
+pub fn decode_as(bytes: Vec<u8>, encoding: Encoding) -> String {
+    match encoding {
+        Encoding::Utf8 => {
+            let text = match String::from_utf8(bytes) {
+                Ok(text) => text,
+                Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            };
+            match text.strip_prefix('\u{feff}') {
+                Some(text) => text.to_string(),
+                None => text,
+            }
+        }
+        Encoding::Utf16Le => decode_utf16(&bytes, u16::from_le_bytes),
+        Encoding::Utf16Be => decode_utf16(&bytes, u16::from_be_bytes),
+        Encoding::Latin1 => bytes.iter().map(|b| *b as char).collect(),
+        Encoding::Windows1252 => bytes.iter().map(|b| windows_1252(*b)).collect(),
+    }
+}
+
+/// Lines of a source decoded as they're read, so only one line is held at a time. Lines end with
+/// `\n`, `\r\n` or a lone `\r` like in [`split_lines`](crate::parser::stream::split_lines), and
+/// keep their line ending.
+pub struct DecodedLines<R> {
+    reader: R,
+    /// The given encoding, or the detected one once the first line is read
+    encoding: Option<Encoding>,
+    /// Whether the encoding is detected, so lines that aren't valid UTF-8 are code page 1252
+    detected: bool,
+    first: bool,
+    /// The UTF-16 code unit read after a lone `\r`, which starts the next line
+    next_unit: Option<u16>,
+}
+
+/// Decode the lines of a source as they're read, in the given encoding or the one detected from
+/// the start of the source, like [`decode_as`] and [`decode`] do for a whole file
+pub fn decode_lines<R: BufRead>(reader: R, encoding: Option<Encoding>) -> DecodedLines<R> {
+    DecodedLines {
+        reader,
+        encoding,
+        detected: encoding.is_none(),
+        first: true,
+        next_unit: None,
+    }
+}
+
+const CR: u16 = b'\r' as u16;
+const LF: u16 = b'\n' as u16;
+
+impl<R: BufRead> DecodedLines<R> {
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None => *self
+                .encoding
+                .insert(detect_encoding(self.reader.fill_buf()?)),
+        };
+        let line = match encoding {
+            Encoding::Utf16Le => self.read_utf16_line(u16::from_le_bytes)?,
+            Encoding::Utf16Be => self.read_utf16_line(u16::from_be_bytes)?,
+            encoding => {
+                let bytes = self.read_byte_line()?;
+                if bytes.is_empty() {
+                    return Ok(None);
+                }
+                match encoding {
+                    // a corrupted line doesn't change how the rest of the file is read
+                    Encoding::Utf8 | Encoding::Windows1252 if self.detected => {
+                        match String::from_utf8(bytes) {
+                            Ok(line) => Some(line),
+                            Err(e) => Some(e.as_bytes().iter().map(|b| windows_1252(*b)).collect()),
+                        }
+                    }
+                    encoding => Some(decode_as(bytes, encoding)),
+                }
+            }
+        };
+        match line {
+            Some(line) if std::mem::take(&mut self.first) => match line.strip_prefix('\u{feff}') {
+                Some(line) => Ok(Some(line.to_string())),
+                None => Ok(Some(line)),
+            },
+            line => Ok(line),
+        }
+    }
+
+    /// Read the bytes of a line from the reader's buffer. `\r` and `\n` aren't part of other
+    /// characters in UTF-8 or the single byte encodings.
+    fn read_byte_line(&mut self) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            if bytes.last() == Some(&b'\r') {
+                // a lone `\r` ends the line
+                if buffer[0] == b'\n' {
+                    bytes.push(b'\n');
+                    self.reader.consume(1);
+                }
+                break;
+            }
+            let end = buffer.iter().position(|b| *b == b'\n' || *b == b'\r');
+            let used = end.map_or(buffer.len(), |end| end + 1);
+            bytes.extend_from_slice(&buffer[..used]);
+            self.reader.consume(used);
+            if bytes.last() == Some(&b'\n') {
+                break;
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Read the UTF-16 code units of a line
+    fn read_utf16_line(&mut self, unit: fn([u8; 2]) -> u16) -> io::Result<Option<String>> {
+        let mut units = Vec::new();
+        loop {
+            let next = match self.next_unit.take() {
+                Some(next) => next,
+                None => match self.read_unit(unit)? {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            if units.last() == Some(&CR) {
+                // a lone `\r` ends the line
+                match next {
+                    LF => units.push(LF),
+                    next => self.next_unit = Some(next),
+                }
+                break;
+            }
+            units.push(next);
+            if next == LF {
+                break;
+            }
+        }
+        if units.is_empty() {
+            return Ok(None);
+        }
+        let line = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+        Ok(Some(line))
+    }
+
+    /// Read a UTF-16 code unit from the reader's buffer, whose bytes can be split across reads
+    fn read_unit(&mut self, unit: fn([u8; 2]) -> u16) -> io::Result<Option<u16>> {
+        let mut pair = [0; 2];
+        let mut read = 0;
+        while read < pair.len() {
+            let buffer = self.reader.fill_buf()?;
+            if buffer.is_empty() {
+                break;
+            }
+            let used = buffer.len().min(pair.len() - read);
+            pair[read..read + used].copy_from_slice(&buffer[..used]);
+            self.reader.consume(used);
+            read += used;
+        }
+        match read {
+            0 => Ok(None),
+            // a dangling byte can't be a character
+            1 => Ok(Some(0xfffd)),
+            _ => Ok(Some(unit(pair))),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for DecodedLines<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_line().transpose()
+    }
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes.chunks(2).map(|pair| match pair {
+        [a, b] => unit([*a, *b]),
+        // a dangling byte can't be a character
+        _ => 0xfffd,
+    });
+    let text: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    match text.strip_prefix('\u{feff}') {
+        Some(text) => text.to_string(),
+        None => text,
+    }
+}
+
+/// Code page 1252 is Latin-1 except for printable characters in `0x80..=0x9f`
+fn windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž',
+        '\u{8f}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9f => HIGH[(byte - 0x80) as usize],
+        byte => byte as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, unit: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        text.encode_utf16().flat_map(unit).collect()
+    }
+
+    #[test]
+    fn detect_encodings() {
+        let text = "2022-05-01 10:00:00 héllo wörld\r\n";
+        let cases = [
+            (text.as_bytes().to_vec(), Encoding::Utf8),
+            ([b"\xef\xbb\xbf", text.as_bytes()].concat(), Encoding::Utf8),
+            (utf16("\u{feff}€", u16::to_le_bytes), Encoding::Utf16Le),
+            (utf16("\u{feff}€", u16::to_be_bytes), Encoding::Utf16Be),
+            (utf16(text, u16::to_le_bytes), Encoding::Utf16Le),
+            (utf16(text, u16::to_be_bytes), Encoding::Utf16Be),
+            (b"caf\xe9 \x80".to_vec(), Encoding::Windows1252),
+            (Vec::new(), Encoding::Utf8),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(expected, detect_encoding(&bytes), "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn decode_encodings() {
+        let text = "2022-05-01 10:00:00 héllo wörld €\r\n";
+        let cases = [
+            text.as_bytes().to_vec(),
+            [b"\xef\xbb\xbf", text.as_bytes()].concat(),
+            utf16(&format!("\u{feff}{}", text), u16::to_le_bytes),
+            utf16(&format!("\u{feff}{}", text), u16::to_be_bytes),
+            utf16(text, u16::to_le_bytes),
+            utf16(text, u16::to_be_bytes),
+        ];
+        for bytes in cases {
+            assert_eq!(text, decode(bytes.clone()), "{:?}", bytes);
+        }
+
+        assert_eq!("café €", decode(b"caf\xe9 \x80".to_vec()));
+        // only the corrupted line is read as code page 1252
+        assert_eq!(
+            "héllo\ncafé €\r\nwörld",
+            decode(b"h\xc3\xa9llo\ncaf\xe9 \x80\r\nw\xc3\xb6rld".to_vec())
+        );
+        // an odd byte at the end of UTF-16
+        let mut truncated = utf16("\u{feff}ab", u16::to_le_bytes);
+        truncated.push(b'c');
+        assert_eq!("ab\u{fffd}", decode(truncated));
+    }
+
+    #[test]
+    fn decode_lines_as_read() {
+        let lines = |bytes: Vec<u8>, encoding| -> String {
+            let lines = decode_lines(bytes.as_slice(), encoding);
+            lines.map(Result::unwrap).collect::<Vec<_>>().join("|")
+        };
+        let text = "héllo\r\nwörld €\nend";
+        let cases = [
+            text.as_bytes().to_vec(),
+            [b"\xef\xbb\xbf", text.as_bytes()].concat(),
+            utf16(&format!("\u{feff}{}", text), u16::to_le_bytes),
+            utf16(text, u16::to_be_bytes),
+        ];
+        for bytes in cases {
+            assert_eq!(
+                "héllo\r\n|wörld €\n|end",
+                lines(bytes.clone(), None),
+                "{:?}",
+                bytes
+            );
+            assert_eq!(
+                decode(bytes.clone()),
+                lines(bytes.clone(), None).replace('|', "")
+            );
+        }
+
+        // only the corrupted line is read as code page 1252
+        let bytes = b"h\xc3\xa9llo\ncaf\xe9 \x80\n".to_vec();
+        assert_eq!("héllo\n|café €\n", lines(bytes, None));
+        let bytes = "café\n".as_bytes().to_vec();
+        assert_eq!("cafÃ©\n", lines(bytes, Some(Encoding::Latin1)));
+        let mut truncated = utf16("\u{feff}a\nb", u16::to_le_bytes);
+        truncated.push(b'c');
+        assert_eq!("a\n|b\u{fffd}", lines(truncated, None));
+        assert_eq!("", lines(Vec::new(), None));
+
+        // lone `\r` line endings end lines too, even across the reader's reads
+        let text = "a\rb\r\nc\r\rd\r";
+        let cases = [
+            (text.as_bytes().to_vec(), Encoding::Utf8),
+            (utf16(text, u16::to_le_bytes), Encoding::Utf16Le),
+        ];
+        for (bytes, encoding) in cases {
+            assert_eq!("a\r|b\r\n|c\r|\r|d\r", lines(bytes.clone(), None));
+            let reader = io::BufReader::with_capacity(1, bytes.as_slice());
+            let lines: Vec<_> = decode_lines(reader, Some(encoding))
+                .map(Result::unwrap)
+                .collect();
+            assert_eq!(lines, ["a\r", "b\r\n", "c\r", "\r", "d\r"]);
+        }
+    }
+
+    #[test]
+    fn decode_known_encodings() {
+        let text = "2022-05-01 10:00:00 héllo\r\n";
+        // UTF-16 without a byte order mark and without enough ASCII to detect it
+        let bytes = utf16("é€ü", u16::to_le_bytes);
+        assert_eq!("é€ü", decode_as(bytes, Encoding::Utf16Le));
+        assert_eq!(
+            text,
+            decode_as(utf16(text, u16::to_be_bytes), Encoding::Utf16Be)
+        );
+
+        // valid UTF-8 can still be Latin-1
+        let bytes = "café".as_bytes().to_vec();
+        assert_eq!("cafÃ©", decode_as(bytes.clone(), Encoding::Latin1));
+        assert_eq!("café", decode_as(bytes, Encoding::Utf8));
+        assert_eq!("\u{80}", decode_as(vec![0x80], Encoding::Latin1));
+        assert_eq!("€", decode_as(vec![0x80], Encoding::Windows1252));
+
+        let encoding: Encoding = serde_yaml::from_str("utf-16le").unwrap();
+        assert_eq!(Encoding::Utf16Le, encoding);
+        let encoding: Encoding = serde_yaml::from_str("iso-8859-1").unwrap();
+        assert_eq!(Encoding::Latin1, encoding);
+    }
+}
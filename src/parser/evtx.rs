@@ -0,0 +1,131 @@
+use crate::error::Error;
+#[cfg(any(feature = "evtx", test))]
+use serde_json::{json, Value};
+
+/// Convert a Windows Event Log (`.evtx`) file into a JSON array of events for the `evtx` format,
+/// with the fields `record_id`, `time`, `provider`, `level`, `event_id`, `channel`, `computer`
+/// and `message`
+#[cfg(feature = "evtx")]
+pub fn evtx_to_json(buffer: Vec<u8>) -> Result<String, Error> {
+    let mut parser =
+        evtx::EvtxParser::from_buffer(buffer).map_err(|e| Error::InvalidEvtx(e.to_string()))?;
+    let mut events = Vec::new();
+    for record in parser.records_json_value() {
+        let record = record.map_err(|e| Error::InvalidEvtx(e.to_string()))?;
+        let timestamp = record.timestamp.to_rfc3339();
+        events.push(record_fields(
+            record.event_record_id,
+            &timestamp,
+            &record.data,
+        ));
+    }
+
+    Ok(Value::Array(events).to_string())
+}
+
+#[cfg(not(feature = "evtx"))]
+pub fn evtx_to_json(_buffer: Vec<u8>) -> Result<String, Error> {
+    Err(Error::InvalidEvtx(
+        "logql was built without the `evtx` feature".to_string(),
+    ))
+}
+
+/// Pick the standard fields out of a record. Windows renders messages from templates in the
+/// provider's DLLs, which aren't part of the file, so `message` is the record's event data as
+/// JSON.
+#[cfg(any(feature = "evtx", test))]
+fn record_fields(record_id: u64, timestamp: &str, record: &Value) -> Value {
+    let system = &record["Event"]["System"];
+    let attribute = |value: &Value, name: &str| value["#attributes"][name].clone();
+    // elements with attributes keep their content in `#text`
+    let text = |value: &Value| match value.get("#text") {
+        Some(text) => text.clone(),
+        None => value.clone(),
+    };
+
+    let time = match attribute(&system["TimeCreated"], "SystemTime") {
+        Value::Null => Value::String(timestamp.to_string()),
+        time => time,
+    };
+    let level = text(&system["Level"]).as_u64().map(|level| match level {
+        1 => "Critical",
+        2 => "Error",
+        3 => "Warning",
+        5 => "Verbose",
+        // 0 is LogAlways, which the event viewer also shows as information
+        _ => "Information",
+    });
+    let event = &record["Event"];
+    let data = match event.get("EventData").or_else(|| event.get("UserData")) {
+        Some(data) => Value::String(data.to_string()),
+        None => Value::Null,
+    };
+
+    json!({
+        "record_id": record_id,
+        "time": time,
+        "provider": attribute(&system["Provider"], "Name"),
+        "level": level,
+        "event_id": text(&system["EventID"]),
+        "channel": text(&system["Channel"]),
+        "computer": text(&system["Computer"]),
+        "message": data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_fields() {
+        let record = json!({
+            "Event": {
+                "#attributes": {"xmlns": "http://schemas.microsoft.com/win/2004/08/events/event"},
+                "System": {
+                    "Provider": {"#attributes": {"Name": "Microsoft-Windows-Security-Auditing"}},
+                    "EventID": 4625,
+                    "Level": 0,
+                    "TimeCreated": {"#attributes": {"SystemTime": "2022-05-01T10:00:00.123Z"}},
+                    "Channel": "Security",
+                    "Computer": "DC01"
+                },
+                "EventData": {"TargetUserName": "alice", "LogonType": 3}
+            }
+        });
+        let mut fields = record_fields(7, "2022-05-01T10:00:00+00:00", &record);
+        let message: Value = serde_json::from_str(fields["message"].as_str().unwrap()).unwrap();
+        assert_eq!(message, json!({"TargetUserName": "alice", "LogonType": 3}));
+        fields["message"] = Value::Null;
+        assert_eq!(
+            fields,
+            json!({
+                "record_id": 7,
+                "time": "2022-05-01T10:00:00.123Z",
+                "provider": "Microsoft-Windows-Security-Auditing",
+                "level": "Information",
+                "event_id": 4625,
+                "channel": "Security",
+                "computer": "DC01",
+                "message": null,
+            })
+        );
+
+        let record = json!({
+            "Event": {
+                "System": {
+                    "Provider": {"#attributes": {"Name": "Service Control Manager"}},
+                    "EventID": {"#attributes": {"Qualifiers": 16384}, "#text": 7036},
+                    "Level": 2,
+                    "Channel": "System",
+                    "Computer": "WS01"
+                }
+            }
+        });
+        let fields = record_fields(8, "2022-05-01T10:00:00+00:00", &record);
+        assert_eq!(fields["time"], "2022-05-01T10:00:00+00:00");
+        assert_eq!(fields["event_id"], 7036);
+        assert_eq!(fields["level"], "Error");
+        assert_eq!(fields["message"], Value::Null);
+    }
+}
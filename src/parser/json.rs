@@ -0,0 +1,333 @@
+use crate::error::Error;
+use crate::parser::custom::CustomType;
+use crate::parser::stream::ParseStats;
+use crate::parser::values::Event;
+use crate::parser::{ParsedFiles, Parser, Warnings};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Column type for JSON text like the body of `INFO request {"user": {"id": 42}}`, parsed once so
+/// `payload.user.id` and `JSON_EXTRACT` don't parse it again, and output as nested JSON
+pub struct JsonType;
+
+impl CustomType for JsonType {
+    type Value = Value;
+
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn parse(&self, value: &str) -> Result<Self::Value, String> {
+        serde_json::from_str(value).map_err(|e| format!("'{}' is not valid JSON: {}", value, e))
+    }
+
+    /// Numbers, strings and booleans are ordered among their kind, other values are only equal
+    fn compare(&self, a: &Self::Value, b: &Self::Value) -> Option<Ordering> {
+        match (a, b) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (a, b) => (a == b).then_some(Ordering::Equal),
+        }
+    }
+
+    fn display(&self, value: &Self::Value) -> String {
+        value.to_string()
+    }
+
+    fn serialize(&self, value: &Self::Value) -> Value {
+        value.clone()
+    }
+}
+
+impl Parser {
+    /// Parse sources that are each a JSON array of event objects, with the file name of each
+    pub(super) fn parse_json_arrays<T: AsRef<str>>(
+        &self,
+        chunks: Vec<T>,
+        names: &[String],
+    ) -> Result<ParsedFiles, Error> {
+        let mut parsed = Vec::new();
+        let mut warnings = Warnings::default();
+        let mut stats = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let file = names.get(index).map(String::as_str);
+            let mut file_stats = ParseStats {
+                file: file.map(str::to_string),
+                ..ParseStats::default()
+            };
+            let chunk = chunk.as_ref();
+            if chunk.trim().is_empty() {
+                stats.push(file_stats);
+                continue;
+            }
+
+            let objects: Vec<Value> =
+                serde_json::from_str(chunk).map_err(|e| Error::InvalidJson(e.to_string()))?;
+            let failures = warnings.failures;
+            // the `_line` of an object is its position in the array
+            for (position, object) in objects.iter().enumerate() {
+                let mut event = match self.parse_json_object(object, &mut warnings) {
+                    Err(e) if self.skips(&e) => {
+                        warnings.skipped += 1;
+                        warnings.failures += 1;
+                        continue;
+                    }
+                    event => event?,
+                };
+                self.add_meta_columns(&mut event, file, position + 1);
+                parsed.push(event);
+                file_stats.events += 1;
+            }
+            file_stats.lines = objects.len();
+            file_stats.failures = warnings.failures - failures;
+            stats.push(file_stats);
+        }
+
+        Ok((parsed, warnings.take(), stats))
+    }
+
+    /// Parse a JSON Lines line. Returns `None` if it isn't a JSON object.
+    pub(super) fn parse_json_line(
+        &self,
+        line: &str,
+        warnings: &mut Warnings,
+    ) -> Result<Option<Event>, Error> {
+        match serde_json::from_str(line) {
+            Ok(object @ Value::Object(_)) => self.parse_json_object(&object, warnings).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Values are parsed like captured text, so numbers can go into string columns and strings
+    /// like `"1,234 ms"` into numeric columns. Nested objects and arrays are kept as JSON text.
+    fn parse_json_object(&self, object: &Value, warnings: &mut Warnings) -> Result<Event, Error> {
+        let object = object
+            .as_object()
+            .ok_or_else(|| Error::InvalidJson(format!("expected an object, found {}", object)))?;
+
+        let flattened = match self.schema.flatten {
+            Some(depth) => flatten(object, depth),
+            None => HashMap::new(),
+        };
+        let field = |name: &str| match object.get(name).or_else(|| flattened.get(name).copied()) {
+            None | Some(Value::Null) => None,
+            Some(Value::String(value)) => Some(Cow::Borrowed(value.as_str())),
+            Some(value) => Some(Cow::Owned(value.to_string())),
+        };
+        self.parse_fields(field, warnings)
+    }
+}
+
+/// Nested values by their dotted path, e.g. `{"user": {"geo": {"city": "Oslo"}}}` has
+/// `user.geo` and `user.geo.city` with a depth of 2
+fn flatten(object: &Map<String, Value>, depth: usize) -> HashMap<String, &Value> {
+    let mut flattened = HashMap::new();
+    let mut parents: Vec<_> = object
+        .iter()
+        .map(|(key, value)| (key.clone(), value, 0))
+        .collect();
+    while let Some((path, value, level)) = parents.pop() {
+        if level == depth {
+            continue;
+        }
+        if let Value::Object(children) = value {
+            for (key, child) in children {
+                let child_path = format!("{}.{}", path, key);
+                parents.push((child_path.clone(), child, level + 1));
+                flattened.insert(child_path, child);
+            }
+        }
+    }
+    flattened
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::parser::values::Type;
+    use crate::Parser;
+    use chrono::prelude::*;
+
+    const SCHEMA: &str = "\
+format: json_array
+filename: .*
+table: logs
+columns:
+    - name: ts
+      type: datetime
+    - name: status
+      type: i32
+    - name: user
+      type: string
+";
+
+    #[test]
+    fn parse_json_arrays() {
+        let parser = Parser::try_from(SCHEMA).unwrap();
+        let sources = [
+            r#"[
+                {"ts": "2022-01-01T00:00:00Z", "status": 200, "user": "alice", "extra": 1},
+                {"ts": "2022-01-01T00:01:00Z", "status": "500", "user": null}
+            ]"#,
+            "",
+            r#"[{"ts": "2022-01-01T00:02:00Z", "status": 404, "user": {"id": 7}}]"#,
+        ];
+        let events = parser.parse(sources.to_vec()).unwrap();
+
+        let rows: Vec<_> = events
+            .iter()
+            .map(|e| {
+                (
+                    e.values["ts"].clone(),
+                    e.values["status"].clone(),
+                    e.values["user"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    Utc.ymd(2022, 1, 1).and_hms(0, 0, 0).into(),
+                    Type::Int32(200),
+                    "alice".into()
+                ),
+                (
+                    Utc.ymd(2022, 1, 1).and_hms(0, 1, 0).into(),
+                    Type::Int32(500),
+                    Type::Null
+                ),
+                (
+                    Utc.ymd(2022, 1, 1).and_hms(0, 2, 0).into(),
+                    Type::Int32(404),
+                    r#"{"id":7}"#.into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_json_arrays_with_aliases() {
+        let schema = SCHEMA.replace(
+            "    - name: user\n",
+            "    - name: user\n      aliases: [username, login]\n",
+        );
+        let parser = Parser::try_from(schema.as_str()).unwrap();
+        let source = r#"[
+            {"user": "alice", "username": "old"},
+            {"username": "bob"},
+            {"login": "carol"},
+            {"status": 200}
+        ]"#;
+        let users: Vec<_> = parser
+            .parse(vec![source])
+            .unwrap()
+            .into_iter()
+            .map(|e| e.values["user"].clone())
+            .collect();
+        assert_eq!(
+            users,
+            vec!["alice".into(), "bob".into(), "carol".into(), Type::Null]
+        );
+    }
+
+    #[test]
+    fn parse_flattened_json_arrays() {
+        let schema = "\
+format: json_array
+filename: .*
+table: logs
+flatten: 2
+columns:
+    - name: user.id
+      type: i32
+    - name: user.geo
+      type: string
+    - name: user.geo.city
+      type: string
+    - name: user.geo.point.lat
+      type: f64
+    - name: a.b
+      type: string
+";
+        let parser = Parser::try_from(schema).unwrap();
+        let source = r#"[
+            {"user": {"id": 7, "geo": {"city": "Oslo", "point": {"lat": 59.9}}}, "a.b": "top", "a": {"b": "nested"}},
+            {"user": "not an object"}
+        ]"#;
+        let events = parser.parse(vec![source]).unwrap();
+        let values = &events[0].values;
+        assert_eq!(values["user.id"], Type::Int32(7));
+        assert_eq!(values["user.geo.city"], "Oslo".into());
+        // deeper than the flatten depth
+        assert_eq!(values["user.geo.point.lat"], Type::Null);
+        let geo: serde_json::Value = match &values["user.geo"] {
+            Type::String(geo) => serde_json::from_str(geo).unwrap(),
+            x => panic!("{:?}", x),
+        };
+        assert_eq!(geo["point"]["lat"], 59.9);
+        // a key with a dot wins over a nested key
+        assert_eq!(values["a.b"], "top".into());
+        assert!(events[1].values.values().all(|v| *v == Type::Null));
+
+        let parser = Parser::try_from(schema.replace("flatten: 2\n", "").as_str()).unwrap();
+        let events = parser.parse(vec![source]).unwrap();
+        assert_eq!(events[0].values["user.id"], Type::Null);
+    }
+
+    #[test]
+    fn parse_json_lines() {
+        let parser = Parser::try_from(SCHEMA.replace("json_array", "json").as_str()).unwrap();
+        let source = r#"{"ts": "2022-01-01T00:00:00Z", "status": 200, "user": "alice"}
+not json
+{"status": "404", "extra": [1]}
+[{"status": 500}]
+"#;
+        let events = parser.parse(vec![source]).unwrap();
+        let rows: Vec<_> = events
+            .iter()
+            .map(|e| (e.values["status"].clone(), e.values["user"].clone()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Type::Int32(200), "alice".into()),
+                (Type::Int32(404), Type::Null),
+            ]
+        );
+        assert_eq!(
+            events[0].values["ts"],
+            Utc.ymd(2022, 1, 1).and_hms(0, 0, 0).into()
+        );
+
+        match parser.parse(vec![r#"{"status": "ok"}"#]) {
+            Err(Error::InvalidColumnValue(column, _, value)) => {
+                assert_eq!(column, "status");
+                assert_eq!(value, "ok");
+            }
+            x => panic!("Error should be Error::InvalidColumnValue. Actual {:?}", x),
+        }
+    }
+
+    #[test]
+    fn parse_invalid_json_arrays() {
+        let parser = Parser::try_from(SCHEMA).unwrap();
+        for source in [r#"{"status": 200}"#, "[1, 2]", "[{"] {
+            match parser.parse(vec![source]) {
+                Err(Error::InvalidJson(_)) => (),
+                x => panic!("Error should be Error::InvalidJson. Actual {:?}", x),
+            }
+        }
+
+        match parser.parse(vec![r#"[{"status": "ok"}]"#]) {
+            Err(Error::InvalidColumnValue(column, _, value)) => {
+                assert_eq!(column, "status");
+                assert_eq!(value, "ok");
+            }
+            x => panic!("Error should be Error::InvalidColumnValue. Actual {:?}", x),
+        }
+    }
+}
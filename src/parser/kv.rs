@@ -0,0 +1,139 @@
+use crate::error::Error;
+use crate::parser::values::{Event, Type};
+use crate::parser::{Parser, Warnings};
+use crate::schema::{FILE_COLUMN, LINE_COLUMN};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Parse the `key=value` pairs of a line like `ts=2022-05-01T10:00:00Z level=info msg="user
+/// logged in" user=alice`, with words that aren't pairs skipped. Values can be double quoted,
+/// with `\"` and `\\` escapes. Returns `None` if the line has no pairs.
+pub fn parse_key_values(line: &str) -> Option<HashMap<String, String>> {
+    let mut pairs = HashMap::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let key_end = match rest[..word_end].find('=') {
+            Some(end) if is_key(&rest[..end]) => end,
+            _ => {
+                rest = rest[word_end..].trim_start();
+                continue;
+            }
+        };
+        let key = &rest[..key_end];
+        let (value, end) = match rest[key_end + 1..].strip_prefix('"') {
+            Some(quoted) => match unquote(quoted) {
+                // past the key, `="`, the value and its closing quote
+                (value, Some(close)) => (value, key_end + 3 + close),
+                (value, None) => (value, rest.len()),
+            },
+            None => (rest[key_end + 1..word_end].to_string(), word_end),
+        };
+        pairs.entry(key.to_string()).or_insert(value);
+        rest = rest[end..].trim_start();
+    }
+
+    (!pairs.is_empty()).then_some(pairs)
+}
+
+fn is_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+/// Read a quoted value up to its closing quote, or the end of the line without one. Returns the
+/// value and the position of the closing quote.
+fn unquote(quoted: &str) -> (String, Option<usize>) {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return (value, Some(index)),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                Some((_, other)) => {
+                    value.push('\\');
+                    value.push(other);
+                }
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    (value, None)
+}
+
+impl Parser {
+    /// Parse a `key=value` line. The schema's columns are read from their keys with their types,
+    /// and every other key becomes a string column of its own.
+    pub(super) fn parse_key_value_line(
+        &self,
+        line: &str,
+        warnings: &mut Warnings,
+    ) -> Result<Option<Event>, Error> {
+        match parse_key_values(line) {
+            Some(pairs) => self.parse_pairs(pairs, warnings).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Parse the schema's columns from their keys, with every other key as a string column
+    pub(super) fn parse_pairs(
+        &self,
+        mut pairs: HashMap<String, String>,
+        warnings: &mut Warnings,
+    ) -> Result<Event, Error> {
+        let mut event = self.parse_fields(
+            |name| pairs.get(name).map(|v| Cow::Borrowed(v.as_str())),
+            warnings,
+        )?;
+        for column in &self.schema.columns {
+            for field in column.fields() {
+                pairs.remove(field);
+            }
+        }
+        for (key, value) in pairs {
+            let reserved = key == FILE_COLUMN || key == LINE_COLUMN;
+            if !reserved && !event.values.contains_key(&key) {
+                event.values.insert(key, Type::String(value));
+            }
+        }
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_values() {
+        let line = r#"ts=2022-05-01T10:00:00Z level=info msg="user \"bob\" logged in" GET /a user.id=7 empty= x=1 x=2 =3"#;
+        let pairs = parse_key_values(line).unwrap();
+        let mut pairs: Vec<_> = pairs
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        pairs.sort();
+        assert_eq!(
+            pairs,
+            [
+                ("empty", ""),
+                ("level", "info"),
+                ("msg", r#"user "bob" logged in"#),
+                ("ts", "2022-05-01T10:00:00Z"),
+                ("user.id", "7"),
+                ("x", "1"),
+            ]
+        );
+
+        assert_eq!(
+            parse_key_values(r#"a="unterminated b=1"#).unwrap()["a"],
+            "unterminated b=1"
+        );
+        assert_eq!(parse_key_values("no pairs here"), None);
+        assert_eq!(parse_key_values(""), None);
+    }
+}
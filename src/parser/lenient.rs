@@ -0,0 +1,206 @@
+use crate::schema::Timezone;
+use chrono::prelude::*;
+use std::str::FromStr;
+
+/// Parse a timestamp that devices emit but RFC 3339 rejects. Accepts a `T`, `t` or space between
+/// the date and time, a comma before the fraction, offsets without a colon and no offset at all
+/// (in `timezone`). Fields one past their maximum are clamped, e.g. the leap second `23:59:60`
+/// becomes `23:59:59.999999999` and `2022-02-29` becomes `2022-02-28`. `24:00` is midnight of
+/// the next day.
+///
+/// Returns the timestamp and whether any field was clamped.
+pub fn parse_lenient_datetime(
+    value: &str,
+    timezone: Timezone,
+) -> Option<(DateTime<FixedOffset>, bool)> {
+    let value = value.trim();
+    let separator = value.find(['T', 't', ' '])?;
+    let (date, time) = (&value[..separator], value[separator + 1..].trim_start());
+
+    let mut date = date.split('-');
+    let year = i32::from_str(date.next()?).ok()?;
+    let month = parse_digits(date.next()?)?;
+    let day = parse_digits(date.next()?)?;
+    if date.next().is_some() {
+        return None;
+    }
+
+    let (time, offset) = split_offset(time)?;
+    let mut time = time.trim_end().split(':');
+    let hour = parse_digits(time.next()?)?;
+    let minute = parse_digits(time.next()?)?;
+    let (second, nanos) = match time.next() {
+        Some(second) => parse_seconds(second)?,
+        None => (0, 0),
+    };
+    if time.next().is_some() {
+        return None;
+    }
+
+    // only tolerate fields that are one past their maximum, anything further is garbage
+    let last_day = last_day_of_month(year, month)?;
+    if day == 0 || day > last_day + 1 || hour > 24 || minute > 60 || second > 60 {
+        return None;
+    }
+
+    let date = NaiveDate::from_ymd_opt(year, month, day.min(last_day))?;
+    // 24:00 is midnight at the end of the day, later times are clamped to the end of the day
+    let end_of_day = hour == 24 && minute == 0 && second == 0 && nanos == 0;
+    let clamped = day > last_day || hour > 23 && !end_of_day || minute > 59 || second > 59;
+    let datetime = match (hour, minute, second) {
+        _ if end_of_day => date.succ_opt()?.and_hms_opt(0, 0, 0)?,
+        (24, _, _) => date.and_hms_nano_opt(23, 59, 59, 999_999_999)?,
+        (_, 60, _) => date.and_hms_nano_opt(hour, 59, 59, 999_999_999)?,
+        (_, _, 60) => date.and_hms_nano_opt(hour, minute, 59, 999_999_999)?,
+        _ => date.and_hms_nano_opt(hour, minute, second, nanos)?,
+    };
+    let datetime = match offset {
+        Some(offset) => offset.from_local_datetime(&datetime).single()?,
+        None => timezone.localize(&datetime)?,
+    };
+    Some((datetime, clamped))
+}
+
+/// Split a trailing `Z`, `+02:00`, `+0200` or `+02` from the time, or `None` without an offset
+fn split_offset(time: &str) -> Option<(&str, Option<FixedOffset>)> {
+    if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        return Some((time, Some(FixedOffset::east(0))));
+    }
+
+    let index = match time.rfind(['+', '-']) {
+        Some(index) => index,
+        None => return Some((time, None)),
+    };
+    let (time, offset) = time.split_at(index);
+    Some((time, Some(parse_offset(offset)?)))
+}
+
+/// Parse an offset like `+02:00`, `+0200` or `-02`
+pub(crate) fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let digits: String = offset[1..].chars().filter(|c| *c != ':').collect();
+    let (hours, minutes) = match digits.len() {
+        2 => (parse_digits(&digits)?, 0),
+        4 => (parse_digits(&digits[..2])?, parse_digits(&digits[2..])?),
+        _ => return None,
+    };
+    let seconds = (hours * 3600 + minutes * 60) as i32;
+    FixedOffset::east_opt(sign * seconds)
+}
+
+/// Parse `SS`, `SS.fff` or `SS,fff` into seconds and nanoseconds. Digits past nanoseconds are
+/// dropped.
+fn parse_seconds(value: &str) -> Option<(u32, u32)> {
+    let (second, fraction) = match value.find(['.', ',']) {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => (value, ""),
+    };
+    let second = parse_digits(second)?;
+    if fraction.is_empty() {
+        return Some((second, 0));
+    }
+
+    let fraction: String = fraction.chars().take(9).collect();
+    let nanos = parse_digits(&fraction)? * 10u32.pow(9 - fraction.len() as u32);
+    Some((second, nanos))
+}
+
+/// `u32::from_str` accepts a leading `+`, which isn't valid in a timestamp
+fn parse_digits(value: &str) -> Option<u32> {
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    u32::from_str(value).ok()
+}
+
+fn last_day_of_month(year: i32, month: u32) -> Option<u32> {
+    let next_month = match month {
+        12 => NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+        _ => NaiveDate::from_ymd_opt(year, month + 1, 1)?,
+    };
+    Some(next_month.pred().day())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Option<(String, bool)> {
+        parse_lenient_datetime(value, Timezone::Utc).map(|(datetime, clamped)| {
+            (
+                datetime
+                    .with_timezone(&Utc)
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                clamped,
+            )
+        })
+    }
+
+    #[test]
+    fn parse_malformed_formats() {
+        let expected = Some(("2022-06-30T10:15:30.500Z".to_string(), false));
+        let values = [
+            "2022-06-30T10:15:30.5Z",
+            "2022-06-30t10:15:30.5z",
+            "2022-06-30 10:15:30,5",
+            "2022-06-30T12:15:30.5+0200",
+            "2022-06-30T12:15:30.5 +02",
+            "2022-06-30T07:15:30.5-03:00",
+        ];
+        for value in values {
+            assert_eq!(expected, parse(value), "{}", value);
+        }
+
+        assert_eq!(
+            Some(("2022-06-30T10:15:00Z".to_string(), false)),
+            parse("2022-06-30 10:15")
+        );
+        assert_eq!(
+            Some(("2022-07-01T00:00:00Z".to_string(), false)),
+            parse("2022-06-30T24:00:00Z")
+        );
+    }
+
+    #[test]
+    fn parse_clamped_fields() {
+        let cases = [
+            ("2016-12-31T23:59:60Z", "2016-12-31T23:59:59.999999999Z"),
+            ("2016-12-31T23:59:60.5Z", "2016-12-31T23:59:59.999999999Z"),
+            ("2022-06-30T24:30:00Z", "2022-06-30T23:59:59.999999999Z"),
+            ("2022-06-30T10:60:00Z", "2022-06-30T10:59:59.999999999Z"),
+            ("2022-02-29T10:00:00Z", "2022-02-28T10:00:00Z"),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(
+                Some((expected.to_string(), true)),
+                parse(value),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let values = [
+            "",
+            "not a timestamp",
+            "2022-06-30",
+            "2022-13-01T10:00:00Z",
+            "2022-06-00T10:00:00Z",
+            "2022-06-30T25:00:00Z",
+            "2022-06-30T10:00:61Z",
+            "2022-06-30T10:61:00Z",
+            "2022-02-30T10:00:00Z",
+            "2022-06-30T10:00:+1Z",
+            "2022-06-30T10:00:00+2Z",
+        ];
+        for value in values {
+            assert_eq!(None, parse(value), "{}", value);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::schema::Timezone;
+use chrono::prelude::*;
+
+/// Rewrite a number written with a decimal comma, like `1.234,5` or `1 234,5`, as `1234.5`.
+/// Without a comma, dots between groups of three digits are thousands separators, so `1.234`
+/// is `1234` but `1.5` is still one and a half.
+pub fn normalize_european_number(value: &str) -> String {
+    let is_separator = |c: char| matches!(c, ' ' | '\u{a0}' | '\u{202f}' | '\'');
+    if value.contains(',') {
+        return value
+            .chars()
+            .filter(|c| *c != '.' && !is_separator(*c))
+            .map(|c| if c == ',' { '.' } else { c })
+            .collect();
+    }
+
+    let digits = value.trim_start_matches(['-', '+']);
+    let mut groups = digits.split('.');
+    let grouped = groups
+        .next()
+        .is_some_and(|first| (1..=3).contains(&first.len()))
+        && digits.contains('.')
+        && groups.all(|group| group.len() == 3)
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.');
+    match grouped {
+        true => value.replace('.', ""),
+        false => value.chars().filter(|c| !is_separator(*c)).collect(),
+    }
+}
+
+/// Parse a day-first datetime like `31.12.2022 23:59:59`, `31/12/2022 23:59:59.123 +01:00` or
+/// `31-12-2022`. Without an offset it's in `timezone`.
+pub fn parse_day_first_datetime(value: &str, timezone: Timezone) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    for separator in ['.', '/', '-'] {
+        let date = format!("%d{0}%m{0}%Y", separator);
+        for time in [" %H:%M:%S%.f", " %H:%M", ""] {
+            let format = format!("{}{}", date, time);
+            if let Ok(datetime) = DateTime::parse_from_str(value, &format!("{} %:z", format))
+                .or_else(|_| DateTime::parse_from_str(value, &format!("{} %z", format)))
+            {
+                return Some(datetime);
+            }
+            let naive = match time {
+                "" => NaiveDate::parse_from_str(value, &format).map(|date| date.and_hms(0, 0, 0)),
+                _ => NaiveDateTime::parse_from_str(value, &format),
+            };
+            if let Ok(naive) = naive {
+                return timezone.localize(&naive);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_european_numbers() {
+        for (value, expected) in [
+            ("1.234,5", "1234.5"),
+            ("1 234,5", "1234.5"),
+            ("1\u{a0}234\u{a0}567", "1234567"),
+            ("-0,25", "-0.25"),
+            ("1.234", "1234"),
+            ("12.345.678", "12345678"),
+            ("1.5", "1.5"),
+            ("1234.5", "1234.5"),
+            ("42", "42"),
+        ] {
+            assert_eq!(expected, normalize_european_number(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parse_day_first_datetimes() {
+        let utc = |s: &str| Some(DateTime::parse_from_rfc3339(s).unwrap());
+        for (value, expected) in [
+            ("31.12.2022 23:59:59", utc("2022-12-31T23:59:59Z")),
+            ("31/12/2022 23:59:59.250", utc("2022-12-31T23:59:59.250Z")),
+            ("01-02-2022 08:30", utc("2022-02-01T08:30:00Z")),
+            ("01.02.2022", utc("2022-02-01T00:00:00Z")),
+            (
+                "31.12.2022 23:59:59 +01:00",
+                utc("2022-12-31T23:59:59+01:00"),
+            ),
+            (
+                "31.12.2022 23:59:59 +0100",
+                utc("2022-12-31T23:59:59+01:00"),
+            ),
+            ("12.31.2022 23:59:59", None),
+            ("2022-12-31 23:59:59", None),
+        ] {
+            assert_eq!(
+                expected,
+                parse_day_first_datetime(value, Timezone::Utc),
+                "{}",
+                value
+            );
+        }
+    }
+}
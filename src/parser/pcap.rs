@@ -0,0 +1,265 @@
+use crate::error::Error;
+use chrono::prelude::*;
+use serde_json::{json, Value};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const ETHERNET: u32 = 1;
+const RAW_IP: u32 = 101;
+const LOOPBACK: u32 = 0;
+
+/// Convert a packet capture (`.pcap`, not `.pcapng`) into a JSON array of packet summaries for
+/// the `pcap` format, with the fields `ts`, `src`, `dst`, `proto`, `src_port`, `dst_port` and
+/// `len`. Fields a packet doesn't have, like ports of ICMP packets, are null.
+pub fn pcap_to_json(buffer: &[u8]) -> Result<String, Error> {
+    let invalid = |message: &str| Error::InvalidPcap(message.to_string());
+    let magic = buffer.get(..4).ok_or_else(|| invalid("missing header"))?;
+    let (little_endian, nanos) = match magic {
+        [0xd4, 0xc3, 0xb2, 0xa1] => (true, false),
+        [0xa1, 0xb2, 0xc3, 0xd4] => (false, false),
+        [0x4d, 0x3c, 0xb2, 0xa1] => (true, true),
+        [0xa1, 0xb2, 0x3c, 0x4d] => (false, true),
+        [0x0a, 0x0d, 0x0d, 0x0a] => {
+            return Err(invalid(
+                "pcapng isn't supported, convert it with `editcap -F pcap`",
+            ))
+        }
+        _ => return Err(invalid("not a pcap file")),
+    };
+    let read_u32 = |offset: usize| -> Result<u32, Error> {
+        let bytes: [u8; 4] = buffer
+            .get(offset..offset + 4)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| invalid("truncated packet header"))?;
+        if little_endian {
+            Ok(u32::from_le_bytes(bytes))
+        } else {
+            Ok(u32::from_be_bytes(bytes))
+        }
+    };
+
+    let link_type = read_u32(20)?;
+    let mut packets = Vec::new();
+    let mut offset = 24;
+    while offset < buffer.len() {
+        let seconds = read_u32(offset)?;
+        let fraction = read_u32(offset + 4)?;
+        let captured = read_u32(offset + 8)? as usize;
+        let length = read_u32(offset + 12)?;
+        let data = buffer
+            .get(offset + 16..offset + 16 + captured)
+            .ok_or_else(|| invalid("truncated packet"))?;
+        offset += 16 + captured;
+
+        let nanos = match nanos {
+            true => Some(fraction),
+            false => fraction.checked_mul(1000),
+        };
+        let nanos = nanos.ok_or_else(|| invalid("invalid timestamp"))?;
+        let ts = Utc
+            .timestamp_opt(seconds as i64, nanos)
+            .single()
+            .ok_or_else(|| invalid("invalid timestamp"))?;
+        let mut packet = summarize(link_type, data);
+        packet["ts"] = ts.to_rfc3339_opts(SecondsFormat::AutoSi, true).into();
+        packet["len"] = length.into();
+        packets.push(packet);
+    }
+
+    Ok(Value::Array(packets).to_string())
+}
+
+/// Addresses, protocol and ports of a captured frame. Frames that are cut short or use
+/// unknown protocols keep whatever was decoded so far.
+fn summarize(link_type: u32, data: &[u8]) -> Value {
+    let mut packet = json!({
+        "src": null,
+        "dst": null,
+        "proto": null,
+        "src_port": null,
+        "dst_port": null,
+    });
+
+    let (ether_type, payload) = match link_type {
+        ETHERNET => match ethernet(data) {
+            Some(frame) => frame,
+            None => return packet,
+        },
+        RAW_IP => match data.first().map(|b| b >> 4) {
+            Some(4) => (0x0800, data),
+            Some(6) => (0x86dd, data),
+            _ => return packet,
+        },
+        // the address family is in the capturing host's byte order, but the IP version is not
+        LOOPBACK => match data.get(4).map(|b| b >> 4) {
+            Some(4) => (0x0800, &data[4..]),
+            Some(6) => (0x86dd, &data[4..]),
+            _ => return packet,
+        },
+        _ => return packet,
+    };
+
+    let (protocol, transport) = match ether_type {
+        0x0800 if payload.len() >= 20 => {
+            let header = ((payload[0] & 0x0f) as usize) * 4;
+            packet["src"] = ipv4(&payload[12..16]).into();
+            packet["dst"] = ipv4(&payload[16..20]).into();
+            (payload[9], payload.get(header..).unwrap_or_default())
+        }
+        0x86dd if payload.len() >= 40 => {
+            packet["src"] = ipv6(&payload[8..24]).into();
+            packet["dst"] = ipv6(&payload[24..40]).into();
+            (payload[6], &payload[40..])
+        }
+        0x0806 => {
+            packet["proto"] = "ARP".into();
+            // sender and target protocol addresses of IPv4 over ethernet
+            if payload.len() >= 28 {
+                packet["src"] = ipv4(&payload[14..18]).into();
+                packet["dst"] = ipv4(&payload[24..28]).into();
+            }
+            return packet;
+        }
+        _ => return packet,
+    };
+
+    packet["proto"] = match protocol {
+        1 => "ICMP".into(),
+        6 => "TCP".into(),
+        17 => "UDP".into(),
+        58 => "ICMPv6".into(),
+        protocol => protocol.to_string().into(),
+    };
+    if matches!(protocol, 6 | 17) && transport.len() >= 4 {
+        packet["src_port"] = u16::from_be_bytes([transport[0], transport[1]]).into();
+        packet["dst_port"] = u16::from_be_bytes([transport[2], transport[3]]).into();
+    }
+
+    packet
+}
+
+/// EtherType and payload, skipping 802.1Q VLAN tags
+fn ethernet(frame: &[u8]) -> Option<(u16, &[u8])> {
+    let mut offset = 12;
+    loop {
+        let ether_type = u16::from_be_bytes([*frame.get(offset)?, *frame.get(offset + 1)?]);
+        match ether_type {
+            0x8100 | 0x88a8 => offset += 4,
+            _ => return Some((ether_type, frame.get(offset + 2..)?)),
+        }
+    }
+}
+
+fn ipv4(bytes: &[u8]) -> String {
+    let octets: [u8; 4] = bytes.try_into().unwrap();
+    IpAddr::V4(Ipv4Addr::from(octets)).to_string()
+}
+
+fn ipv6(bytes: &[u8]) -> String {
+    let octets: [u8; 16] = bytes.try_into().unwrap();
+    IpAddr::V6(Ipv6Addr::from(octets)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(link_type: u32, packets: &[(u32, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut buffer = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        buffer.extend_from_slice(&[0; 8]);
+        buffer.extend_from_slice(&65535u32.to_le_bytes());
+        buffer.extend_from_slice(&link_type.to_le_bytes());
+        for (seconds, micros, data) in packets {
+            buffer.extend_from_slice(&seconds.to_le_bytes());
+            buffer.extend_from_slice(&micros.to_le_bytes());
+            buffer.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            buffer.extend_from_slice(&(data.len() as u32 + 100).to_le_bytes());
+            buffer.extend_from_slice(data);
+        }
+        buffer
+    }
+
+    fn ipv4_packet(protocol: u8) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 40, 0, 0, 0, 0, 64, protocol, 0, 0];
+        packet.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+        // source port 443 and destination port 51000
+        packet.extend_from_slice(&[0x01, 0xbb, 0xc7, 0x38]);
+        packet
+    }
+
+    #[test]
+    fn packet_summaries() {
+        let mut tcp = vec![0; 12];
+        // VLAN tag before the IPv4 EtherType
+        tcp.extend_from_slice(&[0x81, 0x00, 0, 1, 0x08, 0x00]);
+        tcp.extend(ipv4_packet(6));
+        let mut arp = vec![0; 12];
+        arp.extend_from_slice(&[0x08, 0x06, 0, 1, 0x08, 0, 6, 4, 0, 1]);
+        arp.extend_from_slice(&[0; 6]);
+        arp.extend_from_slice(&[192, 168, 1, 1]);
+        arp.extend_from_slice(&[0; 6]);
+        arp.extend_from_slice(&[192, 168, 1, 2]);
+        let buffer = capture(ETHERNET, &[(1651399200, 250, tcp), (1651399201, 0, arp)]);
+
+        let packets: Value = serde_json::from_str(&pcap_to_json(&buffer).unwrap()).unwrap();
+        assert_eq!(
+            packets,
+            json!([
+                {
+                    "ts": "2022-05-01T10:00:00.000250Z",
+                    "src": "10.0.0.1",
+                    "dst": "10.0.0.2",
+                    "proto": "TCP",
+                    "src_port": 443,
+                    "dst_port": 51000,
+                    "len": 142,
+                },
+                {
+                    "ts": "2022-05-01T10:00:01Z",
+                    "src": "192.168.1.1",
+                    "dst": "192.168.1.2",
+                    "proto": "ARP",
+                    "src_port": null,
+                    "dst_port": null,
+                    "len": 142,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn raw_ip_packets() {
+        let mut ipv6 = vec![0x60, 0, 0, 0, 0, 8, 58, 64];
+        ipv6.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        ipv6.extend_from_slice(&"fe80::1".parse::<Ipv6Addr>().unwrap().octets());
+        ipv6.extend_from_slice(&[128, 0, 0, 0]);
+        let buffer = capture(RAW_IP, &[(0, 0, ipv4_packet(17)), (0, 0, ipv6)]);
+
+        let packets: Value = serde_json::from_str(&pcap_to_json(&buffer).unwrap()).unwrap();
+        assert_eq!(packets[0]["proto"], "UDP");
+        assert_eq!(packets[0]["dst_port"], 51000);
+        assert_eq!(packets[1]["src"], "::1");
+        assert_eq!(packets[1]["dst"], "fe80::1");
+        assert_eq!(packets[1]["proto"], "ICMPv6");
+        assert_eq!(packets[1]["src_port"], Value::Null);
+    }
+
+    #[test]
+    fn invalid_captures() {
+        let mut truncated = capture(ETHERNET, &[(0, 0, vec![0; 20])]);
+        truncated.truncate(truncated.len() - 1);
+        let cases = [
+            vec![],
+            b"not a capture".to_vec(),
+            vec![0x0a, 0x0d, 0x0d, 0x0a, 0, 0, 0, 0],
+            truncated,
+            // microseconds that overflow as nanoseconds
+            capture(ETHERNET, &[(0, u32::MAX, vec![0; 20])]),
+        ];
+        for buffer in cases {
+            match pcap_to_json(&buffer) {
+                Err(Error::InvalidPcap(_)) => (),
+                x => panic!("Error should be Error::InvalidPcap. Actual {:?}", x),
+            }
+        }
+    }
+}
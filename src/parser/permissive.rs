@@ -0,0 +1,94 @@
+/// Shorter versions of a regex for lines whose trailing fields are missing, longest first. Each
+/// ends after one of the regex's top-level groups that has a named capture group, with the
+/// group's repetition, e.g. `(?P<a>\w+) (?P<b>\d+)?` has the prefix `(?P<a>\w+)`. The regex
+/// itself isn't included.
+pub fn prefixes(pattern: &str) -> Vec<String> {
+    let bytes = pattern.as_bytes();
+    let mut ends = Vec::new();
+    let mut depth = 0;
+    let mut named = false;
+    // start of the character class being read, with nested classes counted in `classes`
+    let mut class_start = 0;
+    let mut classes = 0;
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => index += 1,
+            b'[' if classes > 0 => classes += 1,
+            // a `]` right after the opening bracket or its `^` is a literal
+            b']' if classes > 0
+                && !pattern[class_start + 1..index]
+                    .trim_start_matches('^')
+                    .is_empty() =>
+            {
+                classes -= 1
+            }
+            _ if classes > 0 => (),
+            b'[' => {
+                classes = 1;
+                class_start = index;
+            }
+            b'(' => {
+                let rest = &pattern[index..];
+                named |= rest.starts_with("(?P<") || rest.starts_with("(?<");
+                depth += 1;
+            }
+            b')' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 && named {
+                    named = false;
+                    ends.push(index + 1 + repetition(&pattern[index + 1..]));
+                }
+            }
+            _ => (),
+        }
+        index += 1;
+    }
+
+    ends.into_iter()
+        .rev()
+        .filter(|end| *end < pattern.len())
+        .map(|end| pattern[..end].to_string())
+        .collect()
+}
+
+/// Length of a repetition like `?`, `*`, `+` or `{2,3}` at the start of `rest`, with its lazy `?`
+fn repetition(rest: &str) -> usize {
+    let length = match rest.as_bytes().first() {
+        Some(b'?' | b'*' | b'+') => 1,
+        Some(b'{') => rest.find('}').map_or(0, |end| end + 1),
+        _ => return 0,
+    };
+    match rest[length..].starts_with('?') {
+        true => length + 1,
+        false => length,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_prefixes() {
+        let cases = [
+            (
+                r"(?P<a>\w+) (?P<b>\d+)? (?P<c>.*)",
+                vec![r"(?P<a>\w+) (?P<b>\d+)?", r"(?P<a>\w+)"],
+            ),
+            (
+                r"^\[(?P<ts>[^\]]+)\] (?:(?P<level>\w+): )?(?P<msg>.+)$",
+                vec![
+                    r"^\[(?P<ts>[^\]]+)\] (?:(?P<level>\w+): )?(?P<msg>.+)",
+                    r"^\[(?P<ts>[^\]]+)\] (?:(?P<level>\w+): )?",
+                    r"^\[(?P<ts>[^\]]+)",
+                ],
+            ),
+            (r"(?P<a>[])(]+)(x)(?P<b>\d){2,3}?", vec![r"(?P<a>[])(]+)"]),
+            (r"\(?P<a>\) (?P<b>\d+)", vec![]),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(prefixes(pattern), expected, "{}", pattern);
+        }
+    }
+}
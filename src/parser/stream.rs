@@ -0,0 +1,718 @@
+use crate::error::Error;
+use crate::parser::values::Event;
+use crate::parser::{Parser, Warnings};
+use crate::schema::InputFormat;
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Split text into lines ending with `\n`, `\r\n` or a lone `\r`, without their line endings,
+/// so files with old Mac or mixed line endings are read line by line too
+pub fn split_lines(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = Some(text).filter(|text| !text.is_empty());
+    std::iter::from_fn(move || {
+        let text = rest?;
+        let end = match text.find(['\r', '\n']) {
+            Some(end) => end,
+            None => {
+                rest = None;
+                return Some(text);
+            }
+        };
+        let next = match text[end..].starts_with("\r\n") {
+            true => end + 2,
+            false => end + 1,
+        };
+        rest = Some(&text[next..]).filter(|rest| !rest.is_empty());
+        Some(&text[..end])
+    })
+}
+
+/// How well the schema covered the lines of a file, to tell whether its regex matches the lines
+/// it should
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseStats {
+    /// The file, or `None` for sources without names
+    pub file: Option<String>,
+    /// Lines read, or objects of JSON array sources
+    pub lines: usize,
+    /// Lines that started an event
+    pub events: usize,
+    /// Lines that didn't start an event and weren't continuation lines
+    pub unmatched: usize,
+    /// Values that failed to convert, which were read as null or dropped their event
+    pub failures: usize,
+    /// Lines skipped without being parsed since they can't start an event the query selects
+    pub skipped: usize,
+}
+
+impl fmt::Display for ParseStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} lines, {} events, {} unmatched lines, {} values failed to convert",
+            self.file.as_deref().unwrap_or("source"),
+            self.lines,
+            self.events,
+            self.unmatched,
+            self.failures
+        )?;
+        if self.skipped > 0 {
+            write!(f, ", {} lines skipped by the query's filter", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+/// Texts a line needs to contain to start an event that a query selects, derived from its
+/// `WHERE` clause, so other lines are skipped before they're matched and converted. Each regex
+/// is an alternation of literals, at least one of which the line contains.
+#[derive(Debug, Clone)]
+pub struct LineFilter(Vec<Regex>);
+
+impl LineFilter {
+    /// A filter of lines containing one text of each group. Returns `None` if there's nothing
+    /// to filter by.
+    pub fn new(groups: &[Vec<String>]) -> Option<LineFilter> {
+        let regexes: Vec<_> = groups
+            .iter()
+            .filter(|texts| !texts.is_empty() && texts.iter().all(|text| !text.is_empty()))
+            .filter_map(|texts| {
+                let texts: Vec<_> = texts.iter().map(|text| regex::escape(text)).collect();
+                Regex::new(&texts.join("|")).ok()
+            })
+            .collect();
+        Some(LineFilter(regexes)).filter(|filter| !filter.0.is_empty())
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.0.iter().all(|regex| regex.is_match(line))
+    }
+}
+
+/// Assembles events from lines as they arrive, e.g. while following a file. A matching line
+/// starts a new event, but with a multiline column the event is held back while continuation
+/// lines may still arrive. It's emitted once the next matching line arrives, it's flushed after
+/// no lines were added for a while, or the stream is finished.
+pub struct EventStream<'a> {
+    parser: &'a Parser,
+    max_lines: usize,
+    max_bytes: usize,
+    pending: Option<Event>,
+    /// Continuation bytes of the pending event
+    extra_bytes: usize,
+    /// Whether the pending event, or the last one once it's flushed, dropped continuation lines
+    truncated: bool,
+    /// When the pending event last grew
+    updated: Option<Instant>,
+    /// Whether the last event was flushed before the next matching line
+    flushed: bool,
+    warnings: Warnings,
+    /// File the lines are from and the number of the last line, for the meta columns
+    file: Option<String>,
+    line: usize,
+    /// Stats of each file so far, the last one being read
+    stats: Vec<ParseStats>,
+    /// Lines of the record being read with a `record_separator`, and the line it starts at
+    record: Vec<String>,
+    record_start: usize,
+    /// Text of a line ending with the `line_continuation` so far, and the line it starts at
+    continued: Option<(String, usize)>,
+    filter: Option<LineFilter>,
+}
+
+impl<'a> EventStream<'a> {
+    pub fn new(parser: &'a Parser) -> EventStream<'a> {
+        let column = parser.schema.columns.iter().find(|c| c.multiline);
+        EventStream {
+            parser,
+            max_lines: column.and_then(|c| c.max_lines).unwrap_or(usize::MAX),
+            max_bytes: column.and_then(|c| c.max_bytes).unwrap_or(usize::MAX),
+            pending: None,
+            extra_bytes: 0,
+            truncated: false,
+            updated: None,
+            flushed: false,
+            warnings: Warnings::default(),
+            file: None,
+            line: 0,
+            stats: Vec::new(),
+            record: Vec::new(),
+            record_start: 0,
+            continued: None,
+            filter: None,
+        }
+    }
+
+    /// Skip lines, or records, that don't pass the filter without parsing them. Lines of a
+    /// multiline column's event don't start an event, and strict schemas fail on lines that
+    /// don't match, so neither skips lines.
+    pub fn set_filter(&mut self, filter: LineFilter) {
+        if self.parser.multiline_column.is_none() && !self.parser.schema.strict {
+            self.filter = Some(filter);
+        }
+    }
+
+    /// Add a line read at `now`. Returns the previous event once it can't get more lines. Lines
+    /// ending with the `line_continuation` are joined with the next line first. With a
+    /// `record_separator`, lines are collected until the separator, and the record is read like
+    /// a line.
+    pub fn push_line(&mut self, line: &str, now: Instant) -> Result<Option<Event>, Error> {
+        self.line += 1;
+        self.current_stats().lines += 1;
+        let (line, number) = match self.join_continued(line) {
+            Some(joined) => joined,
+            None => return Ok(None),
+        };
+        self.push_logical_line(&line, number, now)
+    }
+
+    /// The line joined with the lines before it that ended with the `line_continuation`, and
+    /// the line they start at. Returns `None` if this line continues too.
+    fn join_continued(&mut self, line: &str) -> Option<(String, usize)> {
+        let (mut text, start) = self.continued.take().unwrap_or((String::new(), self.line));
+        let continuation = self.parser.schema.line_continuation.as_deref();
+        match continuation.and_then(|continuation| line.strip_suffix(continuation)) {
+            Some(continued) => {
+                text.push_str(continued);
+                self.continued = Some((text, start));
+                None
+            }
+            None => {
+                text.push_str(line);
+                Some((text, start))
+            }
+        }
+    }
+
+    fn push_logical_line(
+        &mut self,
+        line: &str,
+        number: usize,
+        now: Instant,
+    ) -> Result<Option<Event>, Error> {
+        if self.parser.ignore.is_match(line) {
+            return Ok(None);
+        }
+        match &self.parser.record_separator {
+            None => self.push_text(line, number, now),
+            Some(separator) if separator.is_match(line) => self.end_record(now),
+            Some(_) => {
+                if self.record.is_empty() {
+                    self.record_start = number;
+                }
+                self.record.push(line.to_string());
+                Ok(None)
+            }
+        }
+    }
+
+    /// Read the record collected so far, e.g. at the end of a file whose last record has no
+    /// separator after it, after a last line that ended with the `line_continuation`
+    pub fn end_record(&mut self, now: Instant) -> Result<Option<Event>, Error> {
+        let mut event = None;
+        if let Some((line, number)) = self.continued.take() {
+            event = self.push_logical_line(&line, number, now)?;
+        }
+        if self.record.is_empty() {
+            return Ok(event);
+        }
+        let record = std::mem::take(&mut self.record).join("\n");
+        self.push_text(&record, self.record_start, now)
+    }
+
+    /// Read a line, or a record, that starts at line `number` of the file
+    fn push_text(
+        &mut self,
+        line: &str,
+        number: usize,
+        now: Instant,
+    ) -> Result<Option<Event>, Error> {
+        if self
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.is_match(line))
+        {
+            self.current_stats().skipped += 1;
+            return Ok(None);
+        }
+        let failures = self.warnings.failures;
+        let parsed = self
+            .parser
+            .parse_line_with_warnings(line, &mut self.warnings);
+        self.current_stats().failures += self.warnings.failures - failures;
+        let parsed = match parsed {
+            Err(e) if self.parser.skips(&e) => {
+                self.warnings.skipped += 1;
+                self.warnings.failures += 1;
+                self.current_stats().failures += 1;
+                // the skipped event's continuation lines are dropped without a warning, like
+                // those of a truncated event
+                self.flushed = true;
+                self.truncated = true;
+                return Ok(self.pending.take());
+            }
+            parsed => parsed?,
+        };
+        if let Some(mut event) = parsed {
+            self.current_stats().events += 1;
+            self.parser
+                .add_meta_columns(&mut event, self.file.as_deref(), number);
+            if self.parser.multiline_column.is_none() {
+                return Ok(Some(event));
+            }
+
+            self.extra_bytes = 0;
+            self.truncated = false;
+            self.flushed = false;
+            self.updated = Some(now);
+            return Ok(self.pending.replace(event));
+        }
+
+        // attempt to get extra lines only if multiline is enabled
+        let column = match &self.parser.multiline_column {
+            Some(column) => column,
+            None => return self.unmatched(line, number),
+        };
+        let continuation = self.parser.multiline_regex.as_ref();
+        if continuation.is_some_and(|regex| !regex.is_match(line)) {
+            if self.parser.schema.strict {
+                return self.unmatched(line, number);
+            }
+            self.current_stats().unmatched += 1;
+            self.warnings.messages.push(format!(
+                "Line did not match the schema or the multiline regex: {}",
+                line
+            ));
+            return Ok(None);
+        }
+        let pending = match self.pending.as_mut() {
+            Some(pending) => pending,
+            None if !self.flushed => return self.unmatched(line, number),
+            None => {
+                // only warn once per event, later lines are dropped the same way
+                if !self.truncated {
+                    self.truncated = true;
+                    self.warnings.messages.push(format!(
+                        "Dropped extra text for multiline column '{}' that arrived after its event was flushed",
+                        column
+                    ));
+                }
+                return Ok(None);
+            }
+        };
+
+        let lines = pending.extra_text.as_ref().map_or(0, Vec::len);
+        // each continuation line is joined with a newline
+        let bytes = self.extra_bytes + line.len() + 1;
+        if self.truncated || lines >= self.max_lines || bytes > self.max_bytes {
+            if !self.truncated {
+                self.truncated = true;
+                self.warnings.messages.push(format!(
+                    "Truncated extra text in multiline column '{}' (kept lines: {}, bytes: {})",
+                    column, lines, self.extra_bytes
+                ));
+            }
+            return Ok(None);
+        }
+
+        self.extra_bytes = bytes;
+        self.updated = Some(now);
+        match pending.extra_text.as_mut() {
+            None => pending.extra_text = Some(vec![line.to_string()]),
+            Some(extra_text) => extra_text.push(line.to_string()),
+        }
+        Ok(None)
+    }
+
+    /// Drop a line that doesn't start an event and can't continue one, or fail on it in strict
+    /// mode
+    fn unmatched(&mut self, line: &str, number: usize) -> Result<Option<Event>, Error> {
+        if !self.parser.schema.strict {
+            self.current_stats().unmatched += 1;
+            return Ok(None);
+        }
+        Err(Error::UnmatchedLine(
+            self.file
+                .clone()
+                .unwrap_or_else(|| "the source".to_string()),
+            number,
+            line.to_string(),
+        ))
+    }
+
+    /// Start reading the lines of another file, counting lines from 1 again. Returns the pending
+    /// event since lines of another file can't continue it. The record being read, if any,
+    /// needs to be ended first.
+    pub fn start_file(&mut self, file: &str) -> Option<Event> {
+        self.file = Some(file.to_string());
+        self.line = 0;
+        self.stats.push(ParseStats {
+            file: self.file.clone(),
+            ..ParseStats::default()
+        });
+        self.flushed = false;
+        self.pending.take()
+    }
+
+    /// Emit the pending event if no lines were added to it for `timeout`
+    pub fn flush_idle(&mut self, now: Instant, timeout: Duration) -> Option<Event> {
+        let idle = self
+            .updated
+            .is_some_and(|updated| now.saturating_duration_since(updated) >= timeout);
+        if !idle || self.pending.is_none() {
+            return None;
+        }
+
+        self.flushed = true;
+        self.pending.take()
+    }
+
+    /// Emit the pending event, e.g. at the end of the input, after ending the record being read
+    pub fn finish(&mut self) -> Option<Event> {
+        self.pending.take()
+    }
+
+    /// Take the warnings about clamped values and truncated or dropped extra text so far
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        self.warnings.take()
+    }
+
+    /// Take the stats of the files read so far
+    pub fn take_stats(&mut self) -> Vec<ParseStats> {
+        std::mem::take(&mut self.stats)
+    }
+
+    /// Stats of the file being read, which lines before the first file count towards
+    fn current_stats(&mut self) -> &mut ParseStats {
+        if self.stats.is_empty() {
+            self.stats.push(ParseStats::default());
+        }
+        self.stats.last_mut().unwrap()
+    }
+}
+
+/// Events parsed from lines as they're read, from [`Parser::parse_iter`]. Only the event that
+/// continuation lines may still be added to is held, so memory doesn't grow with the input.
+pub struct Events<'a, I> {
+    stream: EventStream<'a>,
+    lines: I,
+    now: Instant,
+    /// Events of JSON array sources, which are parsed once they've been read entirely
+    parsed: std::vec::IntoIter<Event>,
+    finished: bool,
+}
+
+impl<'a, I> Events<'a, I> {
+    pub(crate) fn new(parser: &'a Parser, lines: I) -> Events<'a, I> {
+        Events {
+            stream: EventStream::new(parser),
+            lines,
+            now: Instant::now(),
+            parsed: Vec::new().into_iter(),
+            finished: false,
+        }
+    }
+
+    /// Take the warnings about clamped values and truncated or dropped extra text so far
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        self.stream.take_warnings()
+    }
+}
+
+impl<'a, I, S> Iterator for Events<'a, I>
+where
+    I: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.parsed.next() {
+            return Some(Ok(event));
+        }
+        if self.finished {
+            return None;
+        }
+
+        let parser = self.stream.parser;
+        if matches!(
+            parser.schema.format,
+            InputFormat::JsonArray | InputFormat::Evtx | InputFormat::Pcap
+        ) {
+            self.finished = true;
+            let source: Vec<_> = self
+                .lines
+                .by_ref()
+                .map(|line| line.as_ref().to_string())
+                .collect();
+            return match parser.parse_json_arrays(vec![source.join("\n")], &[]) {
+                Ok((events, warnings, _)) => {
+                    self.stream.warnings.messages.extend(warnings);
+                    self.parsed = events.into_iter();
+                    self.parsed.next().map(Ok)
+                }
+                Err(e) => Some(Err(e)),
+            };
+        }
+
+        for line in self.lines.by_ref() {
+            match self.stream.push_line(line.as_ref(), self.now) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => (),
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.finished = true;
+        let last = match self.stream.end_record(self.now) {
+            Ok(last) => last,
+            Err(e) => return Some(Err(e)),
+        };
+        // the record can start an event and return the pending one before it
+        let events: Vec<_> = last.into_iter().chain(self.stream.finish()).collect();
+        self.parsed = events.into_iter();
+        self.parsed.next().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::values::Type;
+    use crate::schema::{Column, ColumnType, InputFormat, OnError, RegexFlags, Schema};
+    use std::collections::HashMap;
+
+    fn parser() -> Parser {
+        let schema = Schema {
+            regex: r"(?P<index>\d+)\t(?P<message>.+)".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
+            filename: ".*".to_string(),
+            table: "log".to_string(),
+            columns: vec![
+                Column::new("index", ColumnType::Int32),
+                Column::multiline_string("message"),
+            ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
+        };
+        Parser::new(schema).unwrap()
+    }
+
+    fn extra_text(event: Option<Event>) -> Option<Vec<String>> {
+        event.unwrap().extra_text
+    }
+
+    #[test]
+    fn buffer_continuations_until_next_event() {
+        let parser = parser();
+        let mut stream = EventStream::new(&parser);
+        let now = Instant::now();
+
+        assert_eq!(None, stream.push_line("1\tfirst", now).unwrap());
+        assert_eq!(None, stream.push_line("  at frame 1", now).unwrap());
+        assert_eq!(None, stream.push_line("  at frame 2", now).unwrap());
+        let first = stream.push_line("2\tsecond", now).unwrap();
+        assert_eq!(
+            Some(vec!["  at frame 1".to_string(), "  at frame 2".to_string()]),
+            extra_text(first)
+        );
+        assert_eq!(None, extra_text(stream.finish()));
+        assert_eq!(None, stream.finish());
+    }
+
+    #[test]
+    fn flush_after_idle_timeout() {
+        let parser = parser();
+        let mut stream = EventStream::new(&parser);
+        let start = Instant::now();
+        let timeout = Duration::from_secs(1);
+
+        stream.push_line("1\tfirst", start).unwrap();
+        stream.push_line("more", start + timeout / 2).unwrap();
+        // the continuation line restarts the timeout
+        assert_eq!(None, stream.flush_idle(start + timeout, timeout));
+        let flushed = stream.flush_idle(start + timeout * 2, timeout);
+        assert_eq!(Some(vec!["more".to_string()]), extra_text(flushed));
+        assert_eq!(None, stream.flush_idle(start + timeout * 3, timeout));
+
+        stream.push_line("late", start + timeout * 3).unwrap();
+        stream.push_line("later", start + timeout * 3).unwrap();
+        assert_eq!(
+            vec![
+                "Dropped extra text for multiline column 'message' that arrived after its event was flushed"
+            ],
+            stream.take_warnings()
+        );
+        assert_eq!(None, stream.finish());
+    }
+
+    #[test]
+    fn fail_on_unmatched_lines_in_strict_mode() {
+        let mut parser = parser();
+        parser.schema.strict = true;
+        parser.multiline_regex = Some(regex::Regex::new(r"^\s+at ").unwrap());
+        let mut stream = EventStream::new(&parser);
+        let now = Instant::now();
+
+        stream.start_file("app.log");
+        stream.push_line("1\tfirst", now).unwrap();
+        stream.push_line("  at frame 1", now).unwrap();
+        match stream.push_line("oops", now) {
+            Err(Error::UnmatchedLine(file, line, text)) => {
+                assert_eq!((file.as_str(), line, text.as_str()), ("app.log", 3, "oops"));
+            }
+            x => panic!("Error should be Error::UnmatchedLine. Actual {:?}", x),
+        }
+
+        // continuation lines need an event to continue
+        stream.start_file("next.log");
+        assert!(matches!(
+            stream.push_line("  at frame 2", now),
+            Err(Error::UnmatchedLine(_, 1, _))
+        ));
+    }
+
+    #[test]
+    fn skip_events_with_invalid_values() {
+        let mut parser = parser();
+        parser.schema.on_error = OnError::Skip;
+        let mut stream = EventStream::new(&parser);
+        let now = Instant::now();
+
+        stream.push_line("1\tfirst", now).unwrap();
+        let first = stream.push_line("99999999999\tbad", now).unwrap();
+        assert_eq!(None, extra_text(first));
+        // continuation lines of the skipped event
+        assert_eq!(None, stream.push_line("  at frame 1", now).unwrap());
+        assert_eq!(None, stream.push_line("2\tsecond", now).unwrap());
+        assert_eq!(None, extra_text(stream.finish()));
+        assert_eq!(
+            vec!["Skipped 1 events with values that failed to convert"],
+            stream.take_warnings()
+        );
+        assert!(stream.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn count_lines_per_file() {
+        let mut parser = parser();
+        parser.schema.on_error = OnError::Null;
+        parser.multiline_regex = Some(regex::Regex::new(r"^\s+at ").unwrap());
+        let mut stream = EventStream::new(&parser);
+        let now = Instant::now();
+
+        stream.start_file("a.log");
+        for line in ["1\tfirst", "  at frame 1", "oops", "99999999999\tbad"] {
+            stream.push_line(line, now).unwrap();
+        }
+        stream.start_file("b.log");
+        for line in ["  at frame 2", "2\tsecond"] {
+            stream.push_line(line, now).unwrap();
+        }
+        assert_eq!(
+            stream.take_stats(),
+            [
+                ParseStats {
+                    file: Some("a.log".to_string()),
+                    lines: 4,
+                    events: 2,
+                    unmatched: 1,
+                    failures: 1,
+                    skipped: 0,
+                },
+                ParseStats {
+                    file: Some("b.log".to_string()),
+                    lines: 2,
+                    events: 1,
+                    unmatched: 1,
+                    failures: 0,
+                    skipped: 0,
+                },
+            ]
+        );
+        assert_eq!(
+            ParseStats::default().to_string(),
+            "source: 0 lines, 0 events, 0 unmatched lines, 0 values failed to convert"
+        );
+    }
+
+    #[test]
+    fn split_line_endings() {
+        let lines: Vec<_> = split_lines("a\r\nb\rc\n\nd\r\n").collect();
+        assert_eq!(lines, ["a", "b", "c", "", "d"]);
+        assert_eq!(split_lines("").count(), 0);
+        assert_eq!(split_lines("\r").collect::<Vec<_>>(), [""]);
+    }
+
+    #[test]
+    fn join_continued_lines() {
+        let mut parser = parser();
+        parser.schema.meta_columns = true;
+        parser.schema.line_continuation = Some("\\".to_string());
+        parser.multiline_column = None;
+        let mut stream = EventStream::new(&parser);
+        let now = Instant::now();
+
+        stream.start_file("a.log");
+        assert_eq!(None, stream.push_line("1\tfirst \\", now).unwrap());
+        assert_eq!(None, stream.push_line("continued \\", now).unwrap());
+        let first = stream.push_line("end", now).unwrap().unwrap();
+        assert_eq!(first.values["message"], "first continued end".into());
+        assert_eq!(first.values["_line"], Type::Int64(1));
+
+        // a continuation on the last line
+        assert_eq!(None, stream.push_line("2\tsecond\\", now).unwrap());
+        let second = stream.end_record(now).unwrap().unwrap();
+        assert_eq!(second.values["message"], "second".into());
+        assert_eq!(second.values["_line"], Type::Int64(4));
+    }
+
+    #[test]
+    fn iterate_events() {
+        let parser = parser();
+        let lines = [
+            "1\tfirst",
+            "  at frame 1",
+            "2\tsecond",
+            "3\tthird",
+            "  at frame 3",
+        ];
+        let mut events = parser.parse_iter(lines);
+
+        let first = events.next().unwrap().unwrap();
+        assert_eq!(Some(vec!["  at frame 1".to_string()]), first.extra_text);
+        assert_eq!(None, events.next().unwrap().unwrap().extra_text);
+        let third = events.next().unwrap().unwrap();
+        assert_eq!(Some(vec!["  at frame 3".to_string()]), third.extra_text);
+        assert!(events.next().is_none());
+        assert!(events.next().is_none());
+
+        let mut events = parser.parse_iter(["99999999999\tfirst", "4\tfourth"]);
+        assert!(matches!(
+            events.next(),
+            Some(Err(Error::InvalidColumnValue(..)))
+        ));
+        assert!(events.next().is_none());
+    }
+}
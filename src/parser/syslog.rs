@@ -0,0 +1,367 @@
+use chrono::prelude::*;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+const SYSLOG_HEADER: [&str; 6] = [
+    "version",
+    "timestamp",
+    "hostname",
+    "app_name",
+    "procid",
+    "msgid",
+];
+
+/// Columns of `syslog` schemas that don't list any, with the names most tools use
+pub const SYSLOG_COLUMNS: &str = "
+- {name: pri, type: i32, group: priority}
+- {name: facility, type: i32}
+- {name: severity, type: i32}
+- {name: timestamp, type: datetimetz, lenient: true}
+- {name: host, type: string, group: hostname}
+- {name: app, type: string, group: app_name}
+- {name: procid, type: string}
+- {name: msgid, type: string}
+- {name: message, type: string}
+";
+
+/// Parse an RFC 5424 syslog line, or else an RFC 3164 one. See [`parse_rfc5424`] and
+/// [`parse_rfc3164`], which reads timestamps as this year's. Returns `None` if the line is
+/// neither.
+pub fn parse_syslog(line: &str) -> Option<HashMap<String, String>> {
+    parse_rfc5424(line).or_else(|| parse_rfc3164(line, Local::today().naive_local()))
+}
+
+/// Parse an RFC 5424 syslog line like
+/// `<165>1 2003-10-11T22:14:15.003Z host app 1234 ID47 [origin@32473 ip="10.0.0.1"] message`.
+/// Besides the header fields, `priority`, `facility`, `severity` and `message`, the structured
+/// data is available nested as a JSON object in `structured_data`, and flattened as
+/// `<SD-ID>.<param>`, e.g. `origin@32473.ip`, or `origin.ip` without the enterprise number. Nil
+/// values (`-`) are missing. Returns `None` if the line isn't RFC 5424 syslog.
+pub fn parse_rfc5424(line: &str) -> Option<HashMap<String, String>> {
+    let line = line.strip_prefix('<')?;
+    let (priority, mut rest) = line.split_once('>')?;
+    let priority: u8 = priority.parse().ok().filter(|p| *p <= 191)?;
+
+    let mut fields = HashMap::new();
+    fields.insert("priority".to_string(), priority.to_string());
+    fields.insert("facility".to_string(), (priority / 8).to_string());
+    fields.insert("severity".to_string(), (priority % 8).to_string());
+    for name in SYSLOG_HEADER {
+        let (value, remaining) = rest.split_once(' ').unwrap_or((rest, ""));
+        if value.is_empty() || (name == "version" && value.parse::<u8>().is_err()) {
+            return None;
+        }
+        if value != "-" {
+            fields.insert(name.to_string(), value.to_string());
+        }
+        rest = remaining;
+    }
+
+    let (elements, message) = parse_structured_data(rest)?;
+    if !elements.is_empty() {
+        let mut nested = Map::new();
+        for (id, params) in elements {
+            let short_id = id.split_once('@').map(|(short, _)| short);
+            let mut object = Map::new();
+            for (name, value) in params {
+                if let Some(short_id) = short_id {
+                    fields
+                        .entry(format!("{}.{}", short_id, name))
+                        .or_insert_with(|| value.clone());
+                }
+                fields.insert(format!("{}.{}", id, name), value.clone());
+                object.insert(name, Value::String(value));
+            }
+            nested.insert(id, Value::Object(object));
+        }
+        fields.insert(
+            "structured_data".to_string(),
+            Value::Object(nested).to_string(),
+        );
+    }
+
+    // the message can start with a byte order mark to say it's UTF-8
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message);
+    if !message.is_empty() {
+        fields.insert("message".to_string(), message.to_string());
+    }
+    Some(fields)
+}
+
+/// Parse a BSD syslog line like `<34>Oct 11 22:14:15 mymachine su[123]: 'su root' failed`, or
+/// one without the priority like syslog daemons write to files, into the fields of RFC 5424
+/// lines. The timestamp has no year, so it's the latest date that isn't after `today`, and no
+/// offset. The `app_name[procid]:` tag is optional. Returns `None` if the line isn't RFC 3164
+/// syslog.
+pub fn parse_rfc3164(line: &str, today: NaiveDate) -> Option<HashMap<String, String>> {
+    let mut fields = HashMap::new();
+    let mut rest = line;
+    if let Some(line) = line.strip_prefix('<') {
+        let (priority, remaining) = line.split_once('>')?;
+        let priority: u8 = priority.parse().ok().filter(|p| *p <= 191)?;
+        fields.insert("priority".to_string(), priority.to_string());
+        fields.insert("facility".to_string(), (priority / 8).to_string());
+        fields.insert("severity".to_string(), (priority % 8).to_string());
+        rest = remaining;
+    }
+
+    // `Mmm dd hh:mm:ss`, with the day padded with a space
+    let timestamp = rest.get(..15)?;
+    let parse = |year: i32| {
+        NaiveDateTime::parse_from_str(&format!("{} {}", year, timestamp), "%Y %b %e %H:%M:%S")
+    };
+    let mut datetime = parse(today.year()).ok()?;
+    // a day ahead for senders in later timezones
+    if datetime.date() > today.succ_opt()? {
+        datetime = parse(today.year() - 1).ok()?;
+    }
+    fields.insert(
+        "timestamp".to_string(),
+        datetime.format("%Y-%m-%dT%H:%M:%S").to_string(),
+    );
+
+    let rest = rest[15..].strip_prefix(' ')?;
+    let (hostname, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    if hostname.is_empty() {
+        return None;
+    }
+    fields.insert("hostname".to_string(), hostname.to_string());
+
+    let message = match rest.split_once(": ") {
+        Some((tag, message)) if !tag.is_empty() && !tag.contains(' ') => {
+            let (app_name, procid) = match tag.strip_suffix(']').and_then(|t| t.split_once('[')) {
+                Some((app_name, procid)) => (app_name, Some(procid)),
+                None => (tag, None),
+            };
+            fields.insert("app_name".to_string(), app_name.to_string());
+            if let Some(procid) = procid {
+                fields.insert("procid".to_string(), procid.to_string());
+            }
+            message
+        }
+        _ => rest,
+    };
+    if !message.is_empty() {
+        fields.insert("message".to_string(), message.to_string());
+    }
+    Some(fields)
+}
+
+type Element = (String, Vec<(String, String)>);
+
+/// Split the structured data elements from the message. The structured data is either `-` or
+/// elements like `[id name="value" ...]`, where `"`, `\` and `]` in values are escaped with a
+/// backslash.
+fn parse_structured_data(line: &str) -> Option<(Vec<Element>, &str)> {
+    if let Some(message) = line.strip_prefix('-') {
+        return Some((Vec::new(), message.strip_prefix(' ').unwrap_or(message)));
+    }
+
+    let mut elements = Vec::new();
+    let mut rest = line;
+    while let Some(element) = rest.strip_prefix('[') {
+        let end = element.find([' ', ']'])?;
+        let id = &element[..end];
+        if id.is_empty() {
+            return None;
+        }
+        let mut params = Vec::new();
+        rest = &element[end..];
+        loop {
+            rest = rest.trim_start_matches(' ');
+            if let Some(remaining) = rest.strip_prefix(']') {
+                rest = remaining;
+                break;
+            }
+            let (name, value) = rest.split_once("=\"")?;
+            let (value, remaining) = unescape_param(value)?;
+            params.push((name.to_string(), value));
+            rest = remaining;
+        }
+        elements.push((id.to_string(), params));
+    }
+
+    if elements.is_empty() {
+        return None;
+    }
+    Some((elements, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+/// Read a param value up to its closing quote, returning the value and the rest of the line
+fn unescape_param(value: &str) -> Option<(String, &str)> {
+    let mut unescaped = String::new();
+    let mut chars = value.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Some((unescaped, &value[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\' | ']'))) => unescaped.push(escaped),
+                Some((_, other)) => {
+                    unescaped.push('\\');
+                    unescaped.push(other);
+                }
+                None => unescaped.push('\\'),
+            },
+            c => unescaped.push(c),
+        }
+    }
+    None
+}
+
+/// Parse a GELF message, which is a JSON object per line. Fields are the standard `version`,
+/// `host`, `short_message`, `full_message`, `timestamp` and `level`, and additional fields
+/// without their leading underscore, e.g. `_user_id` is `user_id`. The unix `timestamp` is
+/// converted to RFC 3339 so it can go into datetime columns. Nested values are kept as JSON
+/// text. Returns `None` if the line isn't a JSON object.
+pub fn parse_gelf(line: &str) -> Option<HashMap<String, String>> {
+    let object = match serde_json::from_str(line).ok()? {
+        Value::Object(object) => object,
+        _ => return None,
+    };
+
+    let mut fields = HashMap::new();
+    for (name, value) in object {
+        let value = match value {
+            Value::Null => continue,
+            Value::Number(timestamp) if name == "timestamp" => {
+                // seconds with a fraction, which is only precise to about microseconds
+                let micros = (timestamp.as_f64()? * 1e6).round() as i64;
+                let nanos = micros.rem_euclid(1_000_000) as u32 * 1000;
+                Utc.timestamp_opt(micros.div_euclid(1_000_000), nanos)
+                    .single()?
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            }
+            Value::String(value) => value,
+            value => value.to_string(),
+        };
+        let name = match name.strip_prefix('_') {
+            Some(name) => name.to_string(),
+            None => name,
+        };
+        fields.insert(name, value);
+    }
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_syslog_lines() {
+        let line = r#"<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut="3" eventSource="App\"lication\]"][examplePriority@32473 class="high"] BOMAn application event"#
+            .replace("BOM", "\u{feff}");
+        let mut fields = parse_syslog(&line).unwrap();
+        let nested: Value = serde_json::from_str(&fields["structured_data"]).unwrap();
+        assert_eq!(
+            nested,
+            serde_json::json!({
+                "exampleSDID@32473": {"iut": "3", "eventSource": "App\"lication]"},
+                "examplePriority@32473": {"class": "high"},
+            })
+        );
+        fields.remove("structured_data");
+
+        let expected: HashMap<_, _> = [
+            ("priority", "165"),
+            ("facility", "20"),
+            ("severity", "5"),
+            ("version", "1"),
+            ("timestamp", "2003-10-11T22:14:15.003Z"),
+            ("hostname", "mymachine.example.com"),
+            ("app_name", "evntslog"),
+            ("msgid", "ID47"),
+            ("exampleSDID@32473.iut", "3"),
+            ("exampleSDID@32473.eventSource", "App\"lication]"),
+            ("examplePriority@32473.class", "high"),
+            ("exampleSDID.iut", "3"),
+            ("exampleSDID.eventSource", "App\"lication]"),
+            ("examplePriority.class", "high"),
+            ("message", "An application event"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(expected, fields);
+
+        let fields =
+            parse_syslog("<34>1 2003-10-11T22:14:15Z host su - - - 'su root' failed").unwrap();
+        assert_eq!(fields["message"], "'su root' failed");
+        assert!(!fields.contains_key("procid"));
+        assert!(!fields.contains_key("structured_data"));
+
+        let fields = parse_syslog("<0>1 - - - - - [timeQuality tzKnown=\"0\"]").unwrap();
+        assert_eq!(fields["timeQuality.tzKnown"], "0");
+        assert!(!fields.contains_key("message"));
+
+        for line in [
+            "not syslog",
+            "<34>Oct 11 22:14:15 host su: 'su root' failed",
+            "<999>1 - - - - - -",
+            "<34>1 - - - - - [unterminated a=\"1\"",
+            "<34>1 - - - - - message without structured data",
+        ] {
+            assert_eq!(None, parse_rfc5424(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parse_rfc3164_lines() {
+        let today = NaiveDate::from_ymd(2022, 5, 1);
+        let fields = parse_rfc3164(
+            "<34>Apr 30 22:14:15 mymachine su[123]: 'su root' failed",
+            today,
+        )
+        .unwrap();
+        let expected: HashMap<_, _> = [
+            ("priority", "34"),
+            ("facility", "4"),
+            ("severity", "2"),
+            ("timestamp", "2022-04-30T22:14:15"),
+            ("hostname", "mymachine"),
+            ("app_name", "su"),
+            ("procid", "123"),
+            ("message", "'su root' failed"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(expected, fields);
+
+        // without a priority or tag, and from last year
+        let fields = parse_rfc3164("Dec  9 08:00:01 web01 cron started", today).unwrap();
+        assert_eq!(fields["timestamp"], "2021-12-09T08:00:01");
+        assert_eq!(fields["message"], "cron started");
+        assert!(!fields.contains_key("priority"));
+        assert!(!fields.contains_key("app_name"));
+
+        for line in [
+            "not syslog",
+            "<34>Foo 11 22:14:15 host su: x",
+            "Oct 11 22:14:15",
+        ] {
+            assert_eq!(None, parse_rfc3164(line, today), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parse_gelf_lines() {
+        let line = r#"{"version": "1.1", "host": "example.org", "short_message": "A short message", "timestamp": 1385053862.3072, "level": 1, "_user_id": 9001, "_tags": ["a", "b"], "_empty": null}"#;
+        let expected: HashMap<_, _> = [
+            ("version", "1.1"),
+            ("host", "example.org"),
+            ("short_message", "A short message"),
+            ("timestamp", "2013-11-21T17:11:02.307200Z"),
+            ("level", "1"),
+            ("user_id", "9001"),
+            ("tags", r#"["a","b"]"#),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(Some(expected), parse_gelf(line));
+
+        assert_eq!(None, parse_gelf("[1, 2]"));
+        assert_eq!(None, parse_gelf("not json"));
+    }
+}
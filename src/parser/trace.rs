@@ -0,0 +1,91 @@
+use crate::parser::custom::CustomType;
+use std::cmp::Ordering;
+
+/// W3C trace context trace id: 32 hex characters that aren't all zero
+pub struct TraceId;
+
+impl CustomType for TraceId {
+    type Value = u128;
+
+    fn name(&self) -> &str {
+        "trace_id"
+    }
+
+    fn parse(&self, value: &str) -> Result<Self::Value, String> {
+        parse_hex_id(value, 32)
+            .and_then(|id| u128::from_str_radix(id, 16).ok())
+            .filter(|id| *id != 0)
+            .ok_or_else(|| format!("'{}' is not a valid trace id", value))
+    }
+
+    fn compare(&self, a: &Self::Value, b: &Self::Value) -> Option<Ordering> {
+        a.partial_cmp(b)
+    }
+
+    fn display(&self, value: &Self::Value) -> String {
+        format!("{:032x}", value)
+    }
+}
+
+/// W3C trace context span (parent) id: 16 hex characters that aren't all zero
+pub struct SpanId;
+
+impl CustomType for SpanId {
+    type Value = u64;
+
+    fn name(&self) -> &str {
+        "span_id"
+    }
+
+    fn parse(&self, value: &str) -> Result<Self::Value, String> {
+        parse_hex_id(value, 16)
+            .and_then(|id| u64::from_str_radix(id, 16).ok())
+            .filter(|id| *id != 0)
+            .ok_or_else(|| format!("'{}' is not a valid span id", value))
+    }
+
+    fn compare(&self, a: &Self::Value, b: &Self::Value) -> Option<Ordering> {
+        a.partial_cmp(b)
+    }
+
+    fn display(&self, value: &Self::Value) -> String {
+        format!("{:016x}", value)
+    }
+}
+
+/// Check an id has exactly `length` hex digits. `from_str_radix` alone would accept a sign.
+fn parse_hex_id(value: &str, length: usize) -> Option<&str> {
+    let value = value.trim();
+    let valid = value.len() == length && value.bytes().all(|b| b.is_ascii_hexdigit());
+    valid.then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_trace_id() {
+        let id = TraceId.parse("4BF92F3577B34DA6A3CE929D0E0E4736").unwrap();
+        assert_eq!(TraceId.display(&id), "4bf92f3577b34da6a3ce929d0e0e4736");
+
+        let invalid = [
+            "4bf92f3577b34da6a3ce929d0e0e473",
+            "4bf92f3577b34da6a3ce929d0e0e47366",
+            "4bf92f3577b34da6a3ce929d0e0e473g",
+            "00000000000000000000000000000000",
+            "+bf92f3577b34da6a3ce929d0e0e4736",
+        ];
+        for value in invalid {
+            assert!(TraceId.parse(value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn parse_span_id() {
+        let id = SpanId.parse("00f067aa0ba902b7").unwrap();
+        assert_eq!(SpanId.display(&id), "00f067aa0ba902b7");
+        assert!(SpanId.parse("0000000000000000").is_err());
+        assert!(SpanId.parse("00f067aa0ba902b").is_err());
+    }
+}
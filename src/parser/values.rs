@@ -1,28 +1,329 @@
+use crate::parser::custom::CustomValue;
+use crate::schema::ColumnType;
 use chrono::prelude::*;
-use serde::Serialize;
+use chrono::Duration;
+use rust_decimal::prelude::*;
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
 
 #[derive(Debug, PartialEq, Clone, Serialize, PartialOrd)]
 pub enum Type {
     String(String),
     Int32(i32),
     Int64(i64),
+    UInt32(u32),
+    UInt64(u64),
     Bool(bool),
     Float(f32),
     Double(f64),
+    Decimal(Decimal),
     DateTime(DateTime<Utc>),
+    /// A datetime that keeps the offset it was logged with
+    DateTimeTz(DateTime<FixedOffset>),
+    /// A signed duration from `INTERVAL '90 minutes'` or subtracting datetimes
+    #[serde(serialize_with = "serialize_interval")]
+    Interval(Duration),
+    Custom(CustomValue),
+    /// A missing value, e.g. the `MAX` of no events
+    Null,
 }
 
-impl ToString for Type {
-    fn to_string(&self) -> String {
+impl Type {
+    /// Compare two values that may have different types. Numbers are compared by value, strings
+    /// are parsed when compared to datetimes and strings like `85%` are compared to numbers as
+    /// fractions. Like postgres, NaN equals itself and is greater than every other number. NULL
+    /// sorts after every other value. Returns `None` if the types are incomparable.
+    pub fn compare(&self, other: &Type) -> Option<Ordering> {
+        match (self, other) {
+            (Type::Null, Type::Null) => Some(Ordering::Equal),
+            (Type::Null, _) => Some(Ordering::Greater),
+            (_, Type::Null) => Some(Ordering::Less),
+            (Type::String(a), Type::String(b)) => a.partial_cmp(b),
+            (Type::Bool(a), Type::Bool(b)) => a.partial_cmp(b),
+            (Type::DateTime(_) | Type::DateTimeTz(_), Type::DateTime(_) | Type::DateTimeTz(_)) => {
+                self.as_utc()?.partial_cmp(&other.as_utc()?)
+            }
+            (Type::Interval(a), Type::Interval(b)) => a.partial_cmp(b),
+            (Type::DateTime(_) | Type::DateTimeTz(_), Type::String(b)) => {
+                let b: DateTime<Utc> = b.parse().ok()?;
+                self.as_utc()?.partial_cmp(&b)
+            }
+            (Type::String(_), Type::DateTime(_) | Type::DateTimeTz(_)) => {
+                other.compare(self).map(Ordering::reverse)
+            }
+            (Type::Custom(a), Type::Custom(b)) => a.compare(b),
+            (Type::Custom(a), Type::String(b)) => a.compare(&a.parse_same_type(b).ok()?),
+            (Type::String(_), Type::Custom(_)) => other.compare(self).map(Ordering::reverse),
+            (_, Type::String(b)) if b.ends_with('%') && self.as_f64().is_some() => {
+                self.compare(&Type::Double(parse_percent(b)?))
+            }
+            (Type::String(a), _) if a.ends_with('%') && other.as_f64().is_some() => {
+                Type::Double(parse_percent(a)?).compare(other)
+            }
+            // exact, unless one side is a double that has no decimal like NaN
+            (Type::Decimal(_), _) | (_, Type::Decimal(_)) => {
+                match (self.as_decimal(), other.as_decimal()) {
+                    (Some(a), Some(b)) => Some(a.cmp(&b)),
+                    _ => Some(compare_floats(self.as_f64()?, other.as_f64()?)),
+                }
+            }
+            _ => match (self.as_i128(), other.as_i128()) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                // compare at f32 precision so a column of 2.2f32 equals the literal 2.2
+                _ if matches!(self, Type::Float(_)) || matches!(other, Type::Float(_)) => {
+                    let a = self.as_f64()? as f32;
+                    let b = other.as_f64()? as f32;
+                    Some(compare_floats(a as f64, b as f64))
+                }
+                _ => Some(compare_floats(self.as_f64()?, other.as_f64()?)),
+            },
+        }
+    }
+
+    /// Convert a value to a builtin type, e.g. for `CAST`. Strings are parsed and floats are
+    /// truncated when converted to integers. Returns `None` if the value cannot be converted.
+    pub fn cast(&self, to: &ColumnType) -> Option<Type> {
+        if *self == Type::Null {
+            return Some(Type::Null);
+        }
+
+        if let Type::String(value) = self {
+            let value = value.trim();
+            return match to {
+                ColumnType::String => Some(self.clone()),
+                ColumnType::Int32 => value.parse().ok().map(Type::Int32),
+                ColumnType::Int64 => value.parse().ok().map(Type::Int64),
+                ColumnType::UInt32 => value.parse().ok().map(Type::UInt32),
+                ColumnType::UInt64 => value.parse().ok().map(Type::UInt64),
+                ColumnType::Bool => match value.to_ascii_lowercase().as_str() {
+                    "true" | "t" | "1" => Some(Type::Bool(true)),
+                    "false" | "f" | "0" => Some(Type::Bool(false)),
+                    _ => None,
+                },
+                ColumnType::Float => value.parse().ok().map(Type::Float),
+                ColumnType::Double => value.parse().ok().map(Type::Double),
+                ColumnType::Decimal => parse_decimal(value).map(Type::Decimal),
+                ColumnType::DateTime => value.parse().ok().map(Type::DateTime),
+                ColumnType::DateTimeTz => value.parse().ok().map(Type::DateTimeTz),
+                ColumnType::Percent => parse_percent(value).map(Type::Double),
+                ColumnType::Custom(_) => None,
+            };
+        }
+
+        match to {
+            ColumnType::String => Some(Type::String(self.to_string())),
+            ColumnType::Int32 => i32::try_from(self.as_truncated_i128()?)
+                .ok()
+                .map(Type::Int32),
+            ColumnType::Int64 => i64::try_from(self.as_truncated_i128()?)
+                .ok()
+                .map(Type::Int64),
+            ColumnType::UInt32 => u32::try_from(self.as_truncated_i128()?)
+                .ok()
+                .map(Type::UInt32),
+            ColumnType::UInt64 => u64::try_from(self.as_truncated_i128()?)
+                .ok()
+                .map(Type::UInt64),
+            ColumnType::Bool => match self {
+                Type::Bool(_) => Some(self.clone()),
+                _ => self.as_i128().map(|x| Type::Bool(x != 0)),
+            },
+            ColumnType::Float => self.as_f64().map(|x| Type::Float(x as f32)),
+            ColumnType::Double | ColumnType::Percent => self.as_f64().map(Type::Double),
+            ColumnType::Decimal => self.as_decimal().map(Type::Decimal),
+            ColumnType::DateTime => self.as_utc().map(Type::DateTime),
+            ColumnType::DateTimeTz => match self {
+                Type::DateTime(x) => Some(Type::DateTimeTz(x.with_timezone(&Utc.fix()))),
+                Type::DateTimeTz(_) => Some(self.clone()),
+                _ => None,
+            },
+            ColumnType::Custom(_) => None,
+        }
+    }
+
+    /// The instant of a datetime, converting datetimes with an offset to UTC
+    pub fn as_utc(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Type::DateTime(x) => Some(*x),
+            Type::DateTimeTz(x) => Some(x.with_timezone(&Utc)),
+            _ => None,
+        }
+    }
+
+    /// Integers and bools as-is and floats truncated towards zero. Wide enough for every i64
+    /// and u64, so the caller checks the range of the type it converts to.
+    fn as_truncated_i128(&self) -> Option<i128> {
+        match self {
+            Type::Bool(x) => Some(*x as i128),
+            Type::Float(_) | Type::Double(_) => {
+                let x = self.as_f64()?.trunc();
+                (x >= i64::MIN as f64 && x < u64::MAX as f64).then_some(x as i128)
+            }
+            Type::Decimal(x) => x.trunc().to_i128(),
+            _ => self.as_i128(),
+        }
+    }
+
+    fn as_i128(&self) -> Option<i128> {
         match self {
-            Type::String(x) => x.to_string(),
-            Type::Int32(x) => x.to_string(),
-            Type::Int64(x) => x.to_string(),
-            Type::Bool(x) => x.to_string(),
-            Type::Float(x) => x.to_string(),
-            Type::Double(x) => x.to_string(),
-            Type::DateTime(x) => x.to_string(),
+            Type::Int32(x) => Some(*x as i128),
+            Type::Int64(x) => Some(*x as i128),
+            Type::UInt32(x) => Some(*x as i128),
+            Type::UInt64(x) => Some(*x as i128),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Type::Int32(x) => Some(*x as f64),
+            Type::Int64(x) => Some(*x as f64),
+            Type::UInt32(x) => Some(*x as f64),
+            Type::UInt64(x) => Some(*x as f64),
+            Type::Float(x) => Some(*x as f64),
+            Type::Double(x) => Some(*x),
+            Type::Decimal(x) => x.to_f64(),
+            _ => None,
+        }
+    }
+
+    /// Numbers as exact decimals. Floats are converted from their shortest decimal form, so the
+    /// double `0.1` is the decimal `0.1`. NaN, infinities and numbers too large for a decimal
+    /// don't have one.
+    pub(crate) fn as_decimal(&self) -> Option<Decimal> {
+        match self {
+            Type::Decimal(x) => Some(*x),
+            Type::Float(x) if x.is_finite() => parse_decimal(&x.to_string()),
+            Type::Double(x) if x.is_finite() => parse_decimal(&x.to_string()),
+            _ => Decimal::try_from_i128_with_scale(self.as_i128()?, 0).ok(),
+        }
+    }
+}
+
+/// Parse a decimal like `19.99` or `1.5e3`
+pub fn parse_decimal(value: &str) -> Option<Decimal> {
+    Decimal::from_str(value)
+        .or_else(|_| Decimal::from_scientific(value))
+        .ok()
+}
+
+/// Parse `85%` as 0.85. Values without a percent sign are already fractions.
+pub fn parse_percent(value: &str) -> Option<f64> {
+    let value = value.trim();
+    match value.strip_suffix('%') {
+        Some(percent) => percent.trim_end().parse::<f64>().ok().map(|x| x / 100.0),
+        None => value.parse().ok(),
+    }
+}
+
+/// Parse an interval such as `90 minutes`, `1 day 2 hours` or `-30s`. Months and years are
+/// rejected since their length depends on the date they are added to.
+pub fn parse_interval(value: &str) -> Option<Duration> {
+    let mut tokens = value.split_whitespace();
+    let mut total = Duration::zero();
+    let mut empty = true;
+    while let Some(token) = tokens.next() {
+        // the unit can be attached to the number, e.g. `30s`
+        let split = token
+            .find(|c: char| c.is_alphabetic())
+            .unwrap_or(token.len());
+        let (amount, unit) = token.split_at(split);
+        let unit = match unit {
+            "" => tokens.next()?,
+            unit => unit,
+        };
+        total = total + interval_unit(unit, amount.parse().ok()?)?;
+        empty = false;
+    }
+
+    (!empty).then_some(total)
+}
+
+/// Duration of `amount` of a unit like `minutes`, `min` or `m`
+pub fn interval_unit(unit: &str, amount: f64) -> Option<Duration> {
+    let unit_nanos = match unit.to_ascii_lowercase().as_str() {
+        "microsecond" | "microseconds" | "us" => 1e3,
+        "millisecond" | "milliseconds" | "ms" => 1e6,
+        "second" | "seconds" | "sec" | "secs" | "s" => 1e9,
+        "minute" | "minutes" | "min" | "mins" | "m" => 60e9,
+        "hour" | "hours" | "h" => 3600e9,
+        "day" | "days" | "d" => 86400e9,
+        "week" | "weeks" | "w" => 604800e9,
+        _ => return None,
+    };
+    let nanos = amount * unit_nanos;
+    (nanos.abs() < i64::MAX as f64).then(|| Duration::nanoseconds(nanos.round() as i64))
+}
+
+/// Render an interval like postgres, e.g. `1 day 01:30:00` or `-00:00:00.25`
+fn format_interval(interval: &Duration) -> String {
+    let sign = if *interval < Duration::zero() {
+        "-"
+    } else {
+        ""
+    };
+    let nanos = interval
+        .num_nanoseconds()
+        .map_or(i64::MAX as u64, i64::unsigned_abs);
+    let seconds = nanos / 1_000_000_000;
+    let fraction = nanos % 1_000_000_000;
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+
+    let mut formatted = sign.to_string();
+    match days {
+        0 => (),
+        1 => formatted.push_str("1 day "),
+        days => formatted.push_str(&format!("{} days ", days)),
+    }
+    formatted.push_str(&format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    ));
+    if fraction != 0 {
+        let fraction = format!("{:09}", fraction);
+        formatted.push('.');
+        formatted.push_str(fraction.trim_end_matches('0'));
+    }
+    formatted
+}
+
+fn serialize_interval<S: Serializer>(
+    interval: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&format_interval(interval))
+}
+
+fn compare_floats(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap(),
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::String(x) => x.fmt(f),
+            Type::Int32(x) => x.fmt(f),
+            Type::Int64(x) => x.fmt(f),
+            Type::UInt32(x) => x.fmt(f),
+            Type::UInt64(x) => x.fmt(f),
+            Type::Bool(x) => x.fmt(f),
+            Type::Float(x) => x.fmt(f),
+            Type::Double(x) => x.fmt(f),
+            Type::Decimal(x) => x.fmt(f),
+            Type::DateTime(x) => x.fmt(f),
+            Type::DateTimeTz(x) => x.fmt(f),
+            Type::Interval(x) => f.write_str(&format_interval(x)),
+            Type::Custom(x) => x.fmt(f),
+            Type::Null => f.write_str("NULL"),
         }
     }
 }
@@ -81,3 +382,138 @@ pub struct Event {
     pub values: HashMap<String, Type>,
     pub extra_text: Option<Vec<String>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(value: &str) -> Type {
+        Type::Decimal(Decimal::from_str(value).unwrap())
+    }
+
+    #[test]
+    fn cast() {
+        let cases = [
+            (Type::from(" 42 "), ColumnType::Int32, Some(42.into())),
+            ("42".into(), ColumnType::Int64, Some(42i64.into())),
+            ("1e3".into(), ColumnType::Double, Some(1000f64.into())),
+            ("TRUE".into(), ColumnType::Bool, Some(true.into())),
+            ("85%".into(), ColumnType::Percent, Some(0.85f64.into())),
+            ("nope".into(), ColumnType::Int64, None),
+            (2.9f64.into(), ColumnType::Int64, Some(2i64.into())),
+            ((-2.9f64).into(), ColumnType::Int32, Some((-2).into())),
+            (f64::NAN.into(), ColumnType::Int64, None),
+            (5_000_000_000i64.into(), ColumnType::Int32, None),
+            (7.into(), ColumnType::String, Some("7".into())),
+            (0.into(), ColumnType::Bool, Some(false.into())),
+            (true.into(), ColumnType::DateTime, None),
+            (
+                "4294967295".into(),
+                ColumnType::UInt32,
+                Some(Type::UInt32(u32::MAX)),
+            ),
+            ("-1".into(), ColumnType::UInt64, None),
+            ((-1).into(), ColumnType::UInt32, None),
+            (Type::UInt64(u64::MAX), ColumnType::Int64, None),
+            (
+                Type::UInt64(u64::MAX),
+                ColumnType::Double,
+                Some(1.8446744073709552e19.into()),
+            ),
+            (2.9f64.into(), ColumnType::UInt64, Some(Type::UInt64(2))),
+            ("19.99".into(), ColumnType::Decimal, Some(decimal("19.99"))),
+            ("1.5e3".into(), ColumnType::Decimal, Some(decimal("1500"))),
+            (0.1f64.into(), ColumnType::Decimal, Some(decimal("0.1"))),
+            (f64::NAN.into(), ColumnType::Decimal, None),
+            (decimal("-2.75"), ColumnType::Int32, Some((-2).into())),
+            (decimal("2.5"), ColumnType::Double, Some(2.5f64.into())),
+        ];
+        for (value, column_type, expected) in cases {
+            assert_eq!(
+                value.cast(&column_type),
+                expected,
+                "{:?} as {}",
+                value,
+                column_type
+            );
+        }
+
+        let timestamp = Utc.ymd(2022, 5, 1).and_hms(10, 0, 0);
+        let local = Type::from("2022-05-01T12:00:00+02:00")
+            .cast(&ColumnType::DateTimeTz)
+            .unwrap();
+        assert_eq!(local.to_string(), "2022-05-01 12:00:00 +02:00");
+        assert_eq!(local.cast(&ColumnType::DateTime), Some(timestamp.into()));
+        assert_eq!(local.compare(&timestamp.into()), Some(Ordering::Equal));
+        assert_eq!(
+            Type::from("2022-05-01T10:00:00Z").cast(&ColumnType::DateTime),
+            Some(timestamp.into())
+        );
+    }
+
+    #[test]
+    fn compare_non_finite() {
+        let nan = Type::Double(f64::NAN);
+        assert_eq!(nan.compare(&Type::Double(f64::NAN)), Some(Ordering::Equal));
+        assert_eq!(
+            nan.compare(&Type::Double(f64::INFINITY)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(Type::Int64(1).compare(&nan), Some(Ordering::Less));
+        assert_eq!(
+            Type::UInt64(u64::MAX).compare(&Type::Int64(i64::MAX)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Type::UInt32(3).compare(&Type::Int64(-3)),
+            Some(Ordering::Greater)
+        );
+        // percents are only fractions next to numbers
+        assert_eq!(
+            Type::from("85%").compare(&Type::Double(0.85)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(Type::Bool(true).compare(&"100%".into()), None);
+        assert_eq!(
+            Type::from("50%").compare(&Type::Interval(Duration::seconds(1))),
+            None
+        );
+        assert_eq!(
+            decimal("0.3").compare(&0.3f64.into()),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(decimal("1.00").compare(&1.into()), Some(Ordering::Equal));
+        assert_eq!(decimal("5").compare(&nan), Some(Ordering::Less));
+        assert_eq!(
+            Type::Float(f32::NEG_INFINITY).compare(&Type::Int32(-5)),
+            Some(Ordering::Less)
+        );
+    }
+
+    #[test]
+    fn intervals() {
+        let cases = [
+            ("90 minutes", Duration::minutes(90)),
+            ("1 day 2 hours", Duration::hours(26)),
+            ("-30s", Duration::seconds(-30)),
+            ("1.5 h", Duration::minutes(90)),
+            ("250 ms", Duration::milliseconds(250)),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_interval(value), Some(expected), "{}", value);
+        }
+        for value in ["", "5", "1 month", "minutes", "1 day 2"] {
+            assert_eq!(parse_interval(value), None, "{}", value);
+        }
+
+        let cases = [
+            (Duration::minutes(90), "01:30:00"),
+            (Duration::hours(49), "2 days 01:00:00"),
+            (Duration::hours(-25), "-1 day 01:00:00"),
+            (Duration::milliseconds(-250), "-00:00:00.25"),
+        ];
+        for (interval, expected) in cases {
+            assert_eq!(Type::Interval(interval).to_string(), expected);
+        }
+    }
+}
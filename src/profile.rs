@@ -0,0 +1,146 @@
+use crate::error::Error;
+use crate::export::Format;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Named sets of command line defaults, so switching between environments is `--profile prod`
+/// instead of retyping the schema, source and output flags. Read from YAML like
+///
+/// ```yaml
+/// default_profile: staging
+/// profiles:
+///   prod:
+///     schema: schemas/app.yml
+///     source: /var/log/app
+///     format: json
+///   staging:
+///     schema: schemas/app.yml
+///     source: /mnt/staging/logs
+/// ```
+///
+/// Relative paths are relative to the config file.
+#[derive(Debug, Deserialize, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Profile used when `--profile` isn't given
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Defaults for command line flags. Flags that are given win over the profile.
+#[derive(Debug, Deserialize, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub schema: Option<PathBuf>,
+    pub source: Option<PathBuf>,
+    pub format: Option<Format>,
+    pub export: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub loki_endpoint: Option<String>,
+    pub loki_labels: Option<Vec<String>>,
+    pub audit_log: Option<PathBuf>,
+}
+
+impl ConfigFile {
+    /// `$XDG_CONFIG_HOME/logql/config.yml`, or `~/.config/logql/config.yml`
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+        Some(config_home.join("logql").join("config.yml"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<ConfigFile, Error> {
+        let path = path.as_ref();
+        let invalid = |e: String| Error::InvalidConfig(format!("{}: {}", path.display(), e));
+        let contents = std::fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let mut config: ConfigFile =
+            serde_yaml::from_str(&contents).map_err(|e| invalid(e.to_string()))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for profile in config.profiles.values_mut() {
+            for path in [
+                &mut profile.schema,
+                &mut profile.source,
+                &mut profile.audit_log,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                if path.is_relative() {
+                    *path = dir.join(&*path);
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// The named profile, or the default profile without a name. `None` if there's neither.
+    pub fn profile(&self, name: Option<&str>) -> Result<Option<&Profile>, Error> {
+        let name = match name.or(self.default_profile.as_deref()) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        match self.profiles.get(name) {
+            Some(profile) => Ok(Some(profile)),
+            None => {
+                let names: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+                Err(Error::UnknownProfile(name.to_string(), names.join(", ")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_profiles() {
+        let dir = std::env::temp_dir().join(format!("logql-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.yml");
+        let config = "\
+default_profile: staging
+profiles:
+  prod:
+    schema: schemas/app.yml
+    source: /var/log/app
+    format: json-headers
+    loki_labels: [host, level]
+  staging:
+    source: logs
+";
+        std::fs::write(&path, config).unwrap();
+        let config = ConfigFile::load(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let prod = config.profile(Some("prod")).unwrap().unwrap();
+        assert_eq!(prod.schema, Some(dir.join("schemas/app.yml")));
+        assert_eq!(prod.source, Some(PathBuf::from("/var/log/app")));
+        assert_eq!(prod.format, Some(Format::JsonHeaders));
+        assert_eq!(
+            prod.loki_labels,
+            Some(vec!["host".to_string(), "level".to_string()])
+        );
+        let staging = config.profile(None).unwrap().unwrap();
+        assert_eq!(staging.source, Some(dir.join("logs")));
+        assert_eq!(staging.schema, None);
+
+        match config.profile(Some("dev")) {
+            Err(Error::UnknownProfile(name, names)) => {
+                assert_eq!(name, "dev");
+                assert_eq!(names, "prod, staging");
+            }
+            x => panic!("Error should be Error::UnknownProfile. Actual {:?}", x),
+        }
+        assert_eq!(None, ConfigFile::default().profile(None).unwrap());
+
+        match ConfigFile::load(dir.join("missing.yml")) {
+            Err(Error::InvalidConfig(_)) => (),
+            x => panic!("Error should be Error::InvalidConfig. Actual {:?}", x),
+        }
+    }
+}
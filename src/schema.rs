@@ -1,30 +1,255 @@
 use crate::error::Error;
-use serde::Deserialize;
+use crate::parser::encoding::Encoding;
+use crate::parser::lenient::parse_offset;
+use crate::parser::values::Type;
+use chrono::prelude::*;
+use serde::{Deserialize, Deserializer};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Deserialize, Eq, PartialEq, Clone)]
 pub struct Schema {
+    /// Matches each line of a `regex` format source, with a capture group per column
+    #[serde(default)]
     pub regex: String,
+    /// More regexes for sources that mix formats, tried in order after `regex`. A line is read
+    /// with the first one that matches, and columns without a capture group in it are null.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+    /// Flags for `regex` and `patterns`, e.g. `is` for case-insensitive with `.` matching
+    /// newlines, instead of inline `(?is)`
+    #[serde(default)]
+    pub regex_flags: RegexFlags,
+    /// Only read lines that `regex` or one of `patterns` matches as a whole, as if they were
+    /// wrapped in `^...$`, so a match in the middle of an unrelated line isn't an event
+    #[serde(default)]
+    pub anchored: bool,
+    /// Read lines that only match the start of a pattern, up to the end of one of its capture
+    /// groups, with the later columns null, e.g. older lines without trailing fields
+    #[serde(default)]
+    pub permissive: bool,
+    /// Fail on lines that don't start an event and aren't continuation lines of a multiline
+    /// column, instead of dropping them, so a schema that stopped matching isn't missed
+    #[serde(default)]
+    pub strict: bool,
+    /// What happens to events with a value that fails to convert to its column's type, unless
+    /// the column has its own policy
+    #[serde(default)]
+    pub on_error: OnError,
+    /// Named regex fragments that `regex` and `patterns` can include as `{{name}}`, e.g.
+    /// `timestamp: '\d{4}-\d{2}-\d{2}T\S+'`. Fragments can include other fragments.
+    #[serde(default)]
+    pub definitions: HashMap<String, String>,
+    /// Lines matching any of these regexes are dropped, e.g. banners and separators that would
+    /// otherwise become extra text of the event before them
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Lines matching this regex separate records, e.g. `^\s*$` for blank lines, and the lines
+    /// of each record are matched as one text joined with newlines instead of line by line
+    pub record_separator: Option<String>,
+    /// Lines ending with this, e.g. `\`, continue on the next line, and are joined with it
+    /// without the continuation before they're matched
+    pub line_continuation: Option<String>,
+    /// Add the `_file` and `_line` columns with the source file and line each event starts at
+    #[serde(default)]
+    pub meta_columns: bool,
     pub filename: String,
     pub table: String,
     pub columns: Vec<Column>,
+    /// Link template for `trace_url(trace_id)`, e.g. `https://tracing.example.com/trace/{trace_id}`
+    pub trace_url: Option<String>,
+    /// What the logs are, shown by `DESCRIBE` and `--dry-run`
+    pub description: Option<String>,
+    /// How sources are split into events
+    #[serde(default)]
+    pub format: InputFormat,
+    /// Filter AND-ed into every query, e.g. `tenant = '$TENANT'` with the tenant taken from the
+    /// environment, so events of other tenants can't be selected
+    pub row_filter: Option<String>,
+    /// Revision of the schema, bumped when columns are renamed or added, shown by `--dry-run`
+    pub version: Option<u32>,
+    /// Nested objects in JSON sources are flattened into `parent.child` columns up to this many
+    /// levels deep. Deeper objects are kept as JSON text.
+    pub flatten: Option<usize>,
+    /// How numbers and datetimes are written, for logs from systems that aren't configured for
+    /// English
+    pub locale: Option<Locale>,
+    /// Zone of timestamps without an offset, e.g. `+02:00` or `local`. Without one they're
+    /// rejected, except by lenient and European datetimes which read them as UTC.
+    pub timezone: Option<Timezone>,
+    /// Encoding of the source files, e.g. `utf-16le`. Without one it's detected from each file.
+    pub encoding: Option<Encoding>,
+}
+
+/// Column with the file an event was read from, with `meta_columns`
+pub const FILE_COLUMN: &str = "_file";
+/// Column with the line an event starts at in its file, counting from 1, with `meta_columns`
+pub const LINE_COLUMN: &str = "_line";
+
+/// How sources are split into events
+#[derive(Debug, Default, Deserialize, Eq, PartialEq, Clone, Copy)]
+pub enum InputFormat {
+    /// Each line matching `regex` starts an event
+    #[default]
+    #[serde(rename = "regex")]
+    Regex,
+    /// JSON Lines, an object per line with a key per column. Missing keys are null, and lines
+    /// that aren't objects don't match.
+    #[serde(rename = "json")]
+    Json,
+    /// `key=value` pairs, with quoted values like `msg="user logged in"`. Columns are read from
+    /// their keys, every other key is a string column of its own, and lines without pairs don't
+    /// match.
+    #[serde(rename = "kv")]
+    KeyValue,
+    /// Each source is a JSON array of objects, with a key per column. Missing keys are null.
+    #[serde(rename = "json_array")]
+    JsonArray,
+    /// Each source is a Windows Event Log file, read with the `evtx` feature. Columns can be any
+    /// of `record_id`, `time`, `provider`, `level`, `event_id`, `channel`, `computer` and
+    /// `message`.
+    #[serde(rename = "evtx")]
+    Evtx,
+    /// Each source is a packet capture, with a row per packet. Columns can be any of `ts`,
+    /// `src`, `dst`, `proto`, `src_port`, `dst_port` and `len`.
+    #[serde(rename = "pcap")]
+    Pcap,
+    /// ArcSight Common Event Format lines. Columns can be the header fields `version`,
+    /// `device_vendor`, `device_product`, `device_version`, `signature_id`, `name` and `severity`,
+    /// or extension keys like `src` and `suser`, or custom extensions by their labels. Without
+    /// columns every field is a string column.
+    #[serde(rename = "cef")]
+    Cef,
+    /// IBM QRadar Log Event Extended Format lines. Columns can be the header fields `version`,
+    /// `device_vendor`, `device_product`, `device_version` and `event_id`, or attribute keys like
+    /// `src` and `usrName`. Without columns every field is a string column.
+    #[serde(rename = "leef")]
+    Leef,
+    /// RFC 5424 or RFC 3164 syslog lines. Columns can be `priority`, `facility`, `severity`,
+    /// `version`, `timestamp`, `hostname`, `app_name`, `procid`, `msgid` and `message`, the
+    /// structured data as JSON in `structured_data`, or its params flattened like
+    /// `origin@32473.ip` or `origin.ip`. Without columns the table has the standard ones, `pri`,
+    /// `facility`, `severity`, `timestamp`, `host`, `app`, `procid`, `msgid` and `message`.
+    #[serde(rename = "syslog")]
+    Syslog,
+    /// GELF messages, a JSON object per line. Columns can be `version`, `host`,
+    /// `short_message`, `full_message`, `timestamp` and `level`, or additional fields without
+    /// their leading underscore.
+    #[serde(rename = "gelf")]
+    Gelf,
+    /// Apache and nginx access logs in the combined or common log format. Columns can be
+    /// `remote_addr`, `ident`, `remote_user`, `time_local`, `timestamp`, `request`, `method`,
+    /// `path`, `protocol`, `status`, `body_bytes_sent`, `http_referer` and `http_user_agent`.
+    /// Without columns the table has `remote_addr`, `remote_user`, `timestamp`, `method`,
+    /// `path`, `protocol`, `status`, `bytes`, `referer` and `user_agent`.
+    #[serde(rename = "combined")]
+    Combined,
 }
 
 impl Schema {
     /// Ensures
     /// - only strings can be multiline enabled
     /// - only one multiline column allowed
+    /// - only multiline columns can have multiline options
+    /// - only numbers can have thousands separators, units or a display unit
+    /// - only datetimes can be lenient
+    /// - only bools can have true and false values, and no value is both
+    /// - column names and aliases are unique, except that conditional columns can share an alias
+    /// - conditions depend on an unconditional column
+    /// - columns don't use the names of meta columns, if they're enabled
     fn validate(&self) -> Result<(), Error> {
         let mut multiline_enabled = false;
+        // whether each name is an alias that conditional columns can share
+        let mut names = HashMap::new();
+        if self.meta_columns {
+            names.insert(FILE_COLUMN, false);
+            names.insert(LINE_COLUMN, false);
+        }
 
         for column in &self.columns {
+            for (index, name) in column.names().enumerate() {
+                let shared = index > 0 && column.when.is_some();
+                match names.insert(name, shared) {
+                    Some(true) if shared => (),
+                    Some(_) => return Err(Error::DuplicateColumnName(name.to_string())),
+                    None => (),
+                }
+            }
+
+            if let Some(condition) = &column.when {
+                let discriminator = self.columns.iter().find(|c| c.name == condition.column);
+                match discriminator {
+                    None => {
+                        return Err(Error::InvalidColumnCondition(
+                            column.name.clone(),
+                            format!("column '{}' does not exist", condition.column),
+                        ))
+                    }
+                    Some(discriminator) if discriminator.when.is_some() => {
+                        return Err(Error::InvalidColumnCondition(
+                            column.name.clone(),
+                            format!("column '{}' is also conditional", condition.column),
+                        ))
+                    }
+                    Some(_) => (),
+                }
+            }
+
+            if (column.thousands_separator.is_some() || column.strip_units || column.unit.is_some())
+                && !column.r#type.is_numeric()
+            {
+                return Err(Error::InvalidNumberFormat(
+                    column.name.clone(),
+                    column.r#type.clone(),
+                ));
+            }
+
+            if column.lenient
+                && !matches!(column.r#type, ColumnType::DateTime | ColumnType::DateTimeTz)
+            {
+                return Err(Error::InvalidLenientType(
+                    column.name.clone(),
+                    column.r#type.clone(),
+                ));
+            }
+
+            if !column.true_values.is_empty() || !column.false_values.is_empty() {
+                if column.r#type != ColumnType::Bool {
+                    return Err(Error::InvalidBoolValues(
+                        column.name.clone(),
+                        format!("is a '{}'. Only bools can.", column.r#type),
+                    ));
+                }
+                if let Some(value) = column
+                    .true_values
+                    .iter()
+                    .find(|value| column.false_values.contains(value))
+                {
+                    return Err(Error::InvalidBoolValues(
+                        column.name.clone(),
+                        format!("reads '{}' as both true and false.", value),
+                    ));
+                }
+            }
+
             if column.multiline && column.r#type != ColumnType::String {
                 return Err(Error::InvalidMultilineType(
                     column.name.clone(),
-                    column.r#type,
+                    column.r#type.clone(),
                 ));
             }
 
+            let multiline_options = column.max_lines.is_some()
+                || column.max_bytes.is_some()
+                || column.multiline_regex.is_some()
+                || column.join_separator.is_some()
+                || !column.keep_leading_whitespace;
+            if !column.multiline && multiline_options {
+                return Err(Error::InvalidMultilineOption(column.name.clone()));
+            }
+
             if column.multiline {
                 if multiline_enabled {
                     // found more than one multiline column
@@ -45,22 +270,394 @@ impl Schema {
     }
 }
 
+impl Schema {
+    /// Read a schema file. YAML and JSON schemas can `include: common.yml`, or a list of files,
+    /// relative to the including file, to share columns and regex fragments. Later includes
+    /// override earlier ones and the including file overrides them all: columns with the same
+    /// name are replaced, lists like `ignore` are appended and other fields are overwritten.
+    /// `.toml` files are read with [`Schema::from_toml`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Schema, Error> {
+        let path = path.as_ref();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            let text = std::fs::read_to_string(path).map_err(|e| {
+                Error::InvalidSchemaFile(
+                    path.display().to_string(),
+                    format!("could not be read: {}", e),
+                )
+            })?;
+            return Schema::from_toml(&text);
+        }
+
+        let schema: Schema = serde_yaml::from_value(load_with_includes(path, &mut Vec::new())?)?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    /// Parse a schema written in TOML, with the same fields as YAML, e.g. `[[columns]]` tables
+    #[cfg(feature = "toml")]
+    pub fn from_toml(value: &str) -> Result<Schema, Error> {
+        let schema: Schema =
+            toml::from_str(value).map_err(|e| Error::InvalidTomlSchema(e.to_string()))?;
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    #[cfg(not(feature = "toml"))]
+    pub fn from_toml(_value: &str) -> Result<Schema, Error> {
+        Err(Error::InvalidTomlSchema(
+            "logql was built without the `toml` feature".to_string(),
+        ))
+    }
+
+    /// Names of the columns of the table, which are the schema's columns and then the meta columns
+    pub fn column_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.columns.iter().map(|c| c.name.clone()).collect();
+        if self.meta_columns {
+            names.push(FILE_COLUMN.to_string());
+            names.push(LINE_COLUMN.to_string());
+        }
+        names
+    }
+
+    /// Whether fields that aren't columns are columns of their own, which they are for `kv`
+    /// sources, and `cef` and `leef` sources without columns
+    pub fn discovers_columns(&self) -> bool {
+        match self.format {
+            InputFormat::KeyValue => true,
+            InputFormat::Cef | InputFormat::Leef => self.columns.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Replace the `{{name}}` fragments in a pattern with their definitions
+    pub fn expand_definitions(&self, pattern: &str) -> Result<String, Error> {
+        self.expand(pattern, &mut Vec::new())
+    }
+
+    fn expand<'a>(&'a self, pattern: &str, expanding: &mut Vec<&'a str>) -> Result<String, Error> {
+        let mut expanded = String::with_capacity(pattern.len());
+        let mut rest = pattern;
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start + 2..].find("}}") {
+                Some(end) => start + 2 + end,
+                None => break,
+            };
+            let name = &rest[start + 2..end];
+            let is_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+            if !is_name {
+                // not a fragment name, so the braces are left to the regex
+                expanded.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            let (name, definition) = self.definitions.get_key_value(name).ok_or_else(|| {
+                Error::InvalidDefinition(name.to_string(), "is not defined".to_string())
+            })?;
+            if expanding.contains(&name.as_str()) {
+                return Err(Error::InvalidDefinition(
+                    name.clone(),
+                    "includes itself".to_string(),
+                ));
+            }
+            expanding.push(name);
+            expanded.push_str(&rest[..start]);
+            expanded.push_str(&self.expand(definition, expanding)?);
+            expanding.pop();
+            rest = &rest[end + 2..];
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
 impl TryFrom<&str> for Schema {
     type Error = Error;
 
+    /// Schemas are YAML, or JSON when they start with `{`, e.g. when generated by other tools
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let schema: Schema = serde_yaml::from_str(value)?;
+        let schema: Schema = if value.trim_start().starts_with('{') {
+            serde_json::from_str(value).map_err(Error::InvalidJsonSchema)?
+        } else {
+            serde_yaml::from_str(value)?
+        };
         schema.validate()?;
         Ok(schema)
     }
 }
 
+/// Read a schema file with its includes merged in. `including` is the chain of files being read,
+/// to find cycles.
+fn load_with_includes(path: &Path, including: &mut Vec<PathBuf>) -> Result<Value, Error> {
+    let error = |message: String| Error::InvalidSchemaFile(path.display().to_string(), message);
+    let text =
+        std::fs::read_to_string(path).map_err(|e| error(format!("could not be read: {}", e)))?;
+    // canonical so `a.yml` and `./a.yml` are the same file
+    let canonical = path
+        .canonicalize()
+        .map_err(|e| error(format!("could not be read: {}", e)))?;
+    if including.contains(&canonical) {
+        let cycle: Vec<_> = including
+            .iter()
+            .chain([&canonical])
+            .map(|path| path.display().to_string())
+            .collect();
+        return Err(error(format!("includes itself: {}", cycle.join(" -> "))));
+    }
+
+    let parsed = if text.trim_start().starts_with('{') {
+        serde_json::from_str::<serde_json::Value>(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|value| serde_yaml::to_value(value).map_err(|e| e.to_string()))
+    } else {
+        serde_yaml::from_str(&text).map_err(|e| e.to_string())
+    };
+    let mut value = parsed.map_err(|e| error(format!("failed to parse: {}", e)))?;
+    let includes = match &mut value {
+        Value::Mapping(mapping) => mapping.remove(&Value::from("include")),
+        _ => None,
+    };
+    let includes: Vec<String> = match includes {
+        None => Vec::new(),
+        Some(Value::String(include)) => vec![include],
+        Some(includes) => serde_yaml::from_value(includes).map_err(|_| {
+            error("has an include that isn't a file or a list of files".to_string())
+        })?,
+    };
+
+    including.push(canonical);
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut merged = Value::Mapping(Mapping::new());
+    for include in includes {
+        merge(
+            &mut merged,
+            load_with_includes(&dir.join(include), including)?,
+        );
+    }
+    including.pop();
+    merge(&mut merged, value);
+    Ok(merged)
+}
+
+/// Merge `value` over `base`. Mappings are merged by key, `columns` by column name, other lists
+/// are appended and anything else is replaced.
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Mapping(base), Value::Mapping(value)) => {
+            for (key, value) in value {
+                match base.get_mut(&key) {
+                    Some(columns) if key.as_str() == Some("columns") => {
+                        merge_columns(columns, value)
+                    }
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(value)) => base.extend(value),
+        (base, value) => *base = value,
+    }
+}
+
+fn merge_columns(columns: &mut Value, value: Value) {
+    let (columns, value) = match (columns, value) {
+        (Value::Sequence(columns), Value::Sequence(value)) => (columns, value),
+        (columns, value) => {
+            *columns = value;
+            return;
+        }
+    };
+    for column in value {
+        let name = column.get("name").cloned();
+        let existing = columns
+            .iter_mut()
+            .find(|c| name.is_some() && c.get("name") == name.as_ref());
+        match existing {
+            Some(existing) => *existing = column,
+            None => columns.push(column),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Eq, PartialEq, Clone)]
 pub struct Column {
     pub name: String,
     pub r#type: ColumnType,
+    /// Earlier names of the column, e.g. `latency` before it was renamed to `duration_ms`. Lines
+    /// without the column's field or capture group are read from the first alias they have, so
+    /// old and new lines can be queried together.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// Capture group, JSON key or field the column is read from when it isn't named like the
+    /// column, e.g. `group: ts2` for a `timestamp` column in a regex that already has a `ts`
+    pub group: Option<String>,
+    /// Regex applied to the column's text whose named groups other columns are read from, e.g.
+    /// `request_id=(?P<request_id>\w+)` on a free-text message column. The top-level regex or
+    /// field wins when both have a name, and the groups of a text that doesn't match are null.
+    pub extract: Option<String>,
+    /// Only parse the column when another column has one of the given values, e.g. fields of
+    /// `event=purchase` lines in logs whose payload depends on the event. Otherwise it's null.
+    pub when: Option<Condition>,
+    /// What happens to events where this column's value fails to convert, instead of the
+    /// schema's `on_error`
+    #[serde(default, deserialize_with = "deserialize_on_error")]
+    pub on_error: Option<OnError>,
+    /// Clean up the field before it's parsed, applied in order, e.g. `[strip_quotes, trim]`
+    #[serde(default)]
+    pub transform: Vec<Transform>,
+    /// Read empty values as null, e.g. `(?P<user>\S*)` matching nothing or `"user": ""`, instead
+    /// of an empty string or a parse error. Fields that are missing entirely, like an optional
+    /// group `(?P<user>\S+)?` that didn't match, are always null.
+    #[serde(default)]
+    pub nullable: bool,
+    /// Texts that mark a missing value and are read as null, e.g. `['-', N/A]` in access logs,
+    /// instead of failing to parse as the column's type
+    #[serde(default, deserialize_with = "deserialize_scalars")]
+    pub null_values: Vec<String>,
+    /// Value used instead of null when the column's field is missing, e.g. `default: unknown`
+    /// for an optional capture group. It's parsed with the column's type.
+    #[serde(default, deserialize_with = "deserialize_scalar")]
+    pub default: Option<String>,
+    /// Texts read as true by a bool column besides `true`, e.g. `[1, yes, Y]`
+    #[serde(default, deserialize_with = "deserialize_scalars")]
+    pub true_values: Vec<String>,
+    /// Texts read as false by a bool column besides `false`, e.g. `[0, no, N]`
+    #[serde(default, deserialize_with = "deserialize_scalars")]
+    pub false_values: Vec<String>,
     #[serde(default)]
     pub multiline: bool,
+    /// Thousands separator removed from numeric values before parsing, e.g. `,` for `1,234`
+    pub thousands_separator: Option<char>,
+    /// Strip a trailing unit from numeric values before parsing, e.g. `ms` in `1234 ms`
+    #[serde(default)]
+    pub strip_units: bool,
+    /// Accept `inf`, `-inf` and `NaN` in float columns
+    #[serde(default = "default_allow_non_finite")]
+    pub allow_non_finite: bool,
+    /// Accept slightly malformed datetimes, clamping out of range fields such as leap seconds
+    #[serde(default)]
+    pub lenient: bool,
+    /// Zone of this column's timestamps without an offset, instead of the schema's
+    pub timezone: Option<Timezone>,
+    /// Unit used to humanize values in table output, e.g. `1536` bytes is shown as `1.5 KiB`
+    pub unit: Option<Unit>,
+    /// What the column holds, shown by `DESCRIBE` and when suggesting columns
+    pub description: Option<String>,
+    /// Most continuation lines kept per event by a multiline column. Later lines are dropped.
+    pub max_lines: Option<usize>,
+    /// Most continuation bytes kept per event by a multiline column. Later lines are dropped.
+    pub max_bytes: Option<usize>,
+    /// Only lines matching this regex are continuation lines of a multiline column, e.g.
+    /// `^\s+at `. Other lines that don't start an event are reported as unmatched instead.
+    pub multiline_regex: Option<String>,
+    /// Joins a multiline column's continuation lines, defaults to `\n`
+    pub join_separator: Option<String>,
+    /// Keep the indentation of continuation lines, e.g. `    at ...` in a stack trace
+    #[serde(default = "default_keep_leading_whitespace")]
+    pub keep_leading_whitespace: bool,
+}
+
+/// Policy for values that fail to convert to their column's type. `on_error: null` is read as
+/// the `null` policy rather than a missing one.
+#[derive(Debug, Default, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(try_from = "Option<String>")]
+pub enum OnError {
+    /// Stop parsing with an error
+    #[default]
+    Fail,
+    /// Read the value as null, counted in a warning
+    Null,
+    /// Drop the event, counted in a warning
+    Skip,
+}
+
+impl TryFrom<Option<String>> for OnError {
+    type Error = String;
+
+    fn try_from(value: Option<String>) -> Result<Self, Self::Error> {
+        match value.as_deref() {
+            Some("fail") => Ok(OnError::Fail),
+            None | Some("null") => Ok(OnError::Null),
+            Some("skip") => Ok(OnError::Skip),
+            Some(value) => Err(format!(
+                "unknown on_error '{}', expected fail, null or skip",
+                value
+            )),
+        }
+    }
+}
+
+/// Values of a discriminator column that a conditional column applies to
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone)]
+pub struct Condition {
+    pub column: String,
+    /// Matched against the discriminator's text before it's parsed
+    pub values: Vec<String>,
+}
+
+impl Column {
+    /// The column's name followed by its aliases
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.name.as_str()).chain(self.aliases.iter().map(String::as_str))
+    }
+
+    /// Fields the column is read from: its group, or its name without one, then its aliases
+    pub fn fields(&self) -> impl Iterator<Item = &str> {
+        let field = self.group.as_deref().unwrap_or(&self.name);
+        std::iter::once(field).chain(self.aliases.iter().map(String::as_str))
+    }
+}
+
+fn default_allow_non_finite() -> bool {
+    true
+}
+
+fn default_keep_leading_whitespace() -> bool {
+    true
+}
+
+/// Read a YAML or JSON scalar as its text, so `default: 0` and `default: true` work like strings
+fn deserialize_scalar<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let value = Option::<serde_yaml::Value>::deserialize(deserializer)?;
+    match value {
+        None | Some(serde_yaml::Value::Null) => Ok(None),
+        Some(value) => scalar_text(value).map(Some),
+    }
+}
+
+/// Read a column's `on_error`, where `null` is a policy rather than no policy
+fn deserialize_on_error<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<OnError>, D::Error> {
+    OnError::deserialize(deserializer).map(Some)
+}
+
+/// Read a list of scalars as their texts, so `true_values: [1, yes]` and `null_values: [0]` work
+/// like strings
+fn deserialize_scalars<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Vec::<serde_yaml::Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(scalar_text)
+        .collect()
+}
+
+fn scalar_text<E: serde::de::Error>(value: serde_yaml::Value) -> Result<String, E> {
+    match value {
+        serde_yaml::Value::String(value) => Ok(value),
+        serde_yaml::Value::Bool(value) => Ok(value.to_string()),
+        serde_yaml::Value::Number(value) => Ok(value.to_string()),
+        _ => Err(E::custom("expected a scalar")),
+    }
 }
 
 #[cfg(test)]
@@ -70,7 +667,30 @@ impl Column {
         Column {
             name: name.into(),
             r#type: column_type,
+            aliases: Vec::new(),
+            group: None,
+            extract: None,
+            when: None,
+            on_error: None,
+            transform: Vec::new(),
+            nullable: false,
+            null_values: Vec::new(),
+            default: None,
+            true_values: Vec::new(),
+            false_values: Vec::new(),
             multiline: false,
+            thousands_separator: None,
+            strip_units: false,
+            allow_non_finite: true,
+            lenient: false,
+            timezone: None,
+            unit: None,
+            description: None,
+            max_lines: None,
+            max_bytes: None,
+            multiline_regex: None,
+            join_separator: None,
+            keep_leading_whitespace: true,
         }
     }
 
@@ -79,27 +699,310 @@ impl Column {
         Column {
             name: name.into(),
             r#type: ColumnType::String,
+            aliases: Vec::new(),
+            group: None,
+            extract: None,
+            when: None,
+            on_error: None,
+            transform: Vec::new(),
+            nullable: false,
+            null_values: Vec::new(),
+            default: None,
+            true_values: Vec::new(),
+            false_values: Vec::new(),
             multiline: true,
+            thousands_separator: None,
+            strip_units: false,
+            allow_non_finite: true,
+            lenient: false,
+            timezone: None,
+            unit: None,
+            description: None,
+            max_lines: None,
+            max_bytes: None,
+            multiline_regex: None,
+            join_separator: None,
+            keep_leading_whitespace: true,
+        }
+    }
+}
+
+/// Normalizes a field's text before it's parsed into its column's type
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Transform {
+    Trim,
+    Lowercase,
+    Uppercase,
+    /// Decode `%XX` escapes and `+` as a space, like in a query string
+    UrlDecode,
+    /// Remove one pair of matching single or double quotes around the value
+    StripQuotes,
+}
+
+impl Transform {
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Transform::Trim => value.trim().to_string(),
+            Transform::Lowercase => value.to_lowercase(),
+            Transform::Uppercase => value.to_uppercase(),
+            Transform::UrlDecode => url_decode(value),
+            Transform::StripQuotes => {
+                for quote in ['"', '\''] {
+                    if let Some(unquoted) = value
+                        .strip_prefix(quote)
+                        .and_then(|value| value.strip_suffix(quote))
+                    {
+                        return unquoted.to_string();
+                    }
+                }
+                value.to_string()
+            }
+        }
+    }
+}
+
+/// Invalid escapes are kept as they are, and invalid UTF-8 is replaced with `U+FFFD`
+fn url_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let hex = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[index], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Regional formats that numeric and datetime columns accept besides the standard ones
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy)]
+pub enum Locale {
+    /// Decimal commas with `.` or space thousands separators, e.g. `1.234,5`, and day-first
+    /// datetimes, e.g. `31.12.2022 23:59:59` or `31/12/2022`
+    #[serde(rename = "european")]
+    European,
+}
+
+/// Regex flags from letters like `(?flags)`: `i` case-insensitive, `s` dot matches newline, `x`
+/// whitespace and `#` comments are ignored, and `U` swaps greedy and lazy repetition
+#[derive(Debug, Default, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(try_from = "String")]
+pub struct RegexFlags {
+    pub case_insensitive: bool,
+    pub dot_matches_new_line: bool,
+    pub ignore_whitespace: bool,
+    pub swap_greed: bool,
+}
+
+impl TryFrom<String> for RegexFlags {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut flags = RegexFlags::default();
+        for flag in value.chars() {
+            match flag {
+                'i' => flags.case_insensitive = true,
+                's' => flags.dot_matches_new_line = true,
+                'x' => flags.ignore_whitespace = true,
+                'U' => flags.swap_greed = true,
+                flag => {
+                    return Err(format!(
+                        "unknown regex flag '{}', expected i, s, x or U",
+                        flag
+                    ))
+                }
+            }
+        }
+        Ok(flags)
+    }
+}
+
+/// How timestamps without an offset are read
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy)]
+#[serde(try_from = "String")]
+pub enum Timezone {
+    Utc,
+    /// The system's timezone, with its daylight saving time
+    Local,
+    Offset(FixedOffset),
+}
+
+impl Timezone {
+    /// The timestamp with this zone's offset at that time. Local times skipped by a daylight
+    /// saving change don't exist, and repeated ones are the earlier time.
+    pub fn localize(&self, datetime: &NaiveDateTime) -> Option<DateTime<FixedOffset>> {
+        match self {
+            Timezone::Utc => FixedOffset::east(0).from_local_datetime(datetime).single(),
+            Timezone::Offset(offset) => offset.from_local_datetime(datetime).single(),
+            Timezone::Local => Local
+                .from_local_datetime(datetime)
+                .earliest()
+                .map(DateTime::from),
+        }
+    }
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "UTC" | "utc" | "Z" => Ok(Timezone::Utc),
+            "local" => Ok(Timezone::Local),
+            offset => parse_offset(offset).map(Timezone::Offset).ok_or_else(|| {
+                format!(
+                    "unknown timezone '{}', expected UTC, local or an offset like +02:00",
+                    value
+                )
+            }),
         }
     }
 }
 
-#[derive(Debug, Deserialize, Eq, PartialEq, Copy, Clone)]
+/// Unit of a numeric column's values. Aggregates like `SUM(bytes)` keep the unit of their column.
+#[derive(Debug, Deserialize, Eq, PartialEq, Clone, Copy)]
+pub enum Unit {
+    #[serde(rename = "ms")]
+    Milliseconds,
+    #[serde(rename = "bytes")]
+    Bytes,
+    /// Fractions, like `percent` columns, so 0.85 is shown as `85%`
+    #[serde(rename = "percent")]
+    Percent,
+}
+
+impl Unit {
+    /// Render a value for table output, e.g. `1500` milliseconds is `1.5 s`. Values that aren't
+    /// numbers are rendered as usual.
+    pub fn format(&self, value: &Type) -> String {
+        let number = match value {
+            Type::Int32(_)
+            | Type::Int64(_)
+            | Type::UInt32(_)
+            | Type::UInt64(_)
+            | Type::Float(_)
+            | Type::Double(_) => value.as_f64(),
+            _ => None,
+        };
+        let value = match number {
+            Some(number) if number.is_finite() => number,
+            _ => return value.to_string(),
+        };
+
+        match self {
+            Unit::Milliseconds => {
+                let scales = [(1.0, "ms"), (1000.0, "s"), (60.0, "min"), (60.0, "h")];
+                format_scaled(value, &scales)
+            }
+            Unit::Bytes => {
+                let scales = [
+                    (1.0, "B"),
+                    (1024.0, "KiB"),
+                    (1024.0, "MiB"),
+                    (1024.0, "GiB"),
+                    (1024.0, "TiB"),
+                    (1024.0, "PiB"),
+                ];
+                format_scaled(value, &scales)
+            }
+            Unit::Percent => format!("{}%", format_number(value * 100.0)),
+        }
+    }
+}
+
+/// Divide by each scale while the value is at least that big. Scales are relative to the last.
+fn format_scaled(mut value: f64, scales: &[(f64, &str)]) -> String {
+    let mut unit = scales[0].1;
+    for (scale, name) in &scales[1..] {
+        if value.abs() < *scale {
+            break;
+        }
+        value /= scale;
+        unit = name;
+    }
+    format!("{} {}", format_number(value), unit)
+}
+
+/// Round to one decimal place, without a trailing `.0`
+fn format_number(value: f64) -> String {
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{:.0}", rounded)
+    } else {
+        format!("{:.1}", rounded)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub enum ColumnType {
-    #[serde(alias = "string")]
     String,
-    #[serde(alias = "i32")]
     Int32,
-    #[serde(alias = "i64")]
     Int64,
-    #[serde(alias = "bool")]
+    /// Unsigned integers for values that are never negative, like byte counts and PIDs
+    UInt32,
+    UInt64,
     Bool,
-    #[serde(alias = "f32")]
     Float,
-    #[serde(alias = "f64")]
     Double,
-    #[serde(alias = "datetime")]
+    /// An exact decimal for amounts like money, where doubles would round
+    Decimal,
     DateTime,
+    /// A datetime that keeps its original offset instead of being converted to UTC
+    DateTimeTz,
+    /// A fraction parsed from `85%` or `0.85`, stored as a double
+    Percent,
+    /// A type registered at runtime, see [`crate::parser::custom::CustomType`]
+    Custom(String),
+}
+
+impl ColumnType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            ColumnType::Int32
+                | ColumnType::Int64
+                | ColumnType::UInt32
+                | ColumnType::UInt64
+                | ColumnType::Float
+                | ColumnType::Double
+                | ColumnType::Decimal
+                | ColumnType::Percent
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for ColumnType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        let column_type = match name.as_str() {
+            "String" | "string" => ColumnType::String,
+            "Int32" | "i32" => ColumnType::Int32,
+            "Int64" | "i64" => ColumnType::Int64,
+            "UInt32" | "u32" => ColumnType::UInt32,
+            "UInt64" | "u64" => ColumnType::UInt64,
+            "Bool" | "bool" => ColumnType::Bool,
+            "Float" | "f32" => ColumnType::Float,
+            "Double" | "f64" => ColumnType::Double,
+            "DateTime" | "datetime" => ColumnType::DateTime,
+            "DateTimeTz" | "datetimetz" => ColumnType::DateTimeTz,
+            "Decimal" | "decimal" => ColumnType::Decimal,
+            "Percent" | "percent" => ColumnType::Percent,
+            _ => ColumnType::Custom(name),
+        };
+        Ok(column_type)
+    }
 }
 
 impl Display for ColumnType {
@@ -108,12 +1011,18 @@ impl Display for ColumnType {
             ColumnType::String => "string",
             ColumnType::Int32 => "i32",
             ColumnType::Int64 => "i64",
+            ColumnType::UInt32 => "u32",
+            ColumnType::UInt64 => "u64",
             ColumnType::Bool => "bool",
             ColumnType::Float => "f32",
             ColumnType::Double => "f64",
             ColumnType::DateTime => "datetime",
+            ColumnType::DateTimeTz => "datetimetz",
+            ColumnType::Decimal => "decimal",
+            ColumnType::Percent => "percent",
+            ColumnType::Custom(name) => name,
         };
-        f.write_str(&value)
+        f.write_str(value)
     }
 }
 
@@ -147,6 +1056,17 @@ columns:
         let schema = Schema::try_from(raw).unwrap();
         let expected = Schema {
             regex: "*".to_string(),
+            patterns: Vec::new(),
+            definitions: HashMap::new(),
+            ignore: Vec::new(),
+            record_separator: None,
+            line_continuation: None,
+            meta_columns: false,
+            regex_flags: RegexFlags::default(),
+            anchored: false,
+            permissive: false,
+            strict: false,
+            on_error: OnError::Fail,
             filename: ".*".to_string(),
             table: "logs".to_string(),
             columns: vec![
@@ -158,6 +1078,15 @@ columns:
                 Column::new("f64", ColumnType::Double),
                 Column::new("datetime", ColumnType::DateTime),
             ],
+            trace_url: None,
+            description: None,
+            format: InputFormat::Regex,
+            row_filter: None,
+            version: None,
+            flatten: None,
+            locale: None,
+            timezone: None,
+            encoding: None,
         };
 
         assert_eq!(expected, schema);
@@ -168,10 +1097,15 @@ columns:
         let cases = [
             ("i32", ColumnType::Int32),
             ("i64", ColumnType::Int64),
+            ("u32", ColumnType::UInt32),
+            ("u64", ColumnType::UInt64),
             ("bool", ColumnType::Bool),
             ("f32", ColumnType::Float),
             ("f64", ColumnType::Double),
             ("datetime", ColumnType::DateTime),
+            ("datetimetz", ColumnType::DateTimeTz),
+            ("decimal", ColumnType::Decimal),
+            ("percent", ColumnType::Percent),
         ];
 
         for case in cases {
@@ -232,4 +1166,356 @@ columns:
             );
         }
     }
+
+    #[test]
+    fn parse_json_schema() {
+        let raw = r#"
+{
+    "regex": "(?P<status>\\d+) (?P<message>.*)",
+    "filename": ".*",
+    "table": "logs",
+    "columns": [
+        {"name": "status", "type": "i32", "default": 0},
+        {"name": "message", "type": "string", "multiline": true}
+    ]
+}
+"#;
+        let yaml = r#"
+regex: '(?P<status>\d+) (?P<message>.*)'
+filename: .*
+table: logs
+columns:
+    - name: status
+      type: i32
+      default: 0
+    - name: message
+      type: string
+      multiline: true
+"#;
+        assert_eq!(
+            Schema::try_from(raw).unwrap(),
+            Schema::try_from(yaml).unwrap()
+        );
+
+        let error = Schema::try_from(r#"{"filename": ".*", "table": "logs"}"#).unwrap_err();
+        assert!(matches!(error, Error::InvalidJsonSchema(_)), "{:?}", error);
+    }
+
+    #[test]
+    fn load_schema_with_includes() {
+        let dir = std::env::temp_dir().join(format!("logql-include-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        let write = |name: &str, text: &str| std::fs::write(dir.join(name), text).unwrap();
+        write(
+            "shared/base.yml",
+            "definitions:\n    ts: '\\S+'\nignore: ['^#']\nfilename: .*\n",
+        );
+        write(
+            "shared/common.yml",
+            "\
+include: base.yml
+definitions:
+    level: '[A-Z]+'
+columns:
+    - name: ts
+      type: datetime
+    - name: level
+      type: string
+      description: severity
+",
+        );
+        write(
+            "app.yml",
+            r#"
+include: [shared/common.yml]
+regex: '(?P<ts>{{ts}}) (?P<level>{{level}}) (?P<message>.*)'
+table: app
+ignore: ['^-+$']
+columns:
+    - name: level
+      type: string
+    - name: message
+      type: string
+"#,
+        );
+
+        let schema = Schema::load(dir.join("app.yml")).unwrap();
+        assert_eq!(schema.table, "app");
+        assert_eq!(schema.filename, ".*");
+        assert_eq!(schema.ignore, ["^#", "^-+$"]);
+        assert_eq!(schema.definitions.len(), 2);
+        let columns: Vec<_> = schema.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(columns, ["ts", "level", "message"]);
+        assert_eq!(schema.columns[0].r#type, ColumnType::DateTime);
+        // the including file's column replaces the whole column
+        assert_eq!(schema.columns[1].description, None);
+
+        write("a.yml", "include: b.yml\n");
+        write("b.yml", "include: ./a.yml\n");
+        match Schema::load(dir.join("a.yml")) {
+            Err(Error::InvalidSchemaFile(file, message)) => {
+                assert!(file.ends_with("a.yml"), "{}", file);
+                assert!(message.starts_with("includes itself: "), "{}", message);
+                assert_eq!(message.matches(" -> ").count(), 2, "{}", message);
+            }
+            x => panic!("Error should be Error::InvalidSchemaFile. Actual {:?}", x),
+        }
+
+        write("missing.yml", "include: nope.yml\n");
+        match Schema::load(dir.join("missing.yml")) {
+            Err(Error::InvalidSchemaFile(file, message)) => {
+                assert!(file.ends_with("nope.yml"), "{}", file);
+                assert!(message.starts_with("could not be read"), "{}", message);
+            }
+            x => panic!("Error should be Error::InvalidSchemaFile. Actual {:?}", x),
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn parse_toml_schema() {
+        let raw = r#"
+regex = '(?P<status>\d+) (?P<message>.*)'
+filename = ".*"
+table = "logs"
+timezone = "+02:00"
+
+[[columns]]
+name = "status"
+type = "i32"
+default = 0
+
+[[columns]]
+name = "message"
+type = "string"
+multiline = true
+"#;
+        let yaml = r#"
+regex: '(?P<status>\d+) (?P<message>.*)'
+filename: .*
+table: logs
+timezone: +02:00
+columns:
+    - name: status
+      type: i32
+      default: 0
+    - name: message
+      type: string
+      multiline: true
+"#;
+        assert_eq!(
+            Schema::from_toml(raw).unwrap(),
+            Schema::try_from(yaml).unwrap()
+        );
+
+        let error = Schema::from_toml("table = \"logs\"").unwrap_err();
+        assert!(matches!(error, Error::InvalidTomlSchema(_)), "{:?}", error);
+    }
+
+    #[test]
+    fn parse_custom_type() {
+        let raw = "
+regex: '*'
+filename: .*
+table: logs
+columns:
+    - name: id
+      type: uuid
+";
+        let schema = Schema::try_from(raw).unwrap();
+        assert_eq!(
+            schema.columns,
+            vec![Column::new("id", ColumnType::Custom("uuid".to_string()))]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_number_format() {
+        let raw = "
+regex: '*'
+filename: .*
+table: logs
+columns:
+    - name: message
+      type: string
+      strip_units: true
+";
+        match Schema::try_from(raw).err().unwrap() {
+            Error::InvalidNumberFormat(column, column_type) => {
+                assert_eq!(column, "message");
+                assert_eq!(column_type, ColumnType::String);
+            }
+            x => panic!(
+                "Error should be Error::InvalidNumberFormat. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn parse_invalid_lenient_type() {
+        let raw = "
+regex: '*'
+filename: .*
+table: logs
+columns:
+    - name: latency
+      type: i32
+      lenient: true
+";
+        match Schema::try_from(raw).err().unwrap() {
+            Error::InvalidLenientType(column, column_type) => {
+                assert_eq!(column, "latency");
+                assert_eq!(column_type, ColumnType::Int32);
+            }
+            x => panic!(
+                "Error should be Error::InvalidLenientType. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn parse_invalid_multiline_option() {
+        let raw = "
+regex: '*'
+filename: .*
+table: logs
+columns:
+    - name: message
+      type: string
+      max_lines: 100
+";
+        match Schema::try_from(raw).err().unwrap() {
+            Error::InvalidMultilineOption(column) => assert_eq!(column, "message"),
+            x => panic!(
+                "Error should be Error::InvalidMultilineOption. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn parse_duplicate_column_names() {
+        for (columns, duplicate) in [
+            ("[{name: a, type: i32}, {name: a, type: i32}]", "a"),
+            (
+                "[{name: a, type: i32}, {name: b, type: i32, aliases: [a]}]",
+                "a",
+            ),
+            (
+                "[{name: a, type: i32, aliases: [b]}, {name: c, type: i32, aliases: [b]}]",
+                "b",
+            ),
+            ("[{name: a, type: i32, aliases: [a]}]", "a"),
+        ] {
+            let raw = format!(
+                "regex: '*'\nfilename: .*\ntable: logs\ncolumns: {}\n",
+                columns
+            );
+            match Schema::try_from(raw.as_str()).err().unwrap() {
+                Error::DuplicateColumnName(name) => assert_eq!(name, duplicate),
+                x => panic!(
+                    "Error should be Error::DuplicateColumnName. Actual error {:?}",
+                    x
+                ),
+            }
+        }
+
+        let raw = "
+regex: '*'
+filename: .*
+table: logs
+version: 2
+columns:
+    - name: duration_ms
+      type: i64
+      aliases: [latency, elapsed]
+";
+        let schema = Schema::try_from(raw).unwrap();
+        assert_eq!(schema.version, Some(2));
+        let names: Vec<_> = schema.columns[0].names().collect();
+        assert_eq!(names, vec!["duration_ms", "latency", "elapsed"]);
+    }
+
+    #[test]
+    fn parse_invalid_column_condition() {
+        for (columns, expected) in [
+            (
+                "[{name: a, type: i32, when: {column: b, values: ['1']}}]",
+                "column 'b' does not exist",
+            ),
+            (
+                "[{name: a, type: i32}, {name: b, type: i32, when: {column: a, values: ['1']}}, \
+                    {name: c, type: i32, when: {column: b, values: ['1']}}]",
+                "column 'b' is also conditional",
+            ),
+        ] {
+            let raw = format!(
+                "regex: '*'\nfilename: .*\ntable: logs\ncolumns: {}\n",
+                columns
+            );
+            match Schema::try_from(raw.as_str()).err().unwrap() {
+                Error::InvalidColumnCondition(_, reason) => assert_eq!(reason, expected),
+                x => panic!(
+                    "Error should be Error::InvalidColumnCondition. Actual error {:?}",
+                    x
+                ),
+            }
+        }
+
+        // only conditional columns can share an alias
+        let raw = "regex: '*'\nfilename: .*\ntable: logs\ncolumns: [{name: a, type: i32}, \
+            {name: b, type: i32, aliases: [x], when: {column: a, values: ['1']}}, \
+            {name: c, type: i32, aliases: [x]}]\n";
+        match Schema::try_from(raw).err().unwrap() {
+            Error::DuplicateColumnName(name) => assert_eq!(name, "x"),
+            x => panic!(
+                "Error should be Error::DuplicateColumnName. Actual error {:?}",
+                x
+            ),
+        }
+    }
+
+    #[test]
+    fn format_units() {
+        let cases = [
+            (Unit::Milliseconds, Type::Int64(250), "250 ms"),
+            (Unit::Milliseconds, Type::Double(1500.0), "1.5 s"),
+            (Unit::Milliseconds, Type::Int32(90_000), "1.5 min"),
+            (Unit::Milliseconds, Type::Int64(7_200_000), "2 h"),
+            (Unit::Bytes, Type::Int32(512), "512 B"),
+            (Unit::Bytes, Type::Int64(1536), "1.5 KiB"),
+            (Unit::Bytes, Type::Int64(3_435_973_837), "3.2 GiB"),
+            (Unit::Percent, Type::Double(0.855), "85.5%"),
+            (Unit::Bytes, Type::Null, "NULL"),
+            (Unit::Bytes, Type::Double(f64::NAN), "NaN"),
+        ];
+        for (unit, value, expected) in cases {
+            assert_eq!(unit.format(&value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn apply_transforms() {
+        let cases = [
+            (Transform::Trim, "  a b \t", "a b"),
+            (Transform::Lowercase, "GET /Index", "get /index"),
+            (Transform::Uppercase, "warn", "WARN"),
+            (
+                Transform::UrlDecode,
+                "a%20b+c%2Fd%zz%C3%A9%",
+                "a b c/d%zzé%",
+            ),
+            (Transform::StripQuotes, "\"quoted\"", "quoted"),
+            (Transform::StripQuotes, "'single'", "single"),
+            (Transform::StripQuotes, "\"mismatched'", "\"mismatched'"),
+            (Transform::StripQuotes, "\"", "\""),
+        ];
+        for (transform, value, expected) in cases {
+            assert_eq!(transform.apply(value), expected, "{:?}", transform);
+        }
+    }
 }
@@ -0,0 +1,259 @@
+//! Snapshot tests for schemas. An example is a directory with a `schema.yml`, one or more
+//! `source.*` files and an optional `query.sql`. It's run like the command line would run it
+//! and its output in every [`Format`] is compared with the `<format>.snap` files next to it,
+//! e.g. `table.snap` and `json-headers.snap`, so a change to the schema, the parser or the
+//! rendering shows up as a mismatch.
+//!
+//! ```no_run
+//! let mismatches = logql::testing::check_examples("examples").unwrap();
+//! assert!(mismatches.is_empty(), "{:#?}", mismatches);
+//! ```
+//!
+//! Set `LOGQL_UPDATE_SNAPSHOTS=1` to write the current output to the snapshots instead.
+
+use crate::engine::Engine;
+use crate::error::Error;
+use crate::export::sql::{Dialect, SqlExporter};
+use crate::export::{render, Format};
+use crate::parser::encoding::decode;
+use crate::parser::Parser;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// Environment variable that makes checks write the snapshots instead of comparing them
+pub const UPDATE_SNAPSHOTS: &str = "LOGQL_UPDATE_SNAPSHOTS";
+
+/// A schema, its sources and a query, loaded from an example directory
+#[derive(Debug, Clone)]
+pub struct Example {
+    pub name: String,
+    pub dir: PathBuf,
+    pub schema: String,
+    /// Contents of the `source.*` files, by file name
+    pub sources: Vec<String>,
+    pub query: Option<String>,
+}
+
+/// Output that doesn't match its snapshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub example: String,
+    pub format: Format,
+    /// The snapshot, or `None` if there isn't one yet
+    pub expected: Option<String>,
+    pub actual: String,
+}
+
+impl Example {
+    pub fn load(dir: impl AsRef<Path>) -> Result<Example, Error> {
+        let dir = dir.as_ref();
+        let name = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let invalid = |message: String| Error::InvalidExample(name.clone(), message);
+        let read = |path: PathBuf| {
+            std::fs::read(&path).map_err(|e| invalid(format!("{}: {}", path.display(), e)))
+        };
+
+        let mut source_paths = Vec::new();
+        let entries = std::fs::read_dir(dir).map_err(|e| invalid(e.to_string()))?;
+        for entry in entries {
+            let path = entry.map_err(|e| invalid(e.to_string()))?.path();
+            let is_source = path
+                .file_stem()
+                .is_some_and(|stem| stem == "source" && path.extension().is_some());
+            if is_source {
+                source_paths.push(path);
+            }
+        }
+        if source_paths.is_empty() {
+            return Err(invalid("no source.* files".to_string()));
+        }
+        source_paths.sort();
+
+        let query_path = dir.join("query.sql");
+        let query = match query_path.exists() {
+            true => Some(decode(read(query_path)?).trim().to_string()),
+            false => None,
+        };
+        let schema_path = match dir.join("schema.json") {
+            path if path.exists() => path,
+            _ => dir.join("schema.yml"),
+        };
+        Ok(Example {
+            schema: decode(read(schema_path)?),
+            sources: source_paths
+                .into_iter()
+                .map(|path| read(path).map(decode))
+                .collect::<Result<_, _>>()?,
+            query,
+            dir: dir.to_path_buf(),
+            name,
+        })
+    }
+
+    /// Run the example and render its results like the command line prints them. The process's
+    /// peak memory isn't the same between runs so it's left out.
+    pub fn render(&self, format: Format) -> Result<String, Error> {
+        let parser = Parser::try_from(self.schema.as_str())?;
+        let sql_exporter = SqlExporter {
+            table: parser.schema.table.clone(),
+            dialect: Dialect::Postgres,
+        };
+        let engine = match &self.query {
+            Some(query) => Engine::with_query(parser, query.clone())?,
+            None => Engine::new(parser),
+        };
+
+        let mut outputs = Vec::new();
+        for mut table_result in engine.execute_all(self.sources.clone())? {
+            table_result.stats.process_peak_memory_bytes = None;
+            outputs.push(render(&table_result, format, &sql_exporter)?);
+        }
+        Ok(outputs.join("\n\n") + "\n")
+    }
+
+    /// Compare the output in every format with its snapshot, or write the snapshots when
+    /// `update` is set
+    pub fn check(&self, update: bool) -> Result<Vec<Mismatch>, Error> {
+        let mut mismatches = Vec::new();
+        for format in Format::ALL {
+            let actual = self.render(format)?;
+            let path = self.dir.join(format!("{}.snap", format.name()));
+            let expected = std::fs::read_to_string(&path).ok();
+            if expected.as_deref() == Some(actual.as_str()) {
+                continue;
+            }
+
+            if update {
+                std::fs::write(&path, &actual).map_err(|e| {
+                    Error::InvalidExample(self.name.clone(), format!("{}: {}", path.display(), e))
+                })?;
+            } else {
+                mismatches.push(Mismatch {
+                    example: self.name.clone(),
+                    format,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(mismatches)
+    }
+}
+
+/// Check every example in the subdirectories of `root`, in name order. Snapshots are written
+/// instead when [`UPDATE_SNAPSHOTS`] is set.
+pub fn check_examples(root: impl AsRef<Path>) -> Result<Vec<Mismatch>, Error> {
+    let root = root.as_ref();
+    let invalid =
+        |e: std::io::Error| Error::InvalidExample(root.display().to_string(), e.to_string());
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(root).map_err(invalid)? {
+        let path = entry.map_err(invalid)?.path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+
+    let update = std::env::var_os(UPDATE_SNAPSHOTS).is_some();
+    let mut mismatches = Vec::new();
+    for dir in dirs {
+        mismatches.extend(Example::load(dir)?.check(update)?);
+    }
+    Ok(mismatches)
+}
+
+impl Display for Mismatch {
+    /// The first line that differs
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let expected = match &self.expected {
+            Some(expected) => expected,
+            None => {
+                return write!(
+                    f,
+                    "{}: no {}.snap snapshot, set {} to write it",
+                    self.example,
+                    self.format.name(),
+                    UPDATE_SNAPSHOTS
+                )
+            }
+        };
+        let mut expected_lines = expected.lines();
+        let mut actual_lines = self.actual.lines();
+        let mut line = 1;
+        loop {
+            match (expected_lines.next(), actual_lines.next()) {
+                (Some(a), Some(b)) if a == b => line += 1,
+                (expected, actual) => {
+                    return write!(
+                        f,
+                        "{}: {}.snap differs at line {}\n  expected: {}\n    actual: {}",
+                        self.example,
+                        self.format.name(),
+                        line,
+                        expected.unwrap_or("<end of snapshot>"),
+                        actual.unwrap_or("<end of output>")
+                    )
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_example_snapshots() {
+        let dir = std::env::temp_dir().join(format!("logql-example-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let schema = "\
+regex: (?P<host>\\w+) (?P<status>\\d+)
+filename: .*
+table: logs
+columns:
+    - name: host
+      type: string
+    - name: status
+      type: i32
+";
+        std::fs::write(dir.join("schema.yml"), schema).unwrap();
+        std::fs::write(dir.join("source.log"), "a 200\nb 500\n").unwrap();
+        std::fs::write(
+            dir.join("query.sql"),
+            "SELECT host FROM logs WHERE status = 500\n",
+        )
+        .unwrap();
+
+        let example = Example::load(&dir).unwrap();
+        let mismatches = example.check(false).unwrap();
+        assert_eq!(mismatches.len(), Format::ALL.len());
+        assert!(mismatches.iter().all(|m| m.expected.is_none()));
+
+        assert!(example.check(true).unwrap().is_empty());
+        assert!(example.check(false).unwrap().is_empty());
+        let json = std::fs::read_to_string(dir.join("json.snap")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+            serde_json::json!([{"values": {"host": {"String": "b"}}, "extra_text": null}])
+        );
+
+        std::fs::write(dir.join("source.log"), "a 500\n").unwrap();
+        let mismatches = Example::load(&dir).unwrap().check(false).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let table = mismatches
+            .iter()
+            .find(|m| m.format == Format::Table)
+            .unwrap();
+        assert!(
+            table.to_string().contains("table.snap differs at line"),
+            "{}",
+            table
+        );
+    }
+}
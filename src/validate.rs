@@ -0,0 +1,273 @@
+//! Check a schema against a sample of its logs before a full run. Lines are parsed one at a time,
+//! so one bad value doesn't hide the others, and the [`Report`] counts the lines that matched,
+//! the lines that didn't and the values each column failed to convert.
+
+use crate::error::Error;
+use crate::parser::stream::split_lines;
+use crate::parser::Parser;
+use crate::schema::InputFormat;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+/// Unmatched lines shown in the report
+const MAX_EXAMPLES: usize = 5;
+
+/// How well a schema parsed a sample
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// Lines checked, or events for sources that aren't read line by line like JSON arrays
+    pub lines: usize,
+    /// Lines that started an event
+    pub matched: usize,
+    /// Lines that didn't match but continue the multiline column of the event before them
+    pub continuation: usize,
+    /// Lines dropped by the schema's `ignore` patterns
+    pub ignored: usize,
+    pub unmatched: usize,
+    /// The first unmatched lines with their line numbers
+    pub unmatched_examples: Vec<(usize, String)>,
+    /// Values that couldn't be converted to their column's type, by column. A line counts
+    /// against the first column that fails.
+    pub failures: BTreeMap<String, Failure>,
+    /// Capture groups that aren't read by any column
+    pub unused_groups: Vec<String>,
+}
+
+/// Conversion failures of a column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    pub count: usize,
+    /// Line number of the first failure
+    pub line: usize,
+    /// Value of the first failure
+    pub value: String,
+}
+
+impl Report {
+    /// Matched lines as a fraction of the lines that weren't ignored or continuation lines
+    pub fn match_rate(&self) -> f64 {
+        let total = self.matched + self.unmatched;
+        match total {
+            0 => 0.0,
+            total => self.matched as f64 / total as f64,
+        }
+    }
+
+    /// Whether every line was read without conversion failures
+    pub fn is_clean(&self) -> bool {
+        self.unmatched == 0 && self.failures.is_empty()
+    }
+
+    fn fail(&mut self, column: String, line: usize, value: String) {
+        self.failures
+            .entry(column)
+            .or_insert(Failure {
+                count: 0,
+                line,
+                value,
+            })
+            .count += 1;
+    }
+}
+
+/// Parse the first `limit` lines of `sample` with the parser's schema
+pub fn validate(parser: &Parser, sample: &str, limit: usize) -> Result<Report, Error> {
+    parser.verify_custom_types()?;
+    let mut report = Report {
+        unused_groups: unused_groups(parser),
+        ..Report::default()
+    };
+
+    if matches!(
+        parser.schema.format,
+        InputFormat::JsonArray | InputFormat::Evtx | InputFormat::Pcap
+    ) {
+        match parser.parse(vec![sample]) {
+            Ok(events) => {
+                report.lines = events.len();
+                report.matched = events.len();
+            }
+            Err(Error::InvalidColumnValue(column, _, value)) => {
+                report.lines = 1;
+                report.fail(column, 1, value);
+            }
+            Err(e) => return Err(e),
+        }
+        return Ok(report);
+    }
+
+    let mut started = false;
+    for (index, line) in split_lines(sample).take(limit).enumerate() {
+        let number = index + 1;
+        report.lines += 1;
+        if parser.ignore.is_match(line) {
+            report.ignored += 1;
+            continue;
+        }
+
+        match parser.parse_line(line) {
+            Ok(Some(_)) => {
+                started = true;
+                report.matched += 1;
+            }
+            Err(Error::InvalidColumnValue(column, _, value)) => {
+                started = true;
+                report.matched += 1;
+                report.fail(column, number, value);
+            }
+            Err(e) => return Err(e),
+            Ok(None) => {
+                let continuation = started
+                    && parser.multiline_column.is_some()
+                    && parser
+                        .multiline_regex
+                        .as_ref()
+                        .is_none_or(|regex| regex.is_match(line));
+                if continuation {
+                    report.continuation += 1;
+                } else {
+                    report.unmatched += 1;
+                    if report.unmatched_examples.len() < MAX_EXAMPLES {
+                        report.unmatched_examples.push((number, line.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn unused_groups(parser: &Parser) -> Vec<String> {
+    if parser.schema.format != InputFormat::Regex {
+        return Vec::new();
+    }
+
+    let fields: HashSet<_> = parser
+        .schema
+        .columns
+        .iter()
+        .flat_map(|column| column.fields())
+        .collect();
+    let mut groups: Vec<_> = parser
+        .patterns
+        .iter()
+        .chain(parser.extractors.iter().map(|(_, regex)| regex))
+        .flat_map(|pattern| pattern.capture_names().flatten())
+        .filter(|name| !fields.contains(name))
+        .map(str::to_string)
+        .collect();
+    groups.sort();
+    groups.dedup();
+    groups
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Checked {} lines: {} matched ({:.1}%)",
+            self.lines,
+            self.matched,
+            self.match_rate() * 100.0
+        )?;
+        if self.continuation > 0 {
+            write!(f, ", {} continuation", self.continuation)?;
+        }
+        if self.ignored > 0 {
+            write!(f, ", {} ignored", self.ignored)?;
+        }
+        writeln!(f, ", {} unmatched", self.unmatched)?;
+
+        for (line, text) in &self.unmatched_examples {
+            writeln!(f, "  unmatched line {}: {}", line, text)?;
+        }
+        for (column, failure) in &self.failures {
+            writeln!(
+                f,
+                "Column '{}' failed to convert {} values, first on line {}: '{}'",
+                column, failure.count, failure.line, failure.value
+            )?;
+        }
+        for group in &self.unused_groups {
+            writeln!(f, "Capture group '{}' isn't read by any column", group)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_sample() {
+        let schema = "\
+regex: (?P<level>[A-Z]+) (?P<status>\\S+) (?P<pid>\\d+) (?P<message>.+)
+ignore: ['^#']
+filename: .*
+table: logs
+columns:
+    - name: level
+      type: string
+    - name: status
+      type: i32
+    - name: message
+      type: string
+      multiline: true
+";
+        let sample = "\
+# started
+INFO 200 1 ok
+  continued
+ERROR abc 2 broken
+WARN 5xx 3 also broken
+ERROR 500 4 fine
+not a line";
+        let parser = Parser::try_from(schema).unwrap();
+        let report = validate(&parser, sample, 1000).unwrap();
+
+        let mut failures = BTreeMap::new();
+        failures.insert(
+            "status".to_string(),
+            Failure {
+                count: 2,
+                line: 4,
+                value: "abc".to_string(),
+            },
+        );
+        assert_eq!(
+            report,
+            Report {
+                lines: 7,
+                matched: 4,
+                continuation: 2,
+                ignored: 1,
+                unmatched: 0,
+                unmatched_examples: Vec::new(),
+                failures,
+                unused_groups: vec!["pid".to_string()],
+            }
+        );
+        assert!(!report.is_clean());
+        assert_eq!(
+            report.to_string(),
+            "\
+Checked 7 lines: 4 matched (100.0%), 2 continuation, 1 ignored, 0 unmatched
+Column 'status' failed to convert 2 values, first on line 4: 'abc'
+Capture group 'pid' isn't read by any column
+"
+        );
+
+        // without a multiline column, lines that don't match are unmatched
+        let parser = Parser::try_from(schema.replace("multiline: true", "").as_str()).unwrap();
+        let report = validate(&parser, sample, 3).unwrap();
+        assert_eq!(report.lines, 3);
+        assert_eq!(report.unmatched, 1);
+        assert_eq!(
+            report.unmatched_examples,
+            vec![(3, "  continued".to_string())]
+        );
+        assert_eq!(report.match_rate(), 0.5);
+    }
+}
@@ -0,0 +1 @@
+SELECT region, COUNT(*) AS orders, SUM(amount) AS total FROM orders WHERE paid = true GROUP BY region ORDER BY region
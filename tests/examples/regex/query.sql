@@ -0,0 +1 @@
+SELECT timestamp, host, latency FROM requests WHERE latency > 20 ORDER BY latency DESC
@@ -0,0 +1,2 @@
+SELECT COUNT(*) AS requests FROM logs;
+SELECT host FROM logs WHERE status = 500 ORDER BY host
@@ -0,0 +1,9 @@
+use logql::testing::check_examples;
+
+#[test]
+fn examples_match_snapshots() {
+    let mismatches =
+        check_examples(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/examples")).unwrap();
+    let report: Vec<_> = mismatches.iter().map(ToString::to_string).collect();
+    assert!(report.is_empty(), "\n{}", report.join("\n"));
+}